@@ -0,0 +1,186 @@
+use std::{path::PathBuf, process};
+
+use indicatif::HumanBytes;
+use lessanvil::analysis::{self, CompressionStats, DeadSpace, DuplicateStats, ThresholdSuggestion};
+use owo_colors::OwoColorize;
+
+/// Show statistics about a world without modifying it.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "analyze")]
+pub struct AnalyzeArgs {
+    /// the world folder
+    #[argh(positional)]
+    world_folder: PathBuf,
+    /// whether the output should be in json
+    #[argh(switch)]
+    json: bool,
+    /// also read the InhabitedTime of all chunks and suggest a value for --max-inhabited-time
+    #[argh(switch)]
+    suggest: bool,
+    /// also find chunks identical apart from their position, e.g. ocean or void chunks, and show
+    /// how much storing them once would save
+    #[argh(switch)]
+    duplicates: bool,
+    /// also find the space in the region files no chunk occupies, which `compact` releases
+    #[argh(switch)]
+    dead_space: bool,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CliAnalysis<'a> {
+    #[serde(flatten)]
+    compression_stats: &'a CompressionStats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    threshold_suggestion: Option<ThresholdSuggestion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duplicate_chunks: Option<DuplicateStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dead_space: Option<DeadSpace>,
+}
+
+pub fn run(args: AnalyzeArgs) {
+    let stats = match analysis::compression_stats(&args.world_folder) {
+        Ok(stats) => stats,
+        Err(err) => {
+            log::error!("{}", err);
+            process::exit(1)
+        }
+    };
+
+    let suggestion = args.suggest.then(|| {
+        analysis::threshold_suggestion(&args.world_folder).unwrap_or_else(|err| {
+            log::error!("{}", err);
+            process::exit(1)
+        })
+    });
+
+    let duplicates = args.duplicates.then(|| {
+        analysis::duplicate_chunks(&args.world_folder).unwrap_or_else(|err| {
+            log::error!("{}", err);
+            process::exit(1)
+        })
+    });
+
+    let dead_space = args.dead_space.then(|| {
+        analysis::dead_space(&args.world_folder).unwrap_or_else(|err| {
+            log::error!("{}", err);
+            process::exit(1)
+        })
+    });
+
+    if args.json {
+        let analysis = CliAnalysis {
+            compression_stats: &stats,
+            threshold_suggestion: suggestion,
+            duplicate_chunks: duplicates,
+            dead_space,
+        };
+        anstream::println!("{}", serde_json::to_string(&analysis).unwrap());
+        return;
+    }
+
+    let format_ratio = |ratio: Option<f64>| ratio.map_or("-".to_string(), |r| format!("{r:.2}x"));
+    anstream::println!(
+        "{:<16} {:>10} {:>12} {:>12} {:>8}",
+        "Compression".bold(),
+        "Chunks".bold(),
+        "Compressed".bold(),
+        "Uncompressed".bold(),
+        "Ratio".bold()
+    );
+    for (compression, usage) in &stats.compressions {
+        anstream::println!(
+            "{:<16} {:>10} {:>12} {:>12} {:>8}",
+            compression.to_string(),
+            usage.chunks,
+            HumanBytes(usage.compressed_bytes).to_string(),
+            HumanBytes(usage.uncompressed_bytes).to_string(),
+            format_ratio(usage.ratio())
+        );
+    }
+    let total = stats.total();
+    anstream::println!(
+        "Average compression ratio: {}",
+        format_ratio(total.ratio()).green()
+    );
+    if let Some(suggestion) = suggestion {
+        anstream::println!();
+        print_suggestion(&suggestion);
+    }
+    if let Some(duplicates) = duplicates {
+        anstream::println!();
+        print_duplicates(&duplicates);
+    }
+    if let Some(dead_space) = dead_space {
+        anstream::println!();
+        crate::compact::print_dead_space(&dead_space, &args.world_folder);
+    }
+}
+
+fn print_suggestion(suggestion: &ThresholdSuggestion) {
+    let percent = |part: u64| part as f64 / suggestion.total_chunks.max(1) as f64 * 100.0;
+    anstream::println!(
+        "{:>10} {:>16} {:>12} {:>14}",
+        "Threshold".bold(),
+        "Deleted chunks".bold(),
+        "Freed".bold(),
+        "Time share".bold()
+    );
+    for estimate in &suggestion.thresholds {
+        anstream::println!(
+            "{:>10} {:>16} {:>12} {:>13.1}%",
+            estimate.max_inhabited_time.to_string(),
+            format!(
+                "{} ({:.0}%)",
+                estimate.deleted_chunks,
+                percent(estimate.deleted_chunks)
+            ),
+            HumanBytes(estimate.freed_bytes).to_string(),
+            estimate.inhabited_time_share * 100.0
+        );
+    }
+
+    let Some(suggested) = suggestion
+        .thresholds
+        .iter()
+        .find(|estimate| estimate.max_inhabited_time == suggestion.suggested_max_inhabited_time)
+    else {
+        return;
+    };
+    anstream::println!(
+        "Suggested: {}. Players spent {:.1}% of their time in the {:.0}% of chunks it deletes, and it frees about {}.",
+        format!("-m {}", suggested.max_inhabited_time).green(),
+        suggested.inhabited_time_share * 100.0,
+        percent(suggested.deleted_chunks),
+        HumanBytes(suggested.freed_bytes).yellow()
+    );
+}
+
+fn print_duplicates(duplicates: &DuplicateStats) {
+    anstream::println!(
+        "{:>10} {:>12} {:>6} {}",
+        "Chunks".bold(),
+        "Duplicate".bold(),
+        "Empty".bold(),
+        "Example".bold()
+    );
+    for group in &duplicates.largest_groups {
+        anstream::println!(
+            "{:>10} {:>12} {:>6} {} {}, {}",
+            group.chunks,
+            HumanBytes(group.duplicate_bytes).to_string(),
+            if group.empty { "yes" } else { "no" },
+            group.example.dimension,
+            group.example.x,
+            group.example.z
+        );
+    }
+    anstream::println!(
+        "{} of {} chunks are identical to another chunk. Storing them once, e.g. on a deduplicating file system, would save about {} of {}.",
+        duplicates.duplicate_chunks.yellow(),
+        duplicates.total_chunks,
+        HumanBytes(duplicates.duplicate_bytes).green(),
+        HumanBytes(duplicates.total_bytes)
+    );
+}
@@ -0,0 +1,177 @@
+//! Writing a world into a `.tar.zst` archive for cold storage (`lessanvil-cli archive`). Requires
+//! the `archive` feature.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+use indicatif::{HumanBytes, HumanDuration};
+use lessanvil::{lock, Config, InhabitedTime};
+use owo_colors::OwoColorize;
+
+/// Write a world into a reproducible `.tar.zst` archive for long-term storage, optionally pruning
+/// it first.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "archive")]
+pub struct ArchiveArgs {
+    /// the world folder
+    #[argh(option, short = 'w')]
+    world_folder: PathBuf,
+    /// the archive to write, e.g. `world.tar.zst`
+    #[argh(option, short = 'o')]
+    output: PathBuf,
+    /// prune the world before archiving it, deleting the chunks in which players spent at most
+    /// the given time, e.g. `90s`, `5m`, `2h` or `1200t` (game ticks)
+    #[argh(option, short = 'm', from_str_fn(crate::parse_inhabited_time))]
+    max_inhabited_time: Option<InhabitedTime>,
+    /// the Zstandard compression level, from 1 to 22. Default is 19
+    #[argh(option, default = "19")]
+    level: i32,
+    /// the amount of threads used for pruning
+    #[argh(option, short = 't')]
+    thread_count: Option<usize>,
+    /// skip confirmation prompt. Use this with caution!
+    #[argh(switch)]
+    confirm: bool,
+    /// skip all checks for the world being valid. Use this with caution!
+    #[argh(switch)]
+    force: bool,
+}
+
+pub fn run(args: ArchiveArgs) {
+    if !args.force && !crate::is_valid_world(&args.world_folder) {
+        log::error!("Invalid world folder!");
+        process::exit(1);
+    }
+    if !cfg!(feature = "archive") {
+        log::error!("lessanvil-cli was built without the `archive` feature");
+        process::exit(1);
+    }
+    if args.output.exists() {
+        log::error!("{} already exists", args.output.display());
+        process::exit(1);
+    }
+    // the archive must not end up in itself
+    let world_folder = fs::canonicalize(&args.world_folder).unwrap_or(args.world_folder.clone());
+    let output_folder = args
+        .output
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .and_then(|parent| fs::canonicalize(parent).ok())
+        .or_else(|| std::env::current_dir().ok());
+    if output_folder.is_some_and(|folder| folder.starts_with(&world_folder)) {
+        log::error!("The archive has to be written outside of the world folder");
+        process::exit(1);
+    }
+
+    if let Some(max_inhabited_time) = args.max_inhabited_time {
+        if !args.confirm {
+            crate::confirm(&format!(
+                "This tool will delete all chunks with an InhabitedTime of at most {} before archiving the world.",
+                max_inhabited_time
+            ));
+        }
+        let config = Config {
+            world_folder: args.world_folder.clone(),
+            max_inhabited_time,
+            thread_count: args.thread_count.unwrap_or(num_cpus::get()),
+            dry_run: false,
+            ..Default::default()
+        };
+        match lessanvil::execute_blocking(config) {
+            Ok(report) => anstream::println!(
+                "Deleted {} chunks and freed up {}.",
+                report.total_deleted_chunks.yellow(),
+                HumanBytes(report.total_freed_space).yellow()
+            ),
+            Err(err) => {
+                log::error!("{}", err);
+                process::exit(1)
+            }
+        }
+    }
+
+    // keep other runs of lessanvil from modifying the world while it's archived
+    let lock = match lock::WorldLock::acquire(&args.world_folder) {
+        Ok(lock) => lock,
+        Err(err) => {
+            log::error!("{}", err);
+            process::exit(1)
+        }
+    };
+    let start_time = std::time::Instant::now();
+    let result = write_archive(&args.world_folder, &args.output, args.level);
+    drop(lock);
+    match result {
+        Ok(files) => anstream::println!(
+            "Successfully archived {} files into {} ({}) in {}.",
+            files.yellow(),
+            args.output.display(),
+            HumanBytes(fs::metadata(&args.output).map_or(0, |metadata| metadata.len())).yellow(),
+            HumanDuration(start_time.elapsed()).yellow()
+        ),
+        Err(err) => {
+            let _ = fs::remove_file(&args.output);
+            log::error!("Failed to write {}: {}", args.output.display(), err);
+            process::exit(1)
+        }
+    }
+}
+
+/// The files of the world relative to it, sorted so that the archive doesn't depend on the order
+/// the file system lists them in. lessanvil's own files are left out.
+#[cfg_attr(not(feature = "archive"), allow(dead_code))]
+fn world_files(world_folder: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut folders = vec![PathBuf::new()];
+    while let Some(folder) = folders.pop() {
+        for entry in fs::read_dir(world_folder.join(&folder))? {
+            let entry = entry?;
+            let path = folder.join(entry.file_name());
+            if path == Path::new(lock::STATE_FOLDER)
+                || entry
+                    .file_name()
+                    .to_string_lossy()
+                    .ends_with(".lessanvil-tmp")
+            {
+                continue;
+            }
+            if entry.file_type()?.is_dir() {
+                folders.push(path.clone());
+            }
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Streams the world into the archive, every file is read once. Returns the amount of files.
+#[cfg(feature = "archive")]
+fn write_archive(world_folder: &Path, output: &Path, level: i32) -> io::Result<u64> {
+    let name = world_folder
+        .file_name()
+        .map_or(PathBuf::from("world"), PathBuf::from);
+    let files = world_files(world_folder)?;
+
+    let mut encoder = zstd::Encoder::new(fs::File::create(output)?, level)?;
+    encoder.include_checksum(true)?;
+    let mut builder = tar::Builder::new(encoder);
+    // no timestamps, owners or permissions of this machine, so that the same world always results
+    // in the same archive
+    builder.mode(tar::HeaderMode::Deterministic);
+    builder.follow_symlinks(false);
+    builder.append_dir(&name, world_folder)?;
+    for file in &files {
+        builder.append_path_with_name(world_folder.join(file), name.join(file))?;
+    }
+    builder.into_inner()?.finish()?.sync_all()?;
+    Ok(files.len() as u64)
+}
+
+#[cfg(not(feature = "archive"))]
+fn write_archive(_world_folder: &Path, _output: &Path, _level: i32) -> io::Result<u64> {
+    unreachable!("checked by `run`")
+}
@@ -0,0 +1,270 @@
+//! Making way for a server running on the same machine (`--low-priority`): the run pauses while
+//! other processes keep the CPUs busy or, with `--rcon`, while the server's TPS are low, and
+//! resumes once it calmed down.
+
+use std::{
+    fs,
+    io::{self, Read, Write},
+    net::TcpStream,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use indicatif::ProgressBar;
+use lessanvil::{CancelHandle, PauseHandle};
+
+/// How often the load is checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The share of the CPU limit a paused run waits for before resuming, so that it doesn't pause and
+/// resume every few seconds.
+const RESUME_MARGIN: f64 = 0.8;
+
+/// How far above the TPS limit a paused run waits for the server to get before resuming.
+const RESUME_TPS: f64 = 1.0;
+
+pub struct Options {
+    /// The largest share (0 to 1) of the CPUs other processes may use while the run continues.
+    pub max_cpu: f64,
+    /// The address of the server's RCON, e.g. `localhost:25575`.
+    pub rcon: Option<String>,
+    pub rcon_password_file: Option<PathBuf>,
+    /// The lowest TPS of the server at which the run continues.
+    pub min_tps: f64,
+}
+
+/// Watches the load until it's dropped.
+pub struct Monitor {
+    stopped: Arc<AtomicBool>,
+}
+
+impl Drop for Monitor {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Starts watching the load, pausing the execution while it's too high.
+pub fn start(
+    options: Options,
+    pause_handle: PauseHandle,
+    cancel_handle: CancelHandle,
+    progress_bar: ProgressBar,
+) -> Result<Monitor, String> {
+    let password = match &options.rcon_password_file {
+        Some(path) => Some(
+            fs::read_to_string(path)
+                .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?
+                .trim()
+                .to_string(),
+        ),
+        None => None,
+    };
+    let mut rcon = match &options.rcon {
+        Some(address) => Some(
+            Rcon::connect(address, password.as_deref().unwrap_or_default())
+                .map_err(|err| format!("Failed to connect to the RCON at {address}: {err}"))?,
+        ),
+        None => None,
+    };
+    if !cfg!(target_os = "linux") {
+        log::warn!("--low-priority only watches the CPU usage on Linux");
+    }
+
+    let stopped = Arc::new(AtomicBool::new(false));
+    let monitor = Monitor {
+        stopped: stopped.clone(),
+    };
+    thread::spawn(move || {
+        let mut cpu = CpuUsage::read();
+        while !stopped.load(Ordering::Relaxed) && !cancel_handle.is_cancelled() {
+            thread::sleep(CHECK_INTERVAL);
+            let paused = pause_handle.is_paused();
+            let (max_cpu, min_tps) = if paused {
+                (
+                    options.max_cpu * RESUME_MARGIN,
+                    (options.min_tps + RESUME_TPS).min(20.0),
+                )
+            } else {
+                (options.max_cpu, options.min_tps)
+            };
+
+            let mut reason = None;
+            let current = CpuUsage::read();
+            if let (Some(before), Some(after)) = (&cpu, &current) {
+                let usage = after.others_since(before);
+                if usage > max_cpu {
+                    reason = Some(format!(
+                        "other processes use {:.0}% of the CPUs",
+                        usage * 100.0
+                    ));
+                }
+            }
+            cpu = current;
+            if let Some(connection) = &mut rcon {
+                match connection.tps() {
+                    Ok(Some(tps)) if tps < min_tps => {
+                        reason = Some(format!("the server runs at {tps:.1} TPS"));
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        progress_bar.suspend(|| {
+                            log::warn!("Stopped watching the TPS, the RCON failed: {}", err)
+                        });
+                        rcon = None;
+                    }
+                }
+            }
+
+            match (paused, reason) {
+                (false, Some(reason)) => {
+                    progress_bar.suspend(|| anstream::eprintln!("Pausing, {reason}."));
+                    pause_handle.pause();
+                }
+                (true, None) => {
+                    progress_bar.suspend(|| anstream::eprintln!("Resuming."));
+                    pause_handle.resume();
+                }
+                _ => {}
+            }
+        }
+    });
+    Ok(monitor)
+}
+
+/// The CPU time spent by all processes and by lessanvil itself, in clock ticks.
+struct CpuUsage {
+    total: u64,
+    busy: u64,
+    own: u64,
+}
+
+impl CpuUsage {
+    #[cfg(target_os = "linux")]
+    fn read() -> Option<Self> {
+        // cpu  user nice system idle iowait irq softirq steal ...
+        let stat = fs::read_to_string("/proc/stat").ok()?;
+        let times = stat
+            .lines()
+            .next()?
+            .strip_prefix("cpu ")?
+            .split_whitespace()
+            .take(8)
+            .map(|time| time.parse::<u64>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        let total = times.iter().sum::<u64>();
+        let idle = times.get(3)? + times.get(4)?;
+
+        // the fields after the parenthesized name, which may contain spaces, start with the state
+        let own = fs::read_to_string("/proc/self/stat").ok()?;
+        let fields = own
+            .rsplit_once(')')?
+            .1
+            .split_whitespace()
+            .collect::<Vec<_>>();
+        let utime = fields.get(11)?.parse::<u64>().ok()?;
+        let stime = fields.get(12)?.parse::<u64>().ok()?;
+        Some(Self {
+            total,
+            busy: total - idle,
+            own: utime + stime,
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn read() -> Option<Self> {
+        None
+    }
+
+    /// The share of the CPU time used by other processes since the earlier reading.
+    fn others_since(&self, earlier: &Self) -> f64 {
+        let total = self.total.saturating_sub(earlier.total);
+        let busy = self.busy.saturating_sub(earlier.busy);
+        let own = self.own.saturating_sub(earlier.own);
+        if total == 0 {
+            0.0
+        } else {
+            busy.saturating_sub(own) as f64 / total as f64
+        }
+    }
+}
+
+/// A connection to the RCON of a server, see <https://wiki.vg/RCON>.
+struct Rcon {
+    stream: TcpStream,
+}
+
+impl Rcon {
+    const LOGIN: i32 = 3;
+    const COMMAND: i32 = 2;
+
+    fn connect(address: &str, password: &str) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+        let mut rcon = Self { stream };
+        let (id, _) = rcon.request(Self::LOGIN, password)?;
+        if id == -1 {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "wrong password",
+            ));
+        }
+        Ok(rcon)
+    }
+
+    /// The TPS of the last minute according to the `tps` command of Paper, Spigot and similar
+    /// servers. `None` if the server doesn't know the command.
+    fn tps(&mut self) -> io::Result<Option<f64>> {
+        let (_, response) = self.request(Self::COMMAND, "tps")?;
+        // e.g. "§6TPS from last 1m, 5m, 15m: §a20.0, §a20.0, §a20.0"
+        let Some((_, values)) = response.split_once(": ") else {
+            return Ok(None);
+        };
+        let first = strip_formatting(values.split(',').next().unwrap_or_default());
+        Ok(first.trim().trim_start_matches('*').parse().ok())
+    }
+
+    fn request(&mut self, kind: i32, payload: &str) -> io::Result<(i32, String)> {
+        let mut packet = vec![];
+        packet.extend_from_slice(&(10 + payload.len() as i32).to_le_bytes());
+        packet.extend_from_slice(&1i32.to_le_bytes());
+        packet.extend_from_slice(&kind.to_le_bytes());
+        packet.extend_from_slice(payload.as_bytes());
+        packet.extend_from_slice(&[0, 0]);
+        self.stream.write_all(&packet)?;
+
+        let mut length = [0; 4];
+        self.stream.read_exact(&mut length)?;
+        let length = i32::from_le_bytes(length);
+        if !(10..=4096 + 10).contains(&length) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid RCON packet",
+            ));
+        }
+        let mut body = vec![0; length as usize];
+        self.stream.read_exact(&mut body)?;
+        let id = i32::from_le_bytes([body[0], body[1], body[2], body[3]]);
+        let payload = String::from_utf8_lossy(&body[8..body.len() - 2]).into_owned();
+        Ok((id, payload))
+    }
+}
+
+/// Removes the `§` color and formatting codes of a message.
+fn strip_formatting(text: &str) -> String {
+    let mut stripped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
@@ -0,0 +1,541 @@
+//! `lessanvil-cli batch`: processing many worlds with the settings listed in a manifest, e.g. the
+//! nightly maintenance of every server of a host. Every world is processed by its own lessanvil
+//! process, so that the worlds don't share their log and a crash only fails a single world.
+
+use std::{
+    collections::HashSet,
+    env, fs,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Instant,
+};
+
+use argh::FromArgs;
+use owo_colors::OwoColorize;
+use serde_json::{json, Value};
+
+use lessanvil::{Dimension, InhabitedTime};
+
+use crate::Args;
+
+/// Process many worlds with the settings listed in a manifest, a few at a time, writing a log
+/// per world and a summary of all of them.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "batch")]
+pub struct BatchArgs {
+    /// the manifest listing the worlds and their settings (YAML)
+    #[argh(option)]
+    manifest: PathBuf,
+    /// how many worlds are processed at the same time (default: 1)
+    #[argh(option, default = "1")]
+    concurrency: usize,
+    /// the folder the log of every world is written into (default: `logs` next to the manifest)
+    #[argh(option)]
+    log_dir: Option<PathBuf>,
+    /// write the summary of all worlds as JSON into this file instead of printing it
+    #[argh(option)]
+    summary: Option<PathBuf>,
+    /// modify the worlds. Without it, every world is only a dry run
+    #[argh(switch)]
+    apply: bool,
+}
+
+/// The manifest, e.g.
+///
+/// ```yaml
+/// defaults:
+///   max-inhabited-time: 5m
+///   protections:
+///     players: 8
+/// worlds:
+///   - path: /srv/servers/survival/world
+///   - name: creative
+///     path: /srv/servers/creative/world
+///     max-inhabited-time: 30s
+///     dimensions: [overworld]
+/// ```
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    #[serde(default)]
+    defaults: Policy,
+    worlds: Vec<WorldEntry>,
+}
+
+/// The settings of a run, either the defaults of all worlds or the overrides of one.
+#[derive(serde::Deserialize, Default)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct Policy {
+    max_inhabited_time: Option<InhabitedTime>,
+    /// Only these dimensions, all by default.
+    dimensions: Option<Vec<Dimension>>,
+    #[serde(default)]
+    protections: Protections,
+    #[serde(default)]
+    throttle: Throttle,
+    /// The threads of the world's process. Defaults to the CPUs shared by the worlds processed at
+    /// the same time.
+    threads: Option<usize>,
+    /// Further arguments of a run, e.g. `--recompress zlib`. The ones of a world are added to the
+    /// default ones.
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+/// See the protection options of a run. Every field of a world overrides the default one.
+#[derive(serde::Deserialize, Default)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct Protections {
+    /// `--protect-players`
+    players: Option<u32>,
+    /// `--player-positions`
+    player_positions: Option<Vec<PathBuf>>,
+    /// `--waypoints`
+    waypoints: Option<Vec<PathBuf>>,
+    /// `--waypoint-radius`
+    waypoint_radius: Option<u32>,
+    /// `--protected-areas`
+    areas: Option<Vec<PathBuf>>,
+    /// `false` for `--no-protect-whitelist`
+    whitelist: Option<bool>,
+}
+
+/// `--low-priority` and its options. Every field of a world overrides the default one.
+#[derive(serde::Deserialize, Default)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct Throttle {
+    /// `--max-cpu`, setting it enables `--low-priority`
+    max_cpu: Option<u8>,
+    /// `--rcon`, setting it enables `--low-priority`
+    rcon: Option<String>,
+    rcon_password_file: Option<PathBuf>,
+    min_tps: Option<f64>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct WorldEntry {
+    /// The name of the world in the summary and of its log file. Defaults to the name of the
+    /// folder containing the world.
+    name: Option<String>,
+    path: PathBuf,
+    max_inhabited_time: Option<InhabitedTime>,
+    dimensions: Option<Vec<Dimension>>,
+    #[serde(default)]
+    protections: Protections,
+    #[serde(default)]
+    throttle: Throttle,
+    threads: Option<usize>,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+impl Policy {
+    /// The policy with the settings of the world overriding these ones.
+    fn apply(&self, world: &WorldEntry) -> Policy {
+        let defaults = &self.protections;
+        let protections = &world.protections;
+        let throttle = &world.throttle;
+        Policy {
+            max_inhabited_time: world.max_inhabited_time.or(self.max_inhabited_time),
+            dimensions: world.dimensions.clone().or(self.dimensions.clone()),
+            protections: Protections {
+                players: protections.players.or(defaults.players),
+                player_positions: protections
+                    .player_positions
+                    .clone()
+                    .or(defaults.player_positions.clone()),
+                waypoints: protections.waypoints.clone().or(defaults.waypoints.clone()),
+                waypoint_radius: protections.waypoint_radius.or(defaults.waypoint_radius),
+                areas: protections.areas.clone().or(defaults.areas.clone()),
+                whitelist: protections.whitelist.or(defaults.whitelist),
+            },
+            throttle: Throttle {
+                max_cpu: throttle.max_cpu.or(self.throttle.max_cpu),
+                rcon: throttle.rcon.clone().or(self.throttle.rcon.clone()),
+                rcon_password_file: throttle
+                    .rcon_password_file
+                    .clone()
+                    .or(self.throttle.rcon_password_file.clone()),
+                min_tps: throttle.min_tps.or(self.throttle.min_tps),
+            },
+            threads: world.threads.or(self.threads),
+            args: self.args.iter().chain(&world.args).cloned().collect(),
+        }
+    }
+
+    /// The arguments of a run of the world with this policy. The problems are returned as
+    /// `<setting>: <problem>`.
+    fn run_args(&self, path: &Path, default_threads: usize) -> Result<Vec<String>, Vec<String>> {
+        let mut problems = vec![];
+        let mut args = vec!["--world-folder".to_string(), path.display().to_string()];
+        match self.max_inhabited_time {
+            Some(time) => args.extend(["--max-inhabited-time".to_string(), time.to_string()]),
+            None => problems
+                .push("max-inhabited-time: missing, set it here or in the defaults".to_string()),
+        }
+        args.extend([
+            "--thread-count".to_string(),
+            self.threads.unwrap_or(default_threads).to_string(),
+        ]);
+        if let Some(dimensions) = &self.dimensions {
+            if dimensions.is_empty() {
+                problems
+                    .push("dimensions: empty, leave it out to process all dimensions".to_string());
+            }
+            for dimension in dimensions {
+                args.extend(["--dimension".to_string(), dimension.to_string()]);
+            }
+        }
+
+        let protections = &self.protections;
+        if let Some(radius) = protections.players {
+            args.extend(["--protect-players".to_string(), radius.to_string()]);
+        }
+        for path in protections.player_positions.iter().flatten() {
+            args.extend(["--player-positions".to_string(), path.display().to_string()]);
+        }
+        for path in protections.waypoints.iter().flatten() {
+            args.extend(["--waypoints".to_string(), path.display().to_string()]);
+        }
+        if let Some(radius) = protections.waypoint_radius {
+            args.extend(["--waypoint-radius".to_string(), radius.to_string()]);
+        }
+        for path in protections.areas.iter().flatten() {
+            args.extend(["--protected-areas".to_string(), path.display().to_string()]);
+        }
+        if protections.whitelist == Some(false) {
+            args.push("--no-protect-whitelist".to_string());
+        }
+        let files = [
+            (
+                "protections.player-positions",
+                protections.player_positions.iter().flatten(),
+            ),
+            (
+                "protections.waypoints",
+                protections.waypoints.iter().flatten(),
+            ),
+            ("protections.areas", protections.areas.iter().flatten()),
+        ];
+        for (setting, paths) in files {
+            for path in paths.filter(|path| !path.is_file()) {
+                problems.push(format!("{setting}: {} doesn't exist", path.display()));
+            }
+        }
+
+        let throttle = &self.throttle;
+        if throttle.max_cpu.is_some() || throttle.rcon.is_some() {
+            args.push("--low-priority".to_string());
+        } else if throttle.rcon_password_file.is_some() || throttle.min_tps.is_some() {
+            problems.push("throttle: rcon-password-file and min-tps require rcon".to_string());
+        }
+        if let Some(max_cpu) = throttle.max_cpu {
+            if !(1..=100).contains(&max_cpu) {
+                problems.push(format!(
+                    "throttle.max-cpu: {max_cpu} isn't between 1 and 100"
+                ));
+            }
+            args.extend(["--max-cpu".to_string(), max_cpu.to_string()]);
+        }
+        if let Some(rcon) = &throttle.rcon {
+            args.extend(["--rcon".to_string(), rcon.clone()]);
+        }
+        if let Some(path) = &throttle.rcon_password_file {
+            args.extend([
+                "--rcon-password-file".to_string(),
+                path.display().to_string(),
+            ]);
+        }
+        if let Some(min_tps) = throttle.min_tps {
+            if !(0.0..=20.0).contains(&min_tps) {
+                problems.push(format!(
+                    "throttle.min-tps: {min_tps} isn't between 0 and 20"
+                ));
+            }
+            args.extend(["--min-tps".to_string(), min_tps.to_string()]);
+        }
+        if self.threads == Some(0) {
+            problems.push("threads: must be at least 1".to_string());
+        }
+
+        args.extend(self.args.iter().cloned());
+        if problems.is_empty() {
+            Ok(args)
+        } else {
+            Err(problems)
+        }
+    }
+}
+
+/// A world ready to be processed.
+struct Job {
+    name: String,
+    path: PathBuf,
+    args: Vec<String>,
+}
+
+pub fn run(args: BatchArgs) {
+    let jobs = match read_manifest(&args) {
+        Ok(jobs) => jobs,
+        Err(err) => {
+            log::error!("{}: {}", args.manifest.display(), err);
+            process::exit(1)
+        }
+    };
+    let log_dir = args.log_dir.clone().unwrap_or_else(|| {
+        args.manifest
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join("logs")
+    });
+    if let Err(err) = fs::create_dir_all(&log_dir) {
+        log::error!("Failed to create {}: {}", log_dir.display(), err);
+        process::exit(1)
+    }
+    let binary = env::current_exe().unwrap_or_else(|err| {
+        log::error!("Failed to locate the lessanvil binary: {}", err);
+        process::exit(1)
+    });
+
+    // Ctrl+C reaches the worlds being processed as well, they stop on their own
+    let stopping = Arc::new(AtomicBool::new(false));
+    let _ = ctrlc::set_handler({
+        let stopping = stopping.clone();
+        move || {
+            if !stopping.swap(true, Ordering::Relaxed) {
+                anstream::eprintln!("Stopping after the worlds currently being processed.");
+            }
+        }
+    });
+
+    let started = Instant::now();
+    let total = jobs.len();
+    let queue = Mutex::new(jobs.into_iter().enumerate());
+    let results = Mutex::new(vec![Value::Null; total]);
+    let concurrency = args.concurrency.clamp(1, total.max(1));
+    thread::scope(|scope| {
+        for _ in 0..concurrency {
+            scope.spawn(|| loop {
+                if stopping.load(Ordering::Relaxed) {
+                    break;
+                }
+                let Some((index, job)) = queue.lock().unwrap().next() else {
+                    break;
+                };
+                anstream::eprintln!("Processing {} ({}/{})", job.name.yellow(), index + 1, total);
+                let result = process_world(&binary, &job, &log_dir);
+                anstream::eprintln!("{}: {}", job.name, result["status"].as_str().unwrap_or("?"));
+                results.lock().unwrap()[index] = result;
+            });
+        }
+    });
+
+    let worlds = results.into_inner().unwrap();
+    let sum = |key: &str| {
+        worlds
+            .iter()
+            .filter_map(|world| world["report"][key].as_u64())
+            .sum::<u64>()
+    };
+    let count = |status: &str| {
+        worlds
+            .iter()
+            .filter(|world| world["status"] == status)
+            .count()
+    };
+    let summary = json!({
+        "dryRun": !args.apply,
+        "timeTakenSecs": started.elapsed().as_secs(),
+        "finished": count("finished"),
+        "cancelled": count("cancelled"),
+        "failed": count("failed"),
+        "skipped": worlds.iter().filter(|world| world.is_null()).count(),
+        "totalDeletedChunks": sum("totalDeletedChunks"),
+        "totalFreedSpace": sum("totalFreedSpace"),
+        "worlds": worlds,
+    });
+    let summary_json = serde_json::to_string_pretty(&summary).unwrap();
+    match &args.summary {
+        Some(path) => {
+            if let Err(err) = fs::write(path, summary_json) {
+                log::error!("Failed to write {}: {}", path.display(), err);
+                process::exit(1)
+            }
+        }
+        None => anstream::println!("{}", summary_json),
+    }
+    let all_finished = worlds.iter().all(|world| world["status"] == "finished");
+    process::exit(if all_finished { 0 } else { 1 })
+}
+
+/// Reads the manifest and checks the settings of every world before any is processed, reporting
+/// all problems at once.
+fn read_manifest(args: &BatchArgs) -> Result<Vec<Job>, String> {
+    let content = fs::read_to_string(&args.manifest).map_err(|err| err.to_string())?;
+    // the errors name the setting and its line, e.g. `worlds[3].max-inhabited-time: ... at line 12`
+    let manifest: Manifest = serde_yaml::from_str(&content).map_err(|err| err.to_string())?;
+    if manifest.worlds.is_empty() {
+        return Err("no worlds listed".to_string());
+    }
+    let cpus = num_cpus::get();
+    let default_threads = (cpus / args.concurrency.clamp(1, manifest.worlds.len())).max(1);
+
+    let mut names = HashSet::new();
+    let mut jobs = vec![];
+    let mut problems = vec![];
+    for (index, world) in manifest.worlds.iter().enumerate() {
+        let name = world.name.clone().unwrap_or_else(|| {
+            let folder = world.path.parent().and_then(Path::file_name);
+            folder
+                .or(world.path.file_name())
+                .map_or_else(|| index.to_string(), |name| name.to_string_lossy().into())
+        });
+        let location = format!("worlds[{index}] ({name})");
+        if !names.insert(name.clone()) {
+            problems.push(format!("{location}.name: used by another world"));
+        }
+        if !world.path.is_dir() {
+            problems.push(format!(
+                "{location}.path: {} isn't a folder",
+                world.path.display()
+            ));
+        }
+
+        let mut run_args = match manifest
+            .defaults
+            .apply(world)
+            .run_args(&world.path, default_threads)
+        {
+            Ok(run_args) => run_args,
+            Err(world_problems) => {
+                problems.extend(
+                    world_problems
+                        .into_iter()
+                        .map(|problem| format!("{location}.{problem}")),
+                );
+                continue;
+            }
+        };
+        // nobody can confirm the worlds of a batch
+        run_args.extend(["--confirm".to_string(), "--json".to_string()]);
+        if args.apply {
+            run_args.push("--apply".to_string());
+        }
+        let strs = run_args.iter().map(String::as_str).collect::<Vec<_>>();
+        match Args::from_args(&["lessanvil-cli"], &strs) {
+            Ok(parsed) if parsed.command.is_some() || parsed.worlds_root.is_some() => problems
+                .push(format!(
+                    "{location}.args: only the arguments of a prune of a single world are supported"
+                )),
+            Ok(_) => {}
+            Err(exit) => problems.push(format!("{location}.args: {}", exit.output.trim())),
+        }
+        jobs.push(Job {
+            name,
+            path: world.path.clone(),
+            args: run_args,
+        });
+    }
+    if problems.is_empty() {
+        Ok(jobs)
+    } else {
+        Err(format!("invalid settings\n{}", problems.join("\n")))
+    }
+}
+
+/// Processes the world in a child process writing into its log, returning its entry of the
+/// summary.
+fn process_world(binary: &Path, job: &Job, log_dir: &Path) -> Value {
+    let log_path = log_dir.join(format!("{}.log", sanitize(&job.name)));
+    let started = Instant::now();
+    let entry = |status: &str, report: Option<Value>, error: Option<String>| {
+        json!({
+            "name": job.name,
+            "path": job.path,
+            "status": status,
+            "timeTakenSecs": started.elapsed().as_secs(),
+            "log": log_path,
+            "report": report,
+            "error": error,
+        })
+    };
+
+    let log = match fs::File::create(&log_path) {
+        Ok(log) => log,
+        Err(err) => {
+            return entry(
+                "failed",
+                None,
+                Some(format!("failed to create the log: {err}")),
+            )
+        }
+    };
+    let stderr = match log.try_clone() {
+        Ok(stderr) => stderr,
+        Err(err) => return entry("failed", None, Some(err.to_string())),
+    };
+    let child = Command::new(binary)
+        .args(&job.args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(stderr)
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(err) => return entry("failed", None, Some(format!("failed to start: {err}"))),
+    };
+
+    // the report is the last line of the output, the progress lines before it only go into the log
+    let mut last_update = None;
+    if let Some(stdout) = child.stdout.take() {
+        let mut log = &log;
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            let _ = writeln!(log, "{line}");
+            last_update = serde_json::from_str::<Value>(&line).ok();
+        }
+    }
+    let status = match child.wait() {
+        Ok(status) => status,
+        Err(err) => return entry("failed", None, Some(err.to_string())),
+    };
+
+    match last_update {
+        Some(Value::Object(mut update)) if update.contains_key("finished") && status.success() => {
+            entry(
+                "finished",
+                update["finished"].get_mut("report").map(Value::take),
+                None,
+            )
+        }
+        Some(Value::Object(mut update)) if update.contains_key("cancelled") => entry(
+            "cancelled",
+            update["cancelled"].get_mut("report").map(Value::take),
+            None,
+        ),
+        _ => entry(
+            "failed",
+            None,
+            Some(format!("exited with {status}, see the log")),
+        ),
+    }
+}
+
+/// The name as a file name.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
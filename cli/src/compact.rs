@@ -0,0 +1,102 @@
+use std::{
+    path::{Path, PathBuf},
+    process,
+};
+
+use indicatif::{HumanBytes, HumanDuration};
+use lessanvil::analysis::{self, DeadSpace};
+use owo_colors::OwoColorize;
+
+/// The amount of regions listed by `print_dead_space`.
+const LISTED_REGIONS: usize = 10;
+
+/// Release the space in the region files that no chunk occupies by rewriting them, without
+/// deleting any chunk. Only shows how much it would release unless --apply is passed.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "compact")]
+pub struct CompactArgs {
+    /// the world to compact
+    #[argh(positional)]
+    world_folder: PathBuf,
+    /// rewrite the region files. Without it nothing is changed
+    #[argh(switch)]
+    apply: bool,
+    /// skip confirmation prompt. Use this with caution!
+    #[argh(switch)]
+    confirm: bool,
+    /// skip all checks for the world being valid. Use this with caution!
+    #[argh(switch)]
+    force: bool,
+    /// whether the output should be in json
+    #[argh(switch)]
+    json: bool,
+}
+
+pub fn run(args: CompactArgs) {
+    if !args.force && !crate::is_valid_world(&args.world_folder) {
+        log::error!("Invalid world folder!");
+        process::exit(1);
+    }
+
+    if args.apply && !args.confirm {
+        crate::confirm("This tool will rewrite the region files containing unused space.");
+    }
+
+    let start_time = std::time::Instant::now();
+    let result = if args.apply {
+        lessanvil::compact(&args.world_folder)
+    } else {
+        analysis::dead_space(&args.world_folder)
+    };
+    let dead_space = match result {
+        Ok(dead_space) => dead_space,
+        Err(err) => {
+            log::error!("{}", err);
+            process::exit(1)
+        }
+    };
+
+    if args.json {
+        anstream::println!("{}", serde_json::to_string(&dead_space).unwrap());
+    } else if args.apply {
+        anstream::println!(
+            "Released {} in {} region files in {}.",
+            HumanBytes(dead_space.dead_bytes).green(),
+            dead_space.regions.len().yellow(),
+            HumanDuration(start_time.elapsed()).yellow()
+        );
+    } else {
+        print_dead_space(&dead_space, &args.world_folder);
+    }
+}
+
+/// Lists the regions with the most dead space and how much compacting the world releases.
+pub fn print_dead_space(dead_space: &DeadSpace, world_folder: &Path) {
+    if dead_space.dead_bytes == 0 {
+        anstream::println!(
+            "No space to release, the chunks of all {} region files are stored right after each other.",
+            dead_space.total_regions
+        );
+        return;
+    }
+    anstream::println!("{:>12} {}", "Dead space".bold(), "Region".bold());
+    for region in dead_space.regions.iter().take(LISTED_REGIONS) {
+        anstream::println!(
+            "{:>12} {}",
+            HumanBytes(region.dead_bytes).to_string(),
+            region
+                .path
+                .strip_prefix(world_folder)
+                .unwrap_or(&region.path)
+                .display()
+        );
+    }
+    anstream::println!(
+        "{} of {} region files contain {} no chunk occupies, {:.1}% of their {}. `compact --apply` releases it without deleting any chunk.",
+        dead_space.regions.len().yellow(),
+        dead_space.total_regions,
+        HumanBytes(dead_space.dead_bytes).green(),
+        dead_space.dead_bytes as f64 / dead_space.total_bytes.max(1) as f64 * 100.0,
+        HumanBytes(dead_space.total_bytes)
+    );
+}
@@ -0,0 +1,287 @@
+use std::fmt::Write;
+
+use argh::{ArgsInfo, CommandInfoWithArgs, FlagInfoKind};
+
+use crate::Args;
+
+/// Print a shell completion script, e.g. `lessanvil-cli completions bash >
+/// /usr/share/bash-completion/completions/lessanvil-cli`.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "completions")]
+pub struct CompletionsArgs {
+    /// the shell: `bash`, `zsh` or `fish`
+    #[argh(positional, from_str_fn(parse_shell))]
+    shell: Shell,
+}
+
+/// Print the man page, e.g. `lessanvil-cli man > /usr/share/man/man1/lessanvil-cli.1`.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "man")]
+pub struct ManArgs {}
+
+#[derive(Clone, Copy, Debug)]
+enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+const BIN: &str = "lessanvil-cli";
+
+pub fn run(args: CompletionsArgs) {
+    let info = Args::get_args_info();
+    let script = match args.shell {
+        Shell::Bash => bash(&info),
+        Shell::Zsh => zsh(&info),
+        Shell::Fish => fish(&info),
+    };
+    anstream::print!("{}", script);
+}
+
+pub fn run_man(_args: ManArgs) {
+    anstream::print!("{}", man_page(&Args::get_args_info()));
+}
+
+fn parse_shell(value: &str) -> Result<Shell, String> {
+    match value {
+        "bash" => Ok(Shell::Bash),
+        "zsh" => Ok(Shell::Zsh),
+        "fish" => Ok(Shell::Fish),
+        _ => Err(format!("unknown shell `{value}`")),
+    }
+}
+
+/// The first sentence of a description on a single line, short enough for a completion menu.
+fn summary(description: &str) -> String {
+    let description = description.split_whitespace().collect::<Vec<_>>().join(" ");
+    match description.split_once(". ") {
+        Some((first, _)) => first.to_string(),
+        None => description.trim_end_matches('.').to_string(),
+    }
+}
+
+/// The words completed for a command: its flags (long and short) and its subcommands.
+fn words(info: &CommandInfoWithArgs) -> Vec<String> {
+    let mut words = vec![];
+    for flag in info.flags {
+        words.push(flag.long.to_string());
+        if let Some(short) = flag.short {
+            words.push(format!("-{short}"));
+        }
+    }
+    words.extend(info.commands.iter().map(|command| command.name.to_string()));
+    words
+}
+
+fn bash(info: &CommandInfoWithArgs) -> String {
+    let names = info
+        .commands
+        .iter()
+        .map(|command| command.name)
+        .collect::<Vec<_>>()
+        .join("|");
+    let mut script = String::new();
+    writeln!(script, "_lessanvil_cli() {{").unwrap();
+    writeln!(
+        script,
+        "    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\" cmd=\"\" i"
+    )
+    .unwrap();
+    writeln!(script, "    for ((i = 1; i < COMP_CWORD; i++)); do").unwrap();
+    writeln!(script, "        case \"${{COMP_WORDS[i]}}\" in").unwrap();
+    writeln!(
+        script,
+        "            {names}) cmd=\"${{COMP_WORDS[i]}}\"; break ;;"
+    )
+    .unwrap();
+    writeln!(script, "        esac").unwrap();
+    writeln!(script, "    done").unwrap();
+    writeln!(script, "    case \"$cmd\" in").unwrap();
+    writeln!(
+        script,
+        "        \"\") opts=\"{}\" ;;",
+        words(info).join(" ")
+    )
+    .unwrap();
+    for command in &info.commands {
+        writeln!(
+            script,
+            "        {}) opts=\"{}\" ;;",
+            command.name,
+            words(&command.command).join(" ")
+        )
+        .unwrap();
+    }
+    writeln!(script, "    esac").unwrap();
+    writeln!(script, "    if [[ \"$cur\" == -* ]]; then").unwrap();
+    writeln!(
+        script,
+        "        COMPREPLY=($(compgen -W \"$opts\" -- \"$cur\"))"
+    )
+    .unwrap();
+    writeln!(script, "    else").unwrap();
+    writeln!(
+        script,
+        "        COMPREPLY=($(compgen -W \"$opts\" -f -- \"$cur\"))"
+    )
+    .unwrap();
+    writeln!(script, "    fi").unwrap();
+    writeln!(script, "}}").unwrap();
+    writeln!(script, "complete -o filenames -F _lessanvil_cli {BIN}").unwrap();
+    script
+}
+
+fn zsh(info: &CommandInfoWithArgs) -> String {
+    let quote = |text: &str| format!("'{}'", text.replace('\'', "'\\''"));
+    let entries = |info: &CommandInfoWithArgs| {
+        let mut entries = vec![];
+        for flag in info.flags {
+            let description = summary(flag.description);
+            entries.push(quote(&format!("{}:{description}", flag.long)));
+            if let Some(short) = flag.short {
+                entries.push(quote(&format!("-{short}:{description}")));
+            }
+        }
+        for command in &info.commands {
+            let description = summary(command.command.description);
+            entries.push(quote(&format!("{}:{description}", command.name)));
+        }
+        entries.join(" ")
+    };
+
+    let names = info
+        .commands
+        .iter()
+        .map(|command| command.name)
+        .collect::<Vec<_>>()
+        .join("|");
+    let mut script = String::new();
+    writeln!(script, "#compdef {BIN}").unwrap();
+    writeln!(script).unwrap();
+    writeln!(script, "_lessanvil_cli() {{").unwrap();
+    writeln!(script, "    local cmd i").unwrap();
+    writeln!(script, "    local -a opts").unwrap();
+    writeln!(script, "    for ((i = 2; i < CURRENT; i++)); do").unwrap();
+    writeln!(script, "        case $words[i] in").unwrap();
+    writeln!(script, "            {names}) cmd=$words[i]; break ;;").unwrap();
+    writeln!(script, "        esac").unwrap();
+    writeln!(script, "    done").unwrap();
+    writeln!(script, "    case $cmd in").unwrap();
+    writeln!(script, "        '') opts=({}) ;;", entries(info)).unwrap();
+    for command in &info.commands {
+        writeln!(
+            script,
+            "        {}) opts=({}) ;;",
+            command.name,
+            entries(&command.command)
+        )
+        .unwrap();
+    }
+    writeln!(script, "    esac").unwrap();
+    writeln!(script, "    _describe 'lessanvil-cli' opts").unwrap();
+    writeln!(script, "    _files").unwrap();
+    writeln!(script, "}}").unwrap();
+    writeln!(script).unwrap();
+    writeln!(script, "_lessanvil_cli \"$@\"").unwrap();
+    script
+}
+
+fn fish(info: &CommandInfoWithArgs) -> String {
+    let quote = |text: &str| format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"));
+    let flags = |script: &mut String, info: &CommandInfoWithArgs, condition: &str| {
+        for flag in info.flags {
+            write!(
+                script,
+                "complete -c {BIN} -n {} -l {}",
+                quote(condition),
+                flag.long.trim_start_matches("--")
+            )
+            .unwrap();
+            if let Some(short) = flag.short {
+                write!(script, " -s {short}").unwrap();
+            }
+            if let FlagInfoKind::Option { .. } = flag.kind {
+                write!(script, " -r").unwrap();
+            }
+            writeln!(script, " -d {}", quote(&summary(flag.description))).unwrap();
+        }
+    };
+
+    let mut script = String::new();
+    flags(&mut script, info, "__fish_use_subcommand");
+    for command in &info.commands {
+        writeln!(
+            script,
+            "complete -c {BIN} -n __fish_use_subcommand -f -a {} -d {}",
+            command.name,
+            quote(&summary(command.command.description))
+        )
+        .unwrap();
+    }
+    for command in &info.commands {
+        let condition = format!("__fish_seen_subcommand_from {}", command.name);
+        flags(&mut script, &command.command, &condition);
+    }
+    script
+}
+
+/// Escapes text for roff.
+fn roff(text: &str) -> String {
+    let text = text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('\\', "\\\\")
+        .replace('-', "\\-");
+    // lines starting with a dot or an apostrophe would be read as requests
+    if text.starts_with(['.', '\'']) {
+        format!("\\&{text}")
+    } else {
+        text
+    }
+}
+
+fn man_page(info: &CommandInfoWithArgs) -> String {
+    let mut page = String::new();
+    writeln!(
+        page,
+        ".TH LESSANVIL\\-CLI 1 \"\" \"{BIN} {}\"",
+        env!("CARGO_PKG_VERSION")
+    )
+    .unwrap();
+    writeln!(page, ".SH NAME").unwrap();
+    writeln!(page, "{} \\- {}", roff(BIN), roff(info.description)).unwrap();
+    writeln!(page, ".SH SYNOPSIS").unwrap();
+    writeln!(page, ".B {}", roff(BIN)).unwrap();
+    writeln!(page, "[\\fIOPTIONS\\fR] [\\fICOMMAND\\fR]").unwrap();
+    writeln!(page, ".SH OPTIONS").unwrap();
+    man_arguments(&mut page, info);
+    writeln!(page, ".SH COMMANDS").unwrap();
+    for command in &info.commands {
+        writeln!(page, ".SS {}", roff(command.name)).unwrap();
+        writeln!(page, "{}", roff(command.command.description)).unwrap();
+        man_arguments(&mut page, &command.command);
+    }
+    page
+}
+
+fn man_arguments(page: &mut String, info: &CommandInfoWithArgs) {
+    for positional in info.positionals {
+        writeln!(page, ".TP").unwrap();
+        writeln!(page, "\\fI{}\\fR", roff(positional.name)).unwrap();
+        writeln!(page, "{}", roff(positional.description)).unwrap();
+    }
+    for flag in info.flags {
+        writeln!(page, ".TP").unwrap();
+        let mut names = String::new();
+        if let Some(short) = flag.short {
+            write!(names, "\\fB\\-{short}\\fR, ").unwrap();
+        }
+        write!(names, "\\fB{}\\fR", roff(flag.long)).unwrap();
+        if let FlagInfoKind::Option { arg_name } = flag.kind {
+            write!(names, " \\fI{}\\fR", roff(arg_name)).unwrap();
+        }
+        writeln!(page, "{names}").unwrap();
+        writeln!(page, "{}", roff(flag.description)).unwrap();
+    }
+}
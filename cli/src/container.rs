@@ -0,0 +1,62 @@
+use std::{
+    fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+};
+
+/// Whether a user can answer prompts. Not the case e.g. in cron jobs or containers started
+/// without `-it`, where prompts are skipped or refused instead of failing halfway.
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal() && io::stderr().is_terminal()
+}
+
+/// The worlds directly inside the folder, sorted by name.
+pub fn find_worlds(root: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut worlds = vec![];
+    for entry in fs::read_dir(root)? {
+        let path = entry?.path();
+        if path.is_dir() && crate::is_valid_world(&path) {
+            worlds.push(path);
+        }
+    }
+    worlds.sort();
+    Ok(worlds)
+}
+
+/// Explains a permission error caused by running as another user than the owner of the world,
+/// as it happens when a container mounts a folder of the host.
+#[cfg(unix)]
+pub fn ownership_hint(world_folder: &Path) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+
+    let owner = fs::metadata(world_folder).ok()?;
+    // the entry of the process itself belongs to the user it runs as
+    let user = fs::metadata("/proc/self").ok()?.uid();
+    (owner.uid() != user).then(|| {
+        format!(
+            "{} belongs to user {} while lessanvil runs as user {}. Run it as the owner of the world, e.g. with `docker run --user {}:{}`.",
+            world_folder.display(),
+            owner.uid(),
+            user,
+            owner.uid(),
+            owner.gid()
+        )
+    })
+}
+
+#[cfg(not(unix))]
+pub fn ownership_hint(_: &Path) -> Option<String> {
+    None
+}
+
+/// Exits the process with an error if nobody can answer a prompt, instead of waiting for input
+/// that never comes.
+pub fn require_terminal(alternative: &str) {
+    if !is_interactive() {
+        log::error!(
+            "Not attached to a terminal to ask for input! {}",
+            alternative
+        );
+        std::process::exit(1);
+    }
+}
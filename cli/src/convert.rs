@@ -0,0 +1,122 @@
+use std::{path::PathBuf, process};
+
+use indicatif::HumanDuration;
+use owo_colors::OwoColorize;
+
+/// Convert the region files of a world between Anvil and the Linear format of some server forks
+/// (e.g. LinearPaper). Prune a Linear world by converting it to Anvil and back afterwards. Only
+/// shows how many region files would be converted unless --apply is passed.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "convert")]
+pub struct ConvertArgs {
+    /// the world to convert. The nether and the end next to it (`world_nether`, `world_the_end`)
+    /// are converted as well
+    #[argh(positional)]
+    world_folder: PathBuf,
+    /// the format to convert to: `anvil` or `linear`. Only the chunks are converted to Linear,
+    /// entity and POI data stays in Anvil files
+    #[argh(option, from_str_fn(parse_format))]
+    to: Format,
+    /// convert the region files. Without it nothing is changed
+    #[argh(switch)]
+    apply: bool,
+    /// only show how many region files would be converted, the default without --apply
+    #[argh(switch)]
+    dry_run: bool,
+    /// skip confirmation prompt. Use this with caution!
+    #[argh(switch)]
+    confirm: bool,
+    /// skip all checks for the world being valid. Use this with caution!
+    #[argh(switch)]
+    force: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(not(feature = "linear"), allow(dead_code))]
+enum Format {
+    Anvil,
+    Linear,
+}
+
+fn parse_format(value: &str) -> Result<Format, String> {
+    match value {
+        "anvil" => Ok(Format::Anvil),
+        "linear" => Ok(Format::Linear),
+        _ => Err("expected `anvil` or `linear`".to_string()),
+    }
+}
+
+pub fn run(args: ConvertArgs) {
+    if !args.force && !crate::is_valid_world(&args.world_folder) {
+        log::error!("Invalid world folder!");
+        process::exit(1);
+    }
+
+    let apply = crate::applies(args.apply, args.dry_run);
+    if !apply {
+        match files_to_convert(&args.world_folder, args.to) {
+            Ok(files) => anstream::println!(
+                "{} region files would be converted. Pass --apply to convert them.",
+                files.yellow()
+            ),
+            Err(err) => {
+                log::error!("{}", err);
+                process::exit(1)
+            }
+        }
+        return;
+    }
+    if !args.confirm {
+        crate::confirm("This tool will rewrite every region file of the world. Make sure the server is stopped and supports the format.");
+    }
+
+    let start_time = std::time::Instant::now();
+    match convert(&args.world_folder, args.to) {
+        Ok((regions, chunks)) => anstream::println!(
+            "Successfully converted {} chunks in {} files in {}.",
+            chunks.yellow(),
+            regions.yellow(),
+            HumanDuration(start_time.elapsed()).yellow()
+        ),
+        Err(err) => {
+            log::error!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+/// Returns the amount of region files that would be converted.
+#[cfg(feature = "linear")]
+fn files_to_convert(world_folder: &std::path::Path, to: Format) -> Result<usize, String> {
+    lessanvil::linear::files_to_convert(world_folder, to.into())
+        .map(|files| files.len())
+        .map_err(|err| err.to_string())
+}
+
+/// Returns the amount of converted regions and chunks.
+#[cfg(feature = "linear")]
+fn convert(world_folder: &std::path::Path, to: Format) -> Result<(u64, u64), String> {
+    lessanvil::linear::convert(world_folder, to.into())
+        .map(|report| (report.converted_regions, report.converted_chunks))
+        .map_err(|err| err.to_string())
+}
+
+#[cfg(feature = "linear")]
+impl From<Format> for lessanvil::linear::RegionFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Anvil => Self::Anvil,
+            Format::Linear => Self::Linear,
+        }
+    }
+}
+
+#[cfg(not(feature = "linear"))]
+fn files_to_convert(_world_folder: &std::path::Path, _to: Format) -> Result<usize, String> {
+    Err("lessanvil-cli was built without the `linear` feature".to_string())
+}
+
+#[cfg(not(feature = "linear"))]
+fn convert(_world_folder: &std::path::Path, _to: Format) -> Result<(u64, u64), String> {
+    Err("lessanvil-cli was built without the `linear` feature".to_string())
+}
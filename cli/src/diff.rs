@@ -0,0 +1,55 @@
+use std::{path::PathBuf, process};
+
+use lessanvil::ChunkPos;
+use owo_colors::OwoColorize;
+
+/// Report the chunks added, removed and changed between two copies of a world.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "diff")]
+pub struct DiffArgs {
+    /// the first world, e.g. a backup from before pruning
+    #[argh(positional)]
+    world_a: PathBuf,
+    /// the second world
+    #[argh(positional)]
+    world_b: PathBuf,
+    /// list every differing chunk instead of just the totals
+    #[argh(switch)]
+    list: bool,
+    /// whether the output should be in json
+    #[argh(switch)]
+    json: bool,
+}
+
+pub fn run(args: DiffArgs) {
+    let diff = match lessanvil::diff::diff(&args.world_a, &args.world_b) {
+        Ok(diff) => diff,
+        Err(err) => {
+            log::error!("{}", err);
+            process::exit(1)
+        }
+    };
+
+    if args.json {
+        anstream::println!("{}", serde_json::to_string(&diff).unwrap());
+        return;
+    }
+
+    if args.list {
+        let print = |sign: &str, chunks: &[ChunkPos]| {
+            for chunk in chunks {
+                anstream::println!("{} {} {} {}", sign, chunk.dimension, chunk.x, chunk.z);
+            }
+        };
+        print("+", &diff.added);
+        print("-", &diff.removed);
+        print("~", &diff.changed);
+    }
+    anstream::println!(
+        "{} chunks added, {} removed, {} changed and {} unchanged.",
+        diff.added.len().green(),
+        diff.removed.len().red(),
+        diff.changed.len().yellow(),
+        diff.unchanged
+    );
+}
@@ -0,0 +1,155 @@
+//! Mailing the report of a run (`--email-to`), for scheduled runs nobody watches. Sending requires
+//! the `email` feature.
+
+use std::{fs, path::Path};
+
+use indicatif::HumanBytes;
+use lessanvil::RunId;
+
+use crate::summary::Summary;
+
+/// The SMTP server and credentials, read from the file given with `--email-config`.
+#[cfg_attr(not(feature = "email"), allow(dead_code))]
+pub struct Mailer {
+    to: Vec<String>,
+    from: String,
+    server: String,
+    port: Option<u16>,
+    encryption: Encryption,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(not(feature = "email"), allow(dead_code))]
+enum Encryption {
+    StartTls,
+    Tls,
+    None,
+}
+
+impl Mailer {
+    /// Reads the config, one `key = value` per line: `server`, `from` and optionally `port`,
+    /// `encryption` (`starttls`, `tls` or `none`), `username` and `password`.
+    pub fn new(to: Vec<String>, config: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(config)
+            .map_err(|err| format!("Failed to read {}: {}", config.display(), err))?;
+        let mut mailer = Mailer {
+            to,
+            from: String::new(),
+            server: String::new(),
+            port: None,
+            encryption: Encryption::StartTls,
+            username: None,
+            password: None,
+        };
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |message: &str| format!("{}:{}: {}", config.display(), i + 1, message);
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(invalid("expected `key = value`"));
+            };
+            let value = value.trim().to_string();
+            match key.trim() {
+                "server" => mailer.server = value,
+                "port" => mailer.port = Some(value.parse().map_err(|_| invalid("invalid port"))?),
+                "encryption" => {
+                    mailer.encryption = match value.as_str() {
+                        "starttls" => Encryption::StartTls,
+                        "tls" => Encryption::Tls,
+                        "none" => Encryption::None,
+                        _ => return Err(invalid("expected `starttls`, `tls` or `none`")),
+                    }
+                }
+                "username" => mailer.username = Some(value),
+                "password" => mailer.password = Some(value),
+                "from" => mailer.from = value,
+                key => return Err(invalid(&format!("unknown key `{key}`"))),
+            }
+        }
+        if mailer.server.is_empty() || mailer.from.is_empty() {
+            return Err(format!("{} needs a `server` and `from`", config.display()));
+        }
+        if !cfg!(feature = "email") {
+            return Err("lessanvil-cli was built without the `email` feature".to_string());
+        }
+        Ok(mailer)
+    }
+
+    /// Mails the summary. Failing to send it only logs an error, the run itself succeeded.
+    pub fn send_summary(&self, summary: &Summary) {
+        let world = summary.world_folder.display();
+        let subject = match (summary.finished, summary.dry_run) {
+            (true, false) => format!(
+                "lessanvil freed {} in {}",
+                HumanBytes(summary.freed_space),
+                world
+            ),
+            (true, true) => format!("lessanvil dry run of {} finished", world),
+            (false, _) => format!("lessanvil run on {} was aborted", world),
+        };
+        if let Err(err) = self.send(&subject, &summary.to_text()) {
+            log::error!("Failed to mail the report: {}", err);
+        }
+    }
+
+    /// Mails an error that kept the run from starting.
+    pub fn send_error(&self, world_folder: &Path, run_id: RunId, error: &str) {
+        let subject = format!("lessanvil run on {} failed", world_folder.display());
+        let body = format!("Run {run_id} failed to start:\n\n{error}\n");
+        if let Err(err) = self.send(&subject, &body) {
+            log::error!("Failed to mail the error: {}", err);
+        }
+    }
+
+    #[cfg(feature = "email")]
+    fn send(&self, subject: &str, body: &str) -> Result<(), String> {
+        use lettre::{
+            message::header::ContentType, transport::smtp::authentication::Credentials, Message,
+            SmtpTransport, Transport,
+        };
+
+        let mut message = Message::builder()
+            .from(
+                self.from
+                    .parse()
+                    .map_err(|err| format!("invalid from: {err}"))?,
+            )
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            message = message.to(to
+                .parse()
+                .map_err(|err| format!("invalid address {to}: {err}"))?);
+        }
+        let message = message
+            .body(body.to_string())
+            .map_err(|err| err.to_string())?;
+
+        let mut transport = match self.encryption {
+            Encryption::StartTls => SmtpTransport::starttls_relay(&self.server),
+            Encryption::Tls => SmtpTransport::relay(&self.server),
+            Encryption::None => Ok(SmtpTransport::builder_dangerous(&self.server)),
+        }
+        .map_err(|err| err.to_string())?;
+        if let Some(port) = self.port {
+            transport = transport.port(port);
+        }
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        transport
+            .build()
+            .send(&message)
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+
+    #[cfg(not(feature = "email"))]
+    fn send(&self, _subject: &str, _body: &str) -> Result<(), String> {
+        unreachable!("checked by `Mailer::new`")
+    }
+}
@@ -0,0 +1,74 @@
+use std::{path::PathBuf, process};
+
+use indicatif::HumanDuration;
+use lessanvil::protection::ProtectedArea;
+use lessanvil::transfer::{self, Selection};
+use owo_colors::OwoColorize;
+
+/// Write the chunks inside the given areas into a new world.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "extract")]
+pub struct ExtractArgs {
+    /// the world to extract chunks from
+    #[argh(positional)]
+    source: PathBuf,
+    /// the folder of the new world. Must not exist yet
+    #[argh(positional)]
+    destination: PathBuf,
+    /// an area to extract as `dimension,x1,z1,x2,z2` in block coordinates. Can be given
+    /// multiple times
+    #[argh(option, from_str_fn(parse_area))]
+    area: Vec<ProtectedArea>,
+    /// a CSV file with the `dimension,x,z` chunk coordinates to extract
+    #[argh(option)]
+    chunks: Option<PathBuf>,
+}
+
+pub fn run(args: ExtractArgs) {
+    let selection = match (args.area.is_empty(), &args.chunks) {
+        (false, None) => Selection::Areas(args.area),
+        (true, Some(path)) => match transfer::chunks_from_csv(path) {
+            Ok(chunks) => Selection::Chunks(chunks),
+            Err(err) => {
+                log::error!("{}", err);
+                process::exit(1)
+            }
+        },
+        _ => {
+            log::error!("Exactly one of --area and --chunks is required!");
+            process::exit(1)
+        }
+    };
+
+    let start_time = std::time::Instant::now();
+    match transfer::extract(&args.source, &args.destination, &selection) {
+        Ok(report) => anstream::println!(
+            "Successfully extracted {} chunks from {} files in {}.",
+            report.copied_chunks.yellow(),
+            report.total_regions.yellow(),
+            HumanDuration(start_time.elapsed()).yellow()
+        ),
+        Err(err) => {
+            log::error!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+fn parse_area(value: &str) -> Result<ProtectedArea, String> {
+    let [dimension, x1, z1, x2, z2] = value.split(',').map(str::trim).collect::<Vec<_>>()[..]
+    else {
+        return Err("expected `dimension,x1,z1,x2,z2`".to_string());
+    };
+    let coordinate = |value: &str| {
+        value
+            .parse::<i32>()
+            .map_err(|_| format!("invalid coordinate `{value}`"))
+    };
+
+    Ok(ProtectedArea::Rectangle {
+        dimension: dimension.parse().map_err(|err| format!("{err}"))?,
+        from: (coordinate(x1)?, coordinate(z1)?),
+        to: (coordinate(x2)?, coordinate(z2)?),
+    })
+}
@@ -0,0 +1,85 @@
+use std::{path::PathBuf, process};
+
+use indicatif::HumanBytes;
+use owo_colors::OwoColorize;
+
+use crate::results_db;
+
+/// Show the runs recorded with --results-db: the latest runs, the space freed per week and how
+/// the thresholds used compare. Requires the `results-db` feature.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "history")]
+pub struct HistoryArgs {
+    /// the results database
+    #[argh(positional)]
+    database: PathBuf,
+    /// the amount of runs listed. Default is 10
+    #[argh(option, default = "10")]
+    runs: usize,
+}
+
+pub fn run(args: HistoryArgs) {
+    let history = match results_db::history(&args.database, args.runs) {
+        Ok(history) => history,
+        Err(err) => {
+            log::error!("{}", err);
+            process::exit(1)
+        }
+    };
+    if history.runs.is_empty() {
+        anstream::println!("No runs were recorded yet.");
+        return;
+    }
+
+    anstream::println!("{}", "Latest runs".bold());
+    for run in &history.runs {
+        anstream::println!(
+            "  {}  {}  {:>6}  freed {:>10} by deleting {} chunks{}",
+            run.started_at,
+            run.world,
+            run.max_inhabited_time.as_deref().unwrap_or("?"),
+            HumanBytes(run.freed_space).yellow(),
+            run.deleted_chunks,
+            match (run.finished, run.dry_run) {
+                (false, _) => " (aborted)",
+                (true, true) => " (dry run)",
+                (true, false) => "",
+            }
+        );
+    }
+
+    if !history.weeks.is_empty() {
+        anstream::println!("\n{}", "Freed space per week".bold());
+        let max = history
+            .weeks
+            .iter()
+            .map(|week| week.freed_space)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        for week in &history.weeks {
+            // a bar of at most 40 characters
+            let bar = "#".repeat((week.freed_space * 40 / max) as usize);
+            anstream::println!(
+                "  {}  {:>10}  {:>3} runs  {}",
+                week.week,
+                HumanBytes(week.freed_space),
+                week.runs,
+                bar.yellow()
+            );
+        }
+    }
+
+    if !history.thresholds.is_empty() {
+        anstream::println!("\n{}", "Thresholds (average per finished run)".bold());
+        for threshold in &history.thresholds {
+            anstream::println!(
+                "  {:>6}  {:>3} runs  freed {:>10} by deleting {:.0} chunks",
+                threshold.max_inhabited_time,
+                threshold.runs,
+                HumanBytes(threshold.freed_space as u64).yellow(),
+                threshold.deleted_chunks
+            );
+        }
+    }
+}
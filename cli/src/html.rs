@@ -0,0 +1,329 @@
+//! The HTML report of a run (`--report-html`): a single file without external resources, e.g. to
+//! attach to a support ticket.
+
+use std::{cmp::Reverse, collections::BTreeMap, fmt::Write, fs, io, path::Path};
+
+use indicatif::{HumanBytes, HumanDuration};
+use lessanvil::{
+    analysis::SUGGESTION_THRESHOLDS, protection::ProtectedArea, Dimension, ProcessedRegion,
+};
+
+use crate::summary::Summary;
+
+/// The amount of regions listed in the table of the regions freeing the most space.
+const TOP_REGIONS: usize = 10;
+
+/// The size of a region in the heatmap in pixels.
+const CELL_SIZE: i32 = 8;
+
+/// The smallest size of a protected area in the heatmap in pixels, so that single chunks show up.
+const MIN_AREA_SIZE: f64 = 2.0;
+
+/// Collects the regions of a run while it is followed, see [`HtmlReport::write`].
+#[derive(Default)]
+pub struct HtmlReport {
+    regions: Vec<RegionEntry>,
+    /// The areas of the run's config, drawn over the heatmaps to check they were loaded as meant.
+    protected_areas: Vec<ProtectedArea>,
+    /// The chunks with an InhabitedTime up to each of the [`SUGGESTION_THRESHOLDS`] (above the previous one), the
+    /// last entry counts the chunks above all of them.
+    histogram: [u64; SUGGESTION_THRESHOLDS.len() + 1],
+}
+
+struct RegionEntry {
+    dimension: Dimension,
+    x: i32,
+    z: i32,
+    total_chunks: u16,
+    deleted_chunks: u16,
+    freed_space: u64,
+}
+
+impl HtmlReport {
+    pub fn new(protected_areas: Vec<ProtectedArea>) -> Self {
+        HtmlReport {
+            protected_areas,
+            ..Default::default()
+        }
+    }
+
+    pub fn add(&mut self, region: &ProcessedRegion) {
+        self.regions.push(RegionEntry {
+            dimension: region.dimension.clone(),
+            x: region.x,
+            z: region.y,
+            total_chunks: region.total_chunks,
+            deleted_chunks: region.deleted_chunks,
+            freed_space: region.freed_space,
+        });
+        for time in &region.inhabited_times {
+            let bucket = SUGGESTION_THRESHOLDS
+                .iter()
+                .position(|threshold| time <= threshold)
+                .unwrap_or(SUGGESTION_THRESHOLDS.len());
+            self.histogram[bucket] += 1;
+        }
+    }
+
+    pub fn write(&self, path: &Path, summary: &Summary) -> io::Result<()> {
+        fs::write(path, self.to_html(summary))
+    }
+
+    fn to_html(&self, summary: &Summary) -> String {
+        let mut html = String::new();
+        let world = escape(&summary.world_folder.display().to_string());
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>lessanvil report: {world}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n"
+        );
+        let _ = writeln!(html, "<h1>lessanvil report</h1>");
+        let _ = writeln!(html, "<table class=\"summary\">");
+        for (label, value) in [
+            ("World", world.clone()),
+            ("Run", summary.run_id.to_string()),
+            (
+                "Status",
+                match (summary.finished, summary.dry_run) {
+                    (true, false) => "Finished",
+                    (true, true) => "Finished (dry run)",
+                    (false, _) => "Aborted",
+                }
+                .to_string(),
+            ),
+            ("Time taken", HumanDuration(summary.time_taken).to_string()),
+            (
+                "Regions",
+                format!("{} of {}", summary.processed_regions, summary.total_regions),
+            ),
+            (
+                if summary.dry_run {
+                    "Chunks to delete"
+                } else {
+                    "Deleted chunks"
+                },
+                summary.deleted_chunks.to_string(),
+            ),
+            ("Freed space", HumanBytes(summary.freed_space).to_string()),
+        ] {
+            let _ = writeln!(html, "<tr><th>{label}</th><td>{value}</td></tr>");
+        }
+        let _ = writeln!(html, "</table>");
+
+        self.write_space_by_dimension(&mut html);
+        self.write_histogram(&mut html);
+        self.write_top_regions(&mut html);
+        self.write_heatmaps(&mut html);
+        for (title, lines) in [
+            (
+                "Failed regions",
+                summary
+                    .failed_regions
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect(),
+            ),
+            ("Skipped files", summary.skipped_files.clone()),
+            ("Warnings", summary.warnings.clone()),
+        ] {
+            if lines.is_empty() {
+                continue;
+            }
+            let _ = writeln!(html, "<h2>{title}</h2>\n<ul>");
+            for line in lines {
+                let _ = writeln!(html, "<li>{}</li>", escape(&line));
+            }
+            let _ = writeln!(html, "</ul>");
+        }
+        let _ = writeln!(html, "</body>\n</html>");
+        html
+    }
+
+    fn write_space_by_dimension(&self, html: &mut String) {
+        let mut by_dimension = BTreeMap::<String, u64>::new();
+        for region in &self.regions {
+            *by_dimension
+                .entry(region.dimension.to_string())
+                .or_default() += region.freed_space;
+        }
+        let max = by_dimension.values().copied().max().unwrap_or(0).max(1);
+        let _ = writeln!(
+            html,
+            "<h2>Freed space by dimension</h2>\n<table class=\"bars\">"
+        );
+        for (dimension, space) in by_dimension {
+            let _ = writeln!(
+                html,
+                "<tr><th>{}</th><td><div class=\"bar\" style=\"width: {:.1}%\"></div></td><td>{}</td></tr>",
+                escape(&dimension),
+                space as f64 * 100.0 / max as f64,
+                HumanBytes(space)
+            );
+        }
+        let _ = writeln!(html, "</table>");
+    }
+
+    fn write_histogram(&self, html: &mut String) {
+        const HEIGHT: f64 = 160.0;
+        const BAR_WIDTH: usize = 60;
+        let max = self.histogram.iter().copied().max().unwrap_or(0).max(1);
+        let width = self.histogram.len() * BAR_WIDTH;
+        let _ = writeln!(
+            html,
+            "<h2>InhabitedTime of the chunks</h2>\n<svg class=\"histogram\" viewBox=\"0 0 {width} {}\" width=\"{width}\">",
+            HEIGHT + 40.0
+        );
+        for (i, &count) in self.histogram.iter().enumerate() {
+            let label = match SUGGESTION_THRESHOLDS.get(i) {
+                Some(threshold) => format!("\u{2264} {threshold}"),
+                None => format!("> {}", SUGGESTION_THRESHOLDS[i - 1]),
+            };
+            let height = count as f64 * HEIGHT / max as f64;
+            let x = i * BAR_WIDTH;
+            let _ = writeln!(
+                html,
+                "<rect x=\"{}\" y=\"{:.1}\" width=\"{}\" height=\"{:.1}\"><title>{count} chunks</title></rect>\n<text x=\"{}\" y=\"{}\">{label}</text>\n<text x=\"{}\" y=\"{:.1}\">{count}</text>",
+                x + 4,
+                HEIGHT + 15.0 - height,
+                BAR_WIDTH - 8,
+                height,
+                x + BAR_WIDTH / 2,
+                HEIGHT + 32.0,
+                x + BAR_WIDTH / 2,
+                HEIGHT + 11.0 - height,
+            );
+        }
+        let _ = writeln!(html, "</svg>");
+    }
+
+    fn write_top_regions(&self, html: &mut String) {
+        let mut regions = self
+            .regions
+            .iter()
+            .filter(|region| region.freed_space > 0)
+            .collect::<Vec<_>>();
+        if regions.is_empty() {
+            return;
+        }
+        regions.sort_by_key(|region| Reverse(region.freed_space));
+        let _ = writeln!(
+            html,
+            "<h2>Regions freeing the most space</h2>\n<table>\n<tr><th>Dimension</th><th>Region</th><th>Deleted chunks</th><th>Freed space</th></tr>"
+        );
+        for region in regions.into_iter().take(TOP_REGIONS) {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>r.{}.{}</td><td>{} of {}</td><td>{}</td></tr>",
+                escape(&region.dimension.to_string()),
+                region.x,
+                region.z,
+                region.deleted_chunks,
+                region.total_chunks,
+                HumanBytes(region.freed_space)
+            );
+        }
+        let _ = writeln!(html, "</table>");
+    }
+
+    /// A map of the regions of every dimension with north at the top, the more of a region's chunks
+    /// were deleted the darker it is. The protected areas are drawn on top.
+    fn write_heatmaps(&self, html: &mut String) {
+        let mut by_dimension = BTreeMap::<String, Vec<&RegionEntry>>::new();
+        for region in &self.regions {
+            by_dimension
+                .entry(region.dimension.to_string())
+                .or_default()
+                .push(region);
+        }
+        if by_dimension.is_empty() {
+            return;
+        }
+        let _ = writeln!(html, "<h2>Deleted chunks</h2>");
+        if !self.protected_areas.is_empty() {
+            let _ = writeln!(
+                html,
+                "<p>The {} protected areas are drawn in <span class=\"protected\">green</span>.</p>",
+                self.protected_areas.len()
+            );
+        }
+        for (dimension, regions) in by_dimension {
+            let min_x = regions.iter().map(|region| region.x).min().unwrap_or(0);
+            let max_x = regions.iter().map(|region| region.x).max().unwrap_or(0);
+            let min_z = regions.iter().map(|region| region.z).min().unwrap_or(0);
+            let max_z = regions.iter().map(|region| region.z).max().unwrap_or(0);
+            let width = (max_x - min_x + 1) * CELL_SIZE;
+            let height = (max_z - min_z + 1) * CELL_SIZE;
+            let _ = writeln!(
+                html,
+                "<h3>{}</h3>\n<svg class=\"heatmap\" viewBox=\"0 0 {width} {height}\" width=\"{width}\">",
+                escape(&dimension)
+            );
+            for region in regions {
+                let share = region.deleted_chunks as f64 / region.total_chunks.max(1) as f64;
+                let _ = writeln!(
+                    html,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{CELL_SIZE}\" height=\"{CELL_SIZE}\" fill-opacity=\"{:.2}\"><title>r.{}.{}: {} of {} chunks deleted</title></rect>",
+                    (region.x - min_x) * CELL_SIZE,
+                    (region.z - min_z) * CELL_SIZE,
+                    0.1 + share * 0.9,
+                    region.x,
+                    region.z,
+                    region.deleted_chunks,
+                    region.total_chunks
+                );
+            }
+            let mut outside = 0;
+            for area in self
+                .protected_areas
+                .iter()
+                .filter(|area| area.dimension().to_string() == dimension)
+            {
+                let ((from_x, from_z), (to_x, to_z)) = area.chunk_bounds();
+                // in pixels, clipped to the map
+                let chunk_size = CELL_SIZE as f64 / 32.0;
+                let (left, top) = (min_x as f64 * 32.0, min_z as f64 * 32.0);
+                let x = ((from_x as f64 - left) * chunk_size).max(0.0);
+                let z = ((from_z as f64 - top) * chunk_size).max(0.0);
+                let right = ((to_x as f64 + 1.0 - left) * chunk_size).min(width as f64);
+                let bottom = ((to_z as f64 + 1.0 - top) * chunk_size).min(height as f64);
+                if right <= 0.0 || bottom <= 0.0 || x >= width as f64 || z >= height as f64 {
+                    outside += 1;
+                    continue;
+                }
+                let _ = writeln!(
+                    html,
+                    "<rect class=\"protected\" x=\"{x:.2}\" y=\"{z:.2}\" width=\"{:.2}\" height=\"{:.2}\"><title>Protected: chunks {from_x}, {from_z} to {to_x}, {to_z}</title></rect>",
+                    (right - x).max(MIN_AREA_SIZE),
+                    (bottom - z).max(MIN_AREA_SIZE)
+                );
+            }
+            let _ = writeln!(html, "</svg>");
+            if outside > 0 {
+                let _ = writeln!(
+                    html,
+                    "<p>{outside} protected areas are outside of the processed regions.</p>"
+                );
+            }
+        }
+    }
+}
+
+const STYLE: &str =
+    "body { font-family: sans-serif; max-width: 60em; margin: 2em auto; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { text-align: left; padding: 0.2em 0.8em; }
+table.bars { width: 100%; }
+table.bars td:nth-child(2) { width: 70%; }
+.bar { height: 1em; background: #c0392b; }
+svg.histogram rect { fill: #2980b9; }
+svg.histogram text { font-size: 11px; text-anchor: middle; }
+svg.heatmap { max-width: 100%; height: auto; background: #eee; }
+svg.heatmap rect { fill: #c0392b; }
+svg.heatmap rect.protected { fill: #27ae60; fill-opacity: 0.35; stroke: #27ae60; stroke-width: 0.5; }
+.protected { color: #27ae60; }";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
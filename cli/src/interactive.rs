@@ -0,0 +1,67 @@
+use std::{collections::HashSet, process};
+
+use dialoguer::Select;
+use indicatif::HumanBytes;
+use lessanvil::{ChunkPos, Config, ProcessingUpdate};
+use owo_colors::OwoColorize;
+
+use crate::map;
+
+/// Finds the chunks the config would delete without modifying the world and asks for each region
+/// whether to delete them. Returns the chunks to delete.
+pub fn select_chunks(config: &Config) -> HashSet<ChunkPos> {
+    crate::container::require_terminal("Use plan and apply to choose the chunks without one.");
+    anstream::eprintln!("Looking for chunks to delete...");
+    let execution = lessanvil::execute(Config {
+        dry_run: true,
+        ..config.clone()
+    })
+    .unwrap_or_else(|err| {
+        log::error!("{}", err);
+        process::exit(1)
+    });
+
+    let mut regions = vec![];
+    while let Ok(msg) = execution.updates().recv() {
+        match msg {
+            ProcessingUpdate::ProcessedRegion(Ok(region)) if region.deleted_chunks > 0 => {
+                regions.push(region)
+            }
+            ProcessingUpdate::ProcessedRegion(Err(err)) => log::warn!("{}", err),
+            _ => {}
+        }
+    }
+    regions.sort_by_key(|region| (region.dimension.clone(), region.x, region.y));
+
+    let mut selected = HashSet::new();
+    for (i, region) in regions.iter().enumerate() {
+        anstream::eprintln!();
+        anstream::eprintln!(
+            "Region {}/{}: {} r.{}.{} with {} of {} chunks to delete, freeing about {}",
+            i + 1,
+            regions.len(),
+            region.dimension,
+            region.x,
+            region.y,
+            region.deleted_chunks.yellow(),
+            region.total_chunks,
+            HumanBytes(region.freed_space).yellow()
+        );
+        map::print_region(region);
+
+        let choice = Select::new()
+            .items(&["Delete", "Keep", "Keep this and all remaining regions"])
+            .default(0)
+            .interact()
+            .unwrap_or_else(|err| {
+                log::error!("{}", err);
+                process::exit(1)
+            });
+        match choice {
+            0 => selected.extend(region.deleted_positions.iter().cloned()),
+            1 => {}
+            _ => break,
+        }
+    }
+    selected
+}
@@ -1,25 +1,92 @@
+mod analyze;
+mod archive;
+mod backoff;
+mod batch;
+mod compact;
+mod completions;
+mod container;
+mod convert;
+mod diff;
+mod email;
+mod extract;
+mod history;
+mod html;
+mod interactive;
+mod manifest;
+mod map;
+mod merge;
+mod panel;
+mod parquet_export;
+mod plan;
+mod players;
+mod repair;
+mod reset_end;
+mod restore;
+mod results_db;
+mod scan;
+mod serve;
+mod service;
+mod summary;
+mod unlock;
+#[cfg(feature = "self-update")]
+mod update;
+mod window;
+mod wizard;
+
 use std::{
-    path::PathBuf,
+    collections::BTreeMap,
+    io::Write,
+    path::{Path, PathBuf},
     process,
-    sync::{atomic::AtomicBool, Arc},
-    time::Duration,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use dialoguer::Confirm;
 use indicatif::{HumanBytes, HumanDuration, ProgressBar, ProgressStyle};
-use lessanvil::Config;
+use lessanvil::cleanup::{self, PostClean};
+use lessanvil::clusters::{self, ChunkCluster};
+use lessanvil::protection::{self, ProtectedArea};
+use lessanvil::snapshot::{self, Snapshot};
+use lessanvil::{
+    BlockEntityLimit, CancelHandle, ChunkCompression, ChunkPos, Config, Dimension, EntityLimits,
+    HardlinkPolicy, InhabitedTime, NetherRoof, Operation, OutdatedChunks, OversizedChunks,
+    ParseInhabitedTimeError, ParseRunIdError, ProcessingOrder, RegionProcessingError, RunId,
+    WorldLayout,
+};
 use owo_colors::OwoColorize;
+use summary::{Summary, SummaryFormat};
 
 /// CLI for reducing a Minecraft: Java Edition's world size by removing unused chunks.
-#[derive(argh::FromArgs, Debug)]
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
 struct Args {
     /// the world folder
     #[argh(option, short = 'w')]
-    world_folder: PathBuf,
-    /// the maximum amount of time players can have spent spent in a chunk for it to get
-    /// remmoved in seconds. See https://minecraft.fandom.com/wiki/Chunk_format#NBT_structure
-    #[argh(option, short = 'm', default = "0")]
-    max_inhabited_time: usize,
+    world_folder: Option<PathBuf>,
+    /// a folder containing several worlds, e.g. the `/data` volume of a container. Every world in
+    /// it is processed one after another, after a single confirmation
+    #[argh(option)]
+    worlds_root: Option<PathBuf>,
+    /// the maximum amount of time players can have spent in a chunk for it to get removed, e.g.
+    /// `90s`, `5m`, `2h` or `1200t` (game ticks). Plain numbers are seconds. See
+    /// https://minecraft.fandom.com/wiki/Chunk_format#NBT_structure
+    #[argh(
+        option,
+        short = 'm',
+        default = "InhabitedTime::default()",
+        from_str_fn(parse_inhabited_time)
+    )]
+    max_inhabited_time: InhabitedTime,
+    /// judge chunks by the time they were last saved instead of their InhabitedTime: prune the
+    /// chunks not saved for this long, e.g. `90d` (units `h`, `d` and `w`). Much faster, but a
+    /// chunk a player passed by counts as touched and tools rewriting chunks reset the time
+    #[argh(option, from_str_fn(parse_age))]
+    not_touched_for: Option<Duration>,
+    /// like --not-touched-for, but prune the chunks not saved since a date, e.g. `2023-01-01`,
+    /// `2023-01-01 18:30` or `2023-01-01T18:30+02:00`. Without a time zone (`Z`, `UTC` or an
+    /// offset) the date is in the local time zone of this machine
+    #[argh(option, from_str_fn(parse_date))]
+    not_touched_since: Option<SystemTime>,
     /// the amount of threads spawned. Default is the same as the number of CPUs available
     #[argh(option, short = 't')]
     thread_count: Option<usize>,
@@ -32,140 +99,1713 @@ struct Args {
     /// whether the final report should be in json
     #[argh(switch)]
     json: bool,
+    /// leave hardlinked region files untouched, keeping them shared with e.g. rsync --link-dest
+    /// backups
+    #[argh(switch)]
+    skip_hardlinked: bool,
+    /// protect all chunks within the given radius (in chunks) around the last known position of
+    /// every player
+    #[argh(option)]
+    protect_players: Option<u32>,
+    /// a CSV file with additional `dimension,x,z` player positions to protect using the
+    /// --protect-players radius. Can be given multiple times
+    #[argh(option)]
+    player_positions: Vec<PathBuf>,
+    /// don't protect the chunks around the players in the whitelist.json and ops.json of the
+    /// server, which are protected even without --protect-players
+    #[argh(switch)]
+    no_protect_whitelist: bool,
+    /// a Xaero's Minimap (.txt) or JourneyMap (.json) waypoint file whose waypoints should be
+    /// protected. Can be given multiple times
+    #[argh(option)]
+    waypoints: Vec<PathBuf>,
+    /// the radius (in chunks) protected around every waypoint
+    #[argh(option, default = "4")]
+    waypoint_radius: u32,
+    /// a CSV file with `x1,z1,x2,z2` rectangles (block coordinates, optionally preceded by the
+    /// dimension) or a GeoJSON (.geojson) file with polygons whose chunks should be protected.
+    /// Can be given multiple times
+    #[argh(option)]
+    protected_areas: Vec<PathBuf>,
+    /// keep chunks containing blocks that are usually placed by players (beds, torches above
+    /// ground, enchanting tables, ...). Slow
+    #[argh(switch)]
+    protect_player_blocks: bool,
+    /// a block id counted as placed by players, replacing the default list. Implies
+    /// --protect-player-blocks. Can be given multiple times
+    #[argh(option)]
+    player_block: Vec<String>,
+    /// instead of deleting chunks, reset their InhabitedTime to 0. Combine with a high
+    /// --max-inhabited-time to reset every chunk
+    #[argh(switch)]
+    reset_inhabited_time: bool,
+    /// instead of deleting chunks, replace their sections within the Y range with air, e.g.
+    /// `..-32` or `200..`
+    #[argh(option, from_str_fn(parse_y_range))]
+    clear_sections: Option<(i32, i32)>,
+    /// stop once this many GiB have been freed, processing the regions that free the most
+    /// space first
+    #[argh(option)]
+    target_free: Option<f64>,
+    /// convert all kept chunks to this compression: `zlib`, `lz4`, `gzip` or `uncompressed`
+    #[argh(option, from_str_fn(parse_compression))]
+    recompress: Option<ChunkCompression>,
+    /// count the chunks last saved by a version of the game older than this DataVersion, e.g.
+    /// `1519` for 1.13. See https://minecraft.wiki/w/Data_version
+    #[argh(option)]
+    min_data_version: Option<i32>,
+    /// delete the chunks older than --min-data-version regardless of their InhabitedTime, unless
+    /// they're protected
+    #[argh(switch)]
+    prune_outdated: bool,
+    /// list the chunks taking up more than this many KiB in their region file, e.g. lag machines
+    #[argh(option)]
+    max_chunk_size: Option<u64>,
+    /// delete the chunks larger than --max-chunk-size regardless of their InhabitedTime, unless
+    /// they're protected
+    #[argh(switch)]
+    prune_oversized: bool,
+    /// list the chunks containing more than this many entities, e.g. mob farms. Only counts the
+    /// entities of chunks saved since 1.17
+    #[argh(option)]
+    max_entities: Option<u32>,
+    /// list the chunks containing more entities of a type than allowed, e.g.
+    /// `minecraft:item=500`. Can be given multiple times
+    #[argh(option, from_str_fn(parse_entity_limit))]
+    max_entities_of: Vec<(String, u32)>,
+    /// delete the chunks exceeding --max-entities or --max-entities-of regardless of their
+    /// InhabitedTime, unless they're protected
+    #[argh(switch)]
+    prune_crowded: bool,
+    /// list the chunks containing more than this many block entities, e.g. abandoned hopper walls
+    /// or storage systems
+    #[argh(option)]
+    max_block_entities: Option<u32>,
+    /// delete the chunks exceeding --max-block-entities regardless of their InhabitedTime, unless
+    /// they're protected
+    #[argh(switch)]
+    prune_cluttered: bool,
+    /// only show what would happen without modifying the world, the default without --apply.
+    /// Works on read-only copies of the world as well
+    #[argh(switch)]
+    dry_run: bool,
+    /// modify the world. Without it, the run only shows what would happen
+    #[argh(switch)]
+    apply: bool,
+    /// show the chunks to delete of each region (count, size and a map) and ask whether to
+    /// delete them
+    #[argh(switch)]
+    interactive: bool,
+    /// print a map of the chunks of each region that would be modified during a dry run
+    #[argh(switch)]
+    map: bool,
+    /// delete the chunks even if more than 95% of all chunks would be deleted, which usually
+    /// means the threshold was given in the wrong unit
+    #[argh(switch)]
+    i_know_what_im_doing: bool,
+    /// the maximum amount of MiB of chunk data held in memory by all threads together
+    #[argh(option)]
+    max_memory: Option<u64>,
+    /// an additional folder containing region files, relative to the world folder. Can be
+    /// given multiple times
+    #[argh(option)]
+    extra_region_dir: Vec<PathBuf>,
+    /// a glob pattern matching additional region folders relative to the world folder, e.g.
+    /// `mymod/*/region`. Can be given multiple times
+    #[argh(option)]
+    region_glob: Vec<String>,
+    /// abort if a region file or folder can't be read instead of skipping it
+    #[argh(switch)]
+    fail_on_unreadable: bool,
+    /// process the regions that will likely free the most space first, so that an interrupted
+    /// run still frees as much as possible
+    #[argh(switch)]
+    savings_first: bool,
+    /// only process these dimensions, e.g. `nether,end` or `mypack:mining`. Can be repeated
+    #[argh(option)]
+    dimension: Vec<String>,
+    /// look for the nether and end in the `<world>_nether` and `<world>_the_end` folders used by
+    /// Spigot and Paper if the world folder doesn't contain them
+    #[argh(switch)]
+    bukkit_layout: bool,
+    /// the folder containing the nether's region folder, relative to the world folder, e.g.
+    /// `../world_nether/DIM-1`
+    #[argh(option)]
+    nether_folder: Option<PathBuf>,
+    /// the folder containing the end's region folder, relative to the world folder, e.g.
+    /// `../world_the_end/DIM1`
+    #[argh(option)]
+    end_folder: Option<PathBuf>,
+    /// what to do with blocks above the nether roof: `trim` clears them in all kept chunks,
+    /// `protect` keeps chunks containing them
+    #[argh(option, from_str_fn(parse_nether_roof))]
+    nether_roof: Option<NetherRoof>,
+    /// clean up afterwards: `raids`, `scoreboard`, `map-ids`, `junk-files` or `all`, comma
+    /// separated. `junk-files` removes e.g. level.dat_old and session.lock, so the server must
+    /// be stopped
+    #[argh(option, from_str_fn(parse_post_clean))]
+    post_clean: Option<PostClean>,
+    /// copy the region files into this folder right before they are modified, keeping their
+    /// paths relative to the world. Only the regions that change are copied. Use a new folder
+    /// for every run
+    #[argh(option)]
+    backup: Option<PathBuf>,
+    /// where to periodically write the state of the run, for bug reports and --resume. Default
+    /// is `.lessanvil/progress.txt` in the world folder. Removed once the run finished
+    #[argh(option)]
+    progress_snapshot: Option<PathBuf>,
+    /// only process the regions an interrupted run left unfinished, according to its progress
+    /// snapshot
+    #[argh(switch)]
+    resume: bool,
+    /// stop after running this long, e.g. `2h`, finishing the files currently being processed.
+    /// Continue the run later with --resume
+    #[argh(option, from_str_fn(window::parse_runtime))]
+    max_runtime: Option<Duration>,
+    /// stop when the local time reaches this time of day, e.g. `06:00`, finishing the files
+    /// currently being processed. Continue the run later with --resume
+    #[argh(option, from_str_fn(window::parse_stop_at))]
+    stop_at: Option<chrono::NaiveTime>,
+    /// pause while other processes, e.g. a server running on the same machine, keep the CPUs
+    /// busy and resume once they calmed down
+    #[argh(switch)]
+    low_priority: bool,
+    /// the share of the CPUs in percent other processes may use before --low-priority pauses
+    #[argh(option, default = "50")]
+    max_cpu: u8,
+    /// the RCON address of the server, e.g. `localhost:25575`, to also pause --low-priority
+    /// runs while its TPS are low. Requires a server with a `tps` command, e.g. Paper
+    #[argh(option)]
+    rcon: Option<String>,
+    /// a file containing the RCON password
+    #[argh(option)]
+    rcon_password_file: Option<PathBuf>,
+    /// the lowest TPS of the server at which --low-priority runs continue
+    #[argh(option, default = "18.0")]
+    min_tps: f64,
+    /// the id of the run (a UUID) in the JSON output, logs and progress snapshot, e.g. one
+    /// assigned by a hosting panel. Default is a random one
+    #[argh(option, from_str_fn(parse_run_id))]
+    run_id: Option<RunId>,
+    /// process the world without following its progress and only print the final report.
+    /// Slightly faster, e.g. for cron jobs. The report doesn't list the largest clusters of
+    /// deleted chunks
+    #[argh(switch)]
+    no_progress: bool,
+    /// run inside a Pterodactyl or Pelican panel: print the progress as lines its console shows,
+    /// stay within the server's memory and CPU limits and find the world through the
+    /// server.properties unless --world-folder is given
+    #[argh(switch)]
+    pterodactyl: bool,
+    /// the format of the summary after the run: `text` or `discord`, compact markdown to paste
+    /// into Discord or post through a webhook
+    #[argh(
+        option,
+        default = "SummaryFormat::Text",
+        from_str_fn(parse_summary_format)
+    )]
+    summary_format: SummaryFormat,
+    /// write a self-contained HTML report of the run to this file, with charts and a map of the
+    /// deleted chunks
+    #[argh(option)]
+    report_html: Option<PathBuf>,
+    /// append the results of the run and its regions to this SQLite database, e.g. to query the
+    /// space freed per week. Created if it doesn't exist
+    #[argh(option)]
+    results_db: Option<PathBuf>,
+    /// mail the report and the errors of the run to this address, e.g. after a scheduled run.
+    /// Can be given multiple times. Needs --email-config
+    #[argh(option)]
+    email_to: Vec<String>,
+    /// a file with the SMTP server and credentials for --email-to, one `key = value` per line:
+    /// `server`, `from` and optionally `port`, `encryption` (`starttls`, `tls` or `none`),
+    /// `username` and `password`
+    #[argh(option)]
+    email_config: Option<PathBuf>,
+    #[argh(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand)]
+enum Command {
+    Merge(merge::MergeArgs),
+    Restore(restore::RestoreArgs),
+    Extract(extract::ExtractArgs),
+    Convert(convert::ConvertArgs),
+    Archive(archive::ArchiveArgs),
+    Diff(diff::DiffArgs),
+    Manifest(manifest::ManifestArgs),
+    VerifyManifest(manifest::VerifyManifestArgs),
+    ResetEnd(reset_end::ResetEndArgs),
+    RepairHeaders(repair::RepairHeadersArgs),
+    PrunePlayers(players::PrunePlayersArgs),
+    Analyze(analyze::AnalyzeArgs),
+    Compact(compact::CompactArgs),
+    Scan(scan::ScanArgs),
+    Plan(plan::PlanArgs),
+    Apply(plan::ApplyArgs),
+    Completions(completions::CompletionsArgs),
+    Man(completions::ManArgs),
+    Wizard(wizard::WizardArgs),
+    Unlock(unlock::UnlockArgs),
+    History(history::HistoryArgs),
+    InstallService(service::InstallServiceArgs),
+    UninstallService(service::UninstallServiceArgs),
+    Serve(serve::ServeArgs),
+    Batch(batch::BatchArgs),
+    #[cfg(feature = "self-update")]
+    SelfUpdate(update::SelfUpdateArgs),
 }
 
 #[derive(serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 enum ProcessingUpdate {
-    Processing { progress: f64 },
-    Finished { report: CliReport },
+    Processing {
+        run_id: RunId,
+        progress: f64,
+        eta: Option<Duration>,
+    },
+    Finished {
+        report: CliReport,
+    },
+    Cancelled {
+        report: CliPartialReport,
+    },
 }
 
 #[derive(serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CliReport {
+    pub run_id: RunId,
     pub time_taken: Duration,
     pub total_freed_space: u64,
     pub total_regions: u64,
     pub total_chunks: u64,
     pub total_deleted_chunks: u64,
+    pub total_reset_chunks: u64,
+    pub total_trimmed_chunks: u64,
+    pub total_recompressed_chunks: u64,
+    pub total_outdated_chunks: u64,
+    pub total_oversized_chunks: u64,
+    pub total_crowded_chunks: u64,
+    pub total_cluttered_chunks: u64,
+    pub freed_space_by_folder: BTreeMap<PathBuf, u64>,
+    pub warnings: Vec<String>,
+    pub skipped_files: Vec<PathBuf>,
+    pub failed_regions: Vec<PathBuf>,
+    pub largest_clusters: Vec<ChunkCluster>,
+    pub oversized_chunks: Vec<ChunkPos>,
+    pub crowded_chunks: Vec<ChunkPos>,
+    pub cluttered_chunks: Vec<ChunkPos>,
+    pub unreadable_chunks: Vec<ChunkPos>,
+    pub post_clean: Option<CliPostCleanReport>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CliPostCleanReport {
+    pub removed_raids: u64,
+    pub removed_scores: u64,
+    pub fixed_map_id: Option<i32>,
+    pub removed_files: Vec<PathBuf>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CliPartialReport {
+    pub run_id: RunId,
+    pub time_taken: Duration,
+    pub total_freed_space: u64,
+    pub processed_regions: u64,
+    pub total_regions: u64,
+    pub total_chunks: u64,
+    pub total_deleted_chunks: u64,
+    pub total_reset_chunks: u64,
+    pub total_trimmed_chunks: u64,
+    pub total_recompressed_chunks: u64,
+    pub total_outdated_chunks: u64,
+    pub total_oversized_chunks: u64,
+    pub total_crowded_chunks: u64,
+    pub total_cluttered_chunks: u64,
+    pub freed_space_by_folder: BTreeMap<PathBuf, u64>,
+    pub warnings: Vec<String>,
+    pub skipped_files: Vec<PathBuf>,
+    pub failed_regions: Vec<PathBuf>,
+    pub largest_clusters: Vec<ChunkCluster>,
+    pub modified_regions: Vec<PathBuf>,
 }
 
 fn main() {
-    env_logger::init();
+    let mut args: Args = argh::from_env();
 
-    let args: Args = argh::from_env();
+    // every log line names the run, to tie it to the JSON output and the records of other tools
+    let run_id = *args.run_id.get_or_insert_with(RunId::new);
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"))
+        .format(move |buf, record| {
+            writeln!(
+                buf,
+                "[{} {} {}] {}",
+                buf.timestamp(),
+                record.level(),
+                run_id,
+                record.args()
+            )
+        })
+        .init();
+
+    window::start(args.max_runtime, args.stop_at);
+    match args.command.take() {
+        Some(Command::Merge(merge_args)) => merge::run(merge_args),
+        Some(Command::Restore(restore_args)) => restore::run(restore_args),
+        Some(Command::Extract(extract_args)) => extract::run(extract_args),
+        Some(Command::Convert(convert_args)) => convert::run(convert_args),
+        Some(Command::Archive(archive_args)) => archive::run(archive_args),
+        Some(Command::Diff(diff_args)) => diff::run(diff_args),
+        Some(Command::Manifest(manifest_args)) => manifest::run(manifest_args),
+        Some(Command::VerifyManifest(verify_args)) => manifest::run_verify(verify_args),
+        Some(Command::ResetEnd(reset_args)) => reset_end::run(reset_args),
+        Some(Command::RepairHeaders(repair_args)) => repair::run(repair_args),
+        Some(Command::PrunePlayers(players_args)) => players::run(players_args),
+        Some(Command::Analyze(analyze_args)) => analyze::run(analyze_args),
+        Some(Command::Compact(compact_args)) => compact::run(compact_args),
+        Some(Command::Scan(scan_args)) => scan::run(scan_args),
+        Some(Command::Completions(completions_args)) => completions::run(completions_args),
+        Some(Command::Man(man_args)) => completions::run_man(man_args),
+        Some(Command::Wizard(wizard_args)) => wizard::run(wizard_args),
+        Some(Command::Unlock(unlock_args)) => unlock::run(unlock_args),
+        Some(Command::History(history_args)) => history::run(history_args),
+        Some(Command::InstallService(service_args)) => service::run_install(service_args),
+        Some(Command::UninstallService(service_args)) => service::run_uninstall(service_args),
+        Some(Command::Serve(serve_args)) => serve::run(serve_args),
+        Some(Command::Batch(batch_args)) => batch::run(batch_args),
+        #[cfg(feature = "self-update")]
+        Some(Command::SelfUpdate(update_args)) => update::run(update_args),
+        Some(Command::Plan(plan_args)) => exit(prune(args, Mode::Plan(plan_args))),
+        Some(Command::Apply(apply_args)) => exit(prune(args, Mode::Apply(apply_args))),
+        None if args.worlds_root.is_some() => prune_worlds(args),
+        None => exit(prune(args, Mode::Prune)),
+    }
+}
+
+fn exit(success: bool) -> ! {
+    process::exit(if success { 0 } else { 1 })
+}
+
+/// The execution Ctrl+C cancels. Only a single handler can be installed, while `--worlds-root` runs
+/// one execution after another.
+static CURRENT_EXECUTION: Mutex<Option<CancelHandle>> = Mutex::new(None);
+
+/// Prunes every world inside `--worlds-root`, stopping at the first one that fails.
+fn prune_worlds(args: Args) -> ! {
+    let root = args.worlds_root.clone().expect("checked by the caller");
+    if args.world_folder.is_some() || args.resume {
+        log::error!("--worlds-root can't be combined with --world-folder or --resume!");
+        process::exit(1)
+    }
+    let worlds = match container::find_worlds(&root) {
+        Ok(worlds) => worlds,
+        Err(err) => {
+            log::error!("Failed to read {}: {}", root.display(), err);
+            if err.kind() == std::io::ErrorKind::PermissionDenied {
+                if let Some(hint) = container::ownership_hint(&root) {
+                    log::error!("{}", hint);
+                }
+            }
+            process::exit(1)
+        }
+    };
+    if worlds.is_empty() {
+        log::error!("No worlds found in {}!", root.display());
+        process::exit(1)
+    }
+
+    if !args.confirm && args.apply && !args.dry_run {
+        for world in &worlds {
+            anstream::eprintln!("  {}", world.display());
+        }
+        anstream::eprintln!(
+            "Threshold: {}",
+            describe_threshold(args.max_inhabited_time, not_touched_since(&args))
+        );
+        confirm(&format!(
+            "This tool will remove all chunks in which players have been less than the given amount of time from these {} worlds.",
+            worlds.len()
+        ));
+    }
+    for world in worlds {
+        if window::reached() {
+            log::warn!(
+                "The time window ended before processing {}, stopping.",
+                world.display()
+            );
+            exit(true)
+        }
+        if !args.json {
+            anstream::println!("Processing {}", world.display().yellow());
+        }
+        // the arguments are parsed again for every world, as pruning consumes them
+        let mut world_args: Args = argh::from_env();
+        world_args.command = None;
+        world_args.run_id = args.run_id;
+        world_args.worlds_root = None;
+        world_args.world_folder = Some(world);
+        world_args.confirm = true;
+        if !prune(world_args, Mode::Prune) {
+            process::exit(1)
+        }
+    }
+    process::exit(0)
+}
+
+/// What [`prune`] does with the chunks it selects.
+enum Mode {
+    Prune,
+    Plan(plan::PlanArgs),
+    Apply(plan::ApplyArgs),
+}
+
+/// Returns whether the execution finished. Errors before it started exit the process.
+fn prune(mut args: Args, mode: Mode) -> bool {
+    if args.apply && (args.dry_run || !matches!(mode, Mode::Prune)) {
+        log::error!("--apply can't be combined with --dry-run, plan or apply!");
+        process::exit(1);
+    }
+    // nothing is modified without --apply, except by applying a plan that was reviewed before. A
+    // plan is computed without modifying the world, e.g. against a read-only backup
+    args.dry_run |= match mode {
+        Mode::Prune => !args.apply,
+        Mode::Plan(_) => true,
+        Mode::Apply(_) => false,
+    };
+    let limits = if args.pterodactyl {
+        if args.world_folder.is_none() && args.worlds_root.is_none() {
+            args.world_folder = panel::world_folder();
+        }
+        panel::Limits::read()
+    } else {
+        panel::Limits::default()
+    };
+    if args.worlds_root.is_some() {
+        log::error!("--worlds-root can't be combined with plan or apply!");
+        process::exit(1);
+    }
+    let Some(world_folder) = args.world_folder.clone() else {
+        log::error!("Missing the world folder (--world-folder)!");
+        process::exit(1);
+    };
 
     // Check if valid world
-    if !args.force
-        && (!args.world_folder.join("level.dat").exists()
-            || !args.world_folder.join("region").exists())
-    {
+    if !args.force && !is_valid_world(&world_folder) {
         log::error!("Invalid world folder!");
         process::exit(1);
     }
 
-    if !args.confirm {
-        anstream::eprintln!("This tool will remove all chunks in which players have been less than the given amount of time.");
-        anstream::eprintln!("{}: This tool will work on the given world folder. Therefore it's recommended to {} before continuing.", "Warning".black().on_red().bold(), "create a backup".black().on_yellow().bold());
-        if !Confirm::new()
-            .with_prompt("Do you want to continue?")
-            .interact()
-            .unwrap()
-        {
-            anstream::eprintln!("Aborting.");
-            process::exit(1);
-        }
+    if args.json && args.summary_format != SummaryFormat::Text {
+        log::error!("--summary-format can't be combined with --json!");
+        process::exit(1);
+    }
+    if args.prune_outdated && args.min_data_version.is_none() {
+        log::error!("--prune-outdated requires --min-data-version!");
+        process::exit(1);
     }
+    if args.prune_oversized && args.max_chunk_size.is_none() {
+        log::error!("--prune-oversized requires --max-chunk-size!");
+        process::exit(1);
+    }
+    if args.prune_crowded && args.max_entities.is_none() && args.max_entities_of.is_empty() {
+        log::error!("--prune-crowded requires --max-entities or --max-entities-of!");
+        process::exit(1);
+    }
+    if args.rcon.is_some() && !args.low_priority {
+        log::error!("--rcon requires --low-priority!");
+        process::exit(1);
+    }
+    if args.prune_cluttered && args.max_block_entities.is_none() {
+        log::error!("--prune-cluttered requires --max-block-entities!");
+        process::exit(1);
+    }
+    let run_id = *args.run_id.get_or_insert_with(RunId::new);
+    let mailer = match (args.email_to.is_empty(), &args.email_config) {
+        (true, _) => None,
+        (false, None) => {
+            log::error!("--email-to needs --email-config!");
+            process::exit(1)
+        }
+        (false, Some(config)) => match email::Mailer::new(args.email_to.clone(), config) {
+            Ok(mailer) => Some(mailer),
+            Err(err) => {
+                log::error!("{}", err);
+                process::exit(1)
+            }
+        },
+    };
+
+    let protected_areas = match load_protections(&args, &world_folder) {
+        Ok(areas) => areas,
+        Err(err) => {
+            log::error!("{}", err);
+            process::exit(1)
+        }
+    };
 
-    let config = Config {
-        world_folder: args.world_folder,
+    let dimensions = match args
+        .dimension
+        .iter()
+        .flat_map(|dimensions| dimensions.split(','))
+        .map(|dimension| dimension.trim().parse::<Dimension>())
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(dimensions) => dimensions,
+        Err(err) => {
+            log::error!("{}", err);
+            process::exit(1)
+        }
+    };
+
+    let operation = match (args.reset_inhabited_time, args.clear_sections) {
+        (false, None) => Operation::Delete,
+        (true, None) => Operation::ResetInhabitedTime,
+        (false, Some((min_y, max_y))) => Operation::ClearSections { min_y, max_y },
+        (true, Some(_)) => {
+            log::error!("--reset-inhabited-time and --clear-sections can't be combined!");
+            process::exit(1)
+        }
+    };
+
+    let snapshot_path = args
+        .progress_snapshot
+        .clone()
+        .unwrap_or_else(|| world_folder.join(snapshot::DEFAULT_PATH));
+    let mut config = Config {
+        world_folder: world_folder.clone(),
         max_inhabited_time: args.max_inhabited_time,
-        thread_count: args.thread_count.unwrap_or(num_cpus::get()),
+        not_touched_since: not_touched_since(&args),
+        thread_count: args.thread_count.unwrap_or_else(|| limits.thread_count()),
+        hardlink_policy: if args.skip_hardlinked {
+            HardlinkPolicy::Skip
+        } else {
+            HardlinkPolicy::Process
+        },
+        protected_areas,
+        player_blocks: player_blocks(&args),
+        operation,
+        dimensions,
+        nether_roof: args.nether_roof,
+        layout: WorldLayout {
+            nether_folder: args.nether_folder.clone(),
+            end_folder: args.end_folder.clone(),
+            detect_bukkit: args.bukkit_layout,
+        },
+        extra_region_folders: args.extra_region_dir.clone(),
+        region_globs: args.region_glob.clone(),
+        recompress: args.recompress,
+        outdated_chunks: args
+            .min_data_version
+            .map(|min_data_version| OutdatedChunks {
+                min_data_version,
+                prune: args.prune_outdated,
+            }),
+        oversized_chunks: args.max_chunk_size.map(|kib| OversizedChunks {
+            max_size: kib * 1024,
+            prune: args.prune_oversized,
+        }),
+        entity_limits: (args.max_entities.is_some() || !args.max_entities_of.is_empty()).then(
+            || EntityLimits {
+                max_entities: args.max_entities,
+                max_by_type: args.max_entities_of.iter().cloned().collect(),
+                prune: args.prune_crowded,
+            },
+        ),
+        block_entity_limit: args.max_block_entities.map(|max| BlockEntityLimit {
+            max_block_entities: max,
+            prune: args.prune_cluttered,
+        }),
+        dry_run: args.dry_run,
+        max_memory: args
+            .max_memory
+            .map(|mib| mib * 1024 * 1024)
+            .or_else(|| limits.max_memory()),
+        fail_on_unreadable: args.fail_on_unreadable,
+        processing_order: if args.savings_first {
+            ProcessingOrder::EstimatedSavings
+        } else {
+            ProcessingOrder::Coordinates
+        },
+        target_free_space: args
+            .target_free
+            .map(|gib| (gib * 1024.0 * 1024.0 * 1024.0) as u64),
+        max_delete_ratio: (!args.i_know_what_im_doing).then_some(MAX_DELETE_RATIO),
+        // a dry run must not write into the world
+        progress_snapshot: (!args.dry_run).then(|| snapshot_path.clone()),
+        run_id: args.run_id,
+        backup_folder: args.backup.clone(),
+        ..Default::default()
     };
+    // the plan records the world and settings it was made with
+    let plan_config = matches!(mode, Mode::Plan(_)).then(|| config.clone());
+    let earlier_plan = match &mode {
+        Mode::Plan(plan_args) => plan::load_earlier(plan_args),
+        _ => None,
+    };
+    if let Mode::Apply(apply_args) = &mode {
+        config.planned_chunks = Some(plan::load(apply_args, &config, args.json));
+    }
+    if args.no_progress
+        && (args.resume
+            || args.max_runtime.is_some()
+            || args.stop_at.is_some()
+            || args.low_priority
+            || args.map
+            || args.report_html.is_some()
+            || args.results_db.is_some()
+            || matches!(mode, Mode::Plan(_)))
+    {
+        log::error!(
+            "--no-progress can't be combined with --resume, --max-runtime, --stop-at, --low-priority, --map, --report-html, --results-db or plan!"
+        );
+        process::exit(1)
+    }
+    if args.interactive {
+        if config.operation != Operation::Delete || matches!(mode, Mode::Apply(_)) {
+            log::error!("--interactive only works when deleting chunks by InhabitedTime!");
+            process::exit(1)
+        }
+        config.planned_chunks = Some(interactive::select_chunks(&config));
+    }
+    if !args.confirm && !args.dry_run {
+        print_settings(&config);
+        confirm(&confirmation(&config));
+    }
+
+    #[cfg(feature = "self-update")]
+    let mut update_check = (!args.json).then(update::check_in_background).flatten();
 
-    let progress_bar = if args.json {
+    let mut panel_progress = (args.pterodactyl && !args.json).then(panel::ProgressLines::default);
+    let progress_bar = if args.json || args.no_progress || args.pterodactyl {
         ProgressBar::hidden()
     } else {
         ProgressBar::new(0).with_style(
             ProgressStyle::with_template(
-                "Processing files: {pos}/{len} files | {per_sec} [{wide_bar:0.yellow}] {percent}% | {elapsed} ",
+                "Processing files: {pos}/{len} files | {per_sec} [{wide_bar:0.yellow}] {percent}% | {elapsed} | ETA {msg} ",
             )
             .unwrap()
             .progress_chars("#> ")
         )
     };
 
-    let rx = match lessanvil::execute(config) {
-        Ok(rx) => rx,
-        Err(err) => {
+    // only known when following the progress
+    let mut deleted_positions = vec![];
+    let mut oversized_positions = vec![];
+    let mut crowded_positions = vec![];
+    let mut cluttered_positions = vec![];
+    let mut unreadable_positions = vec![];
+    let mut results_db = args.results_db.as_ref().map(|path| {
+        results_db::ResultsDb::open(path, args.max_inhabited_time).unwrap_or_else(|err| {
             log::error!("{}", err);
             process::exit(1)
+        })
+    });
+    let mut html_report = args
+        .report_html
+        .as_ref()
+        .map(|_| html::HtmlReport::new(config.protected_areas.clone()));
+    let result = if args.no_progress {
+        match lessanvil::execute_blocking(config) {
+            Ok(report) => Ok(report),
+            Err(lessanvil::Error::Cancelled(report)) => Err(report),
+            Err(err) => {
+                log_error(&err, &world_folder);
+                if let Some(mailer) = &mailer {
+                    mailer.send_error(&world_folder, run_id, &err.to_string());
+                }
+                process::exit(1)
+            }
         }
-    };
-
-    let mut total_items = 1;
-    let mut processed_items = 0;
+    } else {
+        let execution = if args.resume {
+            Snapshot::read(&snapshot_path).and_then(|snapshot| {
+                lessanvil::execute_subset(config, snapshot.unfinished_regions())
+            })
+        } else {
+            lessanvil::execute(config)
+        };
+        let execution = match execution {
+            Ok(execution) => execution,
+            Err(err) => {
+                log_error(&err, &world_folder);
+                if let Some(mailer) = &mailer {
+                    mailer.send_error(&world_folder, run_id, &err.to_string());
+                }
+                process::exit(1)
+            }
+        };
 
-    let running = Arc::new(AtomicBool::new(true));
+        let mut total_items = 1;
+        let mut processed_items = 0;
+        let mut eta = None;
 
-    let r = running.clone();
-    let _ = ctrlc::set_handler(move || r.store(false, std::sync::atomic::Ordering::Relaxed));
+        *CURRENT_EXECUTION.lock().unwrap() = Some(execution.cancel_handle());
+        // the window may have ended while starting the execution
+        if window::reached() {
+            execution.cancel_handle().cancel();
+        }
+        let _backoff = if args.low_priority {
+            let options = backoff::Options {
+                max_cpu: args.max_cpu as f64 / 100.0,
+                rcon: args.rcon.clone(),
+                rcon_password_file: args.rcon_password_file.clone(),
+                min_tps: args.min_tps,
+            };
+            match backoff::start(
+                options,
+                execution.pause_handle(),
+                execution.cancel_handle(),
+                progress_bar.clone(),
+            ) {
+                Ok(monitor) => Some(monitor),
+                Err(err) => {
+                    log::error!("{}", err);
+                    execution.cancel();
+                    let _ = execution.join();
+                    process::exit(1)
+                }
+            }
+        } else {
+            None
+        };
+        // fails for every execution but the first, whose handler is kept
+        let _ = ctrlc::set_handler(|| {
+            if let Some(cancel_handle) = &*CURRENT_EXECUTION.lock().unwrap() {
+                if !cancel_handle.is_cancelled() {
+                    anstream::eprintln!("Aborting after the files currently being processed.");
+                }
+                cancel_handle.cancel();
+            }
+        });
 
-    loop {
-        if let Ok(msg) = rx.recv() {
+        while let Ok(msg) = execution.updates().recv() {
             match msg {
-                lessanvil::ProcessingUpdate::Starting { total_files } => {
+                lessanvil::ProcessingUpdate::Starting {
+                    total_files,
+                    region_folders,
+                } => {
+                    if !args.json
+                        && (!args.extra_region_dir.is_empty() || !args.region_glob.is_empty())
+                    {
+                        progress_bar.suspend(|| {
+                            anstream::println!("Processing the region files in:");
+                            for folder in &region_folders {
+                                anstream::println!("  {}", folder.display());
+                            }
+                        });
+                    }
                     total_items = total_files;
-                    progress_bar.set_length(total_files)
+                    progress_bar.set_length(total_files);
+                    if let Some(panel_progress) = &mut panel_progress {
+                        panel_progress.start(total_files);
+                    }
                 }
-                lessanvil::ProcessingUpdate::ProcessedRegion(_) => {
+                lessanvil::ProcessingUpdate::ProcessedRegion(result) => {
                     progress_bar.inc(1);
+                    if let Some(panel_progress) = &mut panel_progress {
+                        panel_progress.inc(eta);
+                    }
+
+                    match result {
+                        Ok(region) => {
+                            if args.map && args.dry_run && !args.json && map::has_changes(&region) {
+                                progress_bar.suspend(|| map::print_region(&region));
+                            }
+                            if let Some(html_report) = &mut html_report {
+                                html_report.add(&region);
+                            }
+                            if let Some(results_db) = &mut results_db {
+                                results_db.add(&region);
+                            }
+                            deleted_positions.extend(region.deleted_positions);
+                            oversized_positions.extend(region.oversized_chunks);
+                            crowded_positions.extend(region.crowded_chunks);
+                            cluttered_positions.extend(region.cluttered_chunks);
+                            unreadable_positions.extend(region.unreadable_chunks);
+                        }
+                        Err(err @ RegionProcessingError::Hardlinked { .. }) => {
+                            progress_bar.suspend(|| log::warn!("{}", err))
+                        }
+                        _ => {}
+                    }
 
                     if args.json {
                         processed_items += 1;
                         anstream::println!(
                             "{}",
                             serde_json::to_string(&ProcessingUpdate::Processing {
+                                run_id: execution.run_id(),
                                 progress: processed_items as f64 / total_items as f64,
+                                eta,
                             })
                             .unwrap()
                         );
                     }
                 }
-                lessanvil::ProcessingUpdate::Finished(report) => {
-                    anstream::println!(
-                        "{}",
-                        if args.json {
-                            serde_json::to_string(&ProcessingUpdate::Finished {
-                                report: CliReport {
-                                    time_taken: report.time_taken,
-                                    total_freed_space: report.total_freed_space,
-                                    total_regions: report.total_regions,
-                                    total_chunks: report.total_chunks,
-                                    total_deleted_chunks: report.total_deleted_chunks,
-                                },
-                            })
-                            .unwrap()
-                        } else {
-                            format!(
-                                "Successfully processed {} files in {} and freed up {} by deleting {} chunks.",
-                                report.total_regions.yellow(),
-                                HumanDuration(report.time_taken).yellow(),
-                                HumanBytes(report.total_freed_space).yellow(),
-                                report.total_deleted_chunks.yellow()
-                            )
+                lessanvil::ProcessingUpdate::Progress(progress) => {
+                    eta = progress.eta;
+                    if let Some(eta) = eta {
+                        progress_bar.set_message(HumanDuration(eta).to_string());
+                    }
+                }
+                lessanvil::ProcessingUpdate::Finished(_)
+                | lessanvil::ProcessingUpdate::Cancelled(_) => break,
+            }
+        }
+        execution.join()
+    };
+    match result {
+        Ok(report) => {
+            if !args.json {
+                for warning in &report.warnings {
+                    log::warn!("{}", warning);
+                }
+                for file in &report.skipped_files {
+                    log::warn!("Skipped {}: {}", file.path.display(), file.error);
+                }
+            }
+            let summary = Summary {
+                world_folder: &world_folder,
+                run_id: report.run_id,
+                finished: true,
+                dry_run: args.dry_run,
+                time_taken: report.time_taken,
+                freed_space: report.total_freed_space,
+                processed_regions: report.total_regions,
+                total_regions: report.total_regions,
+                deleted_chunks: report.total_deleted_chunks,
+                failed_regions: &report.failed_regions,
+                skipped_files: report
+                    .skipped_files
+                    .iter()
+                    .map(|file| format!("{}: {}", file.path.display(), file.error))
+                    .collect(),
+                warnings: report.warnings.iter().map(ToString::to_string).collect(),
+            };
+            if let Some(mailer) = &mailer {
+                mailer.send_summary(&summary);
+            }
+            if let (Some(path), Some(html_report)) = (&args.report_html, &html_report) {
+                if let Err(err) = html_report.write(path, &summary) {
+                    log::error!("Failed to write {}: {}", path.display(), err);
+                }
+            }
+            if let Some(results_db) = &results_db {
+                if let Err(err) = results_db.write(&summary) {
+                    log::error!("Failed to record the run in the results database: {}", err);
+                }
+            }
+            let largest_clusters = largest_clusters(&deleted_positions);
+            if let (Mode::Plan(plan_args), Some(plan_config)) = (&mode, &plan_config) {
+                let chunks = std::mem::take(&mut deleted_positions);
+                plan::write(
+                    plan_args,
+                    plan_config,
+                    chunks,
+                    earlier_plan.as_ref(),
+                    args.json,
+                );
+            }
+            // a dry run must not modify anything
+            let post_clean = args
+                .post_clean
+                .as_ref()
+                .filter(|_| !args.dry_run)
+                .map(|options| {
+                    cleanup::post_clean(&world_folder, options).unwrap_or_else(|err| {
+                        log::error!("{}", err);
+                        process::exit(1)
+                    })
+                });
+            if args.summary_format == SummaryFormat::Discord {
+                anstream::println!("{}", summary.to_discord());
+                return true;
+            }
+            anstream::println!(
+                "{}",
+                if args.json {
+                    serde_json::to_string(&ProcessingUpdate::Finished {
+                        report: CliReport {
+                            run_id: report.run_id,
+                            time_taken: report.time_taken,
+                            total_freed_space: report.total_freed_space,
+                            total_regions: report.total_regions,
+                            total_chunks: report.total_chunks,
+                            total_deleted_chunks: report.total_deleted_chunks,
+                            total_reset_chunks: report.total_reset_chunks,
+                            total_trimmed_chunks: report.total_trimmed_chunks,
+                            total_recompressed_chunks: report.total_recompressed_chunks,
+                            total_outdated_chunks: report.total_outdated_chunks,
+                            total_oversized_chunks: report.total_oversized_chunks,
+                            total_crowded_chunks: report.total_crowded_chunks,
+                            total_cluttered_chunks: report.total_cluttered_chunks,
+                            freed_space_by_folder: report.freed_space_by_folder.clone(),
+                            warnings: report.warnings.iter().map(ToString::to_string).collect(),
+                            skipped_files: report
+                                .skipped_files
+                                .iter()
+                                .map(|file| file.path.clone())
+                                .collect(),
+                            failed_regions: report.failed_regions.clone(),
+                            largest_clusters: largest_clusters.clone(),
+                            oversized_chunks: oversized_positions.clone(),
+                            crowded_chunks: crowded_positions.clone(),
+                            cluttered_chunks: cluttered_positions.clone(),
+                            unreadable_chunks: unreadable_positions.clone(),
+                            post_clean: post_clean.as_ref().map(|report| CliPostCleanReport {
+                                removed_raids: report.removed_raids,
+                                removed_scores: report.removed_scores,
+                                fixed_map_id: report.fixed_map_id,
+                                removed_files: report.removed_files.clone(),
+                            }),
+                        },
+                    })
+                    .unwrap()
+                } else if args.dry_run {
+                    format!(
+                            "Dry run of {} files finished in {}: {} chunks would be deleted, {} reset and {} trimmed, freeing up about {}.",
+                            report.total_regions.yellow(),
+                            HumanDuration(report.time_taken).yellow(),
+                            report.total_deleted_chunks.yellow(),
+                            report.total_reset_chunks.yellow(),
+                            report.total_trimmed_chunks.yellow(),
+                            HumanBytes(report.total_freed_space).yellow()
+                        )
+                } else if args.clear_sections.is_some() {
+                    format!(
+                            "Successfully processed {} files in {} and freed up {} by clearing sections of {} chunks.",
+                            report.total_regions.yellow(),
+                            HumanDuration(report.time_taken).yellow(),
+                            HumanBytes(report.total_freed_space).yellow(),
+                            report.total_trimmed_chunks.yellow()
+                        )
+                } else if args.reset_inhabited_time {
+                    format!(
+                            "Successfully processed {} files in {} and reset the InhabitedTime of {} chunks.",
+                            report.total_regions.yellow(),
+                            HumanDuration(report.time_taken).yellow(),
+                            report.total_reset_chunks.yellow()
+                        )
+                } else {
+                    format!(
+                            "Successfully processed {} files in {} and freed up {} by deleting {} chunks.",
+                            report.total_regions.yellow(),
+                            HumanDuration(report.time_taken).yellow(),
+                            HumanBytes(report.total_freed_space).yellow(),
+                            report.total_deleted_chunks.yellow()
+                        )
+                },
+            );
+            if !args.json && args.dry_run && matches!(mode, Mode::Prune) {
+                anstream::println!("Nothing was changed. Pass --apply to modify the world.");
+            }
+            if let (false, Some(post_clean)) = (args.json, post_clean) {
+                anstream::println!(
+                    "Removed {} raids and {} scores of removed objectives.",
+                    post_clean.removed_raids.yellow(),
+                    post_clean.removed_scores.yellow()
+                );
+                if let Some(id) = post_clean.fixed_map_id {
+                    anstream::println!("Corrected the last map id to {}.", id.yellow());
+                }
+                for path in &post_clean.removed_files {
+                    anstream::println!("Removed {}", path.display());
+                }
+            }
+            if !args.json && args.clear_sections.is_none() && report.total_trimmed_chunks > 0 {
+                anstream::println!(
+                    "Cleared sections of {} chunks.",
+                    report.total_trimmed_chunks.yellow()
+                );
+            }
+            if !args.json && report.total_recompressed_chunks > 0 {
+                anstream::println!(
+                    "{} {} chunks.",
+                    if args.dry_run {
+                        "Would recompress"
+                    } else {
+                        "Recompressed"
+                    },
+                    report.total_recompressed_chunks.yellow()
+                );
+            }
+            if !args.json && report.total_outdated_chunks > 0 {
+                anstream::println!(
+                    "{} chunks were last saved by a version older than DataVersion {}{}.",
+                    report.total_outdated_chunks.yellow(),
+                    args.min_data_version.unwrap_or_default(),
+                    if args.prune_outdated {
+                        ", all that weren't protected were pruned"
+                    } else {
+                        ", prune them with --prune-outdated"
+                    }
+                );
+            }
+            if !args.json && report.total_oversized_chunks > 0 {
+                anstream::println!(
+                    "{} chunks take up more than {} KiB{}:",
+                    report.total_oversized_chunks.yellow(),
+                    args.max_chunk_size.unwrap_or_default(),
+                    if args.prune_oversized {
+                        ", all that weren't protected were pruned"
+                    } else {
+                        ", prune them with --prune-oversized"
+                    }
+                );
+                print_positions(&mut oversized_positions);
+            }
+            if !args.json && report.total_crowded_chunks > 0 {
+                anstream::println!(
+                    "{} chunks contain more entities than allowed{}:",
+                    report.total_crowded_chunks.yellow(),
+                    if args.prune_crowded {
+                        ", all that weren't protected were pruned"
+                    } else {
+                        ", prune them with --prune-crowded"
+                    }
+                );
+                print_positions(&mut crowded_positions);
+            }
+            if !args.json && report.total_cluttered_chunks > 0 {
+                anstream::println!(
+                    "{} chunks contain more than {} block entities{}:",
+                    report.total_cluttered_chunks.yellow(),
+                    args.max_block_entities.unwrap_or_default(),
+                    if args.prune_cluttered {
+                        ", all that weren't protected were pruned"
+                    } else {
+                        ", prune them with --prune-cluttered"
+                    }
+                );
+                print_positions(&mut cluttered_positions);
+            }
+            if !args.json && !unreadable_positions.is_empty() {
+                anstream::println!(
+                    "{} chunks couldn't be read and were kept:",
+                    unreadable_positions.len().yellow()
+                );
+                print_positions(&mut unreadable_positions);
+            }
+            if !args.json {
+                print_clusters(&largest_clusters, args.dry_run);
+            }
+            #[cfg(feature = "self-update")]
+            update::print_notice(update_check.take());
+            true
+        }
+        Err(report) => {
+            progress_bar.abandon();
+            let largest_clusters = largest_clusters(&deleted_positions);
+            if !args.json {
+                for warning in &report.warnings {
+                    log::warn!("{}", warning);
+                }
+                for file in &report.skipped_files {
+                    log::warn!("Skipped {}: {}", file.path.display(), file.error);
+                }
+            }
+            let summary = Summary {
+                world_folder: &world_folder,
+                run_id: report.run_id,
+                finished: false,
+                dry_run: args.dry_run,
+                time_taken: report.time_taken,
+                freed_space: report.total_freed_space,
+                processed_regions: report.processed_regions,
+                total_regions: report.total_regions,
+                deleted_chunks: report.total_deleted_chunks,
+                failed_regions: &report.failed_regions,
+                skipped_files: report
+                    .skipped_files
+                    .iter()
+                    .map(|file| format!("{}: {}", file.path.display(), file.error))
+                    .collect(),
+                warnings: report.warnings.iter().map(ToString::to_string).collect(),
+            };
+            if let Some(mailer) = &mailer {
+                mailer.send_summary(&summary);
+            }
+            if let (Some(path), Some(html_report)) = (&args.report_html, &html_report) {
+                if let Err(err) = html_report.write(path, &summary) {
+                    log::error!("Failed to write {}: {}", path.display(), err);
+                }
+            }
+            if let Some(results_db) = &results_db {
+                if let Err(err) = results_db.write(&summary) {
+                    log::error!("Failed to record the run in the results database: {}", err);
+                }
+            }
+            if args.summary_format == SummaryFormat::Discord {
+                anstream::println!("{}", summary.to_discord());
+                return false;
+            }
+            anstream::println!(
+                "{}",
+                if args.json {
+                    serde_json::to_string(&ProcessingUpdate::Cancelled {
+                        report: CliPartialReport {
+                            run_id: report.run_id,
+                            time_taken: report.time_taken,
+                            total_freed_space: report.total_freed_space,
+                            processed_regions: report.processed_regions,
+                            total_regions: report.total_regions,
+                            total_chunks: report.total_chunks,
+                            total_deleted_chunks: report.total_deleted_chunks,
+                            total_reset_chunks: report.total_reset_chunks,
+                            total_trimmed_chunks: report.total_trimmed_chunks,
+                            total_recompressed_chunks: report.total_recompressed_chunks,
+                            total_outdated_chunks: report.total_outdated_chunks,
+                            total_oversized_chunks: report.total_oversized_chunks,
+                            total_crowded_chunks: report.total_crowded_chunks,
+                            total_cluttered_chunks: report.total_cluttered_chunks,
+                            freed_space_by_folder: report.freed_space_by_folder.clone(),
+                            warnings: report.warnings.iter().map(ToString::to_string).collect(),
+                            skipped_files: report
+                                .skipped_files
+                                .iter()
+                                .map(|file| file.path.clone())
+                                .collect(),
+                            failed_regions: report.failed_regions.clone(),
+                            largest_clusters: largest_clusters.clone(),
+                            modified_regions: report.modified_regions,
                         },
+                    })
+                    .unwrap()
+                } else {
+                    format!(
+                            "Aborted after processing {} of {} files in {}, freeing up {} by deleting {} chunks. {} files were modified.",
+                            report.processed_regions.yellow(),
+                            report.total_regions.yellow(),
+                            HumanDuration(report.time_taken).yellow(),
+                            HumanBytes(report.total_freed_space).yellow(),
+                            report.total_deleted_chunks.yellow(),
+                            report.modified_regions.len().yellow()
+                        )
+                },
+            );
+            if !args.json {
+                print_clusters(&largest_clusters, false);
+            }
+            // stopping at the end of the window is expected, not a failure
+            if window::reached() {
+                if !args.json {
+                    anstream::println!(
+                        "Stopped at the end of the time window, continue the run with --resume."
                     );
-                    process::exit(0)
                 }
+                return true;
+            }
+            false
+        }
+    }
+}
+
+/// Logs an error of the library, explaining permission errors caused by running as another user
+/// than the owner of the world.
+fn log_error(err: &lessanvil::Error, world_folder: &Path) {
+    log::error!("{}", err);
+    if let lessanvil::Error::IOError(source) = err {
+        if source.kind() == std::io::ErrorKind::PermissionDenied {
+            if let Some(hint) = container::ownership_hint(world_folder) {
+                log::error!("{}", hint);
+            }
+        }
+    }
+}
+
+/// The largest fraction of the chunks deleted without `--i-know-what-im-doing`.
+const MAX_DELETE_RATIO: f64 = 0.95;
+
+/// The amount of clusters of deleted chunks shown in the report.
+const SHOWN_CLUSTERS: usize = 5;
+
+fn largest_clusters(deleted: &[ChunkPos]) -> Vec<ChunkCluster> {
+    let mut clusters = clusters::clusters(deleted.iter().cloned());
+    clusters.truncate(SHOWN_CLUSTERS);
+    clusters
+}
+
+/// Lists the chunks, e.g. to teleport there and have a look.
+fn print_positions(positions: &mut [ChunkPos]) {
+    positions.sort();
+    for position in positions.iter() {
+        anstream::println!(
+            "  {} {}, {} (blocks {}, {})",
+            position.dimension,
+            position.x,
+            position.z,
+            position.x * 16,
+            position.z * 16
+        );
+    }
+}
+
+/// Prints where most chunks were deleted, so that accidentally deleted areas stand out.
+fn print_clusters(clusters: &[ChunkCluster], dry_run: bool) {
+    if clusters.is_empty() {
+        return;
+    }
+    anstream::println!(
+        "Largest areas of {}:",
+        if dry_run {
+            "chunks to delete"
+        } else {
+            "deleted chunks"
+        }
+    );
+    for cluster in clusters {
+        let (x, z) = cluster.center_block();
+        anstream::println!(
+            "  {} chunks in {} around ({}, {}), chunks ({}, {}) to ({}, {})",
+            cluster.chunks.yellow(),
+            cluster.dimension,
+            x,
+            z,
+            cluster.min_x,
+            cluster.min_z,
+            cluster.max_x,
+            cluster.max_z
+        );
+    }
+}
+
+/// Whether a subcommand modifies the world, which it only does with --apply. Exits the process if
+/// --apply is combined with --dry-run.
+fn applies(apply: bool, dry_run: bool) -> bool {
+    if apply && dry_run {
+        log::error!("--apply can't be combined with --dry-run!");
+        process::exit(1);
+    }
+    apply
+}
+
+/// Asks the user whether to continue, exiting the process if not.
+fn confirm(description: &str) {
+    container::require_terminal("Pass --confirm to skip the confirmation.");
+    anstream::eprintln!("{}", description);
+    anstream::eprintln!("{}: This tool will work on the given world folder. Therefore it's recommended to {} before continuing.", "Warning".black().on_red().bold(), "create a backup".black().on_yellow().bold());
+    if !Confirm::new()
+        .with_prompt("Do you want to continue?")
+        .interact()
+        .unwrap()
+    {
+        anstream::eprintln!("Aborting.");
+        process::exit(1);
+    }
+}
+
+/// The time --not-touched-since or --not-touched-for reaches back to.
+fn not_touched_since(args: &Args) -> Option<SystemTime> {
+    match (args.not_touched_since, args.not_touched_for) {
+        (Some(_), Some(_)) => {
+            log::error!("--not-touched-since can't be combined with --not-touched-for!");
+            process::exit(1)
+        }
+        (Some(since), None) => Some(since),
+        (None, age) => age.map(|age| SystemTime::now().checked_sub(age).unwrap_or(UNIX_EPOCH)),
+    }
+}
+
+/// The threshold chunks are judged by, with the InhabitedTime in every unit to spot a wrong one.
+fn describe_threshold(
+    max_inhabited_time: InhabitedTime,
+    not_touched_since: Option<SystemTime>,
+) -> String {
+    match not_touched_since {
+        Some(since) => format!(
+            "not saved since {} (according to the region headers)",
+            chrono::DateTime::<chrono::Local>::from(since)
+                .format("%Y-%m-%d %H:%M %:z")
+                .yellow()
+        ),
+        None => format!(
+            "{} of InhabitedTime ({} seconds, {} ticks)",
+            max_inhabited_time.yellow(),
+            max_inhabited_time.as_secs(),
+            max_inhabited_time.ticks()
+        ),
+    }
+}
+
+/// The question asked before modifying the world, naming what happens to which chunks.
+fn confirmation(config: &Config) -> String {
+    let chunks = if config.planned_chunks.is_some() {
+        "the selected chunks"
+    } else if config.not_touched_since.is_some() {
+        "all chunks that weren't saved since the given time"
+    } else {
+        "all chunks in which players have been less than the given amount of time"
+    };
+    match config.operation {
+        Operation::Delete => format!("This tool will remove {chunks}."),
+        Operation::ResetInhabitedTime => {
+            format!("This tool will reset the InhabitedTime of {chunks} to 0.")
+        }
+        Operation::ClearSections { min_y, max_y } => {
+            format!("This tool will clear the sections from Y {min_y} to {max_y} of {chunks}.")
+        }
+    }
+}
+
+/// Shows what the run is about to do, so that e.g. a threshold in the wrong unit or missing
+/// protections are noticed before anything is deleted.
+fn print_settings(config: &Config) {
+    anstream::eprintln!("World:       {}", config.world_folder.display());
+    anstream::eprintln!(
+        "Threshold:   {}",
+        describe_threshold(config.max_inhabited_time, config.not_touched_since)
+    );
+    let operation = match config.operation {
+        Operation::Delete => "delete the chunks below the threshold".to_string(),
+        Operation::ResetInhabitedTime => {
+            "reset the InhabitedTime of the chunks below the threshold".to_string()
+        }
+        Operation::ClearSections { min_y, max_y } => {
+            format!(
+                "clear the sections from Y {min_y} to {max_y} of the chunks below the threshold"
+            )
+        }
+    };
+    anstream::eprintln!("Operation:   {}", operation);
+    let mut also_pruned = vec![];
+    if let Some(OutdatedChunks {
+        min_data_version,
+        prune: true,
+    }) = config.outdated_chunks
+    {
+        also_pruned.push(format!("older than DataVersion {min_data_version}"));
+    }
+    if let Some(OversizedChunks {
+        max_size,
+        prune: true,
+    }) = config.oversized_chunks
+    {
+        also_pruned.push(format!("larger than {}", HumanBytes(max_size)));
+    }
+    if config
+        .entity_limits
+        .as_ref()
+        .is_some_and(|limits| limits.prune)
+    {
+        also_pruned.push("with too many entities".to_string());
+    }
+    if let Some(BlockEntityLimit {
+        max_block_entities,
+        prune: true,
+    }) = config.block_entity_limit
+    {
+        also_pruned.push(format!(
+            "with more than {max_block_entities} block entities"
+        ));
+    }
+    if !also_pruned.is_empty() {
+        anstream::eprintln!("Also delete: chunks {}", also_pruned.join(", "));
+    }
+    anstream::eprintln!(
+        "Dimensions:  {}",
+        if config.dimensions.is_empty() {
+            "all".to_string()
+        } else {
+            config
+                .dimensions
+                .iter()
+                .map(Dimension::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    );
+    anstream::eprintln!(
+        "Protections: {} areas{}",
+        config.protected_areas.len().yellow(),
+        if config.player_blocks.is_empty() {
+            String::new()
+        } else {
+            format!(", chunks containing {}", config.player_blocks.join(", "))
+        }
+    );
+    if let Some(planned) = &config.planned_chunks {
+        anstream::eprintln!("Selection:   only {} chosen chunks", planned.len().yellow());
+    }
+    match lessanvil::count_chunks(config) {
+        Ok(counts) => {
+            let regions = counts.values().map(|count| count.regions).sum::<u64>();
+            let chunks = counts.values().map(|count| count.chunks).sum::<u64>();
+            anstream::eprintln!(
+                "Chunks:      {} in {} region files ({})",
+                chunks.yellow(),
+                regions,
+                counts
+                    .iter()
+                    .map(|(dimension, count)| format!("{}: {}", dimension, count.chunks))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        Err(err) => anstream::eprintln!("Chunks:      unknown, {}", err),
+    }
+    anstream::eprintln!();
+}
+
+fn parse_inhabited_time(value: &str) -> Result<InhabitedTime, String> {
+    value
+        .parse()
+        .map_err(|err: ParseInhabitedTimeError| err.to_string())
+}
+
+fn parse_age(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit) = value.split_at(
+        value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len()),
+    );
+    let number = number
+        .parse::<u64>()
+        .map_err(|_| format!("invalid age `{value}`"))?;
+    let factor = match unit.trim() {
+        "h" => 3600,
+        "d" => 24 * 3600,
+        "w" => 7 * 24 * 3600,
+        _ => return Err(format!("unknown unit `{unit}`, expected `h`, `d` or `w`")),
+    };
+    Ok(Duration::from_secs(number.saturating_mul(factor)))
+}
+
+fn parse_date(value: &str) -> Result<SystemTime, String> {
+    use chrono::{FixedOffset, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+    let invalid = || {
+        format!("invalid date `{value}`, expected e.g. `2023-01-01`, `2023-01-01 18:30` or `2023-01-01T18:30+02:00`")
+    };
+    let value = value.trim();
+    // the time zone, if any, follows the time of day
+    let (date, offset) = if let Some(date) = value
+        .strip_suffix('Z')
+        .or_else(|| value.strip_suffix("UTC"))
+    {
+        (
+            date.trim_end(),
+            Some(FixedOffset::east_opt(0).expect("valid")),
+        )
+    } else {
+        match value
+            .rfind(['+', '-'])
+            .filter(|&index| index > "2023-01-01".len())
+        {
+            Some(index) => {
+                let (date, offset) = value.split_at(index);
+                let (hours, minutes) = offset[1..].split_once(':').ok_or_else(invalid)?;
+                let (hours, minutes) = (
+                    hours.parse::<i32>().map_err(|_| invalid())?,
+                    minutes.parse::<i32>().map_err(|_| invalid())?,
+                );
+                let seconds = (hours * 60 + minutes) * 60;
+                let seconds = if offset.starts_with('-') {
+                    -seconds
+                } else {
+                    seconds
+                };
+                (
+                    date.trim_end(),
+                    Some(FixedOffset::east_opt(seconds).ok_or_else(invalid)?),
+                )
             }
+            None => (value, None),
+        }
+    };
+    let naive = [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })
+    .ok_or_else(invalid)?;
+
+    match offset {
+        Some(offset) => Ok(Utc.from_utc_datetime(&(naive - offset)).into()),
+        None => match naive.and_local_timezone(chrono::Local) {
+            LocalResult::Single(time) => Ok(time.into()),
+            LocalResult::Ambiguous(..) => Err(format!(
+                "`{value}` occurs twice in the local time zone because of a daylight saving change, add an offset like `+02:00`"
+            )),
+            LocalResult::None => Err(format!(
+                "`{value}` doesn't exist in the local time zone because of a daylight saving change"
+            )),
+        },
+    }
+}
+
+fn parse_y_range(value: &str) -> Result<(i32, i32), String> {
+    let Some((min, max)) = value.split_once("..") else {
+        return Err("expected `<min>..<max>`".to_string());
+    };
+    let coordinate = |value: &str, default| {
+        if value.is_empty() {
+            Ok(default)
+        } else {
+            value
+                .parse::<i32>()
+                .map_err(|_| format!("invalid Y coordinate `{value}`"))
         }
+    };
+    Ok((coordinate(min, i32::MIN)?, coordinate(max, i32::MAX)?))
+}
 
-        if !running.load(std::sync::atomic::Ordering::Relaxed) {
-            anstream::eprintln!("Aborting.");
-            drop(rx);
-            return;
+fn parse_post_clean(value: &str) -> Result<PostClean, String> {
+    let mut options = PostClean::default();
+    for cleanup in value.split(',').map(str::trim) {
+        match cleanup {
+            "raids" => options.raids = true,
+            "scoreboard" => options.scoreboard = true,
+            "map-ids" => options.map_ids = true,
+            "junk-files" => options.junk_files = true,
+            "all" => {
+                options = PostClean {
+                    raids: true,
+                    scoreboard: true,
+                    map_ids: true,
+                    junk_files: true,
+                }
+            }
+            _ => return Err(format!("unknown cleanup `{cleanup}`")),
         }
     }
+    Ok(options)
+}
+
+fn parse_entity_limit(value: &str) -> Result<(String, u32), String> {
+    let Some((id, max)) = value.split_once('=') else {
+        return Err("expected `<entity id>=<max>`".to_string());
+    };
+    let max = max
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid amount of entities `{max}`"))?;
+    let id = id.trim();
+    // like the game, ids without a namespace belong to minecraft
+    let id = if id.contains(':') {
+        id.to_string()
+    } else {
+        format!("minecraft:{id}")
+    };
+    Ok((id, max))
+}
+
+fn parse_run_id(value: &str) -> Result<RunId, String> {
+    value
+        .parse()
+        .map_err(|err: ParseRunIdError| err.to_string())
+}
+
+fn parse_nether_roof(value: &str) -> Result<NetherRoof, String> {
+    match value {
+        "trim" => Ok(NetherRoof::Trim),
+        "protect" => Ok(NetherRoof::Protect),
+        _ => Err("expected `trim` or `protect`".to_string()),
+    }
+}
+
+fn parse_summary_format(value: &str) -> Result<SummaryFormat, String> {
+    match value {
+        "text" => Ok(SummaryFormat::Text),
+        "discord" => Ok(SummaryFormat::Discord),
+        _ => Err("expected `text` or `discord`".to_string()),
+    }
+}
+
+fn parse_compression(value: &str) -> Result<ChunkCompression, String> {
+    match value {
+        "gzip" => Ok(ChunkCompression::Gzip),
+        "zlib" => Ok(ChunkCompression::Zlib),
+        "uncompressed" => Ok(ChunkCompression::Uncompressed),
+        "lz4" => Ok(ChunkCompression::Lz4),
+        _ => Err("expected `zlib`, `lz4`, `gzip` or `uncompressed`".to_string()),
+    }
+}
+
+fn is_valid_world(world_folder: &Path) -> bool {
+    world_folder.join("level.dat").exists() && world_folder.join("region").exists()
+}
+
+/// The radius (in chunks) protected around the whitelisted players and operators without
+/// --protect-players.
+const PRIVILEGED_PLAYER_RADIUS: u32 = 4;
+
+fn load_protections(args: &Args, world_folder: &Path) -> Result<Vec<ProtectedArea>, String> {
+    let mut areas = vec![];
+    for path in &args.protected_areas {
+        areas.append(&mut protection::areas_from_file(path).map_err(|err| err.to_string())?);
+    }
+    for path in &args.waypoints {
+        let waypoints = protection::waypoints(path).map_err(|err| err.to_string())?;
+        areas.extend(waypoints.into_iter().map(|position| ProtectedArea::Around {
+            position,
+            radius: args.waypoint_radius,
+        }));
+    }
+
+    let mut positions = vec![];
+    if args.protect_players.is_some() {
+        positions.append(
+            &mut protection::player_positions(world_folder).map_err(|err| err.to_string())?,
+        );
+    } else if !args.no_protect_whitelist {
+        // --protect-players already covers every player
+        let privileged = players::privileged_players(&players::server_folder(world_folder))?;
+        areas.extend(
+            protection::positions_of_players(world_folder, &privileged.uuids)
+                .map_err(|err| err.to_string())?
+                .into_iter()
+                .map(|position| ProtectedArea::Around {
+                    position,
+                    radius: PRIVILEGED_PLAYER_RADIUS,
+                }),
+        );
+    }
+    for path in &args.player_positions {
+        positions.append(&mut protection::positions_from_csv(path).map_err(|err| err.to_string())?);
+    }
+
+    let radius = args.protect_players.unwrap_or(0);
+    areas.extend(
+        positions
+            .into_iter()
+            .map(|position| ProtectedArea::Around { position, radius }),
+    );
+    Ok(areas)
+}
+
+fn player_blocks(args: &Args) -> Vec<String> {
+    if !args.player_block.is_empty() {
+        args.player_block
+            .iter()
+            .map(|id| {
+                if id.contains(':') {
+                    id.clone()
+                } else {
+                    format!("minecraft:{id}")
+                }
+            })
+            .collect()
+    } else if args.protect_player_blocks {
+        lessanvil::DEFAULT_PLAYER_BLOCKS
+            .iter()
+            .map(|id| id.to_string())
+            .collect()
+    } else {
+        vec![]
+    }
 }
@@ -0,0 +1,79 @@
+use std::{fs::File, io::BufWriter, path::PathBuf, process};
+
+use owo_colors::OwoColorize;
+
+/// Write a manifest of the hashes of every chunk of a world.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "manifest")]
+pub struct ManifestArgs {
+    /// the world folder
+    #[argh(positional)]
+    world_folder: PathBuf,
+    /// the file to write the manifest to
+    #[argh(option, short = 'o')]
+    output: PathBuf,
+}
+
+/// Verify that the chunks of a world are still identical to a manifest.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "verify-manifest")]
+pub struct VerifyManifestArgs {
+    /// the world folder
+    #[argh(positional)]
+    world_folder: PathBuf,
+    /// the manifest written by `manifest`
+    #[argh(positional)]
+    manifest: PathBuf,
+    /// whether the output should be in json
+    #[argh(switch)]
+    json: bool,
+}
+
+pub fn run(args: ManifestArgs) {
+    let result = lessanvil::diff::hash_world(&args.world_folder).and_then(|hashes| {
+        let file = BufWriter::new(File::create(&args.output)?);
+        lessanvil::diff::write_manifest(&hashes, file)?;
+        Ok(hashes.len())
+    });
+
+    match result {
+        Ok(chunks) => anstream::println!(
+            "Successfully wrote the hashes of {} chunks to {}.",
+            chunks.yellow(),
+            args.output.display()
+        ),
+        Err(err) => {
+            log::error!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+pub fn run_verify(args: VerifyManifestArgs) {
+    let diff = match lessanvil::diff::verify_manifest(&args.world_folder, &args.manifest) {
+        Ok(diff) => diff,
+        Err(err) => {
+            log::error!("{}", err);
+            process::exit(1)
+        }
+    };
+
+    if args.json {
+        anstream::println!("{}", serde_json::to_string(&diff).unwrap());
+    } else {
+        for chunk in &diff.changed {
+            anstream::println!("~ {} {} {}", chunk.dimension, chunk.x, chunk.z);
+        }
+        anstream::println!(
+            "{} chunks identical, {} changed, {} removed and {} added since the manifest was written.",
+            diff.unchanged.green(),
+            diff.changed.len().red(),
+            diff.removed.len(),
+            diff.added.len()
+        );
+    }
+
+    if !diff.changed.is_empty() {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,38 @@
+use lessanvil::{ChunkState, ProcessedRegion};
+use owo_colors::OwoColorize;
+
+/// Prints a map of the chunks of the region with north at the top, one character per chunk.
+pub fn print_region(region: &ProcessedRegion) {
+    anstream::eprintln!(
+        "{} r.{}.{} ({} deleted, {} protected, {} modified, {} kept)",
+        region.dimension,
+        region.x,
+        region.y,
+        "x".red(),
+        "P".green(),
+        "~".yellow(),
+        "."
+    );
+    for row in region.chunk_states.chunks(32) {
+        let mut line = String::from("  ");
+        for state in row {
+            let symbol = match state {
+                ChunkState::Empty => " ".to_string(),
+                ChunkState::Kept => ".".to_string(),
+                ChunkState::Deleted => "x".red().to_string(),
+                ChunkState::Protected => "P".green().to_string(),
+                ChunkState::Modified => "~".yellow().to_string(),
+            };
+            line.push_str(&symbol);
+        }
+        anstream::eprintln!("{}", line);
+    }
+}
+
+/// Whether anything happened to the chunks of the region, i.e. whether its map is interesting.
+pub fn has_changes(region: &ProcessedRegion) -> bool {
+    region
+        .chunk_states
+        .iter()
+        .any(|state| !matches!(state, ChunkState::Empty | ChunkState::Kept))
+}
@@ -0,0 +1,92 @@
+use std::{path::PathBuf, process};
+
+use indicatif::HumanDuration;
+use lessanvil::transfer::{self, Selection};
+use lessanvil::InhabitedTime;
+use owo_colors::OwoColorize;
+
+/// Copy chunks from one world into another, e.g. to salvage builds into a fresh map. Only shows
+/// how many chunks would be copied unless --apply is passed.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "merge")]
+pub struct MergeArgs {
+    /// the world to copy chunks from
+    #[argh(positional)]
+    source: PathBuf,
+    /// the world to copy chunks into. Existing chunks get overwritten
+    #[argh(positional)]
+    destination: PathBuf,
+    /// copy all chunks in which players have spent more than the given time, e.g. `90s`, `5m`,
+    /// `2h` or `1200t` (game ticks). Plain numbers are seconds
+    #[argh(option, short = 'm', from_str_fn(crate::parse_inhabited_time))]
+    min_inhabited_time: Option<InhabitedTime>,
+    /// a CSV file with the `dimension,x,z` chunk coordinates to copy
+    #[argh(option)]
+    chunks: Option<PathBuf>,
+    /// copy the chunks. Without it nothing is changed
+    #[argh(switch)]
+    apply: bool,
+    /// only show how many chunks would be copied, the default without --apply
+    #[argh(switch)]
+    dry_run: bool,
+    /// skip confirmation prompt. Use this with caution!
+    #[argh(switch)]
+    confirm: bool,
+    /// skip all checks for the worlds being valid. Use this with caution!
+    #[argh(switch)]
+    force: bool,
+}
+
+pub fn run(args: MergeArgs) {
+    if !args.force
+        && (!crate::is_valid_world(&args.source) || !crate::is_valid_world(&args.destination))
+    {
+        log::error!("Invalid world folder!");
+        process::exit(1);
+    }
+
+    let selection = match (args.min_inhabited_time, &args.chunks) {
+        (Some(min), None) => Selection::InhabitedTimeAbove(min),
+        (None, Some(path)) => match transfer::chunks_from_csv(path) {
+            Ok(chunks) => Selection::Chunks(chunks),
+            Err(err) => {
+                log::error!("{}", err);
+                process::exit(1)
+            }
+        },
+        _ => {
+            log::error!("Exactly one of --min-inhabited-time and --chunks is required!");
+            process::exit(1)
+        }
+    };
+
+    let apply = crate::applies(args.apply, args.dry_run);
+    if apply && !args.confirm {
+        crate::confirm("This tool will overwrite chunks of the destination world with the selected chunks of the source world.");
+    }
+
+    let start_time = std::time::Instant::now();
+    let result = if apply {
+        transfer::merge(&args.source, &args.destination, &selection)
+    } else {
+        transfer::matching_chunks(&args.source, &selection)
+    };
+    match result {
+        Ok(report) if apply => anstream::println!(
+            "Successfully copied {} chunks from {} files in {}.",
+            report.copied_chunks.yellow(),
+            report.total_regions.yellow(),
+            HumanDuration(start_time.elapsed()).yellow()
+        ),
+        Ok(report) => anstream::println!(
+            "{} chunks of {} files would be copied into {}. Pass --apply to copy them.",
+            report.copied_chunks.yellow(),
+            report.total_regions.yellow(),
+            args.destination.display()
+        ),
+        Err(err) => {
+            log::error!("{}", err);
+            process::exit(1)
+        }
+    }
+}
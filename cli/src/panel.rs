@@ -0,0 +1,119 @@
+//! Running inside a game server panel like Pterodactyl or Pelican (`--pterodactyl`), e.g. as a
+//! scheduled task of the server.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use indicatif::HumanDuration;
+
+/// The share of the server's memory limit used for chunk data, leaving room for the rest of the
+/// process.
+const MEMORY_SHARE: u64 = 2;
+
+/// A line is printed every time this many percent of the region files were processed.
+const PROGRESS_STEP: u64 = 5;
+
+/// The name of the world in the `server.properties` of the server folder.
+pub fn level_name(server_folder: &Path) -> Option<String> {
+    let properties = fs::read_to_string(server_folder.join("server.properties")).ok()?;
+    properties
+        .lines()
+        .find_map(|line| line.strip_prefix("level-name="))
+        .map(|name| name.trim().to_string())
+}
+
+/// The world of the server in the current folder, the panel's working directory.
+pub fn world_folder() -> Option<PathBuf> {
+    let server_folder = env::current_dir().ok()?;
+    let name = level_name(&server_folder).unwrap_or_else(|| "world".to_string());
+    Some(server_folder.join(name))
+}
+
+/// The resources the panel grants the server.
+#[derive(Default)]
+pub struct Limits {
+    /// In bytes.
+    pub memory: Option<u64>,
+    /// Rounded up.
+    pub cpus: Option<usize>,
+}
+
+impl Limits {
+    /// Reads the limits from the environment of the server's container. Pterodactyl and Pelican
+    /// pass the memory limit as `SERVER_MEMORY`, both are also enforced through the cgroup.
+    pub fn read() -> Self {
+        let memory = env::var("SERVER_MEMORY")
+            .ok()
+            .and_then(|mib| mib.trim().parse::<u64>().ok())
+            // 0 means unlimited
+            .filter(|&mib| mib > 0)
+            .map(|mib| mib * 1024 * 1024)
+            .or_else(|| cgroup_value("memory.max")?.parse().ok());
+        let cpus = cgroup_value("cpu.max").and_then(|value| {
+            let (quota, period) = value.split_once(' ')?;
+            let (quota, period) = (quota.parse::<u64>().ok()?, period.parse::<u64>().ok()?);
+            Some(((quota + period - 1) / period).max(1) as usize)
+        });
+        Self { memory, cpus }
+    }
+
+    /// The amount of threads to spawn.
+    pub fn thread_count(&self) -> usize {
+        let available = num_cpus::get();
+        self.cpus.map_or(available, |cpus| cpus.min(available))
+    }
+
+    /// The maximum amount of chunk data held in memory, in bytes.
+    pub fn max_memory(&self) -> Option<u64> {
+        self.memory.map(|memory| memory / MEMORY_SHARE)
+    }
+}
+
+/// A value of the cgroup (v2) of the container, `None` if it isn't limited.
+fn cgroup_value(name: &str) -> Option<String> {
+    let value = fs::read_to_string(Path::new("/sys/fs/cgroup").join(name)).ok()?;
+    let value = value.trim();
+    (!value.starts_with("max")).then(|| value.to_string())
+}
+
+/// Prints the progress as plain lines, which the panel's console shows as they come instead of
+/// garbling a progress bar.
+#[derive(Default)]
+pub struct ProgressLines {
+    total: u64,
+    processed: u64,
+    printed_percent: u64,
+}
+
+impl ProgressLines {
+    pub fn start(&mut self, total: u64) {
+        self.total = total;
+        anstream::println!("[lessanvil]: Processing {} region files", total);
+    }
+
+    pub fn inc(&mut self, eta: Option<Duration>) {
+        self.processed += 1;
+        let percent = self.processed * 100 / self.total.max(1);
+        if percent / PROGRESS_STEP > self.printed_percent / PROGRESS_STEP {
+            self.printed_percent = percent;
+            match eta {
+                Some(eta) if self.processed < self.total => anstream::println!(
+                    "[lessanvil]: Processed {}/{} region files ({}%), about {} left",
+                    self.processed,
+                    self.total,
+                    percent,
+                    HumanDuration(eta)
+                ),
+                _ => anstream::println!(
+                    "[lessanvil]: Processed {}/{} region files ({}%)",
+                    self.processed,
+                    self.total,
+                    percent
+                ),
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! Writing the metadata of every chunk into a Parquet file (`scan --format parquet`), one row per
+//! chunk, for queries over the activity in a world with e.g. DuckDB or pandas. Requires the
+//! `parquet` feature.
+//!
+//! The chunks are written in row groups of [`BATCH_SIZE`] as they are scanned, so that worlds with
+//! hundreds of millions of chunks don't have to fit into memory.
+
+use std::path::Path;
+
+use lessanvil::scan::ScanBackend;
+
+/// The amount of chunks written at once.
+#[cfg_attr(not(feature = "parquet"), allow(dead_code))]
+const BATCH_SIZE: usize = 64 * 1024;
+
+/// Scans the world and writes a row for every chunk, returning the amount of chunks written.
+#[cfg(feature = "parquet")]
+pub fn write(world_folder: &Path, backend: ScanBackend, path: &Path) -> Result<u64, String> {
+    use std::{fs::File, sync::Arc};
+
+    use lessanvil::scan::WorldScanner;
+    use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+
+    let scanner = WorldScanner::new(world_folder, backend).map_err(|err| err.to_string())?;
+    let schema = Arc::new(schema());
+    let file = File::create(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_max_row_group_size(BATCH_SIZE)
+        .build();
+    let mut writer = ArrowWriter::try_new(file, schema.clone(), Some(properties))
+        .map_err(|err| err.to_string())?;
+
+    let mut columns = Columns::default();
+    let mut count = 0;
+    for chunk in scanner {
+        columns.push(chunk.map_err(|err| err.to_string())?);
+        count += 1;
+        if columns.len() == BATCH_SIZE {
+            let batch = columns.take_batch(&schema).map_err(|err| err.to_string())?;
+            writer.write(&batch).map_err(|err| err.to_string())?;
+        }
+    }
+    if !columns.is_empty() {
+        let batch = columns.take_batch(&schema).map_err(|err| err.to_string())?;
+        writer.write(&batch).map_err(|err| err.to_string())?;
+    }
+    writer.close().map_err(|err| err.to_string())?;
+    Ok(count)
+}
+
+#[cfg(not(feature = "parquet"))]
+pub fn write(_world_folder: &Path, _backend: ScanBackend, _path: &Path) -> Result<u64, String> {
+    Err("lessanvil-cli was built without the `parquet` feature".to_string())
+}
+
+/// The columns of the file. Times are kept in the units the game stores them in: InhabitedTime
+/// and LastUpdate in ticks, the save time of the region header in seconds.
+#[cfg(feature = "parquet")]
+fn schema() -> arrow_schema::Schema {
+    use arrow_schema::{DataType, Field, Schema, TimeUnit};
+
+    Schema::new(vec![
+        Field::new("dimension", DataType::Utf8, false),
+        Field::new("x", DataType::Int32, false),
+        Field::new("z", DataType::Int32, false),
+        Field::new("inhabited_time", DataType::Int64, false),
+        Field::new("last_update", DataType::Int64, false),
+        Field::new(
+            "saved_at",
+            DataType::Timestamp(TimeUnit::Second, Some("UTC".into())),
+            false,
+        ),
+        Field::new("size", DataType::Int64, false),
+        Field::new("status", DataType::Utf8, true),
+        Field::new("data_version", DataType::Int32, true),
+        Field::new("block_entities", DataType::Int64, false),
+    ])
+}
+
+/// The chunks of the next row group, column by column.
+#[cfg(feature = "parquet")]
+#[derive(Default)]
+struct Columns {
+    dimension: Vec<String>,
+    x: Vec<i32>,
+    z: Vec<i32>,
+    inhabited_time: Vec<i64>,
+    last_update: Vec<i64>,
+    saved_at: Vec<i64>,
+    size: Vec<i64>,
+    status: Vec<Option<String>>,
+    data_version: Vec<Option<i32>>,
+    block_entities: Vec<i64>,
+}
+
+#[cfg(feature = "parquet")]
+impl Columns {
+    fn push(&mut self, chunk: lessanvil::scan::ScannedChunk) {
+        self.dimension.push(chunk.dimension.to_string());
+        self.x.push(chunk.x);
+        self.z.push(chunk.z);
+        self.inhabited_time
+            .push(chunk.meta.inhabited_time.ticks() as i64);
+        self.last_update.push(chunk.meta.last_update);
+        self.saved_at.push(chunk.timestamp as i64);
+        self.size.push(chunk.size as i64);
+        self.status.push(chunk.meta.status);
+        self.data_version.push(chunk.meta.data_version);
+        self.block_entities.push(chunk.meta.block_entities as i64);
+    }
+
+    fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+
+    /// Turns the chunks into a record batch, leaving the columns empty.
+    fn take_batch(
+        &mut self,
+        schema: &arrow_schema::SchemaRef,
+    ) -> Result<arrow_array::RecordBatch, arrow_schema::ArrowError> {
+        use std::sync::Arc;
+
+        use arrow_array::{
+            ArrayRef, Int32Array, Int64Array, RecordBatch, StringArray, TimestampSecondArray,
+        };
+
+        let columns = std::mem::take(self);
+        RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(columns.dimension)) as ArrayRef,
+                Arc::new(Int32Array::from(columns.x)),
+                Arc::new(Int32Array::from(columns.z)),
+                Arc::new(Int64Array::from(columns.inhabited_time)),
+                Arc::new(Int64Array::from(columns.last_update)),
+                Arc::new(TimestampSecondArray::from(columns.saved_at).with_timezone("UTC")),
+                Arc::new(Int64Array::from(columns.size)),
+                Arc::new(StringArray::from(columns.status)),
+                Arc::new(Int32Array::from(columns.data_version)),
+                Arc::new(Int64Array::from(columns.block_entities)),
+            ],
+        )
+    }
+}
@@ -0,0 +1,169 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+use lessanvil::{
+    cache,
+    plan::{self, Plan},
+    ChunkPos, Config,
+};
+use owo_colors::OwoColorize;
+
+/// Don't modify the world but write the chunks that would be deleted with the other options to a
+/// plan, e.g. to compute them against a backup and apply them to the live world later.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "plan")]
+pub struct PlanArgs {
+    /// the file to write the plan to
+    #[argh(option, short = 'o')]
+    pub output: PathBuf,
+    /// an earlier plan of the world to compare the new one with: which chunks newly qualify for
+    /// deletion and which no longer do
+    #[argh(option)]
+    pub compare: Option<PathBuf>,
+    /// with --compare, list the positions of the chunks that changed
+    #[argh(switch)]
+    pub list_changes: bool,
+}
+
+/// Delete exactly the chunks of a plan written by `plan`, ignoring InhabitedTime and
+/// protections. Given a chunk cache written by `scan` instead, delete the chunks selected from it
+/// by the threshold, dimensions and protected areas. Chunks that were saved since the plan or
+/// cache was made are skipped.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "apply")]
+pub struct ApplyArgs {
+    /// the plan or chunk cache to apply
+    #[argh(positional)]
+    pub plan: PathBuf,
+    /// apply the plan even if it was made for a world with a different seed
+    #[argh(switch)]
+    pub any_world: bool,
+}
+
+/// Reads the plan and returns the chunks that can still be deleted safely.
+pub fn load(args: &ApplyArgs, config: &Config, json: bool) -> HashSet<ChunkPos> {
+    let plan = if cache::is_cache(&args.plan) {
+        // the cache only holds the metadata of the chunks
+        if !config.player_blocks.is_empty() || config.nether_roof.is_some() {
+            log::error!("--protect-player-blocks, --player-block and --nether-roof can't be combined with a chunk cache!");
+            process::exit(1)
+        }
+        cache::read_cache(&args.plan).map(|cache| cache.plan(config))
+    } else {
+        plan::read_plan(&args.plan)
+    };
+    let mut plan = plan.unwrap_or_else(|err| {
+        log::error!("{}", err);
+        process::exit(1)
+    });
+    if args.any_world {
+        plan.world = None;
+    }
+
+    let check = plan::check_plan(config, &plan).unwrap_or_else(|err| {
+        log::error!("{}", err);
+        process::exit(1)
+    });
+    if !json && !check.changed.is_empty() {
+        log::warn!(
+            "Skipping {} chunks that were modified since the plan was made.",
+            check.changed.len()
+        );
+    }
+    check.unchanged
+}
+
+/// Reads the earlier plan given with `--compare`, before anything is processed.
+pub fn load_earlier(args: &PlanArgs) -> Option<Plan> {
+    let path = args.compare.as_ref()?;
+    Some(plan::read_plan(path).unwrap_or_else(|err| {
+        log::error!("{}", err);
+        process::exit(1)
+    }))
+}
+
+/// Writes the chunks to delete to the plan file, comparing them with the earlier plan if given.
+pub fn write(
+    args: &PlanArgs,
+    config: &Config,
+    chunks: impl IntoIterator<Item = ChunkPos>,
+    earlier: Option<&Plan>,
+    json: bool,
+) {
+    let path = &args.output;
+    let plan = Plan::new(config, chunks).unwrap_or_else(|err| {
+        log::error!("Failed to make the plan: {}", err);
+        process::exit(1)
+    });
+    let result = File::create(path).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        plan::write_plan(&plan, &mut writer)?;
+        writer.flush()
+    });
+    if let Err(err) = result {
+        log::error!("Failed to write the plan: {}", err);
+        process::exit(1)
+    }
+    if json {
+        return;
+    }
+    anstream::println!(
+        "Wrote the {} chunks to delete to {}.",
+        plan.chunks.len().yellow(),
+        path.display()
+    );
+    if let Some(earlier) = earlier {
+        print_comparison(args, earlier, &plan);
+    }
+}
+
+fn print_comparison(args: &PlanArgs, earlier: &Plan, plan: &Plan) {
+    if let (Some(expected), Some(actual)) = (&earlier.world, &plan.world) {
+        if !expected.matches(actual) {
+            log::warn!(
+                "The earlier plan was made for another world ({}, this one is {})",
+                expected,
+                actual
+            );
+        }
+    }
+    let mut comparison = plan::compare_plans(earlier, plan);
+    anstream::println!(
+        "Compared to {}: {} chunks newly qualify for deletion, {} no longer do and {} still do.",
+        args.compare.as_deref().unwrap_or(Path::new("")).display(),
+        comparison.added.len().yellow(),
+        comparison.removed.len().yellow(),
+        comparison.unchanged
+    );
+    if let (Some(before), Some(after)) = (&earlier.filter, &plan.filter) {
+        if before.max_inhabited_time != after.max_inhabited_time {
+            anstream::println!(
+                "The threshold changed from {} to {}.",
+                before.max_inhabited_time,
+                after.max_inhabited_time
+            );
+        }
+        if before.protected_areas != after.protected_areas {
+            anstream::println!(
+                "The protected areas changed from {} to {}.",
+                before.protected_areas,
+                after.protected_areas
+            );
+        }
+    }
+    if args.list_changes {
+        if !comparison.added.is_empty() {
+            anstream::println!("Newly qualifying chunks:");
+            crate::print_positions(&mut comparison.added);
+        }
+        if !comparison.removed.is_empty() {
+            anstream::println!("No longer qualifying chunks:");
+            crate::print_positions(&mut comparison.removed);
+        }
+    }
+}
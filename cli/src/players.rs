@@ -0,0 +1,193 @@
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+use lessanvil::players;
+use owo_colors::OwoColorize;
+
+/// Remove player data that is no longer needed. Only shows what would be removed unless --apply is
+/// passed.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "prune-players")]
+pub struct PrunePlayersArgs {
+    /// the world folder
+    #[argh(positional)]
+    world_folder: PathBuf,
+    /// remove the data. Without it nothing is changed
+    #[argh(switch)]
+    apply: bool,
+    /// only show what would be removed, the default without --apply
+    #[argh(switch)]
+    dry_run: bool,
+    /// skip confirmation prompt. Use this with caution!
+    #[argh(switch)]
+    confirm: bool,
+    /// skip all checks for the world being valid. Use this with caution!
+    #[argh(switch)]
+    force: bool,
+    /// also remove the scores and team memberships of players without player data from the
+    /// scoreboard. Objectives and teams are kept and the original is kept as scoreboard.dat.bak
+    #[argh(switch)]
+    scoreboard: bool,
+    /// also prune the data of the players in the whitelist.json and ops.json of the server,
+    /// which are kept by default
+    #[argh(switch)]
+    no_protect_whitelist: bool,
+    /// the usercache.json of the server, to look up the names of the players for --scoreboard.
+    /// Default is the one next to the world folder
+    #[argh(option)]
+    usercache: Option<PathBuf>,
+}
+
+pub fn run(args: PrunePlayersArgs) {
+    if !args.force && !crate::is_valid_world(&args.world_folder) {
+        log::error!("Invalid world folder!");
+        process::exit(1);
+    }
+
+    let apply = crate::applies(args.apply, args.dry_run);
+    if apply && !args.confirm {
+        crate::confirm(if args.scoreboard {
+            "This tool will remove the advancements, statistics and scores of players without player data."
+        } else {
+            "This tool will remove the advancements and statistics of players without player data."
+        });
+    }
+
+    let privileged = if args.no_protect_whitelist {
+        PrivilegedPlayers::default()
+    } else {
+        match privileged_players(&server_folder(&args.world_folder)) {
+            Ok(privileged) => privileged,
+            Err(err) => {
+                log::error!("{}", err);
+                process::exit(1)
+            }
+        }
+    };
+
+    let result = if apply {
+        players::remove_orphaned_files_except(&args.world_folder, &privileged.uuids)
+    } else {
+        players::find_orphaned_files_except(&args.world_folder, &privileged.uuids)
+    };
+    match result {
+        Ok(report) if apply => anstream::println!(
+            "Removed {} orphaned advancement and {} orphaned statistics files.",
+            report.removed_advancements.yellow(),
+            report.removed_stats.yellow()
+        ),
+        Ok(report) => anstream::println!(
+            "{} orphaned advancement and {} orphaned statistics files would be removed.",
+            report.removed_advancements.yellow(),
+            report.removed_stats.yellow()
+        ),
+        Err(err) => {
+            log::error!("{}", err);
+            process::exit(1)
+        }
+    }
+
+    if args.scoreboard {
+        let usercache = args
+            .usercache
+            .clone()
+            .unwrap_or_else(|| server_folder(&args.world_folder).join("usercache.json"));
+        let departed = match departed_players(&args.world_folder, &usercache) {
+            Ok(mut departed) => {
+                departed.retain(|name| !privileged.names.contains(name));
+                departed
+            }
+            Err(err) => {
+                log::error!("{}", err);
+                process::exit(1)
+            }
+        };
+        let result = if apply {
+            players::remove_scores(&args.world_folder, &departed)
+        } else {
+            players::find_scores(&args.world_folder, &departed)
+        };
+        match result {
+            Ok(report) if apply => anstream::println!(
+                "Removed {} scores and {} team memberships of {} players without player data.",
+                report.removed_scores.yellow(),
+                report.removed_team_members.yellow(),
+                departed.len().yellow()
+            ),
+            Ok(report) => anstream::println!(
+                "{} scores and {} team memberships of {} players without player data would be removed.",
+                report.removed_scores.yellow(),
+                report.removed_team_members.yellow(),
+                departed.len().yellow()
+            ),
+            Err(err) => {
+                log::error!("{}", err);
+                process::exit(1)
+            }
+        }
+    }
+    if !apply {
+        anstream::println!("Nothing was changed. Pass --apply to remove them.");
+    }
+}
+
+/// The names of the players the server remembers in its `usercache.json` that have no player data (anymore).
+fn departed_players(world_folder: &Path, usercache: &Path) -> Result<HashSet<String>, String> {
+    let player_data = world_folder.join("playerdata");
+    // without any player data every player would look departed
+    if !player_data.is_dir() {
+        return Ok(HashSet::new());
+    }
+    let json = fs::read_to_string(usercache)
+        .map_err(|err| format!("Failed to read {}: {}", usercache.display(), err))?;
+    let users: Vec<serde_json::Value> = serde_json::from_str(&json)
+        .map_err(|err| format!("Failed to read {}: {}", usercache.display(), err))?;
+    Ok(users
+        .iter()
+        .filter_map(|user| Some((user["name"].as_str()?, user["uuid"].as_str()?)))
+        .filter(|(_, uuid)| !player_data.join(format!("{uuid}.dat")).exists())
+        .map(|(name, _)| name.to_string())
+        .collect())
+}
+
+/// The folder of the server running the world, containing e.g. its `usercache.json`.
+pub fn server_folder(world_folder: &Path) -> PathBuf {
+    let world = fs::canonicalize(world_folder).unwrap_or_else(|_| world_folder.to_path_buf());
+    world.parent().unwrap_or(Path::new(".")).to_path_buf()
+}
+
+/// The players in the `whitelist.json` and `ops.json` of a server, whose chunks and data are never pruned unless
+/// `--no-protect-whitelist` is given.
+#[derive(Default)]
+pub struct PrivilegedPlayers {
+    pub uuids: HashSet<String>,
+    pub names: HashSet<String>,
+}
+
+/// Reads the whitelisted players and operators of the server. Missing files are skipped.
+pub fn privileged_players(server_folder: &Path) -> Result<PrivilegedPlayers, String> {
+    let mut players = PrivilegedPlayers::default();
+    for name in ["whitelist.json", "ops.json"] {
+        let path = server_folder.join(name);
+        let json = match fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(format!("Failed to read {}: {}", path.display(), err)),
+        };
+        let entries: Vec<serde_json::Value> = serde_json::from_str(&json)
+            .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+        for entry in &entries {
+            if let Some(uuid) = entry["uuid"].as_str() {
+                players.uuids.insert(uuid.to_ascii_lowercase());
+            }
+            if let Some(name) = entry["name"].as_str() {
+                players.names.insert(name.to_string());
+            }
+        }
+    }
+    Ok(players)
+}
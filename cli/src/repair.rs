@@ -0,0 +1,108 @@
+use std::{path::PathBuf, process};
+
+use lessanvil::repair::{self, EntryProblem, RepairOptions};
+use owo_colors::OwoColorize;
+
+/// Check the headers of the region files of a world for entries pointing into the header, past
+/// the end of the file or at the sectors of other chunks, and rewrite broken regions with the
+/// chunks that can still be read. Only reports the problems unless --apply is passed.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "repair-headers")]
+pub struct RepairHeadersArgs {
+    /// the world to repair
+    #[argh(positional)]
+    world_folder: PathBuf,
+    /// rewrite the broken region files. Without it nothing is changed
+    #[argh(switch)]
+    apply: bool,
+    /// copy the region files into this folder before rewriting them, laid out like the world
+    #[argh(option)]
+    backup: Option<PathBuf>,
+    /// skip confirmation prompt. Use this with caution!
+    #[argh(switch)]
+    confirm: bool,
+    /// skip all checks for the world being valid. Use this with caution!
+    #[argh(switch)]
+    force: bool,
+    /// whether the output should be in json
+    #[argh(switch)]
+    json: bool,
+}
+
+pub fn run(args: RepairHeadersArgs) {
+    if !args.force && !crate::is_valid_world(&args.world_folder) {
+        log::error!("Invalid world folder!");
+        process::exit(1);
+    }
+
+    if args.apply && !args.confirm {
+        crate::confirm(
+            "This tool will rewrite the region files with broken headers, removing the chunks that can't be read.",
+        );
+    }
+
+    let options = RepairOptions {
+        dry_run: !args.apply,
+        backup_folder: args.backup,
+    };
+    let report = match repair::repair_world(&args.world_folder, &options) {
+        Ok(report) => report,
+        Err(err) => {
+            log::error!("{}", err);
+            process::exit(1)
+        }
+    };
+
+    if args.json {
+        anstream::println!("{}", serde_json::to_string(&report).unwrap());
+        return;
+    }
+
+    for region in &report.regions {
+        anstream::println!("{} ({})", region.path.display(), region.dimension);
+        for entry in &region.entries {
+            let problem = match &entry.problem {
+                EntryProblem::InsideHeader => "points into the header".to_string(),
+                EntryProblem::PastEnd => "points past the end of the file".to_string(),
+                EntryProblem::ZeroLength => "has no data".to_string(),
+                EntryProblem::TooFewSectors => "claims too few sectors".to_string(),
+                EntryProblem::Overlapping { x, z } => format!("overlaps chunk {x},{z}"),
+                EntryProblem::Unreadable => "can't be read".to_string(),
+            };
+            let outcome = if entry.recovered {
+                "kept".green().to_string()
+            } else {
+                "removed".red().to_string()
+            };
+            anstream::println!("  chunk {},{} {}: {}", entry.x, entry.z, problem, outcome);
+        }
+    }
+
+    let broken = report.regions.len();
+    let removed = report
+        .regions
+        .iter()
+        .flat_map(|region| &region.entries)
+        .filter(|entry| !entry.recovered)
+        .count();
+    if broken == 0 {
+        anstream::println!(
+            "The headers of all {} region files are fine.",
+            report.checked_regions
+        );
+    } else if args.apply {
+        anstream::println!(
+            "Repaired {} of {} region files, removing {} chunks.",
+            broken.yellow(),
+            report.checked_regions,
+            removed.yellow()
+        );
+    } else {
+        anstream::println!(
+            "{} of {} region files are broken, repairing them removes {} chunks. Pass --apply to repair them.",
+            broken.yellow(),
+            report.checked_regions,
+            removed.yellow()
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use std::{path::PathBuf, process};
+
+use indicatif::{HumanBytes, HumanDuration};
+use lessanvil::protection::{Position, ProtectedArea};
+use lessanvil::{Config, Dimension, HardlinkPolicy, InhabitedTime, RegionProcessingError};
+use owo_colors::OwoColorize;
+
+/// Delete all End chunks outside of the main island so that the outer islands regenerate.
+/// The dragon fight state in the level.dat is left untouched. Only shows how many chunks would be
+/// deleted unless --apply is passed.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "reset-end")]
+pub struct ResetEndArgs {
+    /// the world to reset the End of
+    #[argh(positional)]
+    world: PathBuf,
+    /// the radius in chunks around 0,0 to keep. The default covers the main island with its
+    /// pillars and the end gateways
+    #[argh(option, default = "12")]
+    radius: u32,
+    /// delete the chunks. Without it nothing is changed
+    #[argh(switch)]
+    apply: bool,
+    /// amount of threads to use
+    #[argh(option, short = 't')]
+    thread_count: Option<usize>,
+    /// leave hardlinked region files untouched
+    #[argh(switch)]
+    skip_hardlinked: bool,
+    /// skip confirmation prompt. Use this with caution!
+    #[argh(switch)]
+    confirm: bool,
+    /// skip all checks for the world being valid. Use this with caution!
+    #[argh(switch)]
+    force: bool,
+}
+
+pub fn run(args: ResetEndArgs) {
+    if !args.force && !crate::is_valid_world(&args.world) {
+        log::error!("Invalid world folder!");
+        process::exit(1);
+    }
+
+    if args.apply && !args.confirm {
+        crate::confirm(&format!(
+            "This tool will remove all End chunks further than {} chunks from the main island.",
+            args.radius
+        ));
+    }
+
+    let config = Config {
+        world_folder: args.world,
+        // every chunk is below the cutoff, only the main island is protected
+        max_inhabited_time: InhabitedTime::from_ticks(u64::MAX),
+        thread_count: args.thread_count.unwrap_or(num_cpus::get()),
+        hardlink_policy: if args.skip_hardlinked {
+            HardlinkPolicy::Skip
+        } else {
+            HardlinkPolicy::Process
+        },
+        protected_areas: vec![ProtectedArea::Around {
+            position: Position {
+                dimension: Dimension::End,
+                x: 0,
+                z: 0,
+            },
+            radius: args.radius,
+        }],
+        dimensions: vec![Dimension::End],
+        dry_run: !args.apply,
+        ..Default::default()
+    };
+
+    let execution = match lessanvil::execute(config) {
+        Ok(execution) => execution,
+        Err(err) => {
+            log::error!("{}", err);
+            process::exit(1)
+        }
+    };
+    while let Ok(update) = execution.updates().recv() {
+        if let lessanvil::ProcessingUpdate::ProcessedRegion(Err(
+            err @ RegionProcessingError::Hardlinked { .. },
+        )) = update
+        {
+            log::warn!("{}", err);
+        }
+    }
+
+    match execution.join() {
+        Ok(report) if args.apply => anstream::println!(
+            "Successfully processed {} files in {} and freed up {} by deleting {} End chunks.",
+            report.total_regions.yellow(),
+            HumanDuration(report.time_taken).yellow(),
+            HumanBytes(report.total_freed_space).yellow(),
+            report.total_deleted_chunks.yellow()
+        ),
+        Ok(report) => anstream::println!(
+            "Resetting the End would free up {} by deleting {} chunks in {} files. Pass --apply to reset it.",
+            HumanBytes(report.total_freed_space).yellow(),
+            report.total_deleted_chunks.yellow(),
+            report.total_regions.yellow()
+        ),
+        Err(_) => {
+            log::error!("The reset was aborted");
+            process::exit(1)
+        }
+    }
+}
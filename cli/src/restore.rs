@@ -0,0 +1,134 @@
+use std::{collections::HashSet, path::PathBuf, process};
+
+use indicatif::HumanDuration;
+use lessanvil::transfer::{self, Selection};
+use lessanvil::{ChunkPos, Dimension};
+use owo_colors::OwoColorize;
+
+/// Put pruned chunks back into a world, taking them from a folder that still holds them, e.g. a
+/// backup. Missing region files are created. Only shows how many chunks would be restored unless
+/// --apply is passed.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "restore")]
+pub struct RestoreArgs {
+    /// the world to restore the chunks into
+    #[argh(positional)]
+    world_folder: PathBuf,
+    /// the folder to take the chunks from, laid out like a world folder (`region`,
+    /// `DIM-1/region`, ...). It doesn't need a level.dat
+    #[argh(option)]
+    from: PathBuf,
+    /// the chunks to restore: a CSV file with `dimension,x,z` chunk coordinates, or entries like
+    /// `overworld,10,-4;nether,2,3` (`10,-4` for the overworld)
+    #[argh(option)]
+    chunks: String,
+    /// restore the chunks. Without it nothing is changed
+    #[argh(switch)]
+    apply: bool,
+    /// only show how many chunks would be restored, the default without --apply
+    #[argh(switch)]
+    dry_run: bool,
+    /// skip confirmation prompt. Use this with caution!
+    #[argh(switch)]
+    confirm: bool,
+    /// skip all checks for the world being valid. Use this with caution!
+    #[argh(switch)]
+    force: bool,
+}
+
+pub fn run(args: RestoreArgs) {
+    if !args.force && !crate::is_valid_world(&args.world_folder) {
+        log::error!("Invalid world folder!");
+        process::exit(1);
+    }
+    let chunks = if PathBuf::from(&args.chunks).is_file() {
+        transfer::chunks_from_csv(args.chunks.as_ref()).map_err(|err| err.to_string())
+    } else {
+        parse_chunks(&args.chunks)
+    };
+    let chunks = match chunks {
+        Ok(chunks) if !chunks.is_empty() => chunks,
+        Ok(_) => {
+            log::error!("No chunks to restore!");
+            process::exit(1)
+        }
+        Err(err) => {
+            log::error!("{}", err);
+            process::exit(1)
+        }
+    };
+
+    let apply = crate::applies(args.apply, args.dry_run);
+    if apply && !args.confirm {
+        crate::confirm(&format!(
+            "This tool will overwrite {} chunks of the world with the ones in {}.",
+            chunks.len(),
+            args.from.display()
+        ));
+    }
+
+    let start_time = std::time::Instant::now();
+    let total = chunks.len() as u64;
+    // the chunks are copied like a merge that only selects them
+    let selection = Selection::Chunks(chunks);
+    let result = if apply {
+        transfer::merge(&args.from, &args.world_folder, &selection)
+    } else {
+        transfer::matching_chunks(&args.from, &selection)
+    };
+    match result {
+        Ok(report) => {
+            if apply {
+                anstream::println!(
+                    "Restored {} chunks in {}.",
+                    report.copied_chunks.yellow(),
+                    HumanDuration(start_time.elapsed()).yellow()
+                );
+            } else {
+                anstream::println!(
+                    "{} chunks would be restored. Pass --apply to restore them.",
+                    report.copied_chunks.yellow()
+                );
+            }
+            if report.copied_chunks < total {
+                log::warn!(
+                    "{} of the chunks weren't found in {}.",
+                    total - report.copied_chunks,
+                    args.from.display()
+                );
+            }
+        }
+        Err(err) => {
+            log::error!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+fn parse_chunks(value: &str) -> Result<HashSet<ChunkPos>, String> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let invalid = || format!("Invalid chunk `{entry}`, expected `dimension,x,z` or `x,z`");
+            let parts = entry.split(',').map(str::trim).collect::<Vec<_>>();
+            let (dimension, x, z) = match parts[..] {
+                [dimension, x, z] => (
+                    dimension
+                        .parse::<Dimension>()
+                        .map_err(|err| err.to_string())?,
+                    x,
+                    z,
+                ),
+                [x, z] => (Dimension::Overworld, x, z),
+                _ => return Err(invalid()),
+            };
+            Ok(ChunkPos {
+                dimension,
+                x: x.parse().map_err(|_| invalid())?,
+                z: z.parse().map_err(|_| invalid())?,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,321 @@
+//! Appending the results of every run to a SQLite database (`--results-db`), for queries across
+//! runs like the space freed per week. Requires the `results-db` feature.
+//!
+//! The schema is versioned with `PRAGMA user_version`, every entry of [`MIGRATIONS`] upgrades it by
+//! one version. Older databases are upgraded when they are opened, newer ones are refused.
+
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use lessanvil::{Dimension, InhabitedTime, ProcessedRegion};
+
+use crate::summary::Summary;
+
+/// The statements upgrading the schema to the version of their index + 1.
+#[cfg_attr(not(feature = "results-db"), allow(dead_code))]
+const MIGRATIONS: &[&str] = &[
+    "
+CREATE TABLE runs (
+    id TEXT PRIMARY KEY,
+    world TEXT NOT NULL,
+    started_at INTEGER NOT NULL,
+    time_taken_ms INTEGER NOT NULL,
+    dry_run INTEGER NOT NULL,
+    finished INTEGER NOT NULL,
+    processed_regions INTEGER NOT NULL,
+    total_regions INTEGER NOT NULL,
+    deleted_chunks INTEGER NOT NULL,
+    freed_space INTEGER NOT NULL
+);
+CREATE TABLE regions (
+    run_id TEXT NOT NULL REFERENCES runs (id),
+    dimension TEXT NOT NULL,
+    x INTEGER NOT NULL,
+    z INTEGER NOT NULL,
+    total_chunks INTEGER NOT NULL,
+    deleted_chunks INTEGER NOT NULL,
+    reset_chunks INTEGER NOT NULL,
+    trimmed_chunks INTEGER NOT NULL,
+    recompressed_chunks INTEGER NOT NULL,
+    protected_chunks INTEGER NOT NULL,
+    freed_space INTEGER NOT NULL
+);
+CREATE INDEX regions_by_run ON regions (run_id);
+CREATE TABLE failed_regions (
+    run_id TEXT NOT NULL REFERENCES runs (id),
+    path TEXT NOT NULL
+);
+",
+    "
+-- NULL for runs recorded before
+ALTER TABLE runs ADD COLUMN max_inhabited_time TEXT;
+",
+];
+
+/// Collects the regions of a run while it is followed and appends them to the database afterwards,
+/// in a single transaction.
+#[cfg_attr(not(feature = "results-db"), allow(dead_code))]
+pub struct ResultsDb {
+    path: PathBuf,
+    started_at: SystemTime,
+    max_inhabited_time: InhabitedTime,
+    regions: Vec<RegionRow>,
+}
+
+#[cfg_attr(not(feature = "results-db"), allow(dead_code))]
+struct RegionRow {
+    dimension: Dimension,
+    x: i32,
+    z: i32,
+    total_chunks: u16,
+    deleted_chunks: u16,
+    reset_chunks: u16,
+    trimmed_chunks: u16,
+    recompressed_chunks: u16,
+    protected_chunks: u16,
+    freed_space: u64,
+}
+
+impl ResultsDb {
+    /// Opens the database, creating or upgrading it, so that a database that can't be written
+    /// fails the run before it started.
+    pub fn open(path: &Path, max_inhabited_time: InhabitedTime) -> Result<Self, String> {
+        open_connection(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            started_at: SystemTime::now(),
+            max_inhabited_time,
+            regions: vec![],
+        })
+    }
+
+    pub fn add(&mut self, region: &ProcessedRegion) {
+        self.regions.push(RegionRow {
+            dimension: region.dimension.clone(),
+            x: region.x,
+            z: region.y,
+            total_chunks: region.total_chunks,
+            deleted_chunks: region.deleted_chunks,
+            reset_chunks: region.reset_chunks,
+            trimmed_chunks: region.trimmed_chunks,
+            recompressed_chunks: region.recompressed_chunks,
+            protected_chunks: region.protected_chunks,
+            freed_space: region.freed_space,
+        });
+    }
+
+    /// Appends the run and its regions.
+    #[cfg(feature = "results-db")]
+    pub fn write(&self, summary: &Summary) -> Result<(), String> {
+        use rusqlite::params;
+        use std::time::UNIX_EPOCH;
+
+        let mut connection = open_connection(&self.path)?;
+        let started_at = self
+            .started_at
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let mut write = || -> rusqlite::Result<()> {
+            let transaction = connection.transaction()?;
+            let run_id = summary.run_id.to_string();
+            transaction.execute(
+                "INSERT INTO runs VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    run_id,
+                    summary.world_folder.display().to_string(),
+                    started_at as i64,
+                    summary.time_taken.as_millis() as i64,
+                    summary.dry_run,
+                    summary.finished,
+                    summary.processed_regions as i64,
+                    summary.total_regions as i64,
+                    summary.deleted_chunks as i64,
+                    summary.freed_space as i64,
+                    self.max_inhabited_time.to_string(),
+                ],
+            )?;
+            {
+                let mut insert = transaction.prepare(
+                    "INSERT INTO regions VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                )?;
+                for region in &self.regions {
+                    insert.execute(params![
+                        run_id,
+                        region.dimension.to_string(),
+                        region.x,
+                        region.z,
+                        region.total_chunks,
+                        region.deleted_chunks,
+                        region.reset_chunks,
+                        region.trimmed_chunks,
+                        region.recompressed_chunks,
+                        region.protected_chunks,
+                        region.freed_space as i64,
+                    ])?;
+                }
+                let mut insert =
+                    transaction.prepare("INSERT INTO failed_regions VALUES (?1, ?2)")?;
+                for path in summary.failed_regions {
+                    insert.execute(params![run_id, path.display().to_string()])?;
+                }
+            }
+            transaction.commit()
+        };
+        write().map_err(|err| err.to_string())
+    }
+
+    #[cfg(not(feature = "results-db"))]
+    pub fn write(&self, _summary: &Summary) -> Result<(), String> {
+        unreachable!("checked by `ResultsDb::open`")
+    }
+}
+
+/// A run read back from the database.
+#[cfg_attr(not(feature = "results-db"), allow(dead_code))]
+pub struct RecordedRun {
+    /// In UTC, e.g. `2024-03-01 04:00:00`.
+    pub started_at: String,
+    pub world: String,
+    pub max_inhabited_time: Option<String>,
+    pub dry_run: bool,
+    pub finished: bool,
+    pub deleted_chunks: u64,
+    pub freed_space: u64,
+}
+
+/// The space freed by the runs of a week, in `YYYY-WW` format.
+#[cfg_attr(not(feature = "results-db"), allow(dead_code))]
+pub struct WeeklyTotal {
+    pub week: String,
+    pub runs: u64,
+    pub freed_space: u64,
+}
+
+/// The average effect of the runs with a threshold.
+#[cfg_attr(not(feature = "results-db"), allow(dead_code))]
+pub struct ThresholdAverage {
+    pub max_inhabited_time: String,
+    pub runs: u64,
+    pub deleted_chunks: f64,
+    pub freed_space: f64,
+}
+
+/// What the runs recorded in the database did, see `lessanvil-cli history`. Dry runs only count
+/// in [`History::runs`].
+pub struct History {
+    /// The latest runs, newest first.
+    pub runs: Vec<RecordedRun>,
+    pub weeks: Vec<WeeklyTotal>,
+    pub thresholds: Vec<ThresholdAverage>,
+}
+
+#[cfg(feature = "results-db")]
+pub fn history(path: &Path, limit: usize) -> Result<History, String> {
+    if !path.exists() {
+        return Err(format!("{} doesn't exist", path.display()));
+    }
+    let connection = open_connection(path)?;
+    let read = || -> rusqlite::Result<History> {
+        let runs = connection
+            .prepare(
+                "SELECT datetime(started_at, 'unixepoch'), world, max_inhabited_time, dry_run,
+                    finished, deleted_chunks, freed_space
+                FROM runs ORDER BY started_at DESC LIMIT ?1",
+            )?
+            .query_map([limit as i64], |row| {
+                Ok(RecordedRun {
+                    started_at: row.get(0)?,
+                    world: row.get(1)?,
+                    max_inhabited_time: row.get(2)?,
+                    dry_run: row.get(3)?,
+                    finished: row.get(4)?,
+                    deleted_chunks: row.get::<_, i64>(5)? as u64,
+                    freed_space: row.get::<_, i64>(6)? as u64,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        let weeks = connection
+            .prepare(
+                "SELECT strftime('%Y-%W', started_at, 'unixepoch') AS week, count(*),
+                    sum(freed_space)
+                FROM runs WHERE NOT dry_run GROUP BY week ORDER BY week",
+            )?
+            .query_map([], |row| {
+                Ok(WeeklyTotal {
+                    week: row.get(0)?,
+                    runs: row.get::<_, i64>(1)? as u64,
+                    freed_space: row.get::<_, i64>(2)? as u64,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        let thresholds = connection
+            .prepare(
+                "SELECT max_inhabited_time, count(*), avg(deleted_chunks), avg(freed_space)
+                FROM runs WHERE NOT dry_run AND finished AND max_inhabited_time IS NOT NULL
+                GROUP BY max_inhabited_time ORDER BY avg(freed_space)",
+            )?
+            .query_map([], |row| {
+                Ok(ThresholdAverage {
+                    max_inhabited_time: row.get(0)?,
+                    runs: row.get::<_, i64>(1)? as u64,
+                    deleted_chunks: row.get(2)?,
+                    freed_space: row.get(3)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(History {
+            runs,
+            weeks,
+            thresholds,
+        })
+    };
+    read().map_err(|err| format!("Failed to read {}: {}", path.display(), err))
+}
+
+#[cfg(not(feature = "results-db"))]
+pub fn history(_path: &Path, _limit: usize) -> Result<History, String> {
+    Err("lessanvil-cli was built without the `results-db` feature".to_string())
+}
+
+/// Opens the database and upgrades its schema to the latest version.
+#[cfg(feature = "results-db")]
+fn open_connection(path: &Path) -> Result<rusqlite::Connection, String> {
+    let failed = |err: rusqlite::Error| format!("Failed to open {}: {}", path.display(), err);
+    let mut connection = rusqlite::Connection::open(path).map_err(failed)?;
+    // another run may be appending to the database at the same time
+    connection
+        .busy_timeout(std::time::Duration::from_secs(30))
+        .map_err(failed)?;
+    let version: usize = connection
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(failed)?;
+    if version > MIGRATIONS.len() {
+        return Err(format!(
+            "{} was written by a newer version of lessanvil-cli",
+            path.display()
+        ));
+    }
+    for (i, statements) in MIGRATIONS.iter().enumerate().skip(version) {
+        migrate(&mut connection, statements, i + 1).map_err(failed)?;
+    }
+    Ok(connection)
+}
+
+#[cfg(feature = "results-db")]
+fn migrate(
+    connection: &mut rusqlite::Connection,
+    statements: &str,
+    version: usize,
+) -> rusqlite::Result<()> {
+    let transaction = connection.transaction()?;
+    transaction.execute_batch(statements)?;
+    transaction.pragma_update(None, "user_version", version)?;
+    transaction.commit()
+}
+
+#[cfg(not(feature = "results-db"))]
+fn open_connection(_path: &Path) -> Result<(), String> {
+    Err("lessanvil-cli was built without the `results-db` feature".to_string())
+}
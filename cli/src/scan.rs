@@ -0,0 +1,87 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+use indicatif::HumanDuration;
+use lessanvil::{cache, scan::ScanBackend};
+use owo_colors::OwoColorize;
+
+/// Read the metadata of every chunk (position, InhabitedTime, LastUpdate, status and size) into a
+/// compact cache file, e.g. to analyze it in other tools. `apply <cache>` deletes the chunks
+/// selected from it later without reading them again.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "scan")]
+pub struct ScanArgs {
+    /// the world to scan
+    #[argh(positional)]
+    world_folder: PathBuf,
+    /// the file to write the cache to
+    #[argh(option, short = 'o')]
+    output: PathBuf,
+    /// the format of the file: `cache` (default), which `apply` accepts, or `parquet` for
+    /// analytics tools like DuckDB (requires the `parquet` feature)
+    #[argh(option, default = "ScanFormat::Cache", from_str_fn(parse_format))]
+    format: ScanFormat,
+    /// the amount of regions read at once. Default is the same as the number of CPUs available
+    #[argh(option)]
+    threads: Option<usize>,
+    /// skip all checks for the world being valid. Use this with caution!
+    #[argh(switch)]
+    force: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScanFormat {
+    Cache,
+    Parquet,
+}
+
+pub fn run(args: ScanArgs) {
+    if !args.force && !crate::is_valid_world(&args.world_folder) {
+        log::error!("Invalid world folder!");
+        process::exit(1);
+    }
+
+    let start_time = std::time::Instant::now();
+    let backend = ScanBackend::Parallel {
+        threads: args.threads.unwrap_or_default(),
+    };
+    let result = match args.format {
+        ScanFormat::Cache => write_cache(&args.world_folder, backend, &args.output),
+        ScanFormat::Parquet => {
+            crate::parquet_export::write(&args.world_folder, backend, &args.output)
+        }
+    };
+    match result {
+        Ok(chunks) => anstream::println!(
+            "Wrote the metadata of {} chunks to {} in {}.",
+            chunks.yellow(),
+            args.output.display(),
+            HumanDuration(start_time.elapsed()).yellow()
+        ),
+        Err(err) => {
+            log::error!("Failed to scan the world: {}", err);
+            process::exit(1)
+        }
+    }
+}
+
+fn write_cache(world_folder: &Path, backend: ScanBackend, path: &Path) -> Result<u64, String> {
+    let file = File::create(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let mut writer = BufWriter::new(file);
+    let chunks =
+        cache::write_cache(world_folder, backend, &mut writer).map_err(|err| err.to_string())?;
+    writer.flush().map_err(|err| err.to_string())?;
+    Ok(chunks)
+}
+
+fn parse_format(value: &str) -> Result<ScanFormat, String> {
+    match value {
+        "cache" => Ok(ScanFormat::Cache),
+        "parquet" => Ok(ScanFormat::Parquet),
+        _ => Err("expected `cache` or `parquet`".to_string()),
+    }
+}
@@ -0,0 +1,587 @@
+//! `lessanvil-cli serve`: a small HTTP API to start a run, follow its progress, cancel it and fetch
+//! its report over a socket, e.g. for web panels or remote administration. Only one run at a time
+//! is supported.
+//!
+//! Every request needs the token of `--token-file` as `Authorization: Bearer <token>`:
+//!
+//! - `POST /run` starts a run. The body is a JSON object with `worldFolder`, `maxInhabitedTime`
+//!   (e.g. `"5m"`) and optionally `apply` (a dry run unless `true`), `threads` and `dimensions`.
+//! - `GET /run` returns the state and the latest progress of the current run.
+//! - `GET /run/events` streams the events of the current run as server-sent events.
+//! - `POST /run/cancel` cancels the current run after the regions being processed.
+//! - `GET /run/report` returns the report of the last run once it stopped.
+//! - `GET /run/regions` returns the regions of the current run processed so far, e.g. for a heatmap.
+//! - `GET /runs` returns the runs started since the server started, newest first.
+//!
+//! With `--dashboard`, `GET /` serves a web page using the API, asking for the token itself.
+
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::PathBuf,
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use lessanvil::{
+    protection::{self, ProtectedArea},
+    CancelHandle, Config, Dimension, InhabitedTime, ProcessingUpdate, RunId,
+};
+use serde_json::{json, Value};
+
+/// Serve an HTTP API to start runs, follow their progress, cancel them and fetch their reports,
+/// e.g. for web panels or remote administration.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "serve")]
+pub struct ServeArgs {
+    /// the address to listen on (default: 127.0.0.1:7878)
+    #[argh(option, default = "SocketAddr::from(([127, 0, 0, 1], 7878))")]
+    listen: SocketAddr,
+    /// a file containing the token clients have to send as `Authorization: Bearer <token>`
+    #[argh(option)]
+    token_file: PathBuf,
+    /// serve a web dashboard at `/` showing the progress, a heatmap of the deleted chunks and the
+    /// previous runs
+    #[argh(switch)]
+    dashboard: bool,
+}
+
+/// The page served with `--dashboard`, without external resources.
+const DASHBOARD: &str = include_str!("dashboard.html");
+
+/// The largest request body accepted.
+const MAX_BODY: usize = 64 * 1024;
+
+/// The largest request line and headers accepted, in bytes.
+const MAX_HEADER: u64 = 8 * 1024;
+
+/// How long a connection may wait between sending parts of its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The most connections handled at the same time, further ones are closed right away.
+const MAX_CONNECTIONS: usize = 64;
+
+pub fn run(args: ServeArgs) {
+    let token = match fs::read_to_string(&args.token_file) {
+        Ok(token) if !token.trim().is_empty() => token.trim().to_string(),
+        Ok(_) => {
+            log::error!("{} is empty!", args.token_file.display());
+            process::exit(1)
+        }
+        Err(err) => {
+            log::error!("Failed to read {}: {}", args.token_file.display(), err);
+            process::exit(1)
+        }
+    };
+    let listener = match TcpListener::bind(args.listen) {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::error!("Failed to listen on {}: {}", args.listen, err);
+            process::exit(1)
+        }
+    };
+    if !args.listen.ip().is_loopback() {
+        log::warn!(
+            "{} is reachable from other machines and the API isn't encrypted, put it behind a TLS proxy",
+            args.listen
+        );
+    }
+    anstream::println!("Listening on http://{}", args.listen);
+
+    if args.dashboard {
+        anstream::println!("Dashboard at http://{}/", args.listen);
+    }
+
+    let server = Arc::new(Server {
+        token,
+        dashboard: args.dashboard,
+        state: Mutex::new(None),
+        history: Mutex::new(vec![]),
+        changed: Condvar::new(),
+    });
+    let connections = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        // every connection gets a thread before its token is checked, so idle ones must not pile up
+        if stream.set_read_timeout(Some(READ_TIMEOUT)).is_err()
+            || connections.fetch_add(1, Ordering::Relaxed) >= MAX_CONNECTIONS
+        {
+            connections.fetch_sub(1, Ordering::Relaxed);
+            continue;
+        }
+        let server = server.clone();
+        let connections = connections.clone();
+        thread::spawn(move || {
+            if let Err(err) = server.handle(stream) {
+                log::debug!("Connection failed: {}", err);
+            }
+            connections.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+}
+
+struct Server {
+    token: String,
+    dashboard: bool,
+    /// The current or last run.
+    state: Mutex<Option<RunState>>,
+    /// The runs that stopped, oldest first.
+    history: Mutex<Vec<Value>>,
+    /// Notified whenever an event is added to the run.
+    changed: Condvar,
+}
+
+struct RunState {
+    run_id: RunId,
+    world_folder: PathBuf,
+    dry_run: bool,
+    /// Seconds since the Unix epoch.
+    started_at: u64,
+    cancel_handle: CancelHandle,
+    status: Status,
+    progress: Option<Value>,
+    report: Option<Value>,
+    events: Vec<Value>,
+    regions: Vec<Value>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Running,
+    Finished,
+    Cancelled,
+    Failed,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Status::Running => "running",
+            Status::Finished => "finished",
+            Status::Cancelled => "cancelled",
+            Status::Failed => "failed",
+        }
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+    body: Vec<u8>,
+}
+
+impl Server {
+    fn handle(self: &Arc<Self>, mut stream: TcpStream) -> io::Result<()> {
+        let request = match read_request(&mut stream)? {
+            Ok(request) => request,
+            Err((status, message)) => {
+                return respond(&mut stream, status, &json!({ "error": message }))
+            }
+        };
+        // the page itself holds no data, it asks for the token before using the API
+        if self.dashboard && request.method == "GET" && request.path == "/" {
+            return respond_with(&mut stream, 200, "text/html; charset=utf-8", DASHBOARD);
+        }
+        if !request
+            .authorization
+            .as_deref()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|token| constant_time_eq(token.trim(), &self.token))
+        {
+            return respond(&mut stream, 401, &json!({ "error": "invalid token" }));
+        }
+
+        match (request.method.as_str(), request.path.as_str()) {
+            ("POST", "/run") => {
+                let (status, body) = match self.start(&request.body) {
+                    Ok(run_id) => (201, json!({ "runId": run_id.to_string() })),
+                    Err((status, message)) => (status, json!({ "error": message })),
+                };
+                respond(&mut stream, status, &body)
+            }
+            ("GET", "/run") => {
+                let state = self.state.lock().unwrap();
+                respond(
+                    &mut stream,
+                    200,
+                    &json!({ "run": state.as_ref().map(describe) }),
+                )
+            }
+            ("GET", "/run/events") => self.stream_events(stream),
+            ("POST", "/run/cancel") => {
+                let state = self.state.lock().unwrap();
+                match &*state {
+                    Some(run) if run.status == Status::Running => {
+                        run.cancel_handle.cancel();
+                        respond(
+                            &mut stream,
+                            202,
+                            &json!({ "runId": run.run_id.to_string() }),
+                        )
+                    }
+                    _ => respond(&mut stream, 409, &json!({ "error": "no run in progress" })),
+                }
+            }
+            ("GET", "/run/report") => {
+                let state = self.state.lock().unwrap();
+                match state.as_ref().and_then(|run| run.report.as_ref()) {
+                    Some(report) => respond(&mut stream, 200, report),
+                    None => respond(&mut stream, 404, &json!({ "error": "no report yet" })),
+                }
+            }
+            ("GET", "/run/regions") => {
+                let state = self.state.lock().unwrap();
+                let regions = state.as_ref().map_or(&[][..], |run| &run.regions);
+                respond(&mut stream, 200, &json!(regions))
+            }
+            ("GET", "/runs") => {
+                let mut runs = self.history.lock().unwrap().clone();
+                if let Some(run) = &*self.state.lock().unwrap() {
+                    if run.status == Status::Running {
+                        runs.push(describe(run));
+                    }
+                }
+                runs.reverse();
+                respond(&mut stream, 200, &json!(runs))
+            }
+            (
+                _,
+                "/run" | "/run/events" | "/run/cancel" | "/run/report" | "/run/regions" | "/runs",
+            ) => respond(&mut stream, 405, &json!({ "error": "method not allowed" })),
+            _ => respond(&mut stream, 404, &json!({ "error": "not found" })),
+        }
+    }
+
+    /// Starts a run with the settings of the request body, unless one is in progress.
+    fn start(self: &Arc<Self>, body: &[u8]) -> Result<RunId, (u16, String)> {
+        let settings: Value =
+            serde_json::from_slice(body).map_err(|err| (400, format!("invalid body: {err}")))?;
+        let mut config = run_config(&settings).map_err(|message| (400, message))?;
+
+        let mut state = self.state.lock().unwrap();
+        if state
+            .as_ref()
+            .is_some_and(|run| run.status == Status::Running)
+        {
+            return Err((409, "a run is already in progress".to_string()));
+        }
+        let run_id = RunId::new();
+        config.run_id = Some(run_id);
+        let world_folder = config.world_folder.clone();
+        let dry_run = config.dry_run;
+        let execution = lessanvil::execute(config).map_err(|err| (422, err.to_string()))?;
+        *state = Some(RunState {
+            run_id,
+            world_folder,
+            dry_run,
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_secs()),
+            cancel_handle: execution.cancel_handle(),
+            status: Status::Running,
+            progress: None,
+            report: None,
+            events: vec![],
+            regions: vec![],
+        });
+        drop(state);
+
+        let server = self.clone();
+        thread::spawn(move || {
+            for update in execution.updates() {
+                let event = match update {
+                    ProcessingUpdate::Starting { total_files, .. } => {
+                        json!({ "type": "starting", "totalFiles": total_files })
+                    }
+                    ProcessingUpdate::ProcessedRegion(Err(err)) => {
+                        json!({ "type": "regionFailed", "error": err.to_string() })
+                    }
+                    ProcessingUpdate::ProcessedRegion(Ok(region)) => {
+                        server.update(|run| {
+                            run.regions.push(json!({
+                                "dimension": region.dimension.to_string(),
+                                "x": region.x,
+                                "z": region.y,
+                                "totalChunks": region.total_chunks,
+                                "deletedChunks": region.deleted_chunks,
+                                "freedSpace": region.freed_space,
+                            }))
+                        });
+                        continue;
+                    }
+                    ProcessingUpdate::Progress(progress) => json!({
+                        "type": "progress",
+                        "processedRegions": progress.processed_regions,
+                        "totalRegions": progress.total_regions,
+                        "processedChunks": progress.processed_chunks,
+                        "deletedChunks": progress.deleted_chunks,
+                        "fraction": progress.fraction(),
+                        "etaSecs": progress.eta.map(|eta| eta.as_secs()),
+                    }),
+                    ProcessingUpdate::Finished(report) => {
+                        server.stop(Status::Finished, serde_json::to_value(&report).ok());
+                        continue;
+                    }
+                    ProcessingUpdate::Cancelled(report) => {
+                        server.stop(Status::Cancelled, serde_json::to_value(&report).ok());
+                        continue;
+                    }
+                };
+                server.update(|run| {
+                    if event["type"] == "progress" {
+                        run.progress = Some(event.clone());
+                    }
+                    run.events.push(event);
+                });
+            }
+            // the execution panicked if it ended without a final update
+            let stopped = server
+                .state
+                .lock()
+                .unwrap()
+                .as_ref()
+                .is_some_and(|run| run.status != Status::Running);
+            if !stopped {
+                server.stop(Status::Failed, None);
+            }
+        });
+        Ok(run_id)
+    }
+
+    /// Records the end of the current run, together with the event telling the clients about it.
+    fn stop(&self, status: Status, report: Option<Value>) {
+        let mut summary = None;
+        self.update(|run| {
+            run.status = status;
+            run.report = report;
+            run.events.push(json!({ "type": status.name() }));
+            summary = Some(describe(run));
+        });
+        self.history.lock().unwrap().extend(summary);
+    }
+
+    fn update(&self, f: impl FnOnce(&mut RunState)) {
+        if let Some(run) = &mut *self.state.lock().unwrap() {
+            f(run);
+        }
+        self.changed.notify_all();
+    }
+
+    /// Sends the events of the current run as server-sent events, starting with the ones that
+    /// already happened, until the run stopped.
+    fn stream_events(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        )?;
+        let mut state = self.state.lock().unwrap();
+        let Some(run_id) = state.as_ref().map(|run| run.run_id) else {
+            return Ok(());
+        };
+        let mut sent = 0;
+        loop {
+            // a new run was started in the meantime
+            let Some(run) = state.as_ref().filter(|run| run.run_id == run_id) else {
+                return Ok(());
+            };
+            let pending = run.events[sent..].to_vec();
+            let stopped = run.status != Status::Running;
+            sent = run.events.len();
+            drop(state);
+            for event in pending {
+                write!(stream, "data: {}\n\n", event)?;
+            }
+            stream.flush()?;
+            if stopped {
+                return Ok(());
+            }
+            state = self
+                .changed
+                .wait_while(self.state.lock().unwrap(), |state| {
+                    state.as_ref().is_some_and(|run| {
+                        run.run_id == run_id
+                            && run.events.len() == sent
+                            && run.status == Status::Running
+                    })
+                })
+                .unwrap();
+        }
+    }
+}
+
+/// The config of a run from the settings of a `POST /run`.
+fn run_config(settings: &Value) -> Result<Config, String> {
+    let world_folder = settings["worldFolder"]
+        .as_str()
+        .ok_or("missing worldFolder")?;
+    let max_inhabited_time = settings["maxInhabitedTime"]
+        .as_str()
+        .ok_or("missing maxInhabitedTime")?
+        .parse::<InhabitedTime>()
+        .map_err(|err| err.to_string())?;
+    let dimensions = match &settings["dimensions"] {
+        Value::Null => vec![],
+        Value::Array(dimensions) => dimensions
+            .iter()
+            .map(|dimension| {
+                dimension
+                    .as_str()
+                    .ok_or_else(|| "invalid dimensions".to_string())?
+                    .parse::<Dimension>()
+                    .map_err(|err| err.to_string())
+            })
+            .collect::<Result<_, _>>()?,
+        _ => return Err("invalid dimensions".to_string()),
+    };
+    let world_folder = PathBuf::from(world_folder);
+    // like a run of the CLI without --no-protect-whitelist
+    let privileged =
+        crate::players::privileged_players(&crate::players::server_folder(&world_folder))?;
+    let protected_areas = protection::positions_of_players(&world_folder, &privileged.uuids)
+        .map_err(|err| err.to_string())?
+        .into_iter()
+        .map(|position| ProtectedArea::Around {
+            position,
+            radius: crate::PRIVILEGED_PLAYER_RADIUS,
+        })
+        .collect();
+    Ok(Config {
+        world_folder,
+        max_inhabited_time,
+        thread_count: settings["threads"]
+            .as_u64()
+            .map_or_else(num_cpus::get, |threads| threads as usize),
+        dimensions,
+        protected_areas,
+        dry_run: settings["apply"].as_bool() != Some(true),
+        max_delete_ratio: Some(crate::MAX_DELETE_RATIO),
+        ..Default::default()
+    })
+}
+
+fn describe(run: &RunState) -> Value {
+    let report = run.report.as_ref();
+    json!({
+        "runId": run.run_id.to_string(),
+        "worldFolder": run.world_folder,
+        "dryRun": run.dry_run,
+        "startedAt": run.started_at,
+        "status": run.status.name(),
+        "progress": run.progress,
+        "deletedChunks": report.map(|report| &report["total_deleted_chunks"]),
+        "freedSpace": report.map(|report| &report["total_freed_space"]),
+        "timeTakenSecs": report.map(|report| &report["time_taken"]["secs"]),
+    })
+}
+
+/// Reads the request line, the headers and the body of an HTTP/1.1 request. The outer error is an
+/// I/O error, the inner one the status and message of a malformed request.
+fn read_request(stream: &mut TcpStream) -> io::Result<Result<Request, (u16, &'static str)>> {
+    const TOO_LARGE: (u16, &str) = (431, "request line or headers too large");
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut head = (&mut reader).take(MAX_HEADER);
+    let mut line = String::new();
+    if read_head_line(&mut head, &mut line)?.is_none() {
+        return Ok(Err(TOO_LARGE));
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Ok(Err((400, "invalid request line")));
+    };
+    let method = method.to_string();
+    let path = target.split('?').next().unwrap_or_default().to_string();
+
+    let mut authorization = None;
+    let mut content_length = 0;
+    loop {
+        let Some(read) = read_head_line(&mut head, &mut line)? else {
+            return Ok(Err(TOO_LARGE));
+        };
+        if read == 0 || line.trim().is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Ok(Err((400, "invalid header")));
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("authorization") {
+            authorization = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("content-length") {
+            content_length = match value.parse() {
+                Ok(length) if length <= MAX_BODY => length,
+                _ => return Ok(Err((400, "invalid content length"))),
+            };
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Ok(Request {
+        method,
+        path,
+        authorization,
+        body,
+    }))
+}
+
+/// Reads the next line of the request line and headers into `line`, returning the amount of bytes
+/// read or `None` once the line doesn't end within [`MAX_HEADER`].
+fn read_head_line(
+    head: &mut io::Take<&mut BufReader<TcpStream>>,
+    line: &mut String,
+) -> io::Result<Option<usize>> {
+    line.clear();
+    let read = head.read_line(line)?;
+    if head.limit() == 0 && !line.ends_with('\n') {
+        return Ok(None);
+    }
+    Ok(Some(read))
+}
+
+fn respond(stream: &mut TcpStream, status: u16, body: &Value) -> io::Result<()> {
+    respond_with(stream, status, "application/json", &body.to_string())
+}
+
+fn respond_with(
+    stream: &mut TcpStream,
+    status: u16,
+    content_type: &str,
+    body: &str,
+) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        422 => "Unprocessable Entity",
+        431 => "Request Header Fields Too Large",
+        _ => "Internal Server Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+/// Compares the tokens without returning early, so that the time taken doesn't tell how much of a
+/// guessed token is right.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
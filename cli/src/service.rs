@@ -0,0 +1,276 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+use argh::FromArgs;
+
+use crate::Args;
+
+/// Run the prune configured in a file on a schedule: as a systemd service and timer, or a
+/// scheduled task on Windows. Install it again after changing the file.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "install-service")]
+pub struct InstallServiceArgs {
+    /// when to run: `daily`, `weekly`, `monthly` or, for systemd, any `OnCalendar=` expression,
+    /// e.g. `Sun 04:00`
+    #[argh(option)]
+    schedule: String,
+    /// a file with the arguments of the run, e.g. `--world-folder /srv/minecraft/world`, one per
+    /// line. Empty lines and lines starting with `#` are ignored
+    #[argh(option)]
+    config: PathBuf,
+    /// the name of the service, to schedule several runs. Default is `lessanvil`
+    #[argh(option, default = "String::from(\"lessanvil\")")]
+    name: String,
+    /// install a systemd user service instead of a system one
+    #[argh(switch)]
+    user: bool,
+    /// only print what would be installed
+    #[argh(switch)]
+    print: bool,
+}
+
+/// Remove a service installed with `install-service`.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "uninstall-service")]
+pub struct UninstallServiceArgs {
+    /// the name of the service. Default is `lessanvil`
+    #[argh(option, default = "String::from(\"lessanvil\")")]
+    name: String,
+    /// remove a systemd user service instead of a system one
+    #[argh(switch)]
+    user: bool,
+}
+
+pub fn run_install(args: InstallServiceArgs) {
+    let run_args = match read_config(&args.config) {
+        Ok(run_args) => run_args,
+        Err(err) => {
+            log::error!("Failed to read {}: {}", args.config.display(), err);
+            process::exit(1)
+        }
+    };
+    if !run_args.iter().any(|arg| arg == "--apply") {
+        log::warn!(
+            "{} doesn't contain --apply, the scheduled runs won't modify the world",
+            args.config.display()
+        );
+    }
+    let binary = env::current_exe().unwrap_or_else(|err| {
+        log::error!("Failed to locate the lessanvil binary: {}", err);
+        process::exit(1)
+    });
+    // nobody can confirm a scheduled run
+    let mut command = vec![binary.display().to_string(), "--confirm".to_string()];
+    command.extend(run_args);
+
+    let result = if cfg!(windows) {
+        install_task(&args, &command)
+    } else {
+        install_systemd(&args, &command)
+    };
+    if let Err(err) = result {
+        log::error!("{}", err);
+        process::exit(1)
+    }
+}
+
+pub fn run_uninstall(args: UninstallServiceArgs) {
+    let result = if cfg!(windows) {
+        run_command("schtasks", &["/Delete", "/TN", &args.name, "/F"])
+    } else {
+        uninstall_systemd(&args)
+    };
+    match result {
+        Ok(()) => anstream::println!("Removed the service {}.", args.name),
+        Err(err) => {
+            log::error!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+/// The arguments in the file, checked to be valid arguments of a run.
+fn read_config(path: &Path) -> Result<Vec<String>, String> {
+    let content = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let mut run_args = vec![];
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // `--option value` on a single line, the value may contain spaces
+        match line.split_once(char::is_whitespace) {
+            Some((option, value)) if option.starts_with('-') => {
+                run_args.push(option.to_string());
+                run_args.push(value.trim().to_string());
+            }
+            _ => run_args.push(line.to_string()),
+        }
+    }
+
+    let strs = run_args.iter().map(String::as_str).collect::<Vec<_>>();
+    let parsed = Args::from_args(&["lessanvil-cli"], &strs).map_err(|exit| exit.output)?;
+    if parsed.command.is_some() {
+        return Err("only the arguments of a prune are supported, not a subcommand".to_string());
+    }
+    if parsed.world_folder.is_none() && parsed.worlds_root.is_none() && !parsed.pterodactyl {
+        return Err("missing --world-folder".to_string());
+    }
+    Ok(run_args)
+}
+
+fn install_systemd(args: &InstallServiceArgs, command: &[String]) -> Result<(), String> {
+    let service = format!(
+        "[Unit]\nDescription=Prune the chunks of a Minecraft world with lessanvil\n\n[Service]\nType=oneshot\nExecStart={}\n",
+        command.iter().map(|arg| quote_systemd(arg)).collect::<Vec<_>>().join(" ")
+    );
+    let timer = format!(
+        "[Unit]\nDescription=Run {name}.service on a schedule\n\n[Timer]\nOnCalendar={schedule}\nPersistent=true\n\n[Install]\nWantedBy=timers.target\n",
+        name = args.name,
+        schedule = args.schedule
+    );
+    if args.print {
+        anstream::println!(
+            "# {}.service\n{}\n# {}.timer\n{}",
+            args.name,
+            service,
+            args.name,
+            timer
+        );
+        return Ok(());
+    }
+
+    let folder = systemd_folder(args.user)?;
+    fs::create_dir_all(&folder)
+        .map_err(|err| format!("Failed to create {}: {}", folder.display(), err))?;
+    for (file, content) in [
+        (format!("{}.service", args.name), service),
+        (format!("{}.timer", args.name), timer),
+    ] {
+        let path = folder.join(file);
+        fs::write(&path, content)
+            .map_err(|err| format!("Failed to write {}: {}", path.display(), err))?;
+    }
+    systemctl(args.user, &["daemon-reload"])?;
+    systemctl(
+        args.user,
+        &["enable", "--now", &format!("{}.timer", args.name)],
+    )?;
+    anstream::println!(
+        "Installed {} in {}. Check its runs with `journalctl {}-u {}`.",
+        args.name,
+        folder.display(),
+        if args.user { "--user " } else { "" },
+        args.name
+    );
+    Ok(())
+}
+
+fn uninstall_systemd(args: &UninstallServiceArgs) -> Result<(), String> {
+    let folder = systemd_folder(args.user)?;
+    let timer = format!("{}.timer", args.name);
+    if !folder.join(&timer).exists() {
+        return Err(format!(
+            "There is no service {} in {}.",
+            args.name,
+            folder.display()
+        ));
+    }
+    systemctl(args.user, &["disable", "--now", &timer])?;
+    for file in [format!("{}.service", args.name), timer] {
+        let path = folder.join(file);
+        fs::remove_file(&path)
+            .map_err(|err| format!("Failed to remove {}: {}", path.display(), err))?;
+    }
+    systemctl(args.user, &["daemon-reload"])
+}
+
+fn systemd_folder(user: bool) -> Result<PathBuf, String> {
+    if !user {
+        return Ok(PathBuf::from("/etc/systemd/system"));
+    }
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .map(|config| config.join("systemd/user"))
+        .ok_or_else(|| "Failed to locate the user's config folder".to_string())
+}
+
+fn systemctl(user: bool, args: &[&str]) -> Result<(), String> {
+    let mut all = vec![];
+    if user {
+        all.push("--user");
+    }
+    all.extend_from_slice(args);
+    run_command("systemctl", &all)
+}
+
+/// Quotes an argument of `ExecStart=`, where `%` and `$` are expanded by systemd.
+fn quote_systemd(arg: &str) -> String {
+    let arg = arg.replace('%', "%%").replace('$', "$$");
+    if !arg.is_empty()
+        && !arg.contains(|c: char| c.is_whitespace() || c == '"' || c == '\'' || c == '\\')
+    {
+        return arg;
+    }
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn install_task(args: &InstallServiceArgs, command: &[String]) -> Result<(), String> {
+    let schedule = match args.schedule.as_str() {
+        "daily" => "DAILY",
+        "weekly" => "WEEKLY",
+        "monthly" => "MONTHLY",
+        schedule => {
+            return Err(format!(
+                "Unsupported schedule `{schedule}`, scheduled tasks only support `daily`, `weekly` or `monthly`."
+            ))
+        }
+    };
+    let command = command
+        .iter()
+        .map(|arg| {
+            if arg.contains(' ') {
+                format!("\"{arg}\"")
+            } else {
+                arg.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    if args.print {
+        anstream::println!(
+            "schtasks /Create /TN {} /SC {} /TR '{}'",
+            args.name,
+            schedule,
+            command
+        );
+        return Ok(());
+    }
+    run_command(
+        "schtasks",
+        &[
+            "/Create", "/TN", &args.name, "/SC", schedule, "/TR", &command, "/F",
+        ],
+    )?;
+    anstream::println!("Installed the scheduled task {}.", args.name);
+    Ok(())
+}
+
+fn run_command(program: &str, args: &[&str]) -> Result<(), String> {
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .map_err(|err| format!("Failed to run {program}: {err}"))?;
+    if !status.success() {
+        return Err(format!(
+            "`{} {}` failed ({})",
+            program,
+            args.join(" "),
+            status
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,149 @@
+//! Summaries of a run for other places than the terminal: mails (see [`crate::email`]) and chat
+//! messages (`--summary-format discord`).
+
+use std::{fmt::Write, path::Path, time::Duration};
+
+use indicatif::{HumanBytes, HumanDuration};
+use lessanvil::RunId;
+
+/// The format of the summary printed after a run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SummaryFormat {
+    /// The regular, colored output.
+    #[default]
+    Text,
+    /// Compact markdown for a Discord message.
+    Discord,
+}
+
+/// The entries of a list shown in a Discord message, which are limited to 2000 characters.
+const DISCORD_LIST_ENTRIES: usize = 5;
+
+/// What a run did, for reports outside of the terminal.
+pub struct Summary<'a> {
+    pub world_folder: &'a Path,
+    pub run_id: RunId,
+    pub finished: bool,
+    pub dry_run: bool,
+    pub time_taken: Duration,
+    pub freed_space: u64,
+    pub processed_regions: u64,
+    pub total_regions: u64,
+    pub deleted_chunks: u64,
+    pub failed_regions: &'a [std::path::PathBuf],
+    pub skipped_files: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl Summary<'_> {
+    /// Plain text, e.g. for a mail.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "World: {}", self.world_folder.display());
+        let _ = writeln!(text, "Run: {}", self.run_id);
+        let _ = writeln!(
+            text,
+            "{} after {}{}.",
+            if self.finished { "Finished" } else { "Aborted" },
+            HumanDuration(self.time_taken),
+            if self.dry_run { " (dry run)" } else { "" }
+        );
+        let _ = writeln!(
+            text,
+            "Processed {} of {} region files. {} {} chunks, freeing up {}.",
+            self.processed_regions,
+            self.total_regions,
+            if self.dry_run {
+                "Would delete"
+            } else {
+                "Deleted"
+            },
+            self.deleted_chunks,
+            HumanBytes(self.freed_space)
+        );
+        for (title, lines) in [
+            (
+                "Failed regions",
+                self.failed_regions
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect(),
+            ),
+            ("Skipped files", self.skipped_files.clone()),
+            ("Warnings", self.warnings.clone()),
+        ] {
+            if lines.is_empty() {
+                continue;
+            }
+            let _ = writeln!(text, "\n{title}:");
+            for line in lines {
+                let _ = writeln!(text, "  {line}");
+            }
+        }
+        text
+    }
+
+    /// Compact markdown for pasting into Discord or posting through a webhook.
+    pub fn to_discord(&self) -> String {
+        let status = if !self.finished {
+            "\u{1f6d1}"
+        } else if self.failed_regions.is_empty() && self.warnings.is_empty() {
+            "\u{2705}"
+        } else {
+            "\u{26a0}\u{fe0f}"
+        };
+        let mut text = format!(
+            "{} **lessanvil** {} `{}`{}\n```\n",
+            status,
+            if self.finished { "finished" } else { "aborted" },
+            self.world_folder.display(),
+            if self.dry_run { " (dry run)" } else { "" }
+        );
+        let _ = writeln!(
+            text,
+            "{:<13}{}/{} in {}",
+            "Regions",
+            self.processed_regions,
+            self.total_regions,
+            HumanDuration(self.time_taken)
+        );
+        let _ = writeln!(
+            text,
+            "{:<13}{} chunks",
+            if self.dry_run {
+                "Would delete"
+            } else {
+                "Deleted"
+            },
+            self.deleted_chunks
+        );
+        let _ = writeln!(text, "{:<13}{}", "Freed", HumanBytes(self.freed_space));
+        if !self.failed_regions.is_empty() {
+            let _ = writeln!(
+                text,
+                "{:<13}{} regions",
+                "Failed",
+                self.failed_regions.len()
+            );
+        }
+        if !self.skipped_files.is_empty() {
+            let _ = writeln!(text, "{:<13}{} files", "Skipped", self.skipped_files.len());
+        }
+        let _ = writeln!(text, "```");
+        if !self.warnings.is_empty() {
+            let _ = writeln!(text, "**Warnings**");
+            for warning in self.warnings.iter().take(DISCORD_LIST_ENTRIES) {
+                let _ = writeln!(text, "- {}", warning.replace('\n', " "));
+            }
+            if self.warnings.len() > DISCORD_LIST_ENTRIES {
+                let _ = writeln!(
+                    text,
+                    "- \u{2026}and {} more",
+                    self.warnings.len() - DISCORD_LIST_ENTRIES
+                );
+            }
+        }
+        let _ = write!(text, "-# run `{}`", self.run_id);
+        text
+    }
+}
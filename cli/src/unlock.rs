@@ -0,0 +1,22 @@
+use std::{path::PathBuf, process};
+
+/// Remove the lock of a world left behind by a run that was killed. Only use this if no other
+/// run is working on the world!
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "unlock")]
+pub struct UnlockArgs {
+    /// the world folder
+    #[argh(positional)]
+    world_folder: PathBuf,
+}
+
+pub fn run(args: UnlockArgs) {
+    match lessanvil::lock::unlock(&args.world_folder) {
+        Ok(true) => anstream::println!("Removed the lock of the world."),
+        Ok(false) => anstream::println!("The world isn't locked."),
+        Err(err) => {
+            log::error!("{}", err);
+            process::exit(1)
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use std::{
+    env, fs,
+    io::IsTerminal,
+    path::Path,
+    process::{self, Command},
+    sync::mpsc,
+    thread,
+};
+
+use owo_colors::OwoColorize;
+use sha2::{Digest, Sha256};
+
+const RELEASES_URL: &str = "https://api.github.com/repos/icrayix/lessanvil/releases/latest";
+
+/// Replace this binary with the latest release from GitHub after verifying its sha256 checksum.
+/// Requires `curl`.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "self-update")]
+pub struct SelfUpdateArgs {
+    /// only check whether a new version is available
+    #[argh(switch)]
+    check: bool,
+    /// skip confirmation prompt
+    #[argh(switch)]
+    confirm: bool,
+}
+
+/// The latest release on GitHub.
+struct Release {
+    version: String,
+    binary_url: String,
+    checksum_url: String,
+}
+
+pub fn run(args: SelfUpdateArgs) {
+    let release = latest_release(30).unwrap_or_else(|err| {
+        log::error!("Failed to look up the latest release: {}", err);
+        process::exit(1)
+    });
+    if !is_newer(&release.version) {
+        anstream::println!("lessanvil-cli {} is up to date.", env!("CARGO_PKG_VERSION"));
+        return;
+    }
+    anstream::println!(
+        "lessanvil-cli {} is available (installed: {}).",
+        release.version.yellow(),
+        env!("CARGO_PKG_VERSION")
+    );
+    if args.check {
+        return;
+    }
+    if !args.confirm {
+        crate::container::require_terminal("Pass --confirm to replace the binary without asking.");
+    }
+    if !args.confirm
+        && !dialoguer::Confirm::new()
+            .with_prompt("Do you want to replace this binary with it?")
+            .interact()
+            .unwrap()
+    {
+        anstream::eprintln!("Aborting.");
+        process::exit(1);
+    }
+
+    if let Err(err) = install(&release) {
+        log::error!("Failed to update: {}", err);
+        process::exit(1)
+    }
+    anstream::println!("Updated to lessanvil-cli {}.", release.version.yellow());
+}
+
+/// Looks for a new version in the background, so that [`print_notice`] can mention it without
+/// delaying the run.
+pub fn check_in_background() -> Option<mpsc::Receiver<String>> {
+    // a notice is only useful to someone watching the output
+    if !std::io::stderr().is_terminal() {
+        return None;
+    }
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        if let Ok(release) = latest_release(5) {
+            if is_newer(&release.version) {
+                let _ = tx.send(release.version);
+            }
+        }
+    });
+    Some(rx)
+}
+
+/// Prints a notice if [`check_in_background`] found a new version by now.
+pub fn print_notice(check: Option<mpsc::Receiver<String>>) {
+    if let Some(version) = check.and_then(|rx| rx.try_recv().ok()) {
+        anstream::eprintln!(
+            "lessanvil-cli {} is available, update with `lessanvil-cli self-update`.",
+            version.yellow()
+        );
+    }
+}
+
+/// Downloads the url with curl.
+fn download(url: &str, timeout_secs: u32) -> Result<Vec<u8>, String> {
+    let output = Command::new("curl")
+        .args(["-fsSL", "--max-time", &timeout_secs.to_string()])
+        .args(["-H", "User-Agent: lessanvil-cli"])
+        .arg(url)
+        .output()
+        .map_err(|err| format!("failed to run curl: {err}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "failed to download {url}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+fn latest_release(timeout_secs: u32) -> Result<Release, String> {
+    let asset_name = asset_name().ok_or("no binaries are released for this platform")?;
+    let response = download(RELEASES_URL, timeout_secs)?;
+    let release: serde_json::Value =
+        serde_json::from_slice(&response).map_err(|err| err.to_string())?;
+
+    let version = release["tag_name"]
+        .as_str()
+        .ok_or("the release has no tag")?
+        .trim_start_matches(|c: char| !c.is_ascii_digit())
+        .to_string();
+    let asset_url = |name: &str| {
+        release["assets"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|asset| asset["name"].as_str() == Some(name))
+            .and_then(|asset| asset["browser_download_url"].as_str())
+            .map(str::to_string)
+            .ok_or_else(|| format!("the release has no {name}"))
+    };
+    Ok(Release {
+        binary_url: asset_url(asset_name)?,
+        checksum_url: asset_url(&format!("{asset_name}.sha256"))?,
+        version,
+    })
+}
+
+/// The name of the binary released for this platform, see `.github/workflows/release-cli.yml`.
+fn asset_name() -> Option<&'static str> {
+    if cfg!(target_os = "linux") {
+        Some("lessanvil-cli-linux")
+    } else if cfg!(target_os = "macos") {
+        Some("lessanvil-cli-macos")
+    } else if cfg!(target_os = "windows") {
+        Some("lessanvil-cli-windows.exe")
+    } else {
+        None
+    }
+}
+
+/// Whether the version is newer than this binary's.
+fn is_newer(version: &str) -> bool {
+    let parse = |version: &str| {
+        version
+            .split(['.', '-'])
+            .map_while(|part| part.parse::<u64>().ok())
+            .collect::<Vec<_>>()
+    };
+    parse(version) > parse(env!("CARGO_PKG_VERSION"))
+}
+
+fn install(release: &Release) -> Result<(), String> {
+    let binary = download(&release.binary_url, 300)?;
+    let checksum = download(&release.checksum_url, 30)?;
+    // `shasum` writes the checksum followed by the file name, `certutil` only the checksum
+    let expected = String::from_utf8_lossy(&checksum)
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let actual = Sha256::digest(&binary)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    if expected != actual {
+        return Err(format!(
+            "the checksum of the download doesn't match ({actual} instead of {expected})"
+        ));
+    }
+
+    let exe = env::current_exe().map_err(|err| err.to_string())?;
+    replace_binary(&exe, &binary)
+        .map_err(|err| format!("failed to replace {}: {err}", exe.display()))
+}
+
+/// Replaces the running binary. It's moved aside first, as Windows doesn't allow overwriting it.
+fn replace_binary(exe: &Path, binary: &[u8]) -> std::io::Result<()> {
+    let new = exe.with_extension("new");
+    let old = exe.with_extension("old");
+    // left behind by a previous update on Windows
+    let _ = fs::remove_file(&old);
+    fs::write(&new, binary)?;
+    fs::set_permissions(&new, fs::metadata(exe)?.permissions())?;
+    fs::rename(exe, &old)?;
+    if let Err(err) = fs::rename(&new, exe) {
+        let _ = fs::rename(&old, exe);
+        return Err(err);
+    }
+    // fails on Windows while the binary is running, it's removed by the next update then
+    let _ = fs::remove_file(&old);
+    Ok(())
+}
@@ -0,0 +1,87 @@
+//! Stopping a run at the end of a maintenance window (`--max-runtime`, `--stop-at`). The run is
+//! cancelled like with Ctrl+C after the regions currently being processed, keeping its progress
+//! snapshot so that `--resume` continues where it stopped.
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
+
+use chrono::{Days, Local, NaiveTime};
+
+use crate::CURRENT_EXECUTION;
+
+/// Set once the end of the window was reached.
+static REACHED: AtomicBool = AtomicBool::new(false);
+
+/// Cancels the current execution (and every one started afterwards, see [`reached`]) once the
+/// runtime is used up or the time of day is reached, whichever comes first.
+pub fn start(max_runtime: Option<Duration>, stop_at: Option<NaiveTime>) {
+    let Some(remaining) = [max_runtime, stop_at.map(until)]
+        .into_iter()
+        .flatten()
+        .min()
+    else {
+        return;
+    };
+    thread::spawn(move || {
+        thread::sleep(remaining);
+        REACHED.store(true, Ordering::Relaxed);
+        if let Some(cancel_handle) = &*CURRENT_EXECUTION.lock().unwrap() {
+            if !cancel_handle.is_cancelled() {
+                anstream::eprintln!(
+                    "The time window ended, stopping after the files currently being processed."
+                );
+            }
+            cancel_handle.cancel();
+        }
+    });
+}
+
+/// Whether the end of the window was reached, so that no further execution should start.
+pub fn reached() -> bool {
+    REACHED.load(Ordering::Relaxed)
+}
+
+/// The time until the next time the local clock shows the time of day.
+fn until(time: NaiveTime) -> Duration {
+    let now = Local::now();
+    let mut day = now.date_naive();
+    loop {
+        // a time skipped by a daylight saving change counts as the next day
+        if let Some(target) = day.and_time(time).and_local_timezone(Local).earliest() {
+            if target > now {
+                return (target - now).to_std().unwrap_or_default();
+            }
+        }
+        match day.checked_add_days(Days::new(1)) {
+            Some(next) => day = next,
+            None => return Duration::ZERO,
+        }
+    }
+}
+
+pub fn parse_runtime(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit) = value.split_at(
+        value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len()),
+    );
+    let number = number
+        .parse::<u64>()
+        .map_err(|_| format!("invalid duration `{value}`"))?;
+    let factor = match unit.trim() {
+        "s" | "" => 1,
+        "m" => 60,
+        "h" => 3600,
+        _ => return Err(format!("unknown unit `{unit}`, expected `s`, `m` or `h`")),
+    };
+    Ok(Duration::from_secs(number.saturating_mul(factor)))
+}
+
+pub fn parse_stop_at(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M")
+        .map_err(|_| format!("invalid time `{value}`, expected `HH:MM`"))
+}
@@ -0,0 +1,251 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use dialoguer::{Confirm, Input, Select};
+use indicatif::{HumanBytes, HumanDuration, ProgressBar, ProgressStyle};
+use lessanvil::{Config, InhabitedTime, ProcessingUpdate, Report};
+use owo_colors::OwoColorize;
+
+use crate::is_valid_world;
+
+/// Guides through removing unused chunks step by step: choosing the world, picking how long
+/// players must have spent in a chunk to keep it and previewing the result before anything is
+/// deleted.
+#[derive(argh::FromArgs, argh::ArgsInfo, Debug)]
+#[argh(subcommand, name = "wizard")]
+pub struct WizardArgs {}
+
+/// The thresholds offered, in seconds, with an example of the chunks they remove.
+const THRESHOLDS: [(u64, &str); 4] = [
+    (
+        10,
+        "10 seconds: only chunks that were generated but never really visited, e.g. while flying past",
+    ),
+    (
+        60,
+        "1 minute: also chunks players only walked or rode through",
+    ),
+    (
+        5 * 60,
+        "5 minutes: also chunks players briefly explored, e.g. looting a village",
+    ),
+    (
+        30 * 60,
+        "30 minutes: everything except where players spent a good while, e.g. bases and farms",
+    ),
+];
+
+pub fn run(_args: WizardArgs) {
+    crate::container::require_terminal("Pass the world and options to lessanvil directly instead.");
+    anstream::println!("{}", "Welcome to lessanvil!".bold());
+    anstream::println!(
+        "This wizard removes the chunks of a world in which players have spent little time. Nothing is deleted before you confirm it at the end.\n"
+    );
+
+    let world_folder = choose_world();
+
+    anstream::println!();
+    anstream::println!(
+        "Minecraft counts how long players have spent near each chunk (its {}). Chunks below the time you choose are removed and the game generates them anew when someone visits them again, so builds in them are lost.",
+        "InhabitedTime".yellow()
+    );
+    let mut items = THRESHOLDS
+        .iter()
+        .map(|(_, description)| description.to_string())
+        .collect::<Vec<_>>();
+    items.push("Enter a custom time in seconds".to_string());
+    let choice = Select::new()
+        .with_prompt("Which chunks should be removed?")
+        .items(&items)
+        .default(1)
+        .interact()
+        .unwrap_or_else(|err| abort(err));
+    let max_inhabited_time = InhabitedTime::from_secs(match THRESHOLDS.get(choice) {
+        Some((seconds, _)) => *seconds,
+        None => Input::<u64>::new()
+            .with_prompt("Seconds")
+            .interact_text()
+            .unwrap_or_else(|err| abort(err)),
+    });
+
+    let config = Config {
+        world_folder: world_folder.clone(),
+        max_inhabited_time,
+        thread_count: num_cpus::get(),
+        dry_run: false,
+        ..Default::default()
+    };
+
+    anstream::println!();
+    anstream::println!("Checking which chunks would be removed, without changing anything...");
+    let preview = run_with_progress(Config {
+        dry_run: true,
+        ..config.clone()
+    });
+    if preview.total_deleted_chunks == 0 {
+        anstream::println!(
+            "None of the {} chunks are below {}, so there's nothing to remove. Try a longer time.",
+            preview.total_chunks.yellow(),
+            HumanDuration(max_inhabited_time.as_duration())
+        );
+        return;
+    }
+    anstream::println!(
+        "{} of the {} chunks would be removed, freeing up about {}.",
+        preview.total_deleted_chunks.yellow(),
+        preview.total_chunks.yellow(),
+        HumanBytes(preview.total_freed_space).yellow()
+    );
+
+    anstream::println!();
+    anstream::println!(
+        "{}: Stop the server or close the world first and {}, removed chunks can't be restored.",
+        "Warning".black().on_red().bold(),
+        "create a backup".black().on_yellow().bold()
+    );
+    if !Confirm::new()
+        .with_prompt("Remove the chunks now?")
+        .default(false)
+        .interact()
+        .unwrap_or_else(|err| abort(err))
+    {
+        anstream::println!("Nothing was changed.");
+        return;
+    }
+
+    let report = run_with_progress(config);
+    anstream::println!(
+        "Done! Freed up {} by removing {} chunks in {}.",
+        HumanBytes(report.total_freed_space).yellow(),
+        report.total_deleted_chunks.yellow(),
+        HumanDuration(report.time_taken).yellow()
+    );
+    anstream::println!(
+        "Next time you can do the same without the wizard: lessanvil-cli -w \"{}\" -m {} --apply",
+        world_folder.display(),
+        max_inhabited_time
+    );
+}
+
+fn abort(err: impl std::fmt::Display) -> ! {
+    log::error!("{}", err);
+    process::exit(1)
+}
+
+/// Asks for the world folder, offering the worlds found in the usual places.
+fn choose_world() -> PathBuf {
+    let worlds = find_worlds();
+    if !worlds.is_empty() {
+        let mut items = worlds
+            .iter()
+            .map(|world| world.display().to_string())
+            .collect::<Vec<_>>();
+        items.push("Another folder".to_string());
+        let choice = Select::new()
+            .with_prompt("Which world do you want to shrink?")
+            .items(&items)
+            .default(0)
+            .interact()
+            .unwrap_or_else(|err| abort(err));
+        if let Some(world) = worlds.get(choice) {
+            return world.clone();
+        }
+    }
+
+    loop {
+        let input = Input::<String>::new()
+            .with_prompt("The world folder (the one containing level.dat)")
+            .interact_text()
+            .unwrap_or_else(|err| abort(err));
+        let world = PathBuf::from(input.trim());
+        if is_valid_world(&world) {
+            return world;
+        }
+        anstream::println!(
+            "{} doesn't contain a level.dat and a region folder.",
+            world.display()
+        );
+    }
+}
+
+/// The worlds in the current folder (e.g. a server's), its subfolders and the singleplayer saves.
+fn find_worlds() -> Vec<PathBuf> {
+    let mut candidates = vec![];
+    if let Ok(current) = env::current_dir() {
+        // servers name their world in server.properties
+        if let Some(name) = crate::panel::level_name(&current) {
+            candidates.push(current.join(name));
+        }
+        candidates.push(current.clone());
+        candidates.extend(subfolders(&current));
+    }
+    if let Some(saves) = saves_folder() {
+        candidates.extend(subfolders(&saves));
+    }
+
+    let mut worlds = vec![];
+    for candidate in candidates {
+        if is_valid_world(&candidate) && !worlds.contains(&candidate) {
+            worlds.push(candidate);
+        }
+    }
+    worlds
+}
+
+fn subfolders(folder: &Path) -> Vec<PathBuf> {
+    let mut folders = fs::read_dir(folder)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect::<Vec<_>>();
+    folders.sort();
+    folders
+}
+
+/// The folder of the singleplayer worlds of the default launcher.
+fn saves_folder() -> Option<PathBuf> {
+    let minecraft = if cfg!(target_os = "windows") {
+        PathBuf::from(env::var_os("APPDATA")?).join(".minecraft")
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(env::var_os("HOME")?).join("Library/Application Support/minecraft")
+    } else {
+        PathBuf::from(env::var_os("HOME")?).join(".minecraft")
+    };
+    Some(minecraft.join("saves"))
+}
+
+/// Runs the config with a progress bar and returns the report.
+fn run_with_progress(config: Config) -> Report {
+    let execution = lessanvil::execute(config).unwrap_or_else(|err| abort(err));
+    let progress_bar = ProgressBar::new(0).with_style(
+        ProgressStyle::with_template("{wide_bar:0.yellow} {percent}% | ETA {eta}")
+            .unwrap()
+            .progress_chars("#> "),
+    );
+    while let Ok(msg) = execution.updates().recv() {
+        match msg {
+            ProcessingUpdate::Starting { total_files, .. } => progress_bar.set_length(total_files),
+            ProcessingUpdate::ProcessedRegion(result) => {
+                progress_bar.inc(1);
+                if let Err(err) = result {
+                    progress_bar.suspend(|| log::warn!("{}", err));
+                }
+            }
+            ProcessingUpdate::Finished(report) => {
+                progress_bar.finish_and_clear();
+                return report;
+            }
+            ProcessingUpdate::Cancelled(_) => {
+                progress_bar.finish_and_clear();
+                abort("The processing was cancelled.")
+            }
+            _ => {}
+        }
+    }
+    abort("The processing stopped unexpectedly.")
+}
@@ -0,0 +1,193 @@
+//! The subcommands that modify a world only do so with --apply: without it they have to leave every file untouched.
+
+#[path = "../../tests/common/mod.rs"]
+mod common;
+
+use common::{TestChunk, TestWorld};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A world with chunks to prune, copy or convert in every dimension, a corrupt chunk in the End and player data to
+/// prune.
+fn world(name: &str) -> TestWorld {
+    let world = TestWorld::new(name);
+    world.write_region(
+        "region",
+        0,
+        0,
+        &[
+            TestChunk::new(0, 0, 0),
+            TestChunk::new(1, 0, 5_000),
+            TestChunk::new(2, 0, 72_000),
+        ],
+    );
+    world.write_region("region", -1, 0, &[TestChunk::new(31, 0, 0)]);
+    world.write_region("DIM-1/region", 0, 0, &[TestChunk::new(0, 0, 0)]);
+    world.write_region(
+        "DIM1/region",
+        0,
+        0,
+        &[
+            TestChunk::new(0, 0, 0),
+            TestChunk::new(20, 20, 0),
+            TestChunk::new(21, 20, 0).corrupt(),
+        ],
+    );
+    for (folder, file) in [
+        ("playerdata", "1a5b2d6e-0000-0000-0000-000000000001.dat"),
+        ("advancements", "1a5b2d6e-0000-0000-0000-000000000001.json"),
+        ("advancements", "1a5b2d6e-0000-0000-0000-000000000002.json"),
+        ("stats", "1a5b2d6e-0000-0000-0000-000000000002.json"),
+    ] {
+        fs::create_dir_all(world.path().join(folder)).unwrap();
+        fs::write(world.path().join(folder).join(file), b"{}").unwrap();
+    }
+    world
+}
+
+/// The contents of every file in the folder.
+fn snapshot(folder: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+    let mut files = BTreeMap::new();
+    let mut folders = vec![folder.to_path_buf()];
+    while let Some(folder) = folders.pop() {
+        for entry in fs::read_dir(folder).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                folders.push(path);
+            } else {
+                files.insert(path.clone(), fs::read(path).unwrap());
+            }
+        }
+    }
+    files
+}
+
+/// Runs the CLI with the arguments, asserting that it succeeds and that the worlds are unchanged, and returns its
+/// output.
+fn assert_unchanged(worlds: &[&TestWorld], args: &[&str]) -> String {
+    let before = worlds
+        .iter()
+        .map(|world| snapshot(world.path()))
+        .collect::<Vec<_>>();
+    let output = Command::new(env!("CARGO_BIN_EXE_lessanvil-cli"))
+        .args(args)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    assert!(
+        output.status.success(),
+        "{args:?} failed: {stdout}{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    for (world, before) in worlds.iter().zip(before) {
+        assert!(
+            snapshot(world.path()) == before,
+            "{args:?} changed {}",
+            world.path().display()
+        );
+    }
+    stdout
+}
+
+fn path(world: &TestWorld) -> &str {
+    world.path().to_str().unwrap()
+}
+
+#[test]
+fn merge() {
+    let source = world("merge-source");
+    let destination = TestWorld::new("merge-destination");
+    let output = assert_unchanged(
+        &[&source, &destination],
+        &["merge", path(&source), path(&destination), "-m", "60"],
+    );
+    assert!(output.contains("Pass --apply"), "{output}");
+}
+
+#[test]
+fn restore() {
+    let world = TestWorld::new("restore");
+    let backup = self::world("restore-backup");
+    let output = assert_unchanged(
+        &[&world, &backup],
+        &[
+            "restore",
+            path(&world),
+            "--from",
+            path(&backup),
+            "--chunks",
+            "overworld,1,0;nether,0,0",
+        ],
+    );
+    assert!(output.contains("Pass --apply"), "{output}");
+}
+
+#[test]
+fn prune_players() {
+    let world = world("prune-players");
+    let output = assert_unchanged(
+        &[&world],
+        &["prune-players", path(&world), "--no-protect-whitelist"],
+    );
+    assert!(output.contains("Pass --apply"), "{output}");
+}
+
+#[cfg(feature = "linear")]
+#[test]
+fn convert() {
+    let world = world("convert");
+    let output = assert_unchanged(&[&world], &["convert", path(&world), "--to", "linear"]);
+    assert!(output.contains("Pass --apply"), "{output}");
+}
+
+#[test]
+fn prune() {
+    let world = world("prune");
+    let output = assert_unchanged(&[&world], &["-w", path(&world), "-m", "60"]);
+    assert!(output.contains("--apply"), "{output}");
+}
+
+#[test]
+fn compact() {
+    let world = world("compact");
+    // dead space at the end of a region file, which compacting releases
+    let region = world.path().join("region/r.0.0.mca");
+    let mut bytes = fs::read(&region).unwrap();
+    bytes.extend([0; 8192]);
+    fs::write(&region, bytes).unwrap();
+    assert_unchanged(&[&world], &["compact", path(&world)]);
+}
+
+#[test]
+fn repair_headers() {
+    let world = world("repair-headers");
+    assert_unchanged(&[&world], &["repair-headers", path(&world)]);
+}
+
+#[test]
+fn reset_end() {
+    let world = world("reset-end");
+    let output = assert_unchanged(&[&world], &["reset-end", path(&world)]);
+    assert!(output.contains("Pass --apply"), "{output}");
+}
+
+#[test]
+fn batch() {
+    let world = world("batch");
+    let folder = TestWorld::new("batch-manifest");
+    let manifest = folder.path().join("worlds.yaml");
+    fs::write(
+        &manifest,
+        format!(
+            "defaults:\n  max-inhabited-time: 60s\nworlds:\n  - path: {}\n",
+            path(&world)
+        ),
+    )
+    .unwrap();
+    assert_unchanged(
+        &[&world],
+        &["batch", "--manifest", manifest.to_str().unwrap()],
+    );
+}
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Some((&compression_id, data)) = data.split_first() {
+        lessanvil::fuzzing::compressed_chunk(compression_id, data);
+    }
+});
@@ -0,0 +1,544 @@
+//! Statistics about a world that don't depend on a [`crate::Config`].
+
+use crate::chunk::ChunkMeta;
+use crate::{
+    collect_all_region_files, collect_region_files, compression, region, region_coordinates,
+    sections, ChunkCompression, ChunkPos, Dimension, Error, InhabitedTime,
+};
+use fastnbt::Value;
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::collections::hash_map::{DefaultHasher, Entry};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// How the chunks of a world are compressed.
+#[derive(Default, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct CompressionStats {
+    /// The chunks of each compression.
+    pub compressions: BTreeMap<ChunkCompression, CompressionUsage>,
+}
+
+/// The chunks of a world using the same [`ChunkCompression`].
+#[derive(Default, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct CompressionUsage {
+    /// The amount of chunks.
+    pub chunks: u64,
+    /// The size of the compressed data of the chunks in bytes.
+    pub compressed_bytes: u64,
+    /// The size of the data of the chunks once decompressed in bytes. 0 if lessanvil can't decompress them.
+    pub uncompressed_bytes: u64,
+}
+
+impl CompressionUsage {
+    /// The uncompressed size divided by the compressed size, if known.
+    pub fn ratio(&self) -> Option<f64> {
+        (self.uncompressed_bytes > 0 && self.compressed_bytes > 0)
+            .then(|| self.uncompressed_bytes as f64 / self.compressed_bytes as f64)
+    }
+
+    fn add(&mut self, other: &CompressionUsage) {
+        self.chunks += other.chunks;
+        self.compressed_bytes += other.compressed_bytes;
+        self.uncompressed_bytes += other.uncompressed_bytes;
+    }
+}
+
+impl CompressionStats {
+    /// The totals of all compressions that lessanvil can decompress.
+    pub fn total(&self) -> CompressionUsage {
+        let mut total = CompressionUsage::default();
+        for usage in self.compressions.values() {
+            if usage.uncompressed_bytes > 0 {
+                total.add(usage);
+            }
+        }
+        total
+    }
+
+    fn merge(mut self, other: CompressionStats) -> CompressionStats {
+        for (compression, usage) in other.compressions {
+            self.compressions
+                .entry(compression)
+                .or_default()
+                .add(&usage);
+        }
+        self
+    }
+}
+
+/// Reads the compression of every chunk of the world (all dimensions) and measures how well it compresses.
+pub fn compression_stats(world_folder: &Path) -> Result<CompressionStats, Error> {
+    if !world_folder.try_exists().is_ok_and(|b| b) {
+        return Err(Error::WorldFolderNotFound);
+    }
+
+    collect_region_files(world_folder)?
+        .into_par_iter()
+        .map(|(_, path)| region_compression_stats(&path))
+        .try_reduce(CompressionStats::default, |a, b| Ok(a.merge(b)))
+}
+
+fn region_compression_stats(path: &Path) -> Result<CompressionStats, Error> {
+    let mut stats = CompressionStats::default();
+    region::for_each_raw_chunk(path, |chunk| {
+        let compression = ChunkCompression::from_id(chunk.id);
+        let data = chunk_data(path, chunk)?;
+        let uncompressed = compression::decompress(compression, &data, &HashMap::new())?;
+        let usage = stats.compressions.entry(compression).or_default();
+        usage.chunks += 1;
+        usage.compressed_bytes += data.len() as u64;
+        usage.uncompressed_bytes += uncompressed.map_or(0, |data| data.len() as u64);
+        Ok::<_, Error>(())
+    })?;
+    Ok(stats)
+}
+
+/// The compressed data of the chunk, read from its `c.<x>.<z>.mcc` file if it's stored outside of the region file.
+pub(crate) fn chunk_data(path: &Path, chunk: region::RawChunk) -> io::Result<Vec<u8>> {
+    if chunk.id & region::EXTERNAL_FLAG == 0 {
+        return Ok(chunk.data);
+    }
+    let (region_x, region_z) = region_coordinates(path);
+    let name = format!(
+        "c.{}.{}.mcc",
+        region_x * 32 + chunk.x as i32,
+        region_z * 32 + chunk.z as i32
+    );
+    fs::read(path.with_file_name(name))
+}
+
+/// The `max_inhabited_time` values compared by [`threshold_suggestion`].
+pub const SUGGESTION_THRESHOLDS: [InhabitedTime; 8] = [
+    InhabitedTime::from_secs(10),
+    InhabitedTime::from_secs(30),
+    InhabitedTime::from_secs(60),
+    InhabitedTime::from_secs(2 * 60),
+    InhabitedTime::from_secs(5 * 60),
+    InhabitedTime::from_secs(10 * 60),
+    InhabitedTime::from_secs(30 * 60),
+    InhabitedTime::from_secs(60 * 60),
+];
+
+/// The largest share of the total InhabitedTime of a world that the chunks deleted with a suggested threshold may
+/// account for, see [`threshold_suggestion`].
+pub const SUGGESTION_MAX_TIME_SHARE: f64 = 0.05;
+
+/// A suggested [`crate::Config::max_inhabited_time`] for a world together with the effects of the alternatives.
+#[derive(Default, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct ThresholdSuggestion {
+    /// The suggested value: the largest of [`SUGGESTION_THRESHOLDS`] whose deleted chunks account for at most
+    /// [`SUGGESTION_MAX_TIME_SHARE`] of the time players spent in the world, or the lowest one deleting the same chunks.
+    pub suggested_max_inhabited_time: InhabitedTime,
+    /// The amount of chunks in the world.
+    pub total_chunks: u64,
+    /// The space taken up by the chunks in the region files in bytes.
+    pub total_bytes: u64,
+    /// What each of [`SUGGESTION_THRESHOLDS`] would delete.
+    pub thresholds: Vec<ThresholdEstimate>,
+}
+
+/// What deleting the chunks below a threshold would do.
+#[derive(Default, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct ThresholdEstimate {
+    /// The threshold, see [`crate::Config::max_inhabited_time`].
+    pub max_inhabited_time: InhabitedTime,
+    /// The amount of chunks that would be deleted.
+    pub deleted_chunks: u64,
+    /// The space the deleted chunks take up in the region files in bytes (without their entity and POI data).
+    pub freed_bytes: u64,
+    /// The share of the total InhabitedTime of the world that was spent in the deleted chunks, from 0 to 1.
+    pub inhabited_time_share: f64,
+}
+
+/// The InhabitedTime distribution of a world, merged across regions.
+#[derive(Default)]
+struct InhabitedTimes {
+    total_chunks: u64,
+    total_bytes: u64,
+    total_time: u64,
+    /// The deleted chunks, bytes and time for each of [`SUGGESTION_THRESHOLDS`].
+    below: [(u64, u64, u64); SUGGESTION_THRESHOLDS.len()],
+}
+
+impl InhabitedTimes {
+    fn merge(mut self, other: InhabitedTimes) -> InhabitedTimes {
+        self.total_chunks += other.total_chunks;
+        self.total_bytes += other.total_bytes;
+        self.total_time += other.total_time;
+        for (a, b) in self.below.iter_mut().zip(other.below) {
+            a.0 += b.0;
+            a.1 += b.1;
+            a.2 += b.2;
+        }
+        self
+    }
+}
+
+/// Reads the InhabitedTime of every chunk of the world (all dimensions) and suggests a threshold that frees as much
+/// space as possible while keeping the chunks players actually spent time in. Protections aren't taken into account.
+pub fn threshold_suggestion(world_folder: &Path) -> Result<ThresholdSuggestion, Error> {
+    if !world_folder.try_exists().is_ok_and(|b| b) {
+        return Err(Error::WorldFolderNotFound);
+    }
+
+    let times = collect_region_files(world_folder)?
+        .into_par_iter()
+        .map(|(_, path)| region_inhabited_times(&path))
+        .try_reduce(InhabitedTimes::default, |a, b| Ok(a.merge(b)))?;
+
+    let thresholds = SUGGESTION_THRESHOLDS
+        .iter()
+        .zip(times.below)
+        .map(
+            |(&max_inhabited_time, (chunks, bytes, time))| ThresholdEstimate {
+                max_inhabited_time,
+                deleted_chunks: chunks,
+                freed_bytes: bytes,
+                inhabited_time_share: if times.total_time > 0 {
+                    time as f64 / times.total_time as f64
+                } else {
+                    0.0
+                },
+            },
+        )
+        .collect::<Vec<_>>();
+    let mut suggested = thresholds
+        .iter()
+        .rposition(|estimate| estimate.inhabited_time_share <= SUGGESTION_MAX_TIME_SHARE)
+        .unwrap_or(0);
+    // a lower threshold deleting the same chunks is less surprising later on
+    while suggested > 0
+        && thresholds[suggested - 1].deleted_chunks == thresholds[suggested].deleted_chunks
+    {
+        suggested -= 1;
+    }
+    let suggested_max_inhabited_time = thresholds[suggested].max_inhabited_time;
+    Ok(ThresholdSuggestion {
+        suggested_max_inhabited_time,
+        total_chunks: times.total_chunks,
+        total_bytes: times.total_bytes,
+        thresholds,
+    })
+}
+
+fn region_inhabited_times(path: &Path) -> Result<InhabitedTimes, Error> {
+    let mut times = InhabitedTimes::default();
+    region::for_each_raw_chunk(path, |chunk| {
+        let compression = ChunkCompression::from_id(chunk.id);
+        let bytes = chunk.location.sectors as u64 * region::SECTOR_SIZE;
+        let data = chunk_data(path, chunk)?;
+        let Some(data) = compression::decompress(compression, &data, &HashMap::new())? else {
+            return Ok::<_, Error>(());
+        };
+        let Ok(chunk) = ChunkMeta::from_bytes(&data) else {
+            return Ok(());
+        };
+
+        let time = chunk.inhabited_time.ticks();
+        times.total_chunks += 1;
+        times.total_bytes += bytes;
+        times.total_time += time;
+        for (threshold, below) in SUGGESTION_THRESHOLDS.iter().zip(&mut times.below) {
+            // compared the same way as when processing, see `judge_chunk`
+            if chunk.inhabited_time <= *threshold {
+                below.0 += 1;
+                below.1 += bytes;
+                below.2 += time;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(times)
+}
+
+/// The amount of groups of identical chunks listed in [`DuplicateStats::largest_groups`].
+pub const DUPLICATE_GROUPS: usize = 10;
+
+/// The fields of a chunk that differ between otherwise identical chunks and are ignored when comparing them.
+const CHUNK_IDENTITY_FIELDS: [&str; 5] = ["xPos", "zPos", "yPos", "LastUpdate", "InhabitedTime"];
+
+/// How many chunks of a world are identical to another chunk, e.g. the ocean or void chunks generated from the same
+/// noise. A file system or storage compressing and deduplicating the region files could store them once.
+#[derive(Default, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct DuplicateStats {
+    /// The amount of chunks in the world.
+    pub total_chunks: u64,
+    /// The space taken up by the chunks in the region files in bytes.
+    pub total_bytes: u64,
+    /// The amount of chunks identical to another chunk, without counting one chunk of each group.
+    pub duplicate_chunks: u64,
+    /// The space taken up by [`DuplicateStats::duplicate_chunks`] in the region files in bytes, which storing every
+    /// group of identical chunks once would save.
+    pub duplicate_bytes: u64,
+    /// The [`DUPLICATE_GROUPS`] groups saving the most space, largest first.
+    pub largest_groups: Vec<DuplicateGroup>,
+}
+
+/// Chunks with the same content.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct DuplicateGroup {
+    /// The amount of identical chunks.
+    pub chunks: u64,
+    /// The space taken up by all but one of the chunks in the region files in bytes.
+    pub duplicate_bytes: u64,
+    /// Whether the chunks contain nothing but air, e.g. in a void world or an unused dimension.
+    pub empty: bool,
+    /// One of the chunks.
+    pub example: ChunkPos,
+}
+
+/// A group of identical chunks while reading the world.
+struct ChunkGroup {
+    chunks: u64,
+    bytes: u64,
+    smallest: u64,
+    empty: bool,
+    example: ChunkPos,
+}
+
+impl ChunkGroup {
+    fn merge(&mut self, other: ChunkGroup) {
+        self.chunks += other.chunks;
+        self.bytes += other.bytes;
+        self.smallest = self.smallest.min(other.smallest);
+        // the same group is found in any order, the example shouldn't depend on it
+        self.example = self.example.clone().min(other.example);
+    }
+}
+
+/// Reads every chunk of the world (all dimensions) and finds the chunks that are identical apart from their position
+/// and timestamps ([`CHUNK_IDENTITY_FIELDS`]). Chunks are compared by a 64-bit hash of their content, and one entry
+/// per distinct chunk is kept in memory while reading.
+pub fn duplicate_chunks(world_folder: &Path) -> Result<DuplicateStats, Error> {
+    if !world_folder.try_exists().is_ok_and(|b| b) {
+        return Err(Error::WorldFolderNotFound);
+    }
+
+    let groups = collect_region_files(world_folder)?
+        .into_par_iter()
+        .map(|(dimension, path)| region_chunk_groups(dimension, &path))
+        .try_reduce(HashMap::new, |mut a, b| {
+            for (hash, group) in b {
+                match a.entry(hash) {
+                    Entry::Occupied(mut entry) => entry.get_mut().merge(group),
+                    Entry::Vacant(entry) => {
+                        entry.insert(group);
+                    }
+                }
+            }
+            Ok(a)
+        })?;
+
+    let mut stats = DuplicateStats::default();
+    let mut duplicates = vec![];
+    for group in groups.into_values() {
+        stats.total_chunks += group.chunks;
+        stats.total_bytes += group.bytes;
+        if group.chunks < 2 {
+            continue;
+        }
+        let duplicate_bytes = group.bytes - group.smallest;
+        stats.duplicate_chunks += group.chunks - 1;
+        stats.duplicate_bytes += duplicate_bytes;
+        duplicates.push(DuplicateGroup {
+            chunks: group.chunks,
+            duplicate_bytes,
+            empty: group.empty,
+            example: group.example,
+        });
+    }
+    duplicates.sort_by(|a, b| {
+        (b.duplicate_bytes, b.chunks)
+            .cmp(&(a.duplicate_bytes, a.chunks))
+            .then_with(|| a.example.cmp(&b.example))
+    });
+    duplicates.truncate(DUPLICATE_GROUPS);
+    stats.largest_groups = duplicates;
+    Ok(stats)
+}
+
+fn region_chunk_groups(
+    dimension: Dimension,
+    path: &Path,
+) -> Result<HashMap<u64, ChunkGroup>, Error> {
+    let (region_x, region_z) = region_coordinates(path);
+    let mut groups = HashMap::<u64, ChunkGroup>::new();
+    region::for_each_raw_chunk(path, |chunk| {
+        let compression = ChunkCompression::from_id(chunk.id);
+        let bytes = chunk.location.sectors as u64 * region::SECTOR_SIZE;
+        let (x, z) = (chunk.x, chunk.z);
+        let data = chunk_data(path, chunk)?;
+        let Some(data) = compression::decompress(compression, &data, &HashMap::new())? else {
+            return Ok::<_, Error>(());
+        };
+        let Ok(value) = crate::nbt::from_bytes::<Value>(&data) else {
+            return Ok(());
+        };
+
+        let mut hasher = DefaultHasher::new();
+        hash_chunk(&value, &mut hasher);
+        let example = ChunkPos {
+            dimension: dimension.clone(),
+            x: region_x * 32 + x as i32,
+            z: region_z * 32 + z as i32,
+        };
+        match groups.entry(hasher.finish()) {
+            Entry::Occupied(mut entry) => entry.get_mut().merge(ChunkGroup {
+                chunks: 1,
+                bytes,
+                smallest: bytes,
+                empty: false,
+                example,
+            }),
+            Entry::Vacant(entry) => {
+                // only checked once for every distinct chunk
+                let empty = !sections::has_blocks(&data, &(i32::MIN..=i32::MAX)).unwrap_or(true);
+                entry.insert(ChunkGroup {
+                    chunks: 1,
+                    bytes,
+                    smallest: bytes,
+                    empty,
+                    example,
+                });
+            }
+        }
+        Ok(())
+    })?;
+    Ok(groups)
+}
+
+/// Hashes the chunk without its [`CHUNK_IDENTITY_FIELDS`], which are nested in `Level` before 1.18.
+fn hash_chunk(chunk: &Value, hasher: &mut impl Hasher) {
+    let Value::Compound(root) = chunk else {
+        return hash_value(chunk, hasher);
+    };
+    let mut keys = root.keys().collect::<Vec<_>>();
+    keys.sort();
+    for key in keys {
+        if CHUNK_IDENTITY_FIELDS.contains(&key.as_str()) {
+            continue;
+        }
+        key.hash(hasher);
+        match &root[key] {
+            level @ Value::Compound(_) if key == "Level" => hash_chunk(level, hasher),
+            value => hash_value(value, hasher),
+        }
+    }
+}
+
+/// Hashes the NBT value independently of the order of the entries of its compounds.
+fn hash_value(value: &Value, hasher: &mut impl Hasher) {
+    std::mem::discriminant(value).hash(hasher);
+    match value {
+        Value::Byte(v) => v.hash(hasher),
+        Value::Short(v) => v.hash(hasher),
+        Value::Int(v) => v.hash(hasher),
+        Value::Long(v) => v.hash(hasher),
+        Value::Float(v) => v.to_bits().hash(hasher),
+        Value::Double(v) => v.to_bits().hash(hasher),
+        Value::String(v) => v.hash(hasher),
+        Value::ByteArray(v) => (**v).hash(hasher),
+        Value::IntArray(v) => (**v).hash(hasher),
+        Value::LongArray(v) => (**v).hash(hasher),
+        Value::List(list) => {
+            list.len().hash(hasher);
+            for value in list {
+                hash_value(value, hasher);
+            }
+        }
+        Value::Compound(compound) => {
+            let mut keys = compound.keys().collect::<Vec<_>>();
+            keys.sort();
+            keys.len().hash(hasher);
+            for key in keys {
+                key.hash(hasher);
+                hash_value(&compound[key], hasher);
+            }
+        }
+    }
+}
+
+/// The space in the region files of a world that no chunk occupies, see [`dead_space`].
+#[derive(Default, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct DeadSpace {
+    /// The amount of region files, including the ones of entities and POIs.
+    pub total_regions: u64,
+    /// The size of all region files in bytes.
+    pub total_bytes: u64,
+    /// The space no chunk occupies in bytes, which [`crate::compact`] releases.
+    pub dead_bytes: u64,
+    /// The region files containing dead space, the most first.
+    pub regions: Vec<RegionDeadSpace>,
+}
+
+/// The dead space of a region file.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct RegionDeadSpace {
+    pub dimension: Dimension,
+    pub path: PathBuf,
+    /// The size of the file in bytes.
+    pub bytes: u64,
+    /// The space no chunk occupies in bytes.
+    pub dead_bytes: u64,
+}
+
+/// Finds the sectors of every region file of the world (chunks, entities and POIs) that no entry of its header refers
+/// to, e.g. left behind by the game when chunks grew and were moved to the end of the file. Only the headers are read.
+pub fn dead_space(world_folder: &Path) -> Result<DeadSpace, Error> {
+    if !world_folder.try_exists().is_ok_and(|b| b) {
+        return Err(Error::WorldFolderNotFound);
+    }
+
+    let regions = collect_all_region_files(world_folder)?
+        .into_par_iter()
+        .map(|(dimension, path)| {
+            let unreadable = |source| Error::UnreadableFile {
+                path: path.clone(),
+                source,
+            };
+            let bytes = fs::metadata(&path).map_err(unreadable)?.len();
+            let dead_bytes = region::dead_space(&path).map_err(unreadable)?;
+            Ok(RegionDeadSpace {
+                dimension,
+                path,
+                bytes,
+                dead_bytes,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let mut stats = DeadSpace {
+        total_regions: regions.len() as u64,
+        total_bytes: regions.iter().map(|region| region.bytes).sum(),
+        dead_bytes: regions.iter().map(|region| region.dead_bytes).sum(),
+        regions,
+    };
+    stats.regions.retain(|region| region.dead_bytes > 0);
+    stats.regions.sort_by(|a, b| {
+        b.dead_bytes
+            .cmp(&a.dead_bytes)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    Ok(stats)
+}
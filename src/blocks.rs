@@ -0,0 +1,81 @@
+//! Scanning the block palettes of a chunk.
+
+use crate::SURFACE_PLAYER_BLOCKS;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct ChunkBlocks {
+    /// 1.18+
+    #[serde(default)]
+    sections: Vec<Section>,
+    /// 1.13 - 1.17
+    #[serde(rename = "Level")]
+    level: Option<LegacyLevel>,
+}
+
+#[derive(Deserialize)]
+struct Section {
+    #[serde(rename = "Y")]
+    y: Option<i8>,
+    block_states: Option<BlockStates>,
+}
+
+#[derive(Deserialize)]
+struct BlockStates {
+    #[serde(default)]
+    palette: Vec<BlockState>,
+}
+
+#[derive(Deserialize)]
+struct LegacyLevel {
+    #[serde(rename = "Sections", default)]
+    sections: Vec<LegacySection>,
+}
+
+#[derive(Deserialize)]
+struct LegacySection {
+    #[serde(rename = "Y")]
+    y: Option<i8>,
+    #[serde(rename = "Palette", default)]
+    palette: Vec<BlockState>,
+}
+
+#[derive(Deserialize)]
+struct BlockState {
+    #[serde(rename = "Name")]
+    name: String,
+}
+
+/// Whether any section palette of the (uncompressed) chunk contains one of the given block ids. The
+/// [`SURFACE_PLAYER_BLOCKS`] among them only count in sections reaching `surface_y` or above, if it's given.
+pub(crate) fn contains_any(
+    chunk: &[u8],
+    block_ids: &[String],
+    surface_y: Option<i32>,
+) -> Result<bool, fastnbt::error::Error> {
+    let chunk: ChunkBlocks = crate::nbt::from_bytes(chunk)?;
+
+    let modern = chunk.sections.iter().filter_map(|section| {
+        section
+            .block_states
+            .as_ref()
+            .map(|states| (section.y, &states.palette))
+    });
+    let legacy = chunk
+        .level
+        .iter()
+        .flat_map(|level| &level.sections)
+        .map(|section| (section.y, &section.palette));
+
+    Ok(modern.chain(legacy).any(|(y, palette)| {
+        // sections without a Y coordinate are searched completely
+        let above_surface = match (y, surface_y) {
+            (Some(y), Some(surface_y)) => y as i32 * 16 + 15 >= surface_y,
+            _ => true,
+        };
+        palette.iter().any(|state| {
+            block_ids.contains(&state.name)
+                && (above_surface || !SURFACE_PLAYER_BLOCKS.contains(&state.name.as_str()))
+        })
+    }))
+}
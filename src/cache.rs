@@ -0,0 +1,237 @@
+//! Chunk caches: the metadata of every chunk of a world, saved by one [scan](crate::scan) for offline analysis and for
+//! selecting the chunks to delete later without reading them again.
+//!
+//! A cache starts with the 16 bytes `lessanvil cache\n` and the version as a big-endian `u32` (see
+//! [`CACHE_VERSION`]), followed by a gzip stream. All numbers in it are big-endian and strings are prefixed with their
+//! length in bytes as a `u16`:
+//!
+//! - the world: its level name (empty if unknown), a `u8` that's 1 if the seed is known and the seed as an `i64`
+//! - the time of the scan as a `u64`, in seconds since the unix epoch
+//! - for every chunk the `u8` 1, followed by its dimension (e.g. `minecraft:overworld`), x and z as `i32`s, its
+//!   InhabitedTime in ticks as a `u64`, its LastUpdate as an `i64`, the time it was saved at according to the region
+//!   header and its size in bytes as `u32`s and its status (empty if unknown)
+//! - the `u8` 0
+
+use crate::plan::{Plan, PlanFilter, PlannedChunk, WorldFingerprint, PLAN_VERSION};
+use crate::scan::{ScanBackend, WorldScanner};
+use crate::{ChunkPos, Config, Error, InhabitedTime};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The version of the caches written by [`write_cache`].
+pub const CACHE_VERSION: u32 = 1;
+
+/// The first bytes of every cache.
+const MAGIC: &[u8; 16] = b"lessanvil cache\n";
+
+/// The metadata of a chunk as saved in a cache.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CachedChunk {
+    pub position: ChunkPos,
+    pub inhabited_time: InhabitedTime,
+    /// The game tick the chunk was last saved at.
+    pub last_update: i64,
+    /// The generation status without the `minecraft:` namespace, e.g. `full`.
+    pub status: Option<String>,
+    /// The size of the sectors the chunk occupies in the region file in bytes.
+    pub size: u32,
+    /// The time the chunk was last saved at according to the region header, in seconds since the unix epoch.
+    pub timestamp: u32,
+}
+
+/// A cache read by [`read_cache`].
+#[derive(Clone, Debug)]
+pub struct ChunkCache {
+    /// The world the cache was made of.
+    pub world: WorldFingerprint,
+    /// When the world was scanned, in seconds since the unix epoch.
+    pub created: u64,
+    /// The chunks, sorted by dimension and region unless the scan was parallel.
+    pub chunks: Vec<CachedChunk>,
+}
+
+impl ChunkCache {
+    /// Makes a plan to delete the cached chunks that a run with the config would delete, judged by
+    /// [`Config::max_inhabited_time`] or [`Config::not_touched_since`], [`Config::dimensions`] and
+    /// [`Config::protected_areas`]. The settings that need the data of the chunks (e.g. [`Config::player_blocks`]) are
+    /// ignored.
+    ///
+    /// Every planned chunk records the timestamp of the scan, so that [`check_plan`](crate::plan::check_plan) sorts out
+    /// the chunks saved since.
+    pub fn plan(&self, config: &Config) -> Plan {
+        let since = config.not_touched_since.map(|time| {
+            time.duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs())
+        });
+        let mut chunks = self
+            .chunks
+            .iter()
+            .filter(|chunk| {
+                config.dimensions.is_empty()
+                    || config.dimensions.contains(&chunk.position.dimension)
+            })
+            // compared the same way as when processing, see `judge_chunk`
+            .filter(|chunk| match since {
+                Some(since) => chunk.timestamp != 0 && (chunk.timestamp as u64) < since,
+                None => chunk.inhabited_time <= config.max_inhabited_time,
+            })
+            .filter(|chunk| {
+                let position = &chunk.position;
+                !config
+                    .protected_areas
+                    .iter()
+                    .any(|area| area.contains(&position.dimension, position.x, position.z))
+            })
+            .map(|chunk| PlannedChunk {
+                position: chunk.position.clone(),
+                timestamp: Some(chunk.timestamp),
+            })
+            .collect::<Vec<_>>();
+        chunks.sort_by(|a, b| a.position.cmp(&b.position));
+        Plan {
+            version: PLAN_VERSION,
+            filter: Some(PlanFilter::from_config(config)),
+            world: Some(self.world.clone()),
+            chunks,
+        }
+    }
+}
+
+/// Scans every chunk of the world and writes its metadata as a cache, returning the amount of chunks written. Fails on
+/// the first region or chunk that can't be read.
+pub fn write_cache(
+    world_folder: &Path,
+    backend: ScanBackend,
+    mut writer: impl Write,
+) -> Result<u64, Error> {
+    let world = WorldFingerprint::read(world_folder)?;
+    let scanner = WorldScanner::new(world_folder, backend)?;
+    let created = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&CACHE_VERSION.to_be_bytes())?;
+    let mut writer = GzEncoder::new(writer, Compression::default());
+    write_str(&mut writer, world.level_name.as_deref().unwrap_or_default())?;
+    writer.write_all(&[world.seed.is_some() as u8])?;
+    writer.write_all(&world.seed.unwrap_or_default().to_be_bytes())?;
+    writer.write_all(&created.to_be_bytes())?;
+
+    let mut count = 0;
+    for chunk in scanner {
+        let chunk = chunk?;
+        writer.write_all(&[1])?;
+        write_str(&mut writer, &chunk.dimension.to_string())?;
+        writer.write_all(&chunk.x.to_be_bytes())?;
+        writer.write_all(&chunk.z.to_be_bytes())?;
+        writer.write_all(&chunk.meta.inhabited_time.ticks().to_be_bytes())?;
+        writer.write_all(&chunk.meta.last_update.to_be_bytes())?;
+        writer.write_all(&chunk.timestamp.to_be_bytes())?;
+        writer.write_all(&(chunk.size as u32).to_be_bytes())?;
+        write_str(
+            &mut writer,
+            chunk.meta.status.as_deref().unwrap_or_default(),
+        )?;
+        count += 1;
+    }
+    writer.write_all(&[0])?;
+    writer.finish()?.flush()?;
+    Ok(count)
+}
+
+/// Whether the file starts like a cache written by [`write_cache`], e.g. to tell it apart from a plan.
+pub fn is_cache(path: &Path) -> bool {
+    let mut magic = [0; MAGIC.len()];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|_| &magic == MAGIC)
+}
+
+/// Reads a cache written by [`write_cache`].
+pub fn read_cache(path: &Path) -> Result<ChunkCache, Error> {
+    let invalid = |message: &str| Error::InvalidCache {
+        path: path.to_path_buf(),
+        message: message.to_string(),
+    };
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic = [0; MAGIC.len()];
+    reader
+        .read_exact(&mut magic)
+        .ok()
+        .filter(|_| &magic == MAGIC)
+        .ok_or_else(|| invalid("not a chunk cache"))?;
+    let version = u32::from_be_bytes(read_array(&mut reader).map_err(|_| invalid("truncated"))?);
+    if version > CACHE_VERSION {
+        return Err(invalid("cache was written by a newer version of lessanvil"));
+    }
+
+    let mut reader = GzDecoder::new(reader);
+    read_body(&mut reader).map_err(|err| match err.kind() {
+        io::ErrorKind::UnexpectedEof => invalid("truncated"),
+        _ => invalid(&err.to_string()),
+    })
+}
+
+fn read_body(reader: &mut impl Read) -> io::Result<ChunkCache> {
+    let level_name = read_str(reader)?;
+    let [has_seed] = read_array(reader)?;
+    let seed = i64::from_be_bytes(read_array(reader)?);
+    let world = WorldFingerprint {
+        level_name: (!level_name.is_empty()).then_some(level_name),
+        seed: (has_seed != 0).then_some(seed),
+    };
+    let created = u64::from_be_bytes(read_array(reader)?);
+
+    let mut chunks = vec![];
+    while read_array::<1>(reader)? == [1] {
+        let dimension = read_str(reader)?
+            .parse()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let x = i32::from_be_bytes(read_array(reader)?);
+        let z = i32::from_be_bytes(read_array(reader)?);
+        let inhabited_time = InhabitedTime::from_ticks(u64::from_be_bytes(read_array(reader)?));
+        let last_update = i64::from_be_bytes(read_array(reader)?);
+        let timestamp = u32::from_be_bytes(read_array(reader)?);
+        let size = u32::from_be_bytes(read_array(reader)?);
+        let status = read_str(reader)?;
+        chunks.push(CachedChunk {
+            position: ChunkPos { dimension, x, z },
+            inhabited_time,
+            last_update,
+            status: (!status.is_empty()).then_some(status),
+            size,
+            timestamp,
+        });
+    }
+    Ok(ChunkCache {
+        world,
+        created,
+        chunks,
+    })
+}
+
+fn write_str(writer: &mut impl Write, value: &str) -> io::Result<()> {
+    let length = u16::try_from(value.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "string too long"))?;
+    writer.write_all(&length.to_be_bytes())?;
+    writer.write_all(value.as_bytes())
+}
+
+fn read_str(reader: &mut impl Read) -> io::Result<String> {
+    let length = u16::from_be_bytes(read_array(reader)?);
+    let mut bytes = vec![0; length as usize];
+    reader.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
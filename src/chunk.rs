@@ -0,0 +1,132 @@
+//! Reading the metadata of a chunk, regardless of the version it was saved with.
+
+use crate::InhabitedTime;
+use serde::de::{Error as _, IgnoredAny};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// The first DataVersion (21w43a, released as 1.18) storing the chunk data in the root compound instead of a `Level`
+/// compound.
+pub const FLAT_LAYOUT_DATA_VERSION: i32 = 2844;
+
+/// The metadata of a chunk.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChunkMeta {
+    /// The version of the game the chunk was saved with, see [the wiki](https://minecraft.wiki/w/Data_version).
+    /// `None` for chunks saved before 1.9.
+    pub data_version: Option<i32>,
+    /// The total time players have spent near the chunk.
+    pub inhabited_time: InhabitedTime,
+    /// The game tick the chunk was last saved at.
+    pub last_update: i64,
+    /// The generation status without the `minecraft:` namespace, e.g. `full` for fully generated chunks.
+    pub status: Option<String>,
+    /// The ids of the structures starting in or reaching into the chunk, e.g. `village`. Sorted and without
+    /// duplicates.
+    pub structures: Vec<String>,
+    /// The amount of block entities in the chunk, e.g. chests and hoppers.
+    pub block_entities: u32,
+}
+
+impl ChunkMeta {
+    /// Reads the metadata of the (uncompressed) chunk.
+    pub fn from_bytes(chunk: &[u8]) -> Result<Self, fastnbt::error::Error> {
+        let raw: RawChunk = crate::nbt::from_bytes(chunk)?;
+        // chunks before 1.18 keep their data inside of a `Level` compound
+        let fields = raw.level.unwrap_or(RawFields {
+            inhabited_time: raw.inhabited_time,
+            last_update: raw.last_update,
+            status: raw.status,
+            structures: raw.structures,
+            block_entities: raw.block_entities,
+        });
+        let inhabited_time = fields
+            .inhabited_time
+            .ok_or_else(|| fastnbt::error::Error::missing_field("InhabitedTime"))?;
+
+        let mut structures = vec![];
+        if let Some(raw_structures) = fields.structures {
+            structures.extend(
+                raw_structures
+                    .starts
+                    .into_iter()
+                    // chunks without a start of a structure used to store it with the id `INVALID`
+                    .filter(|(_, start)| start.id.as_deref() != Some("INVALID"))
+                    .map(|(id, _)| id),
+            );
+            structures.extend(raw_structures.references.into_keys());
+        }
+        let mut structures = structures
+            .into_iter()
+            .map(|id| strip_namespace(id).to_ascii_lowercase())
+            .collect::<Vec<_>>();
+        structures.sort();
+        structures.dedup();
+
+        Ok(ChunkMeta {
+            data_version: raw.data_version,
+            inhabited_time: InhabitedTime::from_ticks(inhabited_time.max(0) as u64),
+            last_update: fields.last_update.unwrap_or_default(),
+            status: fields.status.map(strip_namespace),
+            structures,
+            block_entities: fields
+                .block_entities
+                .map_or(0, |entities| entities.len() as u32),
+        })
+    }
+}
+
+fn strip_namespace(id: String) -> String {
+    match id.strip_prefix("minecraft:") {
+        Some(stripped) => stripped.to_string(),
+        None => id,
+    }
+}
+
+/// The root compound of a chunk. Its fields are read separately instead of flattening [`RawFields`] into it, as
+/// flattening would buffer the whole chunk.
+#[derive(Deserialize)]
+struct RawChunk {
+    #[serde(rename = "DataVersion")]
+    data_version: Option<i32>,
+    /// 1.13 - 1.17
+    #[serde(rename = "Level")]
+    level: Option<RawFields>,
+    /// 1.18+
+    #[serde(rename = "InhabitedTime")]
+    inhabited_time: Option<i64>,
+    #[serde(rename = "LastUpdate")]
+    last_update: Option<i64>,
+    #[serde(rename = "Status")]
+    status: Option<String>,
+    structures: Option<RawStructures>,
+    block_entities: Option<Vec<IgnoredAny>>,
+}
+
+/// The `Level` compound of chunks before 1.18.
+#[derive(Deserialize)]
+struct RawFields {
+    #[serde(rename = "InhabitedTime")]
+    inhabited_time: Option<i64>,
+    #[serde(rename = "LastUpdate")]
+    last_update: Option<i64>,
+    #[serde(rename = "Status")]
+    status: Option<String>,
+    #[serde(rename = "Structures")]
+    structures: Option<RawStructures>,
+    #[serde(rename = "TileEntities")]
+    block_entities: Option<Vec<IgnoredAny>>,
+}
+
+#[derive(Deserialize)]
+struct RawStructures {
+    #[serde(alias = "Starts", default)]
+    starts: BTreeMap<String, RawStart>,
+    #[serde(rename = "References", default)]
+    references: BTreeMap<String, IgnoredAny>,
+}
+
+#[derive(Deserialize)]
+struct RawStart {
+    id: Option<String>,
+}
@@ -0,0 +1,265 @@
+//! Keeping the rest of the world consistent after chunks were deleted.
+
+use crate::{region, Dimension, Error, DIMENSION_FOLDERS};
+use fastnbt::Value;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// The cleanups to run, see [`post_clean`]. Nothing is enabled by default.
+#[derive(Default, Clone, Debug)]
+pub struct PostClean {
+    /// Remove raids (`data/raids.dat`) whose center lies in a chunk that doesn't exist anymore.
+    pub raids: bool,
+    /// Remove scores of the scoreboard (`data/scoreboard.dat`) belonging to objectives that don't exist anymore.
+    pub scoreboard: bool,
+    /// Make sure the last map id in `data/idcounts.dat` isn't below the id of an existing map, which would cause new
+    /// maps to overwrite existing ones.
+    pub map_ids: bool,
+    /// Remove files that serve no purpose for the world, see [`JUNK_FILES`]. `session.lock` is removed as well, so the
+    /// server must not be running.
+    pub junk_files: bool,
+}
+
+/// Names of files and folders that are left behind by the game, tools or the operating system and can be removed.
+pub const JUNK_FILES: &[&str] = &[
+    "level.dat_old",
+    "session.lock",
+    "##MCEDIT.TEMP##",
+    ".DS_Store",
+    "Thumbs.db",
+    "desktop.ini",
+];
+
+/// What [`post_clean`] changed.
+#[derive(Default, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct PostCleanReport {
+    /// The amount of raids removed.
+    pub removed_raids: u64,
+    /// The amount of scores removed.
+    pub removed_scores: u64,
+    /// The corrected last map id, if it had to be changed.
+    pub fixed_map_id: Option<i32>,
+    /// The junk files and folders that were removed.
+    pub removed_files: Vec<PathBuf>,
+}
+
+/// Cleans up data referring to chunks or other data that doesn't exist anymore, e.g. after pruning the world.
+pub fn post_clean(world_folder: &Path, options: &PostClean) -> Result<PostCleanReport, Error> {
+    if !world_folder.try_exists().is_ok_and(|b| b) {
+        return Err(Error::WorldFolderNotFound);
+    }
+
+    let mut report = PostCleanReport::default();
+    if options.raids {
+        for (dimension, folder) in DIMENSION_FOLDERS {
+            for name in ["raids.dat", "raids_end.dat"] {
+                let path = world_folder.join(folder).join("data").join(name);
+                if path.try_exists().is_ok_and(|b| b) {
+                    report.removed_raids += clean_raids(world_folder, &dimension, &path)?;
+                }
+            }
+        }
+    }
+    let data = world_folder.join("data");
+    if options.scoreboard {
+        let path = data.join("scoreboard.dat");
+        if path.try_exists().is_ok_and(|b| b) {
+            report.removed_scores = clean_scoreboard(&path)?;
+        }
+    }
+    if options.map_ids {
+        let path = data.join("idcounts.dat");
+        if path.try_exists().is_ok_and(|b| b) {
+            report.fixed_map_id = fix_map_ids(&data, &path)?;
+        }
+    }
+    if options.junk_files {
+        remove_junk(world_folder, &mut report.removed_files)?;
+    }
+    Ok(report)
+}
+
+/// Removes all [`JUNK_FILES`] and temporary files of interrupted lessanvil runs inside of the folder.
+fn remove_junk(folder: &Path, removed: &mut Vec<PathBuf>) -> Result<(), Error> {
+    for entry in folder.read_dir()? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name();
+        let is_dir = entry.file_type()?.is_dir();
+        let is_junk = name.to_str().is_some_and(|name| {
+            JUNK_FILES.contains(&name) || (!is_dir && name.ends_with(".lessanvil-tmp"))
+        });
+
+        if is_junk {
+            if is_dir {
+                fs::remove_dir_all(&path)?;
+            } else {
+                fs::remove_file(&path)?;
+            }
+            removed.push(path);
+        } else if is_dir {
+            remove_junk(&path, removed)?;
+        }
+    }
+    Ok(())
+}
+
+fn clean_raids(world_folder: &Path, dimension: &Dimension, path: &Path) -> Result<u64, Error> {
+    let mut nbt = read_nbt(path)?;
+    let Some(Value::List(raids)) = data(&mut nbt).and_then(|data| data.get_mut("Raids")) else {
+        return Ok(0);
+    };
+
+    let region_folder = world_folder.join(dimension.folder()).join("region");
+    let mut headers = HashMap::new();
+    let before = raids.len();
+    raids.retain(|raid| {
+        let Value::Compound(raid) = raid else {
+            return true;
+        };
+        let (Some(x), Some(z)) = (
+            raid.get("CX").and_then(Value::as_i64),
+            raid.get("CZ").and_then(Value::as_i64),
+        ) else {
+            return true;
+        };
+        let (chunk_x, chunk_z) = ((x as i32) >> 4, (z as i32) >> 4);
+        let header = headers
+            .entry((chunk_x >> 5, chunk_z >> 5))
+            .or_insert_with(|| {
+                let path = region_folder.join(format!("r.{}.{}.mca", chunk_x >> 5, chunk_z >> 5));
+                File::open(path).and_then(region::Header::read).ok()
+            });
+        header.as_ref().is_some_and(|header| {
+            header
+                .location(
+                    chunk_x.rem_euclid(32) as usize,
+                    chunk_z.rem_euclid(32) as usize,
+                )
+                .is_present()
+        })
+    });
+
+    let removed = (before - raids.len()) as u64;
+    if removed > 0 {
+        write_nbt(path, &nbt)?;
+    }
+    Ok(removed)
+}
+
+fn clean_scoreboard(path: &Path) -> Result<u64, Error> {
+    let mut nbt = read_nbt(path)?;
+    let Some(data) = data(&mut nbt) else {
+        return Ok(0);
+    };
+    let objectives = match data.get("Objectives") {
+        Some(Value::List(objectives)) => objectives
+            .iter()
+            .filter_map(|objective| match objective {
+                Value::Compound(objective) => objective.get("Name")?.as_str().map(str::to_string),
+                _ => None,
+            })
+            .collect::<HashSet<_>>(),
+        _ => HashSet::new(),
+    };
+    let Some(Value::List(scores)) = data.get_mut("PlayerScores") else {
+        return Ok(0);
+    };
+
+    let before = scores.len();
+    scores.retain(|score| match score {
+        Value::Compound(score) => score
+            .get("Objective")
+            .and_then(Value::as_str)
+            .map_or(true, |objective| objectives.contains(objective)),
+        _ => true,
+    });
+
+    let removed = (before - scores.len()) as u64;
+    if removed > 0 {
+        write_nbt(path, &nbt)?;
+    }
+    Ok(removed)
+}
+
+fn fix_map_ids(data_folder: &Path, path: &Path) -> Result<Option<i32>, Error> {
+    let highest = data_folder
+        .read_dir()?
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name();
+            name.to_str()?
+                .strip_prefix("map_")?
+                .strip_suffix(".dat")?
+                .parse::<i32>()
+                .ok()
+        })
+        .max();
+    let Some(highest) = highest else {
+        return Ok(None);
+    };
+
+    let mut nbt = read_nbt(path)?;
+    let Some(data) = data(&mut nbt) else {
+        return Ok(None);
+    };
+    let last = data.get("map").and_then(Value::as_i64);
+    if last.is_some_and(|last| last >= highest as i64) {
+        return Ok(None);
+    }
+    data.insert("map".to_string(), Value::Int(highest));
+    write_nbt(path, &nbt)?;
+    Ok(Some(highest))
+}
+
+/// The `data` compound of a saved data file.
+fn data(nbt: &mut Value) -> Option<&mut HashMap<String, Value>> {
+    match nbt {
+        Value::Compound(root) => match root.get_mut("data") {
+            Some(Value::Compound(data)) => Some(data),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Reads a gzip compressed NBT file.
+pub(crate) fn read_nbt(path: &Path) -> Result<Value, Error> {
+    let mut bytes = vec![];
+    GzDecoder::new(File::open(path)?).read_to_end(&mut bytes)?;
+    crate::nbt::from_bytes(&bytes).map_err(|source| Error::InvalidDataFile {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Writes a gzip compressed NBT file, replacing the old file only once the new one was written completely. The new
+/// file keeps the permissions and owner of the old one.
+pub(crate) fn write_nbt(path: &Path, nbt: &Value) -> Result<(), Error> {
+    let bytes = fastnbt::to_bytes(nbt).map_err(|source| Error::InvalidDataFile {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".lessanvil-tmp");
+    let tmp = PathBuf::from(tmp);
+
+    let mut encoder = GzEncoder::new(
+        region::create_replacement(&tmp, path)?,
+        Compression::default(),
+    );
+    encoder.write_all(&bytes)?;
+    encoder.finish()?;
+    if let Err(err) = fs::rename(&tmp, path) {
+        let _ = fs::remove_file(&tmp);
+        return Err(err.into());
+    }
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! Grouping chunks into clusters of adjacent chunks, e.g. to check where chunks were deleted.
+
+use crate::{ChunkPos, Dimension};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// Chunks of a dimension that touch each other, including diagonally.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct ChunkCluster {
+    /// The dimension the chunks are in.
+    pub dimension: Dimension,
+    /// The amount of chunks.
+    pub chunks: u64,
+    /// The lowest x chunk coordinate.
+    pub min_x: i32,
+    /// The lowest z chunk coordinate.
+    pub min_z: i32,
+    /// The highest x chunk coordinate.
+    pub max_x: i32,
+    /// The highest z chunk coordinate.
+    pub max_z: i32,
+}
+
+impl ChunkCluster {
+    /// The block coordinates (x, z) of the center of the bounding box.
+    pub fn center_block(&self) -> (i32, i32) {
+        let center = |min: i32, max: i32| ((min as i64 + max as i64 + 1) * 8) as i32;
+        (
+            center(self.min_x, self.max_x),
+            center(self.min_z, self.max_z),
+        )
+    }
+}
+
+/// Groups the chunks into clusters, largest first.
+pub fn clusters(chunks: impl IntoIterator<Item = ChunkPos>) -> Vec<ChunkCluster> {
+    let mut dimensions = HashMap::<Dimension, HashSet<(i32, i32)>>::new();
+    for chunk in chunks {
+        dimensions
+            .entry(chunk.dimension)
+            .or_default()
+            .insert((chunk.x, chunk.z));
+    }
+
+    let mut clusters = vec![];
+    for (dimension, mut remaining) in dimensions {
+        while let Some(&start) = remaining.iter().next() {
+            remaining.remove(&start);
+            let mut cluster = ChunkCluster {
+                dimension: dimension.clone(),
+                chunks: 0,
+                min_x: start.0,
+                min_z: start.1,
+                max_x: start.0,
+                max_z: start.1,
+            };
+            let mut queue = vec![start];
+            while let Some((x, z)) = queue.pop() {
+                cluster.chunks += 1;
+                cluster.min_x = cluster.min_x.min(x);
+                cluster.min_z = cluster.min_z.min(z);
+                cluster.max_x = cluster.max_x.max(x);
+                cluster.max_z = cluster.max_z.max(z);
+                for dx in -1..=1 {
+                    for dz in -1..=1 {
+                        let neighbour = (x.saturating_add(dx), z.saturating_add(dz));
+                        if remaining.remove(&neighbour) {
+                            queue.push(neighbour);
+                        }
+                    }
+                }
+            }
+            clusters.push(cluster);
+        }
+    }
+    clusters.sort_by(|a, b| {
+        b.chunks
+            .cmp(&a.chunks)
+            .then_with(|| (&a.dimension, a.min_x, a.min_z).cmp(&(&b.dimension, b.min_x, b.min_z)))
+    });
+    clusters
+}
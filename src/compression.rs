@@ -0,0 +1,66 @@
+//! Compressing and decompressing chunk data, see [`ChunkCompression`].
+
+use crate::{ChunkCompression, CustomCompression};
+use flate2::read::{GzDecoder, GzEncoder, ZlibDecoder, ZlibEncoder};
+use flate2::Compression;
+use std::collections::HashMap;
+use std::io::{self, Read};
+
+#[cfg(feature = "lz4")]
+mod lz4;
+
+/// Decompresses chunk data. Returns `None` for compressions lessanvil can't decompress and that aren't in `custom`,
+/// including LZ4 without the `lz4` feature.
+pub(crate) fn decompress(
+    compression: ChunkCompression,
+    data: &[u8],
+    custom: &HashMap<u8, CustomCompression>,
+) -> io::Result<Option<Vec<u8>>> {
+    let mut decompressed = vec![];
+    match compression {
+        ChunkCompression::Gzip => {
+            GzDecoder::new(data).read_to_end(&mut decompressed)?;
+        }
+        ChunkCompression::Zlib => {
+            ZlibDecoder::new(data).read_to_end(&mut decompressed)?;
+        }
+        ChunkCompression::Uncompressed => decompressed.extend_from_slice(data),
+        #[cfg(feature = "lz4")]
+        ChunkCompression::Lz4 => decompressed = lz4::decompress(data)?,
+        ChunkCompression::Custom(id) => match custom.get(&id) {
+            Some(custom) => decompressed = (custom.decompress)(data)?,
+            None => return Ok(None),
+        },
+        #[cfg(not(feature = "lz4"))]
+        ChunkCompression::Lz4 => return Ok(None),
+    }
+    Ok(Some(decompressed))
+}
+
+/// Compresses chunk data. Returns `None` for compressions lessanvil can't compress and that aren't in `custom`,
+/// including LZ4 without the `lz4` feature.
+pub(crate) fn compress(
+    compression: ChunkCompression,
+    data: &[u8],
+    custom: &HashMap<u8, CustomCompression>,
+) -> io::Result<Option<Vec<u8>>> {
+    let mut compressed = vec![];
+    match compression {
+        ChunkCompression::Gzip => {
+            GzEncoder::new(data, Compression::fast()).read_to_end(&mut compressed)?;
+        }
+        ChunkCompression::Zlib => {
+            ZlibEncoder::new(data, Compression::fast()).read_to_end(&mut compressed)?;
+        }
+        ChunkCompression::Uncompressed => compressed.extend_from_slice(data),
+        #[cfg(feature = "lz4")]
+        ChunkCompression::Lz4 => compressed = lz4::compress(data),
+        ChunkCompression::Custom(id) => match custom.get(&id) {
+            Some(custom) => compressed = (custom.compress)(data)?,
+            None => return Ok(None),
+        },
+        #[cfg(not(feature = "lz4"))]
+        ChunkCompression::Lz4 => return Ok(None),
+    }
+    Ok(Some(compressed))
+}
@@ -0,0 +1,90 @@
+//! Chunks compressed with LZ4 (1.20.5+).
+
+use std::io;
+
+// The game writes LZ4 chunks with lz4-java's `LZ4BlockOutputStream`: a sequence of blocks, each starting with a header
+// of the magic, a token, the compressed length, the decompressed length and a checksum, terminated by an empty block.
+
+const LZ4_MAGIC: &[u8; 8] = b"LZ4Block";
+const LZ4_HEADER_SIZE: usize = LZ4_MAGIC.len() + 1 + 4 + 4 + 4;
+/// The default block size of `LZ4BlockOutputStream`.
+const LZ4_BLOCK_SIZE: usize = 1 << 16;
+/// The largest block size `LZ4BlockOutputStream` supports. Larger lengths come from corrupted chunks and are rejected
+/// before allocating for them.
+const LZ4_MAX_BLOCK_SIZE: usize = 1 << 25;
+const LZ4_METHOD_RAW: u8 = 0x10;
+const LZ4_METHOD_LZ4: u8 = 0x20;
+/// The compression level stored in the token, derived from the block size.
+const LZ4_LEVEL: u8 = (32 - (LZ4_BLOCK_SIZE as u32 - 1).leading_zeros() - 10) as u8;
+const LZ4_CHECKSUM_SEED: u32 = 0x9747b28c;
+
+/// The checksum of a block: lz4-java only keeps the lower 28 bits of the xxHash32.
+fn block_checksum(data: &[u8]) -> u32 {
+    xxhash_rust::xxh32::xxh32(data, LZ4_CHECKSUM_SEED) & 0x0fff_ffff
+}
+
+pub(super) fn decompress(mut data: &[u8]) -> io::Result<Vec<u8>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let read_u32 = |bytes: &[u8]| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+    let mut decompressed = vec![];
+    while !data.is_empty() {
+        if data.len() < LZ4_HEADER_SIZE || &data[..LZ4_MAGIC.len()] != LZ4_MAGIC {
+            return Err(invalid("invalid LZ4 block header"));
+        }
+        let token = data[8];
+        let compressed_length = read_u32(&data[9..]) as usize;
+        let length = read_u32(&data[13..]) as usize;
+        let checksum = read_u32(&data[17..]);
+        data = &data[LZ4_HEADER_SIZE..];
+        if length == 0 {
+            break;
+        }
+        if length > LZ4_MAX_BLOCK_SIZE {
+            return Err(invalid("LZ4 block too large"));
+        }
+        let block = data
+            .get(..compressed_length)
+            .ok_or_else(|| invalid("LZ4 block exceeds the chunk"))?;
+        data = &data[compressed_length..];
+
+        let start = decompressed.len();
+        match token & 0xf0 {
+            LZ4_METHOD_RAW => decompressed.extend_from_slice(block),
+            LZ4_METHOD_LZ4 => decompressed.extend(
+                lz4_flex::block::decompress(block, length)
+                    .map_err(|err| invalid(&err.to_string()))?,
+            ),
+            _ => return Err(invalid("unknown LZ4 block compression method")),
+        }
+        if decompressed.len() - start != length
+            || block_checksum(&decompressed[start..]) != checksum
+        {
+            return Err(invalid("corrupted LZ4 block"));
+        }
+    }
+    Ok(decompressed)
+}
+
+pub(super) fn compress(data: &[u8]) -> Vec<u8> {
+    let mut compressed = vec![];
+    let mut write_block = |token: u8, block: &[u8], length: usize, checksum: u32| {
+        compressed.extend_from_slice(LZ4_MAGIC);
+        compressed.push(token | LZ4_LEVEL);
+        compressed.extend_from_slice(&(block.len() as u32).to_le_bytes());
+        compressed.extend_from_slice(&(length as u32).to_le_bytes());
+        compressed.extend_from_slice(&checksum.to_le_bytes());
+        compressed.extend_from_slice(block);
+    };
+    for chunk in data.chunks(LZ4_BLOCK_SIZE) {
+        let block = lz4_flex::block::compress(chunk);
+        // blocks that don't get smaller are stored as they are
+        if block.len() < chunk.len() {
+            write_block(LZ4_METHOD_LZ4, &block, chunk.len(), block_checksum(chunk));
+        } else {
+            write_block(LZ4_METHOD_RAW, chunk, chunk.len(), block_checksum(chunk));
+        }
+    }
+    write_block(LZ4_METHOD_RAW, &[], 0, 0);
+    compressed
+}
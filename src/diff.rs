@@ -0,0 +1,209 @@
+//! Comparing the chunks of two copies of a world.
+
+use crate::{collect_region_files, region, region_coordinates, ChunkPos, Dimension, Error};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// The SHA-256 hash of the uncompressed data of a chunk.
+pub type ChunkHash = [u8; 32];
+
+/// The differences between two worlds.
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct WorldDiff {
+    /// Chunks only present in the second world.
+    pub added: Vec<ChunkPos>,
+    /// Chunks only present in the first world.
+    pub removed: Vec<ChunkPos>,
+    /// Chunks present in both worlds but with different data.
+    pub changed: Vec<ChunkPos>,
+    /// The amount of chunks that are identical in both worlds.
+    pub unchanged: u64,
+}
+
+/// Compares the chunks of two worlds, e.g. a world before and after pruning it.
+pub fn diff(a: &Path, b: &Path) -> Result<WorldDiff, Error> {
+    if !a.try_exists().is_ok_and(|b| b) || !b.try_exists().is_ok_and(|b| b) {
+        return Err(Error::WorldFolderNotFound);
+    }
+
+    // pair up the region files of both worlds by dimension and file name
+    let mut regions: BTreeMap<(Dimension, OsString), (Option<PathBuf>, Option<PathBuf>)> =
+        BTreeMap::new();
+    for (dimension, path) in collect_region_files(a)? {
+        let name = path.file_name().unwrap_or_default().to_owned();
+        regions.entry((dimension, name)).or_default().0 = Some(path);
+    }
+    for (dimension, path) in collect_region_files(b)? {
+        let name = path.file_name().unwrap_or_default().to_owned();
+        regions.entry((dimension, name)).or_default().1 = Some(path);
+    }
+
+    let diffs = regions
+        .into_iter()
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|((dimension, _), (a, b))| {
+            let a = a.map_or(Ok(BTreeMap::new()), |path| hash_region(&dimension, &path))?;
+            let b = b.map_or(Ok(BTreeMap::new()), |path| hash_region(&dimension, &path))?;
+            Ok(diff_hashes(&a, &b))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let mut result = WorldDiff::default();
+    for mut diff in diffs {
+        result.added.append(&mut diff.added);
+        result.removed.append(&mut diff.removed);
+        result.changed.append(&mut diff.changed);
+        result.unchanged += diff.unchanged;
+    }
+    Ok(result)
+}
+
+/// The first line of every manifest file.
+const MANIFEST_HEADER: &str = "# lessanvil manifest v1";
+
+/// Hashes every chunk of the world.
+pub fn hash_world(world: &Path) -> Result<BTreeMap<ChunkPos, ChunkHash>, Error> {
+    if !world.try_exists().is_ok_and(|b| b) {
+        return Err(Error::WorldFolderNotFound);
+    }
+
+    collect_region_files(world)?
+        .into_par_iter()
+        .map(|(dimension, path)| hash_region(&dimension, &path))
+        .try_reduce(BTreeMap::new, |mut a, mut b| {
+            a.append(&mut b);
+            Ok(a)
+        })
+}
+
+/// Writes a manifest with one `<sha256> <dimension> <x> <z>` line per chunk.
+pub fn write_manifest(
+    hashes: &BTreeMap<ChunkPos, ChunkHash>,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    writeln!(writer, "{MANIFEST_HEADER}")?;
+    for (position, hash) in hashes {
+        let hex = hash.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        writeln!(
+            writer,
+            "{hex} {} {} {}",
+            position.dimension, position.x, position.z
+        )?;
+    }
+    Ok(())
+}
+
+/// Reads a manifest written by [`write_manifest`].
+pub fn read_manifest(path: &Path) -> Result<BTreeMap<ChunkPos, ChunkHash>, Error> {
+    let content = fs::read_to_string(path)?;
+    let mut lines = content.lines().enumerate();
+    let invalid = |line: usize, message: &str| Error::InvalidManifest {
+        path: path.to_path_buf(),
+        line,
+        message: message.to_string(),
+    };
+
+    if lines.next().map(|(_, line)| line) != Some(MANIFEST_HEADER) {
+        return Err(invalid(1, "not a lessanvil manifest"));
+    }
+
+    let mut hashes = BTreeMap::new();
+    for (i, line) in lines {
+        let [hex, dimension, x, z] = line.split(' ').collect::<Vec<_>>()[..] else {
+            return Err(invalid(i + 1, "expected `<sha256> <dimension> <x> <z>`"));
+        };
+        let mut hash = [0; 32];
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err(invalid(i + 1, "invalid hash"));
+        }
+        for (byte, chunk) in hash.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            *byte = std::str::from_utf8(chunk)
+                .ok()
+                .and_then(|chunk| u8::from_str_radix(chunk, 16).ok())
+                .ok_or_else(|| invalid(i + 1, "invalid hash"))?;
+        }
+
+        let position = ChunkPos {
+            dimension: dimension
+                .parse()
+                .map_err(|_| invalid(i + 1, "unknown dimension"))?,
+            x: x.parse()
+                .map_err(|_| invalid(i + 1, "invalid x coordinate"))?,
+            z: z.parse()
+                .map_err(|_| invalid(i + 1, "invalid z coordinate"))?,
+        };
+        hashes.insert(position, hash);
+    }
+    Ok(hashes)
+}
+
+/// Compares the world against a manifest. [`WorldDiff::changed`] is empty if every chunk still present in the world is
+/// bit-identical to the state recorded in the manifest.
+pub fn verify_manifest(world: &Path, manifest: &Path) -> Result<WorldDiff, Error> {
+    let expected = read_manifest(manifest)?;
+    let actual = hash_world(world)?;
+    Ok(diff_hashes(&expected, &actual))
+}
+
+/// Compares two sets of chunk hashes.
+pub(crate) fn diff_hashes(
+    a: &BTreeMap<ChunkPos, ChunkHash>,
+    b: &BTreeMap<ChunkPos, ChunkHash>,
+) -> WorldDiff {
+    let mut diff = WorldDiff::default();
+    let positions = a.keys().chain(b.keys()).collect::<BTreeSet<_>>();
+    for position in positions {
+        match (a.get(position), b.get(position)) {
+            (Some(a), Some(b)) if a == b => diff.unchanged += 1,
+            (Some(_), Some(_)) => diff.changed.push(position.clone()),
+            (Some(_), None) => diff.removed.push(position.clone()),
+            (None, Some(_)) => diff.added.push(position.clone()),
+            (None, None) => {}
+        }
+    }
+    diff
+}
+
+/// Hashes every chunk of a region file.
+pub(crate) fn hash_region(
+    dimension: &Dimension,
+    region_file_path: &Path,
+) -> Result<BTreeMap<ChunkPos, ChunkHash>, Error> {
+    let region_error = |source: crate::RegionProcessingError| Error::RegionError {
+        path: region_file_path.to_path_buf(),
+        source,
+    };
+    let (x, z) = region_coordinates(region_file_path);
+    let mut file = File::open(region_file_path).map_err(|err| region_error(err.into()))?;
+    let header = region::Header::read(&mut file).map_err(|err| region_error(err.into()))?;
+
+    let mut hashes = BTreeMap::new();
+    for chunk_x in 0..32 {
+        for chunk_z in 0..32 {
+            let Some((_, chunk)) =
+                region::read_chunk(&mut file, &header, chunk_x, chunk_z, &HashMap::new())
+                    .map_err(|err| region_error(err.into()))?
+            else {
+                continue;
+            };
+            hashes.insert(
+                ChunkPos {
+                    dimension: dimension.clone(),
+                    x: x * 32 + chunk_x as i32,
+                    z: z * 32 + chunk_z as i32,
+                },
+                Sha256::digest(&chunk).into(),
+            );
+        }
+    }
+    Ok(hashes)
+}
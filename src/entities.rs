@@ -0,0 +1,57 @@
+//! Counting the entities of chunks, stored in the `entities` folder next to the region folder since 1.17.
+
+use crate::{nbt, region, CustomCompression};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+
+/// The amount of entities of a chunk by id, e.g. `minecraft:item`.
+pub(crate) type EntityCounts = HashMap<String, u32>;
+
+/// Counts the entities of every chunk of an entity region file, indexed by `z * 32 + x` with coordinates relative to
+/// the region. Chunks that can't be read count as empty.
+pub(crate) fn count_region(
+    path: &Path,
+    custom: &HashMap<u8, CustomCompression>,
+) -> io::Result<Vec<EntityCounts>> {
+    let mut file = BufReader::new(File::open(path)?);
+    let header = region::Header::read(&mut file)?;
+    let mut counts = vec![EntityCounts::new(); 32 * 32];
+    for z in 0..32 {
+        for x in 0..32 {
+            if let Ok(Some((_, chunk))) = region::read_chunk(&mut file, &header, x, z, custom) {
+                counts[z * 32 + x] = count(&chunk).unwrap_or_default();
+            }
+        }
+    }
+    Ok(counts)
+}
+
+/// Counts the entities of the (uncompressed) entity chunk, including the entities riding others.
+pub(crate) fn count(chunk: &[u8]) -> Result<EntityCounts, fastnbt::error::Error> {
+    let raw: RawEntityChunk = nbt::from_bytes(chunk)?;
+    let mut counts = EntityCounts::new();
+    let mut pending = raw.entities;
+    while let Some(entity) = pending.pop() {
+        if let Some(id) = entity.id {
+            *counts.entry(id).or_default() += 1;
+        }
+        pending.extend(entity.passengers);
+    }
+    Ok(counts)
+}
+
+#[derive(Deserialize)]
+struct RawEntityChunk {
+    #[serde(rename = "Entities", default)]
+    entities: Vec<RawEntity>,
+}
+
+#[derive(Deserialize)]
+struct RawEntity {
+    id: Option<String>,
+    #[serde(rename = "Passengers", default)]
+    passengers: Vec<RawEntity>,
+}
@@ -0,0 +1,71 @@
+//! Entry points for fuzzing the handling of untrusted region files and chunks, e.g. with the cargo-fuzz targets in
+//! `fuzz/`. Only available with the `fuzzing` feature.
+//!
+//! Region files of old servers are often corrupted, so none of these may panic, whatever the input.
+
+use crate::chunk::ChunkMeta;
+use crate::{blocks, region, sections, ChunkCompression, SEA_LEVEL};
+use fastanvil::Region;
+use std::collections::HashMap;
+use std::io::Cursor;
+
+/// Processes the bytes as a region file, in memory: reads every chunk, runs [`chunk`] on it and deletes and rewrites
+/// chunks the way processing does.
+pub fn region(data: &[u8]) {
+    let Ok(header) = region::Header::read(data) else {
+        return;
+    };
+    let mut raw = Cursor::new(data);
+    let mut chunks = vec![];
+    for x in 0..32 {
+        for z in 0..32 {
+            if let Ok(Some((compression, nbt))) =
+                region::read_chunk(&mut raw, &header, x, z, &HashMap::new())
+            {
+                chunk(&nbt);
+                chunks.push((x, z, compression, nbt));
+            }
+        }
+    }
+
+    let Ok(mut rewritten) = Region::from_stream(Cursor::new(data.to_vec())) else {
+        return;
+    };
+    let mut file = Cursor::new(data.to_vec());
+    for (i, (x, z, compression, nbt)) in chunks.into_iter().enumerate() {
+        let _ = if i % 2 == 0 {
+            rewritten.remove_chunk(x, z)
+        } else {
+            let target = match compression {
+                ChunkCompression::Zlib => ChunkCompression::Gzip,
+                _ => ChunkCompression::Zlib,
+            };
+            region::write_chunk(
+                &mut rewritten,
+                &mut file,
+                x,
+                z,
+                target,
+                &nbt,
+                &HashMap::new(),
+            )
+        };
+    }
+}
+
+/// Runs everything that reads or edits the (uncompressed) NBT of a chunk on the bytes.
+pub fn chunk(data: &[u8]) {
+    let _ = ChunkMeta::from_bytes(data);
+    let _ = blocks::contains_any(data, &["minecraft:torch".to_string()], Some(SEA_LEVEL));
+    let _ = sections::has_blocks(data, &(128..=i32::MAX));
+    let _ = sections::clear_sections(data, &(-64..=0));
+    let _ = crate::reset_inhabited_time(data);
+}
+
+/// Decompresses the bytes as chunk data with the compression of the given id, then runs [`chunk`] on the result.
+pub fn compressed_chunk(compression_id: u8, data: &[u8]) {
+    let compression = ChunkCompression::from_id(compression_id);
+    if let Ok(Some(nbt)) = crate::compression::decompress(compression, data, &HashMap::new()) {
+        chunk(&nbt);
+    }
+}
@@ -1,37 +1,808 @@
 //! See [`execute`] for the entrypoint of this crate.
 
-use fastanvil::Region;
+use rayon::iter::ParallelBridge;
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
-use rayon::{ThreadPoolBuildError, ThreadPoolBuilder};
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::File;
-use std::io::{self, Seek};
-use std::path::{Path, PathBuf};
-use std::sync::atomic::AtomicU64;
-use std::sync::mpsc;
-use std::time::Duration;
-use std::{fs, thread, time};
-
-/// The subfolders in the world folder in which the region files are contained
-const REGION_SUBFOLDERS: [&str; 3] = ["region", "DIM-1/region", "DIM1/region"];
-
-/// The config to be passed to lessanvil.
-#[derive(Default)]
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use std::{fmt, fs, thread, time};
+
+pub mod analysis;
+mod blocks;
+#[cfg(feature = "plan")]
+pub mod cache;
+pub mod chunk;
+pub mod cleanup;
+pub mod clusters;
+mod compression;
+#[cfg(feature = "diff")]
+pub mod diff;
+mod entities;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+#[cfg(feature = "linear")]
+pub mod linear;
+pub mod lock;
+mod memory;
+mod nbt;
+#[cfg(feature = "plan")]
+pub mod plan;
+pub mod players;
+pub mod protection;
+mod region;
+pub mod repair;
+pub mod scan;
+mod sections;
+#[cfg(feature = "simulation")]
+pub mod simulation;
+mod size;
+pub mod snapshot;
+pub mod transfer;
+#[cfg(all(feature = "uring", target_os = "linux"))]
+mod uring;
+mod writer;
+
+use chunk::ChunkMeta;
+use memory::MemoryBudget;
+use protection::ProtectedArea;
+
+/// The subfolders in the world folder in which the data of each dimension is contained
+const DIMENSION_FOLDERS: [(Dimension, &str); 3] = [
+    (Dimension::Overworld, ""),
+    (Dimension::Nether, "DIM-1"),
+    (Dimension::End, "DIM1"),
+];
+
+/// The folders inside of a dimension folder containing region files. Chunks in `entities` and `poi` belong
+/// to the chunk with the same coordinates in `region`.
+const REGION_KINDS: [&str; 3] = ["region", "entities", "poi"];
+
+/// A dimension of a world.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Dimension {
+    /// The overworld (`minecraft:overworld`).
+    Overworld,
+    /// The nether (`minecraft:the_nether`).
+    Nether,
+    /// The end (`minecraft:the_end`).
+    End,
+    /// A dimension added by a datapack or mod, identified by its namespaced id (e.g. `mypack:mining`).
+    Custom(String),
+}
+
+impl Dimension {
+    /// The folder containing the data of the dimension, relative to the world folder.
+    pub(crate) fn folder(&self) -> PathBuf {
+        if let Dimension::Custom(id) = self {
+            let (namespace, path) = id.split_once(':').unwrap_or(("minecraft", id));
+            return Path::new("dimensions").join(namespace).join(path);
+        }
+        DIMENSION_FOLDERS
+            .iter()
+            .find(|(dimension, _)| dimension == self)
+            .map_or(PathBuf::new(), |(_, folder)| PathBuf::from(folder))
+    }
+
+    /// Maps the numeric dimension ids used before 1.16 to a dimension.
+    pub fn from_legacy_id(id: i32) -> Option<Self> {
+        match id {
+            0 => Some(Dimension::Overworld),
+            -1 => Some(Dimension::Nether),
+            1 => Some(Dimension::End),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Dimension::Overworld => "minecraft:overworld",
+            Dimension::Nether => "minecraft:the_nether",
+            Dimension::End => "minecraft:the_end",
+            Dimension::Custom(id) => id,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Dimension {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Dimension {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = String::deserialize(deserializer)?;
+        id.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// How the data of a chunk is compressed in a region file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ChunkCompression {
+    /// GZip (id 1), never written by the game.
+    Gzip,
+    /// Zlib (id 2), the default.
+    Zlib,
+    /// No compression (id 3), since 1.15.1.
+    Uncompressed,
+    /// LZ4 (id 4), since 1.20.5 if enabled in the server properties.
+    Lz4,
+    /// Any other id, e.g. used by modified servers.
+    Custom(u8),
+}
+
+impl ChunkCompression {
+    /// The compression of the id stored in front of the chunk data. The flag for chunks stored in a separate file is
+    /// ignored.
+    pub fn from_id(id: u8) -> Self {
+        match id & 0x7f {
+            1 => ChunkCompression::Gzip,
+            2 => ChunkCompression::Zlib,
+            3 => ChunkCompression::Uncompressed,
+            4 => ChunkCompression::Lz4,
+            id => ChunkCompression::Custom(id),
+        }
+    }
+
+    /// The id stored in front of the chunk data.
+    pub fn id(&self) -> u8 {
+        match self {
+            ChunkCompression::Gzip => 1,
+            ChunkCompression::Zlib => 2,
+            ChunkCompression::Uncompressed => 3,
+            ChunkCompression::Lz4 => 4,
+            ChunkCompression::Custom(id) => *id,
+        }
+    }
+}
+
+impl fmt::Display for ChunkCompression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkCompression::Gzip => f.write_str("gzip"),
+            ChunkCompression::Zlib => f.write_str("zlib"),
+            ChunkCompression::Uncompressed => f.write_str("uncompressed"),
+            ChunkCompression::Lz4 => f.write_str("lz4"),
+            ChunkCompression::Custom(id) => write!(f, "custom ({id})"),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for ChunkCompression {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// A function compressing or decompressing chunk data, see [`CustomCompression`].
+pub type ChunkCodecFn = Arc<dyn Fn(&[u8]) -> io::Result<Vec<u8>> + Send + Sync>;
+
+/// How to read and write chunks stored with a [`ChunkCompression::Custom`] compression, see
+/// [`Config::custom_decompressor`].
+#[derive(Clone)]
+pub struct CustomCompression {
+    /// Decompresses the data of a chunk.
+    pub decompress: ChunkCodecFn,
+    /// Compresses the data of a chunk, used when a modified chunk is written back.
+    pub compress: ChunkCodecFn,
+}
+
+/// The position of a chunk in a world, in chunk coordinates.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ChunkPos {
+    /// The dimension the chunk is in.
+    pub dimension: Dimension,
+    /// The x chunk coordinate.
+    pub x: i32,
+    /// The z chunk coordinate.
+    pub z: i32,
+}
+
+/// An amount of [InhabitedTime](https://minecraft.fandom.com/wiki/Chunk_format). Minecraft counts it in game ticks,
+/// [`InhabitedTime::TICKS_PER_SECOND`] of which make up a second.
+///
+/// It's serialized as a string with a unit (see [`InhabitedTime::from_str`]), e.g. `"5m"` or `"1200t"`. Plain numbers
+/// are read as seconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct InhabitedTime(u64);
+
+impl InhabitedTime {
+    /// The amount of game ticks per second.
+    pub const TICKS_PER_SECOND: u64 = 20;
+
+    /// An amount of game ticks, i.e. the value stored in a chunk.
+    pub const fn from_ticks(ticks: u64) -> Self {
+        InhabitedTime(ticks)
+    }
+
+    /// An amount of seconds.
+    pub const fn from_secs(secs: u64) -> Self {
+        InhabitedTime(secs.saturating_mul(Self::TICKS_PER_SECOND))
+    }
+
+    /// The amount of game ticks.
+    pub const fn ticks(self) -> u64 {
+        self.0
+    }
+
+    /// The amount of whole seconds.
+    pub const fn as_secs(self) -> u64 {
+        self.0 / Self::TICKS_PER_SECOND
+    }
+
+    /// The time as a [`Duration`], assuming the game ran at full speed.
+    pub fn as_duration(self) -> Duration {
+        Duration::from_millis(self.0.saturating_mul(1000 / Self::TICKS_PER_SECOND))
+    }
+}
+
+impl From<Duration> for InhabitedTime {
+    /// Rounds down to whole ticks.
+    fn from(duration: Duration) -> Self {
+        let ticks = duration.as_millis() / (1000 / Self::TICKS_PER_SECOND) as u128;
+        InhabitedTime(ticks.try_into().unwrap_or(u64::MAX))
+    }
+}
+
+impl From<InhabitedTime> for Duration {
+    fn from(time: InhabitedTime) -> Self {
+        time.as_duration()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for InhabitedTime {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for InhabitedTime {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Value {
+            Secs(u64),
+            Text(String),
+        }
+
+        match Value::deserialize(deserializer)? {
+            Value::Secs(secs) => Ok(InhabitedTime::from_secs(secs)),
+            Value::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+impl fmt::Display for InhabitedTime {
+    /// Writes the time in the largest unit that represents it exactly, in the format read by [`InhabitedTime::from_str`].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 % Self::TICKS_PER_SECOND != 0 {
+            return write!(f, "{}t", self.0);
+        }
+        match self.as_secs() {
+            0 => f.write_str("0s"),
+            secs if secs % 3600 == 0 => write!(f, "{}h", secs / 3600),
+            secs if secs % 60 == 0 => write!(f, "{}m", secs / 60),
+            secs => write!(f, "{secs}s"),
+        }
+    }
+}
+
+/// The error returned when parsing an invalid [`InhabitedTime`].
+#[derive(thiserror::Error, Debug)]
+#[error("Invalid time `{0}`, expected e.g. `90s`, `5m`, `2h` or `1200t` (ticks)")]
+pub struct ParseInhabitedTimeError(String);
+
+impl FromStr for InhabitedTime {
+    type Err = ParseInhabitedTimeError;
+
+    /// Parses a number followed by a unit: `t` (ticks), `s`, `m` or `h`. Numbers without a unit are seconds.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseInhabitedTimeError(s.to_string());
+        let value = s.trim();
+        let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+            Some(index) => value.split_at(index),
+            None => (value, "s"),
+        };
+        let number = number.parse::<u64>().map_err(|_| error())?;
+        let secs = |factor: u64| number.checked_mul(factor).map(InhabitedTime::from_secs);
+        match unit.trim() {
+            "t" => Some(InhabitedTime::from_ticks(number)),
+            "s" => secs(1),
+            "m" => secs(60),
+            "h" => secs(3600),
+            _ => None,
+        }
+        .ok_or_else(error)
+    }
+}
+
+/// The error returned when parsing an unknown [`Dimension`].
+#[derive(thiserror::Error, Debug)]
+#[error("Unknown dimension `{0}`")]
+pub struct ParseDimensionError(String);
+
+impl FromStr for Dimension {
+    type Err = ParseDimensionError;
+
+    /// Parses both namespaced ids (`minecraft:the_nether`) and short names (`nether`). Any other namespaced id is a
+    /// [`Dimension::Custom`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("minecraft:").unwrap_or(s) {
+            "overworld" => Ok(Dimension::Overworld),
+            "the_nether" | "nether" => Ok(Dimension::Nether),
+            "the_end" | "end" => Ok(Dimension::End),
+            _ if s
+                .split_once(':')
+                .is_some_and(|(namespace, path)| !namespace.is_empty() && !path.is_empty()) =>
+            {
+                Ok(Dimension::Custom(s.to_string()))
+            }
+            _ => Err(ParseDimensionError(s.to_string())),
+        }
+    }
+}
+
+/// The unique id of an execution, a random UUID (version 4). It's part of the [`Report`], the [`PartialReport`] and
+/// the [progress snapshots](snapshot), so records of different tools about the same run can be tied together.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RunId(u128);
+
+impl RunId {
+    /// A new random id.
+    pub fn new() -> Self {
+        use std::collections::hash_map::RandomState;
+        use std::hash::{BuildHasher, Hasher};
+
+        // std seeds the keys of `RandomState` from the operating system once per thread and increments them for every
+        // new one, so the two halves differ and ids of other threads and processes are unrelated. Not suitable for
+        // secrets, but unique enough to tell runs apart.
+        let random = || {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u128(
+                time::SystemTime::now()
+                    .duration_since(time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos(),
+            );
+            hasher.finish() as u128
+        };
+        let bits = (random() << 64) | random();
+        // the version and variant bits of a version 4 UUID
+        let bits = (bits & !(0xf << 76)) | (0x4 << 76);
+        RunId((bits & !(0x3 << 62)) | (0x2 << 62))
+    }
+}
+
+impl Default for RunId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for RunId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = format!("{:032x}", self.0);
+        write!(
+            f,
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+}
+
+/// The error returned when parsing an invalid [`RunId`].
+#[derive(thiserror::Error, Debug)]
+#[error("Invalid run id `{0}`, expected a UUID")]
+pub struct ParseRunIdError(String);
+
+impl FromStr for RunId {
+    type Err = ParseRunIdError;
+
+    /// Parses any UUID in its hyphenated (`67e55044-10b1-426f-9247-bb680e5fe0c8`) or simple form, e.g. to reuse the id
+    /// of a run of another tool.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.trim().replace('-', "");
+        if hex.len() != 32 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ParseRunIdError(s.to_string()));
+        }
+        u128::from_str_radix(&hex, 16)
+            .map(RunId)
+            .map_err(|_| ParseRunIdError(s.to_string()))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for RunId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for RunId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = String::deserialize(deserializer)?;
+        id.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// The config to be passed to lessanvil. The default config is a [dry run](Config::dry_run): set `dry_run: false` to
+/// modify the world.
+#[derive(Clone)]
 pub struct Config {
     /// The folder containing the world.
     pub world_folder: PathBuf,
-    /// The maximum [Inhabited Time](https://minecraft.fandom.com/wiki/Chunk_format) value for a chunk to get deleted.
-    pub max_inhabited_time: usize,
+    /// The maximum [Inhabited Time](https://minecraft.fandom.com/wiki/Chunk_format) for a chunk to get deleted.
+    pub max_inhabited_time: InhabitedTime,
+    /// Judge chunks by the time they were last saved according to the header of their region file instead of by their
+    /// InhabitedTime, applying the operation to chunks saved before this time. Chunks without a timestamp are kept.
+    pub not_touched_since: Option<SystemTime>,
     /// The amount of threads lessanvil should use.
     pub thread_count: usize,
+    /// How region files with more than one hardlink should be treated.
+    pub hardlink_policy: HardlinkPolicy,
+    /// Areas in which chunks are never deleted. See [`protection`] for helpers to build them.
+    pub protected_areas: Vec<ProtectedArea>,
+    /// Block ids (e.g. `minecraft:enchanting_table`) that are only expected to exist when placed by players.
+    /// Chunks that would be deleted are kept if they contain any of them, [`SURFACE_PLAYER_BLOCKS`] only above
+    /// ground. Scanning the blocks of a chunk is slow, so this is only done for chunks below the InhabitedTime
+    /// cutoff. Empty by default, see [`DEFAULT_PLAYER_BLOCKS`] for a sensible list.
+    pub player_blocks: Vec<String>,
+    /// What to do with chunks below the InhabitedTime cutoff. Deleting a chunk also deletes its entity and POI data.
+    pub operation: Operation,
+    /// Send [`ProcessingUpdate::ProcessedRegion`]s in the order the regions were scheduled (see
+    /// [`Config::processing_order`]) instead of in the order they finished processing. Results are buffered until all
+    /// preceding regions are done.
+    pub ordered_updates: bool,
+    /// Only process these dimensions. All dimensions (including [`Dimension::Custom`] ones) are processed if empty.
+    pub dimensions: Vec<Dimension>,
+    /// What to do with blocks on top of the nether's bedrock roof, e.g. roof farms.
+    pub nether_roof: Option<NetherRoof>,
+    /// Convert every chunk that is kept to this compression, e.g. to [`ChunkCompression::Lz4`] for faster loading or to
+    /// [`ChunkCompression::Zlib`] for smaller files. Chunks keep their compression if `None`.
+    pub recompress: Option<ChunkCompression>,
+    /// Find chunks last saved by an old version of the game, which it upgrades (slowly, or breaking them for very old
+    /// versions) when they're loaded. They're counted in [`ProcessedRegion::outdated_chunks`] and optionally pruned.
+    pub outdated_chunks: Option<OutdatedChunks>,
+    /// Find chunks taking up more space in their region file than a limit, e.g. lag machines or chunks built to crash
+    /// the game ("chunk bans"). They're listed in [`ProcessedRegion::oversized_chunks`] and optionally pruned.
+    pub oversized_chunks: Option<OversizedChunks>,
+    /// Find chunks with more entities than allowed, e.g. mob farms or item piles slowing down the server. They're
+    /// listed in [`ProcessedRegion::crowded_chunks`] and optionally pruned.
+    pub entity_limits: Option<EntityLimits>,
+    /// Find chunks with more block entities than a limit, e.g. abandoned hopper walls or storage systems. They're
+    /// listed in [`ProcessedRegion::cluttered_chunks`] and optionally pruned.
+    pub block_entity_limit: Option<BlockEntityLimit>,
+    /// Compressions for nonstandard compression ids used by some modified servers, keyed by the id. Chunks with other
+    /// unknown ids are left untouched. Ids of [`ChunkCompression`]s lessanvil supports itself can't be overridden.
+    pub custom_decompressor: HashMap<u8, CustomCompression>,
+    /// Apply the operation to exactly these chunks instead of judging them by their InhabitedTime, protected areas and
+    /// blocks, e.g. a plan computed against a backup of the world (see [`plan`]). Only the regions containing them are
+    /// processed.
+    pub planned_chunks: Option<HashSet<ChunkPos>>,
+    /// Only determine what would happen without modifying the world. The region files are opened read-only, so worlds
+    /// on read-only media (e.g. backups or snapshots) can be analyzed. The freed space is an estimate of what compacting
+    /// the regions would free and [`Report::freed_space_by_folder`] stays empty. Enabled by default.
+    pub dry_run: bool,
+    /// Where the nether and the end are stored if they aren't in the world folder.
+    pub layout: WorldLayout,
+    /// Additional folders containing region files, e.g. of mods that don't use the `dimensions` layout. Relative paths
+    /// are relative to the world folder. Their chunks belong to a [`Dimension::Custom`] named after the path of the
+    /// folder containing them.
+    pub extra_region_folders: Vec<PathBuf>,
+    /// Glob patterns (e.g. `mymod/*/region`) matching additional region folders, relative to the world folder. See
+    /// [`Config::extra_region_folders`]. Without the `region-globs` feature, any pattern fails with
+    /// [`Error::InvalidGlob`].
+    pub region_globs: Vec<String>,
+    /// The maximum amount of bytes of chunk data all threads together may hold in memory at once. Threads wait for
+    /// others to finish their chunks once it's used up. The size of a chunk is only known after decompressing it, so the
+    /// limit may be exceeded by the difference to its compressed size. Unlimited if `None`.
+    pub max_memory: Option<u64>,
+    /// Abort with [`Error::UnreadableFile`] if a region file or folder can't be read instead of skipping it and listing
+    /// it in [`Report::skipped_files`].
+    pub fail_on_unreadable: bool,
+    /// The order in which the regions are processed. Ignored if [`Config::target_free_space`] is set.
+    pub processing_order: ProcessingOrder,
+    /// Stop once this many bytes have been freed. The regions are scanned beforehand and processed in order of the
+    /// space they would free, largest first, so the target is reached with as few regions touched as possible.
+    /// Regions that wouldn't free any space are skipped.
+    pub target_free_space: Option<u64>,
+    /// The largest fraction (0 to 1) of the chunks that may be deleted, e.g. `0.95`. If a scan of the world beforehand
+    /// finds that more chunks would be deleted (e.g. because the threshold was given in the wrong unit), nothing is
+    /// changed and the execution is cancelled with [`ReportWarning::DeleteRatioExceeded`]. Only applies to
+    /// [`Operation::Delete`].
+    pub max_delete_ratio: Option<f64>,
+    /// Periodically (together with [`ProcessingUpdate::Progress`]) write a [`snapshot::Snapshot`] of the execution to
+    /// this file, to diagnose and resume a run that died. The file is removed once the execution finished.
+    pub progress_snapshot: Option<PathBuf>,
+    /// Copy every region file (and its entity and POI data) into this folder right before it's modified, at its path
+    /// relative to the world folder. Only the regions that change are copied, so that backing up a targeted prune
+    /// takes a fraction of the time and space of a backup of the whole world. A region whose backup fails isn't
+    /// modified. Existing files in the folder are kept, so that resuming a run doesn't replace the original of a
+    /// region with its modified version: use a new folder for every run.
+    pub backup_folder: Option<PathBuf>,
+    /// The id of the execution, e.g. one assigned by the tool starting lessanvil. A new one is generated if `None`, see
+    /// [`Execution::run_id`].
+    pub run_id: Option<RunId>,
+    /// I/O failures and cancellations to inject into the processing, see [`simulation`].
+    #[cfg(feature = "simulation")]
+    pub fault_injector: Option<simulation::FaultInjector>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            world_folder: Default::default(),
+            max_inhabited_time: Default::default(),
+            not_touched_since: Default::default(),
+            thread_count: Default::default(),
+            hardlink_policy: Default::default(),
+            protected_areas: Default::default(),
+            player_blocks: Default::default(),
+            operation: Default::default(),
+            ordered_updates: Default::default(),
+            dimensions: Default::default(),
+            nether_roof: Default::default(),
+            recompress: Default::default(),
+            outdated_chunks: Default::default(),
+            oversized_chunks: Default::default(),
+            entity_limits: Default::default(),
+            block_entity_limit: Default::default(),
+            custom_decompressor: Default::default(),
+            planned_chunks: Default::default(),
+            dry_run: true,
+            layout: Default::default(),
+            extra_region_folders: Default::default(),
+            region_globs: Default::default(),
+            max_memory: Default::default(),
+            fail_on_unreadable: Default::default(),
+            processing_order: Default::default(),
+            target_free_space: Default::default(),
+            max_delete_ratio: Default::default(),
+            progress_snapshot: Default::default(),
+            backup_folder: Default::default(),
+            run_id: Default::default(),
+            #[cfg(feature = "simulation")]
+            fault_injector: None,
+        }
+    }
+}
+
+/// What to do with blocks above the bedrock roof of the nether (Y [`NETHER_ROOF_HEIGHT`] and up). See [`Config::nether_roof`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NetherRoof {
+    /// Clear all sections above the roof in every nether chunk that is kept, unless it's protected.
+    Trim,
+    /// Never delete nether chunks with blocks above the roof.
+    Protect,
+}
+
+/// Which chunks count as outdated and what happens to them, see [`Config::outdated_chunks`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutdatedChunks {
+    /// Chunks with an older [DataVersion](https://minecraft.wiki/w/Data_version) are outdated, e.g. `1519` for chunks
+    /// saved before 1.13. Chunks saved before 1.9 have no DataVersion and are always outdated.
+    pub min_data_version: i32,
+    /// Apply [`Config::operation`] to outdated chunks regardless of their InhabitedTime, unless they're protected.
+    pub prune: bool,
+}
+
+/// Which chunks count as oversized and what happens to them, see [`Config::oversized_chunks`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OversizedChunks {
+    /// Chunks taking up more bytes in the region file (whole sectors of 4 KiB) are oversized. Chunks stored in a separate
+    /// file for being larger than 1 MiB aren't processed at all.
+    pub max_size: u64,
+    /// Apply [`Config::operation`] to oversized chunks regardless of their InhabitedTime, unless they're protected.
+    pub prune: bool,
+}
+
+/// How many entities a chunk may contain and what happens to the chunks exceeding it, see [`Config::entity_limits`].
+/// Only the entities in the `entities` folder are counted, chunks saved before 1.17 keep them in the chunk itself.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EntityLimits {
+    /// The most entities of all types together a chunk may contain.
+    pub max_entities: Option<u32>,
+    /// The most entities of a type a chunk may contain, keyed by the entity id, e.g. `minecraft:item`.
+    pub max_by_type: HashMap<String, u32>,
+    /// Apply [`Config::operation`] to crowded chunks regardless of their InhabitedTime, unless they're protected.
+    pub prune: bool,
+}
+
+impl EntityLimits {
+    fn exceeded_by(&self, counts: &entities::EntityCounts) -> bool {
+        self.max_entities
+            .is_some_and(|max| counts.values().sum::<u32>() > max)
+            || self
+                .max_by_type
+                .iter()
+                .any(|(id, max)| counts.get(id).is_some_and(|count| count > max))
+    }
+}
+
+/// How many block entities a chunk may contain and what happens to the chunks exceeding it, see
+/// [`Config::block_entity_limit`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockEntityLimit {
+    /// Chunks with more block entities (chests, hoppers, signs, ...) are cluttered.
+    pub max_block_entities: u32,
+    /// Apply [`Config::operation`] to cluttered chunks regardless of their InhabitedTime, unless they're protected.
+    pub prune: bool,
+}
+
+/// The lowest Y coordinate above the bedrock roof of the nether.
+pub const NETHER_ROOF_HEIGHT: i32 = 128;
+
+/// The order in which regions are processed, see [`Config::processing_order`].
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProcessingOrder {
+    /// By dimension and region coordinates.
+    #[default]
+    Coordinates,
+    /// Regions that will likely free the most space first, so that an interrupted run has still freed as much as
+    /// possible. The estimate only reads the region headers: regions with the most unused sectors come first, ties
+    /// are broken by the space taken up by chunks.
+    EstimatedSavings,
+}
+
+/// The operation applied to every chunk below the InhabitedTime cutoff that isn't protected.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    /// Delete the chunk.
+    #[default]
+    Delete,
+    /// Keep the chunk but reset its InhabitedTime to 0, restarting the local difficulty clock.
+    ResetInhabitedTime,
+    /// Keep the chunk but replace all of its sections between the two Y coordinates (inclusive) with air, e.g. to clear
+    /// everything below Y=-32. Only sections that lie entirely within the range are cleared.
+    ClearSections {
+        /// The lowest Y coordinate to clear.
+        min_y: i32,
+        /// The highest Y coordinate to clear.
+        max_y: i32,
+    },
+}
+
+/// Blocks that (almost) never generate naturally and therefore indicate player activity. See [`Config::player_blocks`].
+///
+/// Beds and torches are included even though villages generate them too, since they are among the first blocks
+/// players place. Torches only count above ground, see [`SURFACE_PLAYER_BLOCKS`].
+pub const DEFAULT_PLAYER_BLOCKS: &[&str] = &[
+    "minecraft:white_bed",
+    "minecraft:orange_bed",
+    "minecraft:magenta_bed",
+    "minecraft:light_blue_bed",
+    "minecraft:yellow_bed",
+    "minecraft:lime_bed",
+    "minecraft:pink_bed",
+    "minecraft:gray_bed",
+    "minecraft:light_gray_bed",
+    "minecraft:cyan_bed",
+    "minecraft:purple_bed",
+    "minecraft:blue_bed",
+    "minecraft:brown_bed",
+    "minecraft:green_bed",
+    "minecraft:red_bed",
+    "minecraft:black_bed",
+    "minecraft:torch",
+    "minecraft:wall_torch",
+    "minecraft:enchanting_table",
+    "minecraft:anvil",
+    "minecraft:beacon",
+    "minecraft:hopper",
+    "minecraft:comparator",
+    "minecraft:observer",
+    "minecraft:piston",
+    "minecraft:shulker_box",
+    "minecraft:ender_chest",
+    "minecraft:nether_portal",
+];
+
+/// Player blocks that only count at or above [`SEA_LEVEL`] in the overworld, since strongholds and other underground
+/// structures generate them.
+pub const SURFACE_PLAYER_BLOCKS: &[&str] = &["minecraft:torch", "minecraft:wall_torch"];
+
+/// The Y coordinate of the sea level of the overworld, where the ground starts for [`SURFACE_PLAYER_BLOCKS`].
+pub const SEA_LEVEL: i32 = 63;
+
+/// Where the data of the nether and the end is stored, see [`Config::layout`]. By default the vanilla layout
+/// (`DIM-1` and `DIM1` inside of the world folder) is used.
+#[derive(Default, Clone, Debug)]
+pub struct WorldLayout {
+    /// The folder containing the `region` folder of the nether. Relative paths are relative to the world folder, e.g.
+    /// `../world_nether/DIM-1`.
+    pub nether_folder: Option<PathBuf>,
+    /// The folder containing the `region` folder of the end, see [`WorldLayout::nether_folder`].
+    pub end_folder: Option<PathBuf>,
+    /// Look for the nether and the end in the sibling folders Bukkit based servers (Spigot, Paper, ...) store them in,
+    /// `<world>_nether/DIM-1` and `<world>_the_end/DIM1`, if the world folder doesn't contain them. Explicitly set
+    /// folders take precedence.
+    pub detect_bukkit: bool,
+}
+
+impl WorldLayout {
+    /// The folder containing the data of the dimension.
+    pub fn dimension_folder(&self, world_folder: &Path, dimension: &Dimension) -> PathBuf {
+        let (folder, suffix) = match dimension {
+            Dimension::Nether => (&self.nether_folder, "_nether"),
+            Dimension::End => (&self.end_folder, "_the_end"),
+            _ => return world_folder.join(dimension.folder()),
+        };
+        if let Some(folder) = folder {
+            return world_folder.join(folder);
+        }
+        let vanilla = world_folder.join(dimension.folder());
+        if !self.detect_bukkit || vanilla.join("region").try_exists().is_ok_and(|b| b) {
+            return vanilla;
+        }
+        // `world_folder` may be `.` or end with `..`, which have no file name
+        let world_folder = world_folder
+            .canonicalize()
+            .unwrap_or_else(|_| world_folder.to_path_buf());
+        let Some(name) = world_folder.file_name() else {
+            return vanilla;
+        };
+        let mut sibling = name.to_os_string();
+        sibling.push(suffix);
+        let bukkit = world_folder
+            .with_file_name(sibling)
+            .join(dimension.folder());
+        if bukkit.join("region").try_exists().is_ok_and(|b| b) {
+            bukkit
+        } else {
+            vanilla
+        }
+    }
+}
+
+/// What to do with region files that are hardlinked (e.g. by `rsync --link-dest` backups).
+///
+/// Modified regions are written to a new file which replaces the link being processed, so the other links keep the
+/// old contents either way. The old file stays on disk for them though, so rewriting a hardlinked region takes up
+/// space instead of freeing it.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HardlinkPolicy {
+    /// Process hardlinked region files like any other, reporting the rewritten ones in
+    /// [`ProcessedRegion::hardlinked_files`]. Their old size doesn't count as freed.
+    #[default]
+    Process,
+    /// Leave hardlinked region files untouched and report them as [`RegionProcessingError::Hardlinked`], keeping
+    /// their data shared with the other links.
+    Skip,
 }
 
 /// A Report that will be handed out ofter the execution finished.
-#[derive(Serialize)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct Report {
+    /// The id of the execution.
+    pub run_id: RunId,
     /// The total time the execution took.
     pub time_taken: Duration,
-    /// The total disk space freed in bytes.
+    /// The total disk space freed in the processed `region`, `entities` and `poi` folders in bytes. Other files of the
+    /// world aren't taken into account. On Windows the space allocated for the files is measured, which is less than
+    /// their length for sparse or NTFS compressed files.
     pub total_freed_space: u64,
     /// The total amount of region(-file-)s processed.
     pub total_regions: u64,
@@ -39,6 +810,97 @@ pub struct Report {
     pub total_chunks: u64,
     /// The total amount of deleted chunks.
     pub total_deleted_chunks: u64,
+    /// The total amount of chunks whose InhabitedTime was reset. See [`Operation::ResetInhabitedTime`].
+    pub total_reset_chunks: u64,
+    /// The total amount of chunks with cleared sections. See [`Operation::ClearSections`] and [`NetherRoof::Trim`].
+    pub total_trimmed_chunks: u64,
+    /// The total amount of chunks converted to another compression. See [`Config::recompress`].
+    pub total_recompressed_chunks: u64,
+    /// The total amount of chunks last saved by an old version of the game. See [`Config::outdated_chunks`].
+    pub total_outdated_chunks: u64,
+    /// The total amount of chunks exceeding [`OversizedChunks::max_size`]. See [`Config::oversized_chunks`].
+    pub total_oversized_chunks: u64,
+    /// The total amount of chunks exceeding the [`EntityLimits`]. See [`Config::entity_limits`].
+    pub total_crowded_chunks: u64,
+    /// The total amount of chunks exceeding the [`BlockEntityLimit`]. See [`Config::block_entity_limit`].
+    pub total_cluttered_chunks: u64,
+    /// The disk space freed in each processed `region`, `entities` and `poi` folder in bytes.
+    pub freed_space_by_folder: BTreeMap<PathBuf, u64>,
+    /// Things that may make the numbers of the report inaccurate.
+    pub warnings: Vec<ReportWarning>,
+    /// Files and folders that couldn't be read and were skipped. See [`Config::fail_on_unreadable`].
+    pub skipped_files: Vec<SkippedFile>,
+    /// The region files that failed to process, e.g. to retry them with [`execute_subset`].
+    pub failed_regions: Vec<PathBuf>,
+}
+
+/// A file or folder that couldn't be read and was skipped.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct SkippedFile {
+    /// The path of the file or folder.
+    pub path: PathBuf,
+    /// Why it couldn't be read.
+    pub error: String,
+}
+
+/// A problem that didn't stop the execution but may make its [`Report`] inaccurate.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum ReportWarning {
+    /// The folder is larger than before the processing, so something else (e.g. a running server) wrote to it in the
+    /// meantime. It is reported as having freed no space.
+    FolderGrew {
+        /// The folder that grew.
+        folder: PathBuf,
+        /// The amount of bytes it grew by.
+        bytes: u64,
+    },
+    /// More chunks would have been deleted than allowed by [`Config::max_delete_ratio`], so nothing was changed.
+    DeleteRatioExceeded {
+        /// The fraction of the chunks that would have been deleted.
+        ratio: f64,
+        /// See [`Config::max_delete_ratio`].
+        max_ratio: f64,
+    },
+    /// Chunks couldn't be read and were kept as they are, see [`ProcessedRegion::unreadable_chunks`].
+    UnreadableChunks {
+        /// The amount of chunks.
+        chunks: u64,
+    },
+    /// Hardlinked files were rewritten, see [`ProcessedRegion::hardlinked_files`].
+    HardlinkedFiles {
+        /// The amount of files.
+        files: u64,
+        /// The space still taken up by their old contents in bytes.
+        bytes: u64,
+    },
+}
+
+impl fmt::Display for ReportWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReportWarning::FolderGrew { folder, bytes } => write!(
+                f,
+                "{} grew by {bytes} bytes during processing, the world was changed by something else",
+                folder.display()
+            ),
+            ReportWarning::DeleteRatioExceeded { ratio, max_ratio } => write!(
+                f,
+                "{:.1}% of the chunks would have been deleted, more than the allowed {:.1}%, so nothing was changed",
+                ratio * 100.0,
+                max_ratio * 100.0
+            ),
+            ReportWarning::UnreadableChunks { chunks } => write!(
+                f,
+                "{chunks} chunks couldn't be read and were kept as they are"
+            ),
+            ReportWarning::HardlinkedFiles { files, bytes } => write!(
+                f,
+                "{files} rewritten files were hardlinked, their old contents still take up {bytes} bytes for the other links"
+            ),
+        }
+    }
 }
 
 /// The error type for errors that occured before the actual processing started.
@@ -53,6 +915,133 @@ pub enum Error {
     /// An error caused when invoking the [`ThreadPoolBuilder`]
     #[error("Failed to build Rayon threadpool")]
     RayonError(#[from] ThreadPoolBuildError),
+    /// A player data file could not be parsed.
+    #[error("Invalid player data in {}", .path.display())]
+    InvalidPlayerData {
+        /// The path of the player data file.
+        path: PathBuf,
+        /// The underlying NBT error.
+        source: fastnbt::error::Error,
+    },
+    /// A file describing protected areas or positions could not be parsed.
+    #[error("{}:{line}: {message}", .path.display())]
+    InvalidProtectionFile {
+        /// The path of the file.
+        path: PathBuf,
+        /// The line the error occured in.
+        line: usize,
+        /// What went wrong.
+        message: String,
+    },
+    /// The destination folder already exists.
+    #[error("{} already exists", .0.display())]
+    DestinationExists(PathBuf),
+    /// The `level.dat` of the world could not be parsed.
+    #[error("Invalid level.dat")]
+    InvalidLevelDat {
+        /// The underlying NBT error.
+        source: fastnbt::error::Error,
+    },
+    /// A gzip compressed NBT file of the world (e.g. `data/raids.dat`) could not be parsed or written.
+    #[error("Invalid data file {}", .path.display())]
+    InvalidDataFile {
+        /// The path of the file.
+        path: PathBuf,
+        /// The underlying NBT error.
+        source: fastnbt::error::Error,
+    },
+    /// A file or folder of the world could not be read. See [`Config::fail_on_unreadable`].
+    #[error("Could not read {}", .path.display())]
+    UnreadableFile {
+        /// The path of the file or folder.
+        path: PathBuf,
+        /// The underlying IO error.
+        source: io::Error,
+    },
+    /// A glob pattern is invalid. See [`Config::region_globs`].
+    #[error("Invalid glob pattern `{pattern}`: {message}")]
+    InvalidGlob {
+        /// The pattern.
+        pattern: String,
+        /// What went wrong.
+        message: String,
+    },
+    /// A manifest file could not be parsed.
+    #[cfg(feature = "diff")]
+    #[error("{}:{line}: {message}", .path.display())]
+    InvalidManifest {
+        /// The path of the manifest.
+        path: PathBuf,
+        /// The line the error occured in.
+        line: usize,
+        /// What went wrong.
+        message: String,
+    },
+    /// A plan was made for a different world than the one it's applied to. See [`plan::check_plan`].
+    #[cfg(feature = "plan")]
+    #[error("The plan was made for a different world ({expected}, this world is {actual})")]
+    PlanWorldMismatch {
+        /// The fingerprint of the world the plan was made for.
+        expected: plan::WorldFingerprint,
+        /// The fingerprint of the world the plan is applied to.
+        actual: plan::WorldFingerprint,
+    },
+    /// A plan file could not be parsed. See [`plan`].
+    #[cfg(feature = "plan")]
+    #[error("{}:{line}: {message}", .path.display())]
+    InvalidPlan {
+        /// The path of the plan.
+        path: PathBuf,
+        /// The line the error occured in.
+        line: usize,
+        /// What went wrong.
+        message: String,
+    },
+    /// A chunk cache could not be parsed. See [`cache`].
+    #[cfg(feature = "plan")]
+    #[error("{}: {message}", .path.display())]
+    InvalidCache {
+        /// The path of the cache.
+        path: PathBuf,
+        /// What went wrong.
+        message: String,
+    },
+    /// Another process is working on the world. See [`lock`].
+    #[error("The world is locked by another run of lessanvil ({})", .path.display())]
+    WorldLocked {
+        /// The path of the lock file.
+        path: PathBuf,
+        /// The id of the process holding the lock, if known.
+        pid: Option<u32>,
+    },
+    /// A progress snapshot could not be parsed. See [`snapshot`].
+    #[error("{}:{line}: {message}", .path.display())]
+    InvalidSnapshot {
+        /// The path of the snapshot.
+        path: PathBuf,
+        /// The line the error occured in.
+        line: usize,
+        /// What went wrong.
+        message: String,
+    },
+    /// A region file passed to [`execute_subset`] isn't inside one of the region folders of the world.
+    #[error("{} isn't a region file of the world", .path.display())]
+    UnknownRegionFile {
+        /// The path of the region file.
+        path: PathBuf,
+    },
+    /// The execution was stopped before all regions were processed, e.g. by [`Config::max_delete_ratio`]. Only
+    /// returned by [`execute_blocking`], an [`Execution`] ends with [`ProcessingUpdate::Cancelled`] instead.
+    #[error("The execution was cancelled")]
+    Cancelled(Box<PartialReport>),
+    /// A region could not be processed.
+    #[error("Failed to process {}", .path.display())]
+    RegionError {
+        /// The path of the region file.
+        path: PathBuf,
+        /// The underlying error.
+        source: RegionProcessingError,
+    },
 }
 
 /// An update during lessanvil's execution.
@@ -61,107 +1050,1035 @@ pub enum ProcessingUpdate {
     Starting {
         /// Total amount of files to be processed.
         total_files: u64,
+        /// The folders the region files were collected from, including [`Config::extra_region_folders`] and the
+        /// matches of [`Config::region_globs`].
+        region_folders: Vec<PathBuf>,
     },
     /// Sent after a region has been processed.
     /// Contains the [`Result`] of the processed region.
     ProcessedRegion(Result<ProcessedRegion, RegionProcessingError>),
+    /// Sent periodically (at most every [`PROGRESS_INTERVAL`]) while regions are being processed.
+    Progress(Progress),
     /// Only sent once after the entire execution finished. This is the last message sent through the Channel.
     Finished(Report),
+    /// Sent instead of [`ProcessingUpdate::Finished`] if the execution was cancelled (see [`Execution::cancel`]) or the
+    /// [`Receiver`](`mpsc::Receiver`) was dropped. Sending is best-effort and this is the last message sent through the Channel.
+    Cancelled(PartialReport),
+}
+
+/// A Report of an execution that was cancelled before all regions were processed.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct PartialReport {
+    /// The id of the execution.
+    pub run_id: RunId,
+    /// The time until the execution stopped.
+    pub time_taken: Duration,
+    /// The total disk space freed in the processed `region`, `entities` and `poi` folders in bytes.
+    pub total_freed_space: u64,
+    /// The amount of region(-file-)s processed before the execution stopped.
+    pub processed_regions: u64,
+    /// The total amount of region(-file-)s that would have been processed.
+    pub total_regions: u64,
+    /// The amount of chunks processed.
+    pub total_chunks: u64,
+    /// The amount of deleted chunks.
+    pub total_deleted_chunks: u64,
+    /// The amount of chunks whose InhabitedTime was reset.
+    pub total_reset_chunks: u64,
+    /// The amount of chunks with cleared sections.
+    pub total_trimmed_chunks: u64,
+    /// The amount of chunks converted to another compression.
+    pub total_recompressed_chunks: u64,
+    /// The amount of chunks last saved by an old version of the game.
+    pub total_outdated_chunks: u64,
+    /// The amount of chunks exceeding [`OversizedChunks::max_size`].
+    pub total_oversized_chunks: u64,
+    /// The amount of chunks exceeding the [`EntityLimits`].
+    pub total_crowded_chunks: u64,
+    /// The amount of chunks exceeding the [`BlockEntityLimit`].
+    pub total_cluttered_chunks: u64,
+    /// The disk space freed in each processed `region`, `entities` and `poi` folder in bytes.
+    pub freed_space_by_folder: BTreeMap<PathBuf, u64>,
+    /// Things that may make the numbers of the report inaccurate.
+    pub warnings: Vec<ReportWarning>,
+    /// Files and folders that couldn't be read and were skipped.
+    pub skipped_files: Vec<SkippedFile>,
+    /// The region files that failed to process before the execution stopped.
+    pub failed_regions: Vec<PathBuf>,
+    /// The region files that were modified before the execution stopped.
+    pub modified_regions: Vec<PathBuf>,
+}
+
+/// The minimum time between two [`ProcessingUpdate::Progress`] updates.
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Running totals of an execution.
+///
+/// Region files vary wildly in size, so the progress and ETA are based on the amount of bytes processed rather than
+/// the amount of regions.
+#[derive(Clone, Debug)]
+pub struct Progress {
+    /// The amount of regions processed so far.
+    pub processed_regions: u64,
+    /// The total amount of regions to process.
+    pub total_regions: u64,
+    /// The size of all region files processed so far in bytes.
+    pub processed_bytes: u64,
+    /// The size of all region files to process in bytes.
+    pub total_bytes: u64,
+    /// The amount of chunks processed so far.
+    pub processed_chunks: u64,
+    /// The amount of chunks deleted so far.
+    pub deleted_chunks: u64,
+    /// The time since the processing started.
+    pub elapsed: Duration,
+    /// The average throughput so far in bytes per second.
+    pub bytes_per_second: f64,
+    /// The estimated time until the processing finishes. `None` until there is enough data for an estimate.
+    pub eta: Option<Duration>,
+}
+
+impl Progress {
+    /// The fraction of bytes processed, between 0 and 1.
+    pub fn fraction(&self) -> f64 {
+        if self.total_bytes == 0 {
+            1.0
+        } else {
+            self.processed_bytes as f64 / self.total_bytes as f64
+        }
+    }
+}
+
+/// A running execution, returned by [`execute`].
+///
+/// Dropping it (or the [`Receiver`](`mpsc::Receiver`) of its updates) will stop the processing as soon as possible.
+pub struct Execution {
+    run_id: RunId,
+    updates: mpsc::Receiver<ProcessingUpdate>,
+    cancel_handle: CancelHandle,
+    pause_handle: PauseHandle,
+    thread: thread::JoinHandle<Result<Report, Box<PartialReport>>>,
+    thread_count: usize,
+}
+
+impl Execution {
+    /// The [`Receiver`](`mpsc::Receiver`) through which [`ProcessingUpdate`]s will be sent.
+    pub fn updates(&self) -> &mpsc::Receiver<ProcessingUpdate> {
+        &self.updates
+    }
+
+    /// Stops the processing after the regions currently being processed.
+    /// The execution will end with [`ProcessingUpdate::Cancelled`].
+    pub fn cancel(&self) {
+        self.cancel_handle.cancel();
+    }
+
+    /// Returns a handle that can cancel the execution from another thread, e.g. a signal handler.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel_handle.clone()
+    }
+
+    /// Returns a handle that pauses and resumes the execution from another thread, e.g. to make way for a server
+    /// running on the same machine.
+    pub fn pause_handle(&self) -> PauseHandle {
+        self.pause_handle.clone()
+    }
+
+    /// Waits for the execution to end, returning the [`Report`] or, if it was cancelled, the [`PartialReport`].
+    ///
+    /// Updates not yet received are discarded.
+    pub fn join(self) -> Result<Report, Box<PartialReport>> {
+        let Execution {
+            updates, thread, ..
+        } = self;
+        let result = thread
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+        drop(updates);
+        result
+    }
+
+    /// The amount of threads used for processing.
+    pub fn thread_count(&self) -> usize {
+        self.thread_count
+    }
+
+    /// The id of the execution, [`Config::run_id`] or a generated one.
+    pub fn run_id(&self) -> RunId {
+        self.run_id
+    }
+}
+
+/// Cancels an [`Execution`]. See [`Execution::cancel_handle`].
+#[derive(Clone, Default)]
+pub struct CancelHandle(Arc<AtomicBool>);
+
+impl CancelHandle {
+    /// See [`Execution::cancel`].
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the execution was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Pauses an [`Execution`]. See [`Execution::pause_handle`].
+#[derive(Clone, Default)]
+pub struct PauseHandle(Arc<AtomicBool>);
+
+/// How often paused workers check whether they may continue.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+impl PauseHandle {
+    /// Stops the processing after the regions currently being processed until [`PauseHandle::resume`] is called.
+    /// Cancelling a paused execution ends it right away.
+    pub fn pause(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Continues a paused execution with the next region. Does nothing if it isn't paused.
+    pub fn resume(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+
+    /// Whether the execution is paused.
+    pub fn is_paused(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Blocks while the execution is paused and not cancelled.
+    fn wait(&self, cancelled: &CancelHandle) {
+        while self.is_paused() && !cancelled.is_cancelled() {
+            thread::sleep(PAUSE_POLL_INTERVAL);
+        }
+    }
 }
 
 /// The entrypoint to this crate.
 ///
-/// The [`Result`] contains a [`Receiver`](`mpsc::Receiver`) through which [`ProcessingUpdate`]s will be sent. Dropping this [`Receiver`](`mpsc::Receiver`) will stop the processing as soon as possible.
-pub fn execute(config: Config) -> Result<mpsc::Receiver<ProcessingUpdate>, Error> {
-    if !config.world_folder.try_exists().map_or(false, |r| r) {
+/// Starts processing the world in the background and returns an [`Execution`] through which the processing can be
+/// followed and controlled.
+pub fn execute(config: Config) -> Result<Execution, Error> {
+    prepare_world(config).map(process_files)
+}
+
+/// Like [`execute`], but processes the world on the calling thread and only returns the final [`Report`], for callers
+/// (e.g. scheduled jobs) that don't follow the progress. No [`ProcessingUpdate`]s are created, which saves a little
+/// time on worlds with many small regions.
+///
+/// An execution stopped by [`Config::max_delete_ratio`] fails with [`Error::Cancelled`].
+pub fn execute_blocking(config: Config) -> Result<Report, Error> {
+    prepare_world(config)?.run(None).map_err(Error::Cancelled)
+}
+
+/// Collects the region files of the world for [`execute`] and [`execute_blocking`].
+fn prepare_world(config: Config) -> Result<Processing, Error> {
+    let mut skipped_files = vec![];
+    let selected = selected_region_files(&config, &mut skipped_files)?;
+    Processing::prepare(
+        config,
+        selected.files,
+        selected.region_folders,
+        skipped_files,
+    )
+}
+
+/// The region files found by [`selected_region_files`].
+struct SelectedRegions {
+    /// The region files to process, with their dimension.
+    files: Vec<(Dimension, PathBuf)>,
+    /// The folders the files were collected from, see [`config_region_folders`].
+    region_folders: Vec<PathBuf>,
+}
+
+/// The region files to process according to the config, together with all region folders of the world.
+fn selected_region_files(
+    config: &Config,
+    skipped_files: &mut Vec<SkippedFile>,
+) -> Result<SelectedRegions, Error> {
+    if !config.world_folder.try_exists().is_ok_and(|r| r) {
         return Err(Error::WorldFolderNotFound);
     }
 
-    ThreadPoolBuilder::new()
-        .num_threads(config.thread_count)
-        .build_global()?;
+    let folders = config_region_folders(config, skipped_files)?;
+    let region_folders = folders
+        .iter()
+        .map(|(_, folder)| folder.clone())
+        .collect::<Vec<_>>();
 
-    let (tx, rx) = mpsc::channel();
+    let mut files = find_region_files(&folders, config.fail_on_unreadable, skipped_files)?;
+    if !config.dimensions.is_empty() {
+        files.retain(|(dimension, _)| config.dimensions.contains(dimension));
+    }
+    if let Some(planned) = &config.planned_chunks {
+        let regions = planned
+            .iter()
+            .map(|chunk| (&chunk.dimension, chunk.x >> 5, chunk.z >> 5))
+            .collect::<HashSet<_>>();
+        files.retain(|(dimension, path)| {
+            let (x, z) = region_coordinates(path);
+            regions.contains(&(dimension, x, z))
+        });
+    }
+    Ok(SelectedRegions {
+        files,
+        region_folders,
+    })
+}
+
+/// The amount of region files and chunks of a dimension, see [`count_chunks`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChunkCount {
+    /// The amount of region files.
+    pub regions: u64,
+    /// The amount of chunks stored in them.
+    pub chunks: u64,
+}
 
-    let files = collect_region_files(Path::new(&config.world_folder))?;
+/// Counts the region files and chunks [`execute`] would process with the config, by dimension. Only the headers of the
+/// region files are read, so this is quick even on large worlds, but says nothing about which of the chunks would be
+/// deleted. Region files whose header can't be read are left out.
+pub fn count_chunks(config: &Config) -> Result<BTreeMap<Dimension, ChunkCount>, Error> {
+    let files = selected_region_files(config, &mut vec![])?.files;
+    let mut counts = BTreeMap::<Dimension, ChunkCount>::new();
+    for (dimension, path) in files {
+        let Ok(header) = File::open(&path).and_then(region::Header::read) else {
+            continue;
+        };
+        let (region_x, region_z) = region_coordinates(&path);
+        let chunks = (0..32)
+            .flat_map(|z| (0..32).map(move |x| (x, z)))
+            .filter(|&(x, z)| header.location(x, z).is_present())
+            .filter(|&(x, z)| {
+                config.planned_chunks.as_ref().map_or(true, |planned| {
+                    planned.contains(&ChunkPos {
+                        dimension: dimension.clone(),
+                        x: region_x * 32 + x as i32,
+                        z: region_z * 32 + z as i32,
+                    })
+                })
+            })
+            .count();
+        let count = counts.entry(dimension).or_default();
+        count.regions += 1;
+        count.chunks += chunks as u64;
+    }
+    Ok(counts)
+}
+
+/// Rewrites the region files of the world (chunks, entities and POIs) that contain [`analysis::dead_space`], storing
+/// their chunks right after each other. The chunks are copied as they are. Returns the dead space that was released.
+pub fn compact(world_folder: &Path) -> Result<analysis::DeadSpace, Error> {
+    if !world_folder.try_exists().is_ok_and(|b| b) {
+        return Err(Error::WorldFolderNotFound);
+    }
+    let _lock = lock::WorldLock::acquire(world_folder)?;
+    let dead_space = analysis::dead_space(world_folder)?;
+    for region in &dead_space.regions {
+        region::RegionWriter::open(&region.path)
+            .and_then(region::RegionWriter::compact)
+            .map_err(|source| Error::UnreadableFile {
+                path: region.path.clone(),
+                source,
+            })?;
+    }
+    Ok(dead_space)
+}
+
+/// Like [`execute`], but only processes the given region files instead of searching the world for them, e.g. to retry
+/// the [`Report::failed_regions`] of a previous execution. The files must be inside the region folders of the world.
+pub fn execute_subset(config: Config, regions: Vec<PathBuf>) -> Result<Execution, Error> {
+    if !config.world_folder.try_exists().is_ok_and(|r| r) {
+        return Err(Error::WorldFolderNotFound);
+    }
+
+    let mut skipped_files = vec![];
+    let folders = config_region_folders(&config, &mut skipped_files)?;
+    let region_folders = folders
+        .iter()
+        .map(|(_, folder)| folder.clone())
+        .collect::<Vec<_>>();
+
+    let files = regions
+        .into_iter()
+        .map(|path| {
+            let dimension = folders
+                .iter()
+                .find(|(_, folder)| path.parent() == Some(folder.as_path()))
+                .map(|(dimension, _)| dimension.clone())
+                .ok_or_else(|| Error::UnknownRegionFile { path: path.clone() })?;
+            Ok((dimension, path))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Processing::prepare(config, files, region_folders, skipped_files).map(process_files)
+}
 
-    let size_before = dir_size(config.world_folder.as_path())?;
-    let start_time = time::Instant::now();
-    let total_regions = files.len() as u64;
-    let total_chunks = AtomicU64::new(0);
-    let total_deleted_chunks = AtomicU64::new(0);
+/// Everything set up to process the region files, see [`Processing::prepare`].
+struct Processing {
+    config: Config,
+    files: Vec<(Dimension, PathBuf)>,
+    region_folders: Vec<PathBuf>,
+    skipped_files: Vec<SkippedFile>,
+    pool: ThreadPool,
+    lock: Option<lock::WorldLock>,
+    cancel_handle: CancelHandle,
+    pause_handle: PauseHandle,
+    run_id: RunId,
+    sizes_before: Vec<(u64, PathBuf)>,
+    start_time: time::Instant,
+}
+
+impl Processing {
+    /// Builds the thread pool, locks the world and measures the region folders before anything is processed.
+    fn prepare(
+        config: Config,
+        mut files: Vec<(Dimension, PathBuf)>,
+        region_folders: Vec<PathBuf>,
+        skipped_files: Vec<SkippedFile>,
+    ) -> Result<Self, Error> {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(config.thread_count)
+            .build()?;
+        // a dry run doesn't modify the world and may run on read-only media
+        let lock = if config.dry_run {
+            None
+        } else {
+            Some(lock::WorldLock::acquire(&config.world_folder)?)
+        };
+        #[cfg(feature = "simulation")]
+        let cancel_handle = config
+            .fault_injector
+            .as_ref()
+            .map_or_else(CancelHandle::default, |injector| injector.cancel_handle());
+        #[cfg(not(feature = "simulation"))]
+        let cancel_handle = CancelHandle::default();
+        let run_id = config.run_id.unwrap_or_default();
+
+        files.sort_by_cached_key(|(dimension, path)| (dimension.clone(), region_coordinates(path)));
+
+        // only the folders lessanvil modifies, everything else in the world folder may change at any time
+        let folders = files
+            .iter()
+            .filter_map(|(_, path)| path.parent())
+            .flat_map(|folder| REGION_KINDS.map(|kind| folder.with_file_name(kind)))
+            .filter(|folder| folder.try_exists().is_ok_and(|b| b))
+            .collect::<BTreeSet<_>>();
+        let sizes_before = folders
+            .into_iter()
+            .map(|folder| Ok((dir_size(&folder)?, folder)))
+            .collect::<io::Result<Vec<_>>>()?;
+        let start_time = time::Instant::now();
+        Ok(Self {
+            config,
+            files,
+            region_folders,
+            skipped_files,
+            pool,
+            lock,
+            cancel_handle,
+            pause_handle: PauseHandle::default(),
+            run_id,
+            sizes_before,
+            start_time,
+        })
+    }
+
+    /// Processes the region files, sending the updates through `tx` if the execution is followed.
+    fn run(self, tx: Option<mpsc::Sender<ProcessingUpdate>>) -> Result<Report, Box<PartialReport>> {
+        let Processing {
+            config,
+            mut files,
+            region_folders,
+            skipped_files,
+            pool,
+            lock,
+            cancel_handle: cancelled,
+            pause_handle: paused,
+            run_id,
+            sizes_before,
+            start_time,
+        } = self;
+
+        let budget = MemoryBudget::new(config.max_memory);
+        let max_delete_ratio = config
+            .max_delete_ratio
+            .filter(|_| config.operation == Operation::Delete && !config.dry_run);
+        // both need every chunk judged, which is done once for both
+        if config.target_free_space.is_some() || max_delete_ratio.is_some() {
+            let estimates = pool.install(|| estimate_regions(files, &config, &budget, &cancelled));
+            let ratio = delete_ratio(&estimates);
+            let total_regions = estimates.len() as u64;
+            files = if config.target_free_space.is_some() {
+                sort_by_reclaimable_space(estimates)
+            } else {
+                estimates.into_iter().map(|(_, file)| file).collect()
+            };
+
+            if let Some(max_ratio) = max_delete_ratio.filter(|max_ratio| ratio > *max_ratio) {
+                let report = PartialReport {
+                    run_id,
+                    time_taken: time::Instant::now() - start_time,
+                    total_freed_space: 0,
+                    processed_regions: 0,
+                    total_regions,
+                    total_chunks: 0,
+                    total_deleted_chunks: 0,
+                    total_reset_chunks: 0,
+                    total_trimmed_chunks: 0,
+                    total_recompressed_chunks: 0,
+                    total_outdated_chunks: 0,
+                    total_oversized_chunks: 0,
+                    total_crowded_chunks: 0,
+                    total_cluttered_chunks: 0,
+                    freed_space_by_folder: BTreeMap::new(),
+                    warnings: vec![ReportWarning::DeleteRatioExceeded { ratio, max_ratio }],
+                    skipped_files,
+                    failed_regions: vec![],
+                    modified_regions: vec![],
+                };
+                // released before the last update, after which the receiver may exit the process
+                drop(lock);
+                let _ = send_update(&tx, || ProcessingUpdate::Cancelled(report.clone()));
+                return Err(Box::new(report));
+            }
+        }
+        if config.target_free_space.is_none()
+            && config.processing_order == ProcessingOrder::EstimatedSavings
+        {
+            files = pool.install(|| sort_by_estimated_savings(files));
+        }
+
+        let total_regions = files.len() as u64;
+        let recorder = snapshot::Recorder::new(
+            &config,
+            run_id,
+            files.iter().map(|(_, path)| path.clone()).collect(),
+        );
+        let total_chunks = AtomicU64::new(0);
+        let total_deleted_chunks = AtomicU64::new(0);
+        let total_reset_chunks = AtomicU64::new(0);
+        let total_trimmed_chunks = AtomicU64::new(0);
+        let total_recompressed_chunks = AtomicU64::new(0);
+        let total_outdated_chunks = AtomicU64::new(0);
+        let total_oversized_chunks = AtomicU64::new(0);
+        let total_crowded_chunks = AtomicU64::new(0);
+        let total_cluttered_chunks = AtomicU64::new(0);
+        let total_unreadable_chunks = AtomicU64::new(0);
+        // the old size of the rewritten hardlinked files by folder, which isn't freed
+        let hardlinked_sizes = Mutex::new(BTreeMap::<PathBuf, u64>::new());
+        let total_hardlinked_files = AtomicU64::new(0);
+        let total_bytes = files
+            .iter()
+            .map(|(_, path)| fs::metadata(path).map_or(0, |metadata| metadata.len()))
+            .sum::<u64>();
+        let processed_regions = AtomicU64::new(0);
+        let processed_bytes = AtomicU64::new(0);
+        let freed_bytes = AtomicU64::new(0);
+        let target_reached = AtomicBool::new(false);
+        let last_progress = Mutex::new(start_time);
+        let modified_regions = Mutex::new(vec![]);
+        let failed_regions = Mutex::new(vec![]);
+        // the index of the next region to send and the results waiting for it, see `Config::ordered_updates`
+        let pending_updates = Mutex::new((
+            0,
+            BTreeMap::<usize, Result<ProcessedRegion, RegionProcessingError>>::new(),
+        ));
 
-    thread::spawn(move || {
-        let _ = tx.send(ProcessingUpdate::Starting {
-            total_files: files.len() as u64,
+        let _ = send_update(&tx, || ProcessingUpdate::Starting {
+            total_files: total_regions,
+            region_folders,
         });
 
-        let result = files
-            .into_par_iter()
-            .try_for_each_with(tx.clone(), |t, path| {
-                let processed_region =
-                    process_region_file(path.as_path(), config.max_inhabited_time * 20);
-
-                if let Ok(ProcessedRegion {
-                    x: _,
-                    y: _,
-                    total_chunks: chunks,
-                    deleted_chunks,
-                }) = processed_region
-                {
-                    total_chunks.fetch_add(chunks as u64, std::sync::atomic::Ordering::Relaxed);
-                    total_deleted_chunks
-                        .fetch_add(deleted_chunks as u64, std::sync::atomic::Ordering::Relaxed);
-                }
+        // bridging the sequential iterator makes the workers pick up the regions in order
+        let result = pool.install(|| {
+            files
+                .into_iter()
+                .enumerate()
+                .par_bridge()
+                .try_for_each_with(tx.clone(), |t, (index, (dimension, path))| {
+                    paused.wait(&cancelled);
+                    if cancelled.is_cancelled() {
+                        return Err(());
+                    }
+                    if config
+                        .target_free_space
+                        .is_some_and(|target| freed_bytes.load(Ordering::Relaxed) >= target)
+                    {
+                        target_reached.store(true, Ordering::Relaxed);
+                        return Err(());
+                    }
 
-                if t.send(ProcessingUpdate::ProcessedRegion(processed_region))
-                    .is_err()
-                {
-                    Err(())
-                } else {
-                    Ok(())
-                }
+                    let size = fs::metadata(&path).map_or(0, |metadata| metadata.len());
+                    recorder.start(&path);
+                    let processed_region =
+                        process_region_file(path.as_path(), dimension, &config, &budget);
+                    recorder.finish(&path, processed_region.as_ref().err());
+
+                    if let Ok(ProcessedRegion {
+                        total_chunks: chunks,
+                        deleted_chunks,
+                        reset_chunks,
+                        trimmed_chunks,
+                        recompressed_chunks,
+                        outdated_chunks,
+                        ref oversized_chunks,
+                        ref crowded_chunks,
+                        ref cluttered_chunks,
+                        ref unreadable_chunks,
+                        ref hardlinked_files,
+                        freed_space,
+                        ..
+                    }) = processed_region
+                    {
+                        if !hardlinked_files.is_empty() {
+                            total_hardlinked_files
+                                .fetch_add(hardlinked_files.len() as u64, Ordering::Relaxed);
+                            let mut sizes = hardlinked_sizes.lock().unwrap();
+                            for file in hardlinked_files {
+                                let folder = file.path.parent().unwrap_or(Path::new(""));
+                                *sizes.entry(folder.to_path_buf()).or_default() += file.size;
+                            }
+                        }
+                        freed_bytes.fetch_add(freed_space, Ordering::Relaxed);
+                        total_chunks.fetch_add(chunks as u64, Ordering::Relaxed);
+                        total_deleted_chunks.fetch_add(deleted_chunks as u64, Ordering::Relaxed);
+                        total_reset_chunks.fetch_add(reset_chunks as u64, Ordering::Relaxed);
+                        total_trimmed_chunks.fetch_add(trimmed_chunks as u64, Ordering::Relaxed);
+                        total_recompressed_chunks
+                            .fetch_add(recompressed_chunks as u64, Ordering::Relaxed);
+                        total_outdated_chunks.fetch_add(outdated_chunks as u64, Ordering::Relaxed);
+                        total_oversized_chunks
+                            .fetch_add(oversized_chunks.len() as u64, Ordering::Relaxed);
+                        total_crowded_chunks
+                            .fetch_add(crowded_chunks.len() as u64, Ordering::Relaxed);
+                        total_cluttered_chunks
+                            .fetch_add(cluttered_chunks.len() as u64, Ordering::Relaxed);
+                        total_unreadable_chunks
+                            .fetch_add(unreadable_chunks.len() as u64, Ordering::Relaxed);
+                        if !config.dry_run
+                            && (deleted_chunks > 0
+                                || reset_chunks > 0
+                                || trimmed_chunks > 0
+                                || recompressed_chunks > 0)
+                        {
+                            modified_regions.lock().unwrap().push(path.clone());
+                        }
+                    } else {
+                        failed_regions.lock().unwrap().push(path.clone());
+                    }
+
+                    let regions = processed_regions.fetch_add(1, Ordering::Relaxed) + 1;
+                    let bytes = processed_bytes.fetch_add(size, Ordering::Relaxed) + size;
+
+                    // without a receiver there is nothing to order
+                    if config.ordered_updates && t.is_some() {
+                        let mut pending_updates = pending_updates.lock().unwrap();
+                        let (next, pending) = &mut *pending_updates;
+                        pending.insert(index, processed_region);
+                        while let Some(processed_region) = pending.remove(next) {
+                            *next += 1;
+                            send_update(t, || ProcessingUpdate::ProcessedRegion(processed_region))?;
+                        }
+                    } else {
+                        send_update(t, || ProcessingUpdate::ProcessedRegion(processed_region))?;
+                    }
+                    if t.is_none() && config.progress_snapshot.is_none() {
+                        return Ok(());
+                    }
+
+                    // only one worker needs to send the update, the others can carry on
+                    let Ok(mut last_progress) = last_progress.try_lock() else {
+                        return Ok(());
+                    };
+                    let now = time::Instant::now();
+                    if now - *last_progress < PROGRESS_INTERVAL {
+                        return Ok(());
+                    }
+                    *last_progress = now;
+
+                    let elapsed = now - start_time;
+                    let bytes_per_second = bytes as f64 / elapsed.as_secs_f64();
+                    let eta = (bytes_per_second > 0.0).then(|| {
+                        Duration::from_secs_f64(
+                            total_bytes.saturating_sub(bytes) as f64 / bytes_per_second,
+                        )
+                    });
+                    let progress = Progress {
+                        processed_regions: regions,
+                        total_regions,
+                        processed_bytes: bytes,
+                        total_bytes,
+                        processed_chunks: total_chunks.load(Ordering::Relaxed),
+                        deleted_chunks: total_deleted_chunks.load(Ordering::Relaxed),
+                        elapsed,
+                        bytes_per_second,
+                        eta,
+                    };
+                    recorder.write(&progress);
+                    send_update(t, || ProcessingUpdate::Progress(progress))
+                })
+        });
+        // regions whose predecessors were never processed
+        if config.ordered_updates {
+            let (_, pending) = pending_updates.into_inner().unwrap();
+            for processed_region in pending.into_values() {
+                let _ = send_update(&tx, || ProcessingUpdate::ProcessedRegion(processed_region));
+            }
+        }
+        let mut warnings = vec![];
+        let unreadable_chunks = total_unreadable_chunks.into_inner();
+        if unreadable_chunks > 0 {
+            warnings.push(ReportWarning::UnreadableChunks {
+                chunks: unreadable_chunks,
             });
-        if result.is_ok() {
-            let freed_space = size_before - dir_size(config.world_folder.as_path()).unwrap_or(0);
-            let time_taken = time::Instant::now() - start_time;
+        }
+        let hardlinked_sizes = hardlinked_sizes.into_inner().unwrap();
+        let hardlinked_files = total_hardlinked_files.into_inner();
+        if hardlinked_files > 0 {
+            warnings.push(ReportWarning::HardlinkedFiles {
+                files: hardlinked_files,
+                bytes: hardlinked_sizes.values().sum(),
+            });
+        }
+        // nothing changed in a dry run, only the estimates of the regions are known
+        let sizes_before = if config.dry_run { vec![] } else { sizes_before };
+        let freed_space_by_folder = sizes_before
+            .into_iter()
+            .map(|(size_before, folder)| {
+                // the folder no longer lists the old files, but they still take up the space
+                let size_after = dir_size(&folder).unwrap_or(0)
+                    + hardlinked_sizes.get(&folder).copied().unwrap_or(0);
+                if size_after > size_before {
+                    warnings.push(ReportWarning::FolderGrew {
+                        folder: folder.clone(),
+                        bytes: size_after - size_before,
+                    });
+                }
+                (folder, size_before.saturating_sub(size_after))
+            })
+            .collect::<BTreeMap<_, _>>();
+        let freed_space = if config.dry_run {
+            freed_bytes.into_inner()
+        } else {
+            freed_space_by_folder.values().sum()
+        };
+        let time_taken = time::Instant::now() - start_time;
 
-            let _ = tx.send(ProcessingUpdate::Finished(Report {
+        let result = if result.is_ok() || target_reached.into_inner() {
+            Ok(Report {
+                run_id,
                 time_taken,
                 total_freed_space: freed_space,
+                total_regions: processed_regions.into_inner(),
+                total_chunks: total_chunks.into_inner(),
+                total_deleted_chunks: total_deleted_chunks.into_inner(),
+                total_reset_chunks: total_reset_chunks.into_inner(),
+                total_trimmed_chunks: total_trimmed_chunks.into_inner(),
+                total_recompressed_chunks: total_recompressed_chunks.into_inner(),
+                total_outdated_chunks: total_outdated_chunks.into_inner(),
+                total_oversized_chunks: total_oversized_chunks.into_inner(),
+                total_crowded_chunks: total_crowded_chunks.into_inner(),
+                total_cluttered_chunks: total_cluttered_chunks.into_inner(),
+                freed_space_by_folder,
+                warnings,
+                skipped_files,
+                failed_regions: failed_regions.into_inner().unwrap(),
+            })
+        } else {
+            Err(Box::new(PartialReport {
+                run_id,
+                time_taken,
+                total_freed_space: freed_space,
+                processed_regions: processed_regions.into_inner(),
                 total_regions,
                 total_chunks: total_chunks.into_inner(),
                 total_deleted_chunks: total_deleted_chunks.into_inner(),
-            }));
+                total_reset_chunks: total_reset_chunks.into_inner(),
+                total_trimmed_chunks: total_trimmed_chunks.into_inner(),
+                total_recompressed_chunks: total_recompressed_chunks.into_inner(),
+                total_outdated_chunks: total_outdated_chunks.into_inner(),
+                total_oversized_chunks: total_oversized_chunks.into_inner(),
+                total_crowded_chunks: total_crowded_chunks.into_inner(),
+                total_cluttered_chunks: total_cluttered_chunks.into_inner(),
+                freed_space_by_folder,
+                warnings,
+                skipped_files,
+                failed_regions: failed_regions.into_inner().unwrap(),
+                modified_regions: modified_regions.into_inner().unwrap(),
+            }))
+        };
+        match &result {
+            Ok(_) => recorder.remove(),
+            Err(report) => recorder.write(&Progress {
+                processed_regions: report.processed_regions,
+                total_regions,
+                processed_bytes: processed_bytes.into_inner(),
+                total_bytes,
+                processed_chunks: report.total_chunks,
+                deleted_chunks: report.total_deleted_chunks,
+                elapsed: time_taken,
+                bytes_per_second: 0.0,
+                eta: None,
+            }),
         }
-    });
+        drop(lock);
+        let _ = send_update(&tx, || match &result {
+            Ok(report) => ProcessingUpdate::Finished(report.clone()),
+            Err(report) => ProcessingUpdate::Cancelled(*report.clone()),
+        });
+        result
+    }
+}
 
-    Ok(rx)
+/// Sends the update if the execution is followed through an [`Execution`], only creating it then. Fails if the
+/// receiver was dropped.
+fn send_update(
+    tx: &Option<mpsc::Sender<ProcessingUpdate>>,
+    update: impl FnOnce() -> ProcessingUpdate,
+) -> Result<(), ()> {
+    match tx {
+        Some(tx) => tx.send(update()).map_err(|_| ()),
+        None => Ok(()),
+    }
 }
 
-fn collect_region_files(base_path: &Path) -> io::Result<Vec<PathBuf>> {
-    let mut files = vec![];
-    for sub_folder in REGION_SUBFOLDERS {
-        let path = base_path.join(Path::new(sub_folder));
-        if !path.try_exists().map_or(false, |b| b) {
-            continue;
+/// Processes the region files in the background, see [`execute`].
+fn process_files(processing: Processing) -> Execution {
+    let run_id = processing.run_id;
+    let cancel_handle = processing.cancel_handle.clone();
+    let pause_handle = processing.pause_handle.clone();
+    let thread_count = processing.pool.current_num_threads();
+    let (tx, rx) = mpsc::channel();
+    let thread = thread::spawn(move || processing.run(Some(tx)));
+
+    Execution {
+        run_id,
+        updates: rx,
+        cancel_handle,
+        pause_handle,
+        thread,
+        thread_count,
+    }
+}
+
+/// The region folders to process according to the config: the ones of all dimensions, [`Config::extra_region_folders`]
+/// and the matches of [`Config::region_globs`].
+fn config_region_folders(
+    config: &Config,
+    skipped: &mut Vec<SkippedFile>,
+) -> Result<Vec<(Dimension, PathBuf)>, Error> {
+    let world_folder = config.world_folder.as_path();
+    let mut folders = region_folders(world_folder, &config.layout)?;
+    folders.extend(config.extra_region_folders.iter().map(|folder| {
+        let folder = world_folder.join(folder);
+        (extra_dimension(world_folder, &folder), folder)
+    }));
+    folders.extend(glob_region_folders(
+        world_folder,
+        &config.region_globs,
+        config.fail_on_unreadable,
+        skipped,
+    )?);
+    let mut seen = BTreeSet::new();
+    folders.retain(|(_, folder)| seen.insert(folder.clone()));
+    Ok(folders)
+}
+
+/// Collects the region files of all dimensions, failing on the first file or folder that can't be read.
+fn collect_region_files(base_path: &Path) -> Result<Vec<(Dimension, PathBuf)>, Error> {
+    find_region_files(
+        &region_folders(base_path, &WorldLayout::default())?,
+        true,
+        &mut vec![],
+    )
+}
+
+/// Collects the region files of all dimensions like [`collect_region_files`], together with the ones in the `entities`
+/// and `poi` folders next to them.
+fn collect_all_region_files(base_path: &Path) -> Result<Vec<(Dimension, PathBuf)>, Error> {
+    let folders = region_folders(base_path, &WorldLayout::default())?
+        .into_iter()
+        .flat_map(|(dimension, folder)| {
+            REGION_KINDS.map(|kind| (dimension.clone(), folder.with_file_name(kind)))
+        })
+        .filter(|(_, folder)| folder.try_exists().is_ok_and(|b| b))
+        .collect::<Vec<_>>();
+    let mut files = find_region_files(&folders, true, &mut vec![])?;
+    files.sort();
+    Ok(files)
+}
+
+/// The existing `region` folders of all dimensions of the world.
+fn region_folders(base_path: &Path, layout: &WorldLayout) -> io::Result<Vec<(Dimension, PathBuf)>> {
+    let dimensions = DIMENSION_FOLDERS
+        .into_iter()
+        .map(|(dimension, _)| dimension)
+        .chain(custom_dimensions(base_path)?);
+    Ok(dimensions
+        .map(|dimension| {
+            let folder = layout
+                .dimension_folder(base_path, &dimension)
+                .join("region");
+            (dimension, folder)
+        })
+        .filter(|(_, folder)| folder.try_exists().is_ok_and(|b| b))
+        .collect())
+}
+
+/// Expands [`Config::region_globs`] relative to the world folder. Matched files are ignored, only folders count.
+#[cfg(feature = "region-globs")]
+fn glob_region_folders(
+    base_path: &Path,
+    patterns: &[String],
+    fail_on_unreadable: bool,
+    skipped: &mut Vec<SkippedFile>,
+) -> Result<Vec<(Dimension, PathBuf)>, Error> {
+    let mut folders = vec![];
+    for pattern in patterns {
+        let full_pattern = base_path.join(pattern);
+        let matches =
+            glob::glob(&full_pattern.to_string_lossy()).map_err(|err| Error::InvalidGlob {
+                pattern: pattern.clone(),
+                message: err.msg.to_string(),
+            })?;
+        for entry in matches {
+            match entry {
+                Ok(folder) if folder.is_dir() => {
+                    folders.push((extra_dimension(base_path, &folder), folder))
+                }
+                Ok(_) => {}
+                Err(err) if fail_on_unreadable => {
+                    return Err(Error::UnreadableFile {
+                        path: err.path().to_path_buf(),
+                        source: err.into(),
+                    })
+                }
+                Err(err) => skipped.push(SkippedFile {
+                    path: err.path().to_path_buf(),
+                    error: err.error().to_string(),
+                }),
+            }
+        }
+    }
+    Ok(folders)
+}
+
+#[cfg(not(feature = "region-globs"))]
+fn glob_region_folders(
+    _base_path: &Path,
+    patterns: &[String],
+    _fail_on_unreadable: bool,
+    _skipped: &mut Vec<SkippedFile>,
+) -> Result<Vec<(Dimension, PathBuf)>, Error> {
+    match patterns.first() {
+        Some(pattern) => Err(Error::InvalidGlob {
+            pattern: pattern.clone(),
+            message: "lessanvil was built without the `region-globs` feature".to_string(),
+        }),
+        None => Ok(vec![]),
+    }
+}
+
+/// The dimension of a region folder outside of the usual layout: a [`Dimension::Custom`] named after the folder
+/// containing it, relative to the world folder.
+fn extra_dimension(base_path: &Path, folder: &Path) -> Dimension {
+    let parent = folder.parent().unwrap_or(folder);
+    let name = parent.strip_prefix(base_path).unwrap_or(parent);
+    Dimension::Custom(name.to_string_lossy().replace('\\', "/"))
+}
+
+/// Collects the region files of the folders. Files and folders that can't be read are added to `skipped`, unless
+/// `fail_on_unreadable` is set.
+fn find_region_files(
+    folders: &[(Dimension, PathBuf)],
+    fail_on_unreadable: bool,
+    skipped: &mut Vec<SkippedFile>,
+) -> Result<Vec<(Dimension, PathBuf)>, Error> {
+    let mut skip = |path: &Path, source: io::Error| {
+        if fail_on_unreadable {
+            return Err(Error::UnreadableFile {
+                path: path.to_path_buf(),
+                source,
+            });
         }
-        let mut contents = path
-            .read_dir()?
-            .map(|entry| entry.unwrap().path())
-            .filter(|path| {
-                if let Some(ext) = path.extension() {
-                    ext == "mca"
-                } else {
-                    false
+        skipped.push(SkippedFile {
+            path: path.to_path_buf(),
+            error: source.to_string(),
+        });
+        Ok(())
+    };
+
+    let mut files = vec![];
+    for (dimension, path) in folders {
+        let entries = match path.read_dir() {
+            Ok(entries) => entries,
+            Err(err) => {
+                skip(path, err)?;
+                continue;
+            }
+        };
+        for entry in entries {
+            // the entry itself is unknown, so the folder containing it is recorded
+            let file = match entry {
+                Ok(entry) => entry.path(),
+                Err(err) => {
+                    skip(path, err)?;
+                    continue;
                 }
-            })
-            .collect();
-        files.append(&mut contents);
+            };
+            if file.extension().is_some_and(|ext| ext == "mca") {
+                files.push((dimension.clone(), file));
+            }
+        }
     }
     Ok(files)
 }
 
+/// Finds the dimensions added by datapacks or mods, stored in `dimensions/<namespace>/<path>`.
+fn custom_dimensions(base_path: &Path) -> io::Result<Vec<Dimension>> {
+    fn find(folder: &Path, id: &str, dimensions: &mut Vec<Dimension>) -> io::Result<()> {
+        for entry in folder.read_dir()? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name == "region" {
+                dimensions.push(Dimension::Custom(id.to_string()));
+            } else if !REGION_KINDS.contains(&name.as_ref()) {
+                // the path of a dimension id may contain slashes
+                let separator = if id.ends_with(':') { "" } else { "/" };
+                find(&entry.path(), &format!("{id}{separator}{name}"), dimensions)?;
+            }
+        }
+        Ok(())
+    }
+
+    let folder = base_path.join("dimensions");
+    let mut dimensions = vec![];
+    if !folder.try_exists().is_ok_and(|b| b) {
+        return Ok(dimensions);
+    }
+    for entry in folder.read_dir()? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            let namespace = entry.file_name();
+            find(
+                &entry.path(),
+                &format!("{}:", namespace.to_string_lossy()),
+                &mut dimensions,
+            )?;
+        }
+    }
+    dimensions.sort();
+    Ok(dimensions)
+}
+
 /// The error type for processed regions.
 #[derive(thiserror::Error, Debug)]
 pub enum RegionProcessingError {
@@ -174,78 +2091,883 @@ pub enum RegionProcessingError {
     /// An arbitrary error for [Minecraft NBT](https://minecraft.fandom.com/wiki/NBT_format) operations.
     #[error("NBT error")]
     NBTError(#[from] fastnbt::error::Error),
+    /// The region file has more than one hardlink and was skipped. See [`HardlinkPolicy::Skip`].
+    #[error("{} is hardlinked {links} times and was left untouched", .path.display())]
+    Hardlinked {
+        /// The path of the skipped region file.
+        path: PathBuf,
+        /// The amount of hardlinks pointing to the file.
+        links: u64,
+    },
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct Chunk {
-    inhabited_time: usize,
+/// A file that was rewritten while other hardlinks pointed to it. The new file replaces only the processed link, the
+/// old one stays on disk for the others.
+#[derive(Clone, Debug)]
+pub struct HardlinkedFile {
+    /// The path of the file.
+    pub path: PathBuf,
+    /// The amount of hardlinks that pointed to the file.
+    pub links: u64,
+    /// The size of the old file, which stays on disk for the other links.
+    pub size: u64,
 }
 
 /// A processed region.
 pub struct ProcessedRegion {
+    /// The dimension the region belongs to.
+    pub dimension: Dimension,
     /// The x-coordinate.
-    pub x: usize,
+    pub x: i32,
     /// The y-coordinate.
-    pub y: usize,
+    pub y: i32,
     /// The total chunks processed in this region.
     pub total_chunks: u16,
     /// The total chunks deleted in this region.
     pub deleted_chunks: u16,
+    /// The positions of the deleted chunks, e.g. to write a plan (see [`plan`]).
+    pub deleted_positions: Vec<ChunkPos>,
+    /// The total chunks whose InhabitedTime was reset in this region.
+    pub reset_chunks: u16,
+    /// The total chunks with cleared sections in this region.
+    pub trimmed_chunks: u16,
+    /// The total chunks converted to [`Config::recompress`] in this region.
+    pub recompressed_chunks: u16,
+    /// The chunks which would have been deleted but are inside a [`ProtectedArea`] or contain one of [`Config::player_blocks`].
+    pub protected_chunks: u16,
+    /// The chunks last saved by an old version of the game in this region, see [`Config::outdated_chunks`].
+    pub outdated_chunks: u16,
+    /// The positions of the chunks exceeding [`OversizedChunks::max_size`] in this region, see
+    /// [`Config::oversized_chunks`].
+    pub oversized_chunks: Vec<ChunkPos>,
+    /// The positions of the chunks exceeding the [`EntityLimits`] in this region, see [`Config::entity_limits`].
+    pub crowded_chunks: Vec<ChunkPos>,
+    /// The positions of the chunks exceeding the [`BlockEntityLimit`] in this region, see
+    /// [`Config::block_entity_limit`].
+    pub cluttered_chunks: Vec<ChunkPos>,
+    /// The positions of the chunks in this region that couldn't be read and were kept as they are, because they are
+    /// corrupted, stored in a separate file or use a compression lessanvil can't decompress.
+    pub unreadable_chunks: Vec<ChunkPos>,
+    /// The files of this region (the region file or its entity and POI data) that were rewritten while other hardlinks
+    /// pointed to them, see [`HardlinkPolicy::Process`].
+    pub hardlinked_files: Vec<HardlinkedFile>,
+    /// The disk space freed in this region (including its entity and POI data) in bytes. The old contents of
+    /// [`ProcessedRegion::hardlinked_files`] aren't freed.
+    pub freed_space: u64,
+    /// What happened to each chunk of the region, indexed by `z * 32 + x` with coordinates relative to the region.
+    pub chunk_states: Vec<ChunkState>,
+    /// The InhabitedTime of every chunk read in this region (before resetting it), e.g. for a histogram.
+    pub inhabited_times: Vec<InhabitedTime>,
+}
+
+/// What happened to a chunk of a [`ProcessedRegion`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChunkState {
+    /// The region doesn't contain the chunk.
+    Empty,
+    /// The chunk was kept as it is (apart from recompressing it).
+    Kept,
+    /// The chunk was deleted.
+    Deleted,
+    /// The chunk would have been deleted or modified but is protected.
+    Protected,
+    /// The chunk was kept but modified, e.g. its InhabitedTime was reset or sections were cleared.
+    Modified,
+}
+
+/// What [`judge_chunk`] found out about a chunk, whatever its [`Verdict`].
+#[derive(Clone, Copy)]
+struct ChunkFacts {
+    inhabited_time: InhabitedTime,
+    /// See [`Config::outdated_chunks`].
+    outdated: bool,
+    /// See [`Config::oversized_chunks`].
+    oversized: bool,
+    /// See [`Config::entity_limits`].
+    crowded: bool,
+    /// See [`Config::block_entity_limit`].
+    cluttered: bool,
+}
+
+/// What happens to a chunk, see [`judge_chunk`].
+enum Verdict {
+    /// The chunk is above the InhabitedTime cutoff.
+    Keep(ChunkFacts),
+    /// The chunk is below the cutoff but protected.
+    Protected(ChunkFacts),
+    /// [`Config::operation`] applies to the chunk.
+    Apply(ChunkFacts),
+}
+
+impl Verdict {
+    fn facts(&self) -> ChunkFacts {
+        match self {
+            Verdict::Keep(facts) | Verdict::Protected(facts) | Verdict::Apply(facts) => *facts,
+        }
+    }
+}
+
+/// Decides what happens to the (uncompressed) chunk at the given absolute chunk coordinates, which is stored at the
+/// `location` in the region file and contains the `entities` counted in its entity data (if there are
+/// [`EntityLimits`]).
+fn judge_chunk(
+    chunk_data: &[u8],
+    location: &region::Location,
+    entities: Option<&entities::EntityCounts>,
+    dimension: &Dimension,
+    (x, z): (i32, i32),
+    protected_areas: &[&ProtectedArea],
+    config: &Config,
+) -> Result<Verdict, fastnbt::error::Error> {
+    let chunk = ChunkMeta::from_bytes(chunk_data)?;
+    let size = location.sectors as u64 * region::SECTOR_SIZE;
+    let facts = ChunkFacts {
+        inhabited_time: chunk.inhabited_time,
+        outdated: config.outdated_chunks.is_some_and(|outdated| {
+            chunk
+                .data_version
+                .map_or(true, |version| version < outdated.min_data_version)
+        }),
+        oversized: config
+            .oversized_chunks
+            .is_some_and(|oversized| size > oversized.max_size),
+        crowded: match (&config.entity_limits, entities) {
+            (Some(limits), Some(counts)) => limits.exceeded_by(counts),
+            _ => false,
+        },
+        cluttered: config
+            .block_entity_limit
+            .is_some_and(|limit| chunk.block_entities > limit.max_block_entities),
+    };
+    if let Some(planned) = &config.planned_chunks {
+        let position = ChunkPos {
+            dimension: dimension.clone(),
+            x,
+            z,
+        };
+        return Ok(if planned.contains(&position) {
+            Verdict::Apply(facts)
+        } else {
+            Verdict::Keep(facts)
+        });
+    }
+    let pruned = (facts.outdated
+        && config
+            .outdated_chunks
+            .is_some_and(|outdated| outdated.prune))
+        || (facts.oversized
+            && config
+                .oversized_chunks
+                .is_some_and(|oversized| oversized.prune))
+        || (facts.crowded
+            && config
+                .entity_limits
+                .as_ref()
+                .is_some_and(|limits| limits.prune))
+        || (facts.cluttered && config.block_entity_limit.is_some_and(|limit| limit.prune));
+    let below_cutoff = match config.not_touched_since {
+        Some(since) => !saved_since(location, since),
+        None => facts.inhabited_time <= config.max_inhabited_time,
+    };
+    if !below_cutoff && !pruned {
+        return Ok(Verdict::Keep(facts));
+    }
+    if protected_areas
+        .iter()
+        .any(|area| area.contains(dimension, x, z))
+        || (!config.player_blocks.is_empty()
+            && blocks::contains_any(
+                chunk_data,
+                &config.player_blocks,
+                (*dimension == Dimension::Overworld).then_some(SEA_LEVEL),
+            )?)
+        || (config.nether_roof == Some(NetherRoof::Protect)
+            && *dimension == Dimension::Nether
+            && sections::has_blocks(chunk_data, &(NETHER_ROOF_HEIGHT..=i32::MAX))?)
+    {
+        return Ok(Verdict::Protected(facts));
+    }
+    Ok(Verdict::Apply(facts))
+}
+
+/// Whether the chunk at the location was saved at or after the time, according to the header of its region file. Some
+/// tools write chunks without a timestamp, their age is unknown so they count as saved since.
+fn saved_since(location: &region::Location, time: SystemTime) -> bool {
+    let since = time
+        .duration_since(time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    location.timestamp == 0 || location.timestamp as u64 >= since
+}
+
+/// Whether chunks are judged by [`Config::not_touched_since`] alone, so that their data isn't needed, see
+/// [`judge_chunk_by_header`].
+fn judges_by_header(config: &Config) -> bool {
+    config.not_touched_since.is_some()
+        && config.operation == Operation::Delete
+        && config.player_blocks.is_empty()
+        && config.nether_roof.is_none()
+        && config.recompress.is_none()
+        && config.outdated_chunks.is_none()
+        && config.oversized_chunks.is_none()
+        && config.entity_limits.is_none()
+        && config.block_entity_limit.is_none()
+        && config.planned_chunks.is_none()
+}
+
+/// Decides what happens to the chunk like [`judge_chunk`], but only by the time it was last saved and the protected
+/// areas. Only for configs that [judge by the header](judges_by_header), nothing is known about the chunk otherwise.
+fn judge_chunk_by_header(
+    location: &region::Location,
+    dimension: &Dimension,
+    (x, z): (i32, i32),
+    protected_areas: &[&ProtectedArea],
+    config: &Config,
+) -> Verdict {
+    let facts = ChunkFacts {
+        inhabited_time: InhabitedTime::default(),
+        outdated: false,
+        oversized: false,
+        crowded: false,
+        cluttered: false,
+    };
+    if config
+        .not_touched_since
+        .map_or(true, |since| saved_since(location, since))
+    {
+        Verdict::Keep(facts)
+    } else if protected_areas
+        .iter()
+        .any(|area| area.contains(dimension, x, z))
+    {
+        Verdict::Protected(facts)
+    } else {
+        Verdict::Apply(facts)
+    }
+}
+
+/// Counts the entities of the region's chunks in its entity data if [`Config::entity_limits`] are set, indexed like
+/// [`ProcessedRegion::chunk_states`]. `None` without limits or entity data.
+fn entity_counts(
+    region_file_path: &Path,
+    config: &Config,
+) -> io::Result<Option<Vec<entities::EntityCounts>>> {
+    if config.entity_limits.is_none() {
+        return Ok(None);
+    }
+    let path = match (region_file_path.parent(), region_file_path.file_name()) {
+        (Some(folder), Some(name)) => folder.with_file_name(REGION_KINDS[1]).join(name),
+        _ => return Ok(None),
+    };
+    if !path.try_exists()? {
+        return Ok(None);
+    }
+    entities::count_region(&path, &config.custom_decompressor).map(Some)
+}
+
+/// Estimates every region with [`estimate_region`] in parallel. Regions that can't be read are estimated to change
+/// nothing, as are all regions once the execution is cancelled.
+fn estimate_regions(
+    files: Vec<(Dimension, PathBuf)>,
+    config: &Config,
+    budget: &MemoryBudget,
+    cancelled: &CancelHandle,
+) -> Vec<(RegionEstimate, (Dimension, PathBuf))> {
+    files
+        .into_par_iter()
+        .map(|(dimension, path)| {
+            let estimate = if cancelled.is_cancelled() {
+                RegionEstimate::default()
+            } else {
+                estimate_region(&path, &dimension, config, budget).unwrap_or_default()
+            };
+            (estimate, (dimension, path))
+        })
+        .collect()
+}
+
+/// Sorts the regions by the amount of space processing them would free, largest first, and drops the regions that
+/// wouldn't free any.
+fn sort_by_reclaimable_space(
+    estimates: Vec<(RegionEstimate, (Dimension, PathBuf))>,
+) -> Vec<(Dimension, PathBuf)> {
+    let mut files = estimates
+        .into_iter()
+        .filter(|(estimate, _)| estimate.reclaimable_space > 0)
+        .collect::<Vec<_>>();
+    // stable, so regions freeing the same amount stay sorted by their coordinates
+    files.sort_by_key(|(estimate, _)| Reverse(estimate.reclaimable_space));
+    files.into_iter().map(|(_, file)| file).collect()
+}
+
+/// The fraction of the chunks of all regions that would be deleted. See [`Config::max_delete_ratio`].
+fn delete_ratio(estimates: &[(RegionEstimate, (Dimension, PathBuf))]) -> f64 {
+    let (deleted, total) = estimates
+        .iter()
+        .fold((0, 0), |(deleted, total), (estimate, _)| {
+            (
+                deleted + estimate.deleted_chunks,
+                total + estimate.total_chunks,
+            )
+        });
+    if total == 0 {
+        0.0
+    } else {
+        deleted as f64 / total as f64
+    }
+}
+
+/// Sorts the regions by [`ProcessingOrder::EstimatedSavings`]. Regions whose header can't be read come last.
+fn sort_by_estimated_savings(files: Vec<(Dimension, PathBuf)>) -> Vec<(Dimension, PathBuf)> {
+    let mut files = files
+        .into_par_iter()
+        .map(|(dimension, path)| {
+            let estimate = estimated_savings(&path).unwrap_or_default();
+            (estimate, (dimension, path))
+        })
+        .collect::<Vec<_>>();
+    files.sort_by(|(a, _), (b, _)| b.cmp(a));
+    files.into_iter().map(|(_, file)| file).collect()
+}
+
+/// The unused space and the space taken up by chunks of the region, based on its header.
+fn estimated_savings(region_file_path: &Path) -> io::Result<(u64, u64)> {
+    let mut file = File::open(region_file_path)?;
+    let size = file.metadata()?.len();
+    let header = region::Header::read(&mut file)?;
+    let chunks = header.used_sectors() * region::SECTOR_SIZE;
+    let unused = size.saturating_sub(2 * region::SECTOR_SIZE + chunks);
+    Ok((unused, chunks))
+}
+
+/// What processing a region would do, see [`estimate_region`].
+#[derive(Clone, Copy, Debug, Default)]
+struct RegionEstimate {
+    /// The space processing the region would free: the sectors of the chunks that would be deleted plus the sectors
+    /// that are already unused.
+    reclaimable_space: u64,
+    /// The chunks that would be deleted.
+    deleted_chunks: u64,
+    /// The chunks that could be judged. Only counted for [`Operation::Delete`].
+    total_chunks: u64,
+}
+
+/// Judges the chunks of the region without modifying it, like a dry run of [`process_region_file`] that only keeps
+/// the numbers needed before processing.
+fn estimate_region(
+    region_file_path: &Path,
+    dimension: &Dimension,
+    config: &Config,
+    budget: &MemoryBudget,
+) -> Result<RegionEstimate, RegionProcessingError> {
+    let (x, y) = region_coordinates(region_file_path);
+    let mut file = File::open(region_file_path)?;
+    let size = file.metadata()?.len();
+    let header = region::Header::read(&mut file)?;
+
+    let protected_areas = config
+        .protected_areas
+        .iter()
+        .filter(|area| area.intersects_region(dimension, x, y))
+        .collect::<Vec<_>>();
+
+    let entity_counts = entity_counts(region_file_path, config)?;
+
+    let used = (2 + header.used_sectors()) * region::SECTOR_SIZE;
+    let mut estimate = RegionEstimate {
+        reclaimable_space: size.saturating_sub(used),
+        ..Default::default()
+    };
+    if config.operation != Operation::Delete {
+        return Ok(estimate);
+    }
+    let header_only = judges_by_header(config);
+    for chunk_x in 0..32 {
+        for chunk_y in 0..32 {
+            let location = header.location(chunk_x, chunk_y);
+            if !location.is_present() {
+                continue;
+            }
+            let position = (x * 32 + chunk_x as i32, y * 32 + chunk_y as i32);
+            let verdict = if header_only {
+                judge_chunk_by_header(&location, dimension, position, &protected_areas, config)
+            } else {
+                let mut reservation = budget.reserve(location.sectors as u64 * region::SECTOR_SIZE);
+                let Ok(Some((_, chunk_data))) = region::read_chunk(
+                    &mut file,
+                    &header,
+                    chunk_x,
+                    chunk_y,
+                    &config.custom_decompressor,
+                ) else {
+                    continue;
+                };
+                reservation.grow_to(chunk_data.len() as u64);
+                let entities = entity_counts
+                    .as_ref()
+                    .map(|counts| &counts[chunk_y * 32 + chunk_x]);
+                // kept by the processing as well
+                let Ok(verdict) = judge_chunk(
+                    &chunk_data,
+                    &location,
+                    entities,
+                    dimension,
+                    position,
+                    &protected_areas,
+                    config,
+                ) else {
+                    continue;
+                };
+                verdict
+            };
+            estimate.total_chunks += 1;
+            if let Verdict::Apply(_) = verdict {
+                estimate.deleted_chunks += 1;
+                estimate.reclaimable_space += location.sectors as u64 * region::SECTOR_SIZE;
+            }
+        }
+    }
+    Ok(estimate)
 }
 
 fn process_region_file(
     region_file_path: &Path,
-    man_inhabited_time: usize,
+    dimension: Dimension,
+    config: &Config,
+    budget: &MemoryBudget,
 ) -> Result<ProcessedRegion, RegionProcessingError> {
     let mut total_chunks = 0;
     let mut deleted_chunks = 0;
+    let mut reset_chunks = 0;
+    let mut trimmed_chunks = 0;
+    let mut recompressed_chunks = 0;
+    let mut protected_chunks = 0;
+    let mut outdated_chunks = 0;
+    let mut oversized_chunks = vec![];
+    let mut crowded_chunks = vec![];
+    let mut cluttered_chunks = vec![];
+    let mut unreadable_chunks = vec![];
 
-    let (y, x) = match region_file_path
-        .file_stem()
-        .and_then(|os| os.to_str())
-        .map(|s| s.split('.').skip(1).collect::<Vec<_>>())
-    {
-        Some(mut vec) => (
-            vec.pop().unwrap_or("0").parse::<usize>().unwrap_or(0),
-            vec.pop().unwrap_or("0").parse::<usize>().unwrap_or(0),
-        ),
-        None => (0, 0),
+    let (x, y) = region_coordinates(region_file_path);
+
+    // the entity and POI data of deleted chunks has to go as well
+    let companion_paths = match (region_file_path.parent(), region_file_path.file_name()) {
+        (Some(folder), Some(name)) if config.operation == Operation::Delete => REGION_KINDS[1..]
+            .iter()
+            .map(|kind| folder.with_file_name(kind).join(name))
+            .filter(|path| path.try_exists().is_ok_and(|b| b))
+            .collect(),
+        _ => vec![],
     };
 
-    let region_file = File::options()
-        .read(true)
-        .write(true)
-        .open(region_file_path)?;
-    let mut region = Region::from_stream(region_file)?;
+    // a dry run doesn't write anything, so the links don't matter
+    let checked_paths = std::iter::once(region_file_path)
+        .chain(companion_paths.iter().map(|p| p.as_path()))
+        .filter(|_| !config.dry_run);
+    let mut links = HashMap::new();
+    for path in checked_paths {
+        let count = hardlink_count(path)?;
+        if count > 1 {
+            if config.hardlink_policy == HardlinkPolicy::Skip {
+                return Err(RegionProcessingError::Hardlinked {
+                    path: path.to_path_buf(),
+                    links: count,
+                });
+            }
+            links.insert(path.to_path_buf(), count);
+        }
+    }
+    let mut hardlinked_files = vec![];
+    // the old file stays on disk for the other links
+    let mut rewritten = |path: &Path, size: u64| match links.get(path) {
+        Some(&links) => {
+            hardlinked_files.push(HardlinkedFile {
+                path: path.to_path_buf(),
+                links,
+                size,
+            });
+            size
+        }
+        None => 0,
+    };
 
-    for x in 0..32 {
-        for y in 0..32 {
-            let Ok(Some(chunk)) = region.read_chunk(x, y) else {
+    #[cfg(feature = "simulation")]
+    inject_fault(config, simulation::IoOperation::Open, region_file_path)?;
+    let mut region_file = File::open(region_file_path)?;
+    let mut size_before = size::file_size(region_file_path)?;
+    // the compressed sizes of the chunks, to reserve memory before reading them
+    let header = region::Header::read(&region_file)?;
+    let writer = if config.dry_run {
+        None
+    } else {
+        Some(writer::WriterThread::spawn(
+            region_file_path,
+            &config.custom_decompressor,
+        )?)
+    };
+
+    let protected_areas = config
+        .protected_areas
+        .iter()
+        .filter(|area| area.intersects_region(&dimension, x, y))
+        .collect::<Vec<_>>();
+
+    let entity_counts = entity_counts(region_file_path, config)?;
+    let header_only = judges_by_header(config);
+
+    let mut deleted = vec![];
+    let mut chunk_states = vec![ChunkState::Empty; 32 * 32];
+    let mut inhabited_times = vec![];
+    for chunk_x in 0..32 {
+        for chunk_y in 0..32 {
+            let location = header.location(chunk_x, chunk_y);
+            if !location.is_present() {
                 continue;
+            }
+            let state = &mut chunk_states[chunk_y * 32 + chunk_x];
+            *state = ChunkState::Kept;
+            let position = (x * 32 + chunk_x as i32, y * 32 + chunk_y as i32);
+            let (compression, chunk_data, _reservation) = if header_only {
+                // never used, the chunk is either deleted or kept as it is
+                (ChunkCompression::Zlib, vec![], None)
+            } else {
+                let mut reservation = budget.reserve(location.sectors as u64 * region::SECTOR_SIZE);
+                let Ok(Some((compression, chunk_data))) = region::read_chunk(
+                    &mut region_file,
+                    &header,
+                    chunk_x,
+                    chunk_y,
+                    &config.custom_decompressor,
+                ) else {
+                    unreadable_chunks.push(ChunkPos {
+                        dimension: dimension.clone(),
+                        x: position.0,
+                        z: position.1,
+                    });
+                    continue;
+                };
+                reservation.grow_to(chunk_data.len() as u64);
+                (compression, chunk_data, Some(reservation))
+            };
+            let mut modified = None;
+            let mut protected = false;
+            let entities = entity_counts
+                .as_ref()
+                .map(|counts| &counts[chunk_y * 32 + chunk_x]);
+            let verdict = if header_only {
+                judge_chunk_by_header(&location, &dimension, position, &protected_areas, config)
+            } else {
+                // the data decompressed, but isn't a valid chunk
+                let Ok(verdict) = judge_chunk(
+                    &chunk_data,
+                    &location,
+                    entities,
+                    &dimension,
+                    position,
+                    &protected_areas,
+                    config,
+                ) else {
+                    unreadable_chunks.push(ChunkPos {
+                        dimension: dimension.clone(),
+                        x: position.0,
+                        z: position.1,
+                    });
+                    continue;
+                };
+                verdict
             };
-            let chunk: Chunk = fastnbt::from_bytes(&chunk)?;
             total_chunks += 1;
-            if chunk.inhabited_time <= (man_inhabited_time / 20) {
-                region.remove_chunk(x, y)?;
-                deleted_chunks += 1;
+            let facts = verdict.facts();
+            if !header_only {
+                inhabited_times.push(facts.inhabited_time);
+            }
+            if facts.outdated {
+                outdated_chunks += 1;
+            }
+            if facts.oversized {
+                oversized_chunks.push(ChunkPos {
+                    dimension: dimension.clone(),
+                    x: position.0,
+                    z: position.1,
+                });
+            }
+            if facts.crowded {
+                crowded_chunks.push(ChunkPos {
+                    dimension: dimension.clone(),
+                    x: position.0,
+                    z: position.1,
+                });
+            }
+            if facts.cluttered {
+                cluttered_chunks.push(ChunkPos {
+                    dimension: dimension.clone(),
+                    x: position.0,
+                    z: position.1,
+                });
+            }
+            match verdict {
+                Verdict::Keep(_) => {}
+                Verdict::Protected(_) => {
+                    protected_chunks += 1;
+                    protected = true;
+                    *state = ChunkState::Protected;
+                }
+                Verdict::Apply(_) if config.operation == Operation::Delete => {
+                    if let Some(writer) = &writer {
+                        #[cfg(feature = "simulation")]
+                        inject_fault(
+                            config,
+                            simulation::IoOperation::RemoveChunk,
+                            region_file_path,
+                        )?;
+                        writer.remove(chunk_x, chunk_y);
+                    }
+                    deleted.push((chunk_x, chunk_y));
+                    deleted_chunks += 1;
+                    *state = ChunkState::Deleted;
+                    continue;
+                }
+                Verdict::Apply(facts) => match config.operation {
+                    Operation::Delete => unreachable!(),
+                    // chunks that never were inhabited stay as they are
+                    Operation::ResetInhabitedTime => {
+                        if facts.inhabited_time != InhabitedTime::default() {
+                            modified = Some(reset_inhabited_time(&chunk_data)?);
+                            reset_chunks += 1;
+                        }
+                    }
+                    Operation::ClearSections { min_y, max_y } => {
+                        modified = sections::clear_sections(&chunk_data, &(min_y..=max_y))?;
+                        if modified.is_some() {
+                            trimmed_chunks += 1;
+                        }
+                    }
+                },
+            }
+            if config.nether_roof == Some(NetherRoof::Trim)
+                && dimension == Dimension::Nether
+                && !protected
+            {
+                let roof = NETHER_ROOF_HEIGHT..=i32::MAX;
+                let data = modified.as_deref().unwrap_or(&chunk_data);
+                if let Some(trimmed) = sections::clear_sections(data, &roof)? {
+                    // already counted if the operation cleared sections of the chunk
+                    if modified.is_none()
+                        || !matches!(config.operation, Operation::ClearSections { .. })
+                    {
+                        trimmed_chunks += 1;
+                    }
+                    modified = Some(trimmed);
+                }
+            }
+            if modified.is_some() && !protected {
+                *state = ChunkState::Modified;
+            }
+            let target_compression = config.recompress.unwrap_or(compression);
+            if target_compression != compression {
+                recompressed_chunks += 1;
+            }
+            if let (Some(writer), true) = (
+                &writer,
+                modified.is_some() || target_compression != compression,
+            ) {
+                #[cfg(feature = "simulation")]
+                inject_fault(
+                    config,
+                    simulation::IoOperation::WriteChunk,
+                    region_file_path,
+                )?;
+                writer.replace(
+                    chunk_x,
+                    chunk_y,
+                    target_compression,
+                    modified.unwrap_or(chunk_data),
+                );
             }
         }
     }
 
-    // truncate region file
-    let mut region_file = region.into_inner()?;
-    let len = region_file.stream_position()?;
-    region_file.set_len(len)?;
+    drop(region_file);
+    let modified =
+        deleted_chunks > 0 || reset_chunks > 0 || trimmed_chunks > 0 || recompressed_chunks > 0;
+
+    let freed_space = if let Some(writer) = writer {
+        // everything that changes is backed up before anything is changed
+        if modified {
+            back_up(region_file_path, config)?;
+        }
+        if !deleted.is_empty() {
+            for path in &companion_paths {
+                back_up(path, config)?;
+            }
+        }
+
+        // the region is rewritten in one go, which also releases the sectors left unused before
+        let mut kept_size = 0;
+        let mut size_after = if modified {
+            #[cfg(feature = "simulation")]
+            inject_fault(config, simulation::IoOperation::Compact, region_file_path)?;
+            writer.finish()?;
+            kept_size += rewritten(region_file_path, size_before);
+            size::file_size(region_file_path)?
+        } else {
+            size_before
+        };
 
+        if !deleted.is_empty() {
+            for path in &companion_paths {
+                #[cfg(feature = "simulation")]
+                inject_fault(config, simulation::IoOperation::Open, path)?;
+                let Some((before, after)) = remove_chunks(path, &deleted)? else {
+                    continue;
+                };
+                kept_size += rewritten(path, before);
+                size_before += before;
+                size_after += after;
+            }
+        }
+        (size_before - kept_size).saturating_sub(size_after)
+    } else {
+        // the size of rewritten chunks may change as well, which isn't taken into account
+        let mut freed_space = 0;
+        if modified {
+            freed_space += region::reclaimable_space(region_file_path, &deleted)?;
+        }
+        if !deleted.is_empty() {
+            for path in &companion_paths {
+                freed_space += region::reclaimable_space(path, &deleted)?;
+            }
+        }
+        freed_space
+    };
+
+    let deleted_positions = deleted
+        .iter()
+        .map(|&(chunk_x, chunk_y)| ChunkPos {
+            dimension: dimension.clone(),
+            x: x * 32 + chunk_x as i32,
+            z: y * 32 + chunk_y as i32,
+        })
+        .collect();
     Ok(ProcessedRegion {
+        dimension,
         x,
         y,
         total_chunks,
         deleted_chunks,
+        reset_chunks,
+        trimmed_chunks,
+        recompressed_chunks,
+        protected_chunks,
+        outdated_chunks,
+        oversized_chunks,
+        crowded_chunks,
+        cluttered_chunks,
+        unreadable_chunks,
+        hardlinked_files,
+        freed_space,
+        deleted_positions,
+        chunk_states,
+        inhabited_times,
     })
 }
 
+/// Injects the faults of [`Config::fault_injector`] into the operation on the file.
+#[cfg(feature = "simulation")]
+fn inject_fault(
+    config: &Config,
+    operation: simulation::IoOperation,
+    path: &Path,
+) -> io::Result<()> {
+    config
+        .fault_injector
+        .as_ref()
+        .map_or(Ok(()), |injector| injector.inject(operation, path))
+}
+
+/// Removes the chunks from the region file and compacts it. Returns the size of the file before and after, or `None`
+/// if it contained none of the chunks and was left as it is.
+fn remove_chunks(path: &Path, chunks: &[(usize, usize)]) -> io::Result<Option<(u64, u64)>> {
+    let size_before = size::file_size(path)?;
+    let mut writer = region::RegionWriter::open(path)?;
+    for &(x, z) in chunks {
+        writer.remove(x, z);
+    }
+    if !writer.has_changes() {
+        return Ok(None);
+    }
+    writer.finish()?;
+    Ok(Some((size_before, size::file_size(path)?)))
+}
+
+/// Copies the file into [`Config::backup_folder`], unless a backup of it exists already.
+fn back_up(path: &Path, config: &Config) -> io::Result<()> {
+    let Some(backup_folder) = &config.backup_folder else {
+        return Ok(());
+    };
+    let relative = path.strip_prefix(&config.world_folder).unwrap_or(path);
+    // e.g. the `../world_nether` of Bukkit's layout must not escape the backup folder
+    let relative = relative
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect::<PathBuf>();
+    let destination = backup_folder.join(relative);
+    if destination.try_exists()? {
+        return Ok(());
+    }
+    if let Some(folder) = destination.parent() {
+        fs::create_dir_all(folder)?;
+    }
+    // a partial copy must not count as a backup
+    let mut tmp = destination.as_os_str().to_owned();
+    tmp.push(".lessanvil-tmp");
+    fs::copy(path, &tmp)?;
+    fs::rename(&tmp, &destination)
+}
+
+/// Returns the chunk with its InhabitedTime set to 0, keeping all other data as is.
+fn reset_inhabited_time(chunk: &[u8]) -> Result<Vec<u8>, fastnbt::error::Error> {
+    let mut chunk: fastnbt::Value = nbt::from_bytes(chunk)?;
+    if let fastnbt::Value::Compound(root) = &mut chunk {
+        // chunks before 1.18 keep their data inside of a `Level` compound
+        let compound = match root.get_mut("Level") {
+            Some(fastnbt::Value::Compound(level)) => level,
+            _ => root,
+        };
+        compound.insert("InhabitedTime".to_string(), fastnbt::Value::Long(0));
+    }
+    fastnbt::to_bytes(&chunk)
+}
+
+/// Parses the coordinates of a region from its file name (`r.<x>.<z>.mca`).
+pub(crate) fn region_coordinates(region_file_path: &Path) -> (i32, i32) {
+    let (y, x) = match region_file_path
+        .file_stem()
+        .and_then(|os| os.to_str())
+        .map(|s| s.split('.').skip(1).collect::<Vec<_>>())
+    {
+        Some(mut vec) => (
+            vec.pop().unwrap_or("0").parse::<i32>().unwrap_or(0),
+            vec.pop().unwrap_or("0").parse::<i32>().unwrap_or(0),
+        ),
+        None => (0, 0),
+    };
+    (x, y)
+}
+
+/// Returns the amount of hardlinks pointing to the given file. Always 1 on platforms where this can't be determined.
+fn hardlink_count(path: &Path) -> io::Result<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Ok(fs::metadata(path)?.nlink())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        Ok(1)
+    }
+}
+
 // Thank you stackoverflow lol
 fn dir_size(path: &Path) -> io::Result<u64> {
     fn dir_size(mut dir: fs::ReadDir) -> io::Result<u64> {
@@ -253,7 +2975,7 @@ fn dir_size(path: &Path) -> io::Result<u64> {
             let file = file?;
             let size = match file.metadata()? {
                 data if data.is_dir() => dir_size(fs::read_dir(file.path())?)?,
-                data => data.len(),
+                data => size::backend().size(&file.path(), &data)?,
             };
             Ok(acc + size)
         })
@@ -0,0 +1,339 @@
+//! The Linear region format (`.linear`) of some performance-focused server forks (e.g. LinearPaper), and converting
+//! worlds between it and Anvil.
+//!
+//! A Linear file stores the chunks of a region uncompressed one after another and compresses the whole region with
+//! Zstandard:
+//!
+//! - a 32 byte header: [`SIGNATURE`], the version, the newest timestamp of the chunks (i64), the compression level
+//!   (i8), the amount of chunks (i16), the length of the compressed data (i32) and 8 unused bytes
+//! - the compressed data: the size (u32) and timestamp (u32) of each of the 1024 chunks in the order of the Anvil
+//!   header, followed by the NBT of the chunks in the same order
+//! - [`SIGNATURE`] again
+//!
+//! All numbers are big-endian.
+
+use crate::region::{self, StoredChunk, EXTERNAL_FLAG};
+use crate::{
+    analysis, compression, region_coordinates, region_folders, ChunkCompression, Error,
+    RegionProcessingError, WorldLayout,
+};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The first and last 8 bytes of a Linear file.
+pub const SIGNATURE: u64 = 0xc3ff_1318_3cca_9d9a;
+
+/// The version written, the versions read are [`SUPPORTED_VERSIONS`].
+const VERSION: u8 = 1;
+
+const SUPPORTED_VERSIONS: [u8; 2] = [1, 2];
+
+const HEADER_SIZE: usize = 32;
+
+/// The size of the size and timestamp of every chunk at the start of the compressed data.
+const CHUNK_TABLE_SIZE: usize = 32 * 32 * 8;
+
+/// The Zstandard level regions are compressed with.
+const COMPRESSION_LEVEL: i8 = 6;
+
+/// The format of region files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegionFormat {
+    /// Minecraft's format (`.mca`), see <https://minecraft.wiki/w/Anvil_file_format>.
+    Anvil,
+    /// The Linear format (`.linear`), see [the module](self).
+    Linear,
+}
+
+impl RegionFormat {
+    /// The extension of the region files.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RegionFormat::Anvil => "mca",
+            RegionFormat::Linear => "linear",
+        }
+    }
+}
+
+/// A Report that will be handed out after a world was converted.
+pub struct ConversionReport {
+    /// The amount of region files converted.
+    pub converted_regions: u64,
+    /// The amount of chunks in the converted region files.
+    pub converted_chunks: u64,
+}
+
+/// A chunk of a Linear region.
+pub(crate) struct LinearChunk {
+    /// The time the chunk was last saved, in seconds since the unix epoch.
+    pub(crate) timestamp: u32,
+    /// The uncompressed NBT of the chunk.
+    pub(crate) data: Vec<u8>,
+}
+
+/// Reads the chunks of a Linear file, indexed like the chunks of an Anvil header.
+pub(crate) fn read(path: &Path) -> io::Result<Vec<Option<LinearChunk>>> {
+    let bytes = fs::read(path)?;
+    let header = bytes
+        .get(..HEADER_SIZE)
+        .ok_or_else(|| invalid("truncated header"))?;
+    if u64::from_be_bytes(header[..8].try_into().expect("8 bytes")) != SIGNATURE {
+        return Err(invalid("not a Linear file"));
+    }
+    if !SUPPORTED_VERSIONS.contains(&header[8]) {
+        return Err(invalid(&format!("unsupported version {}", header[8])));
+    }
+    let length = u32::from_be_bytes(header[20..24].try_into().expect("4 bytes")) as usize;
+    let compressed = bytes
+        .get(HEADER_SIZE..HEADER_SIZE + length)
+        .ok_or_else(|| invalid("truncated data"))?;
+    let footer = bytes
+        .get(HEADER_SIZE + length..HEADER_SIZE + length + 8)
+        .ok_or_else(|| invalid("truncated footer"))?;
+    if u64::from_be_bytes(footer.try_into().expect("8 bytes")) != SIGNATURE {
+        return Err(invalid("invalid footer"));
+    }
+
+    let data = zstd::stream::decode_all(compressed)?;
+    if data.len() < CHUNK_TABLE_SIZE {
+        return Err(invalid("truncated chunk table"));
+    }
+    let (table, mut rest) = data.split_at(CHUNK_TABLE_SIZE);
+    let mut chunks = Vec::with_capacity(32 * 32);
+    for entry in table.chunks_exact(8) {
+        let size = u32::from_be_bytes(entry[..4].try_into().expect("4 bytes")) as usize;
+        if size == 0 {
+            chunks.push(None);
+            continue;
+        }
+        if size > rest.len() {
+            return Err(invalid("chunk exceeds the region"));
+        }
+        let (chunk, next) = rest.split_at(size);
+        rest = next;
+        chunks.push(Some(LinearChunk {
+            timestamp: u32::from_be_bytes(entry[4..].try_into().expect("4 bytes")),
+            data: chunk.to_vec(),
+        }));
+    }
+    Ok(chunks)
+}
+
+/// Writes a Linear file with the chunks, indexed like the chunks of an Anvil header. The file is replaced once it was
+/// written completely and gets the permissions and owner of `original`, see [`region::create_replacement`].
+pub(crate) fn write(
+    path: &Path,
+    original: &Path,
+    chunks: &[Option<LinearChunk>],
+) -> io::Result<()> {
+    let mut data = Vec::with_capacity(CHUNK_TABLE_SIZE);
+    for index in 0..32 * 32 {
+        let (size, timestamp) = match chunks.get(index) {
+            Some(Some(chunk)) => (
+                u32::try_from(chunk.data.len()).map_err(|_| invalid("chunk too large"))?,
+                chunk.timestamp,
+            ),
+            _ => (0, 0),
+        };
+        data.extend_from_slice(&size.to_be_bytes());
+        data.extend_from_slice(&timestamp.to_be_bytes());
+    }
+    for chunk in chunks.iter().take(32 * 32).flatten() {
+        data.extend_from_slice(&chunk.data);
+    }
+    let compressed = zstd::stream::encode_all(&data[..], COMPRESSION_LEVEL as i32)?;
+
+    let chunk_count = chunks.iter().take(32 * 32).flatten().count() as i16;
+    let newest_timestamp = chunks
+        .iter()
+        .flatten()
+        .map(|chunk| chunk.timestamp as i64)
+        .max()
+        .unwrap_or(0);
+    let length = u32::try_from(compressed.len()).map_err(|_| invalid("region too large"))?;
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + compressed.len() + 8);
+    bytes.extend_from_slice(&SIGNATURE.to_be_bytes());
+    bytes.push(VERSION);
+    bytes.extend_from_slice(&newest_timestamp.to_be_bytes());
+    bytes.extend_from_slice(&COMPRESSION_LEVEL.to_be_bytes());
+    bytes.extend_from_slice(&chunk_count.to_be_bytes());
+    bytes.extend_from_slice(&length.to_be_bytes());
+    bytes.extend_from_slice(&[0; 8]);
+    bytes.extend_from_slice(&compressed);
+    bytes.extend_from_slice(&SIGNATURE.to_be_bytes());
+
+    region::replace_file(path, original, &bytes)
+}
+
+/// Converts the region files of all dimensions of the world (including the sibling folders of Bukkit's layout, see
+/// [`WorldLayout::detect_bukkit`]) into the format.
+///
+/// Converting to Linear only converts the `region` folders, the entity and POI data stays in Anvil files. Converting
+/// to Anvil converts the Linear files in all of the folders. Every region is written completely before its old file is
+/// removed, so an interrupted conversion can be finished by running it again.
+pub fn convert(world_folder: &Path, format: RegionFormat) -> Result<ConversionReport, Error> {
+    if !world_folder.try_exists().is_ok_and(|b| b) {
+        return Err(Error::WorldFolderNotFound);
+    }
+
+    let files = files_to_convert(world_folder, format)?;
+    let converted_regions = files.len() as u64;
+    let converted_chunks = AtomicU64::new(0);
+
+    files.into_par_iter().try_for_each(|path| {
+        let chunks = match format {
+            RegionFormat::Anvil => linear_to_anvil(&path),
+            RegionFormat::Linear => anvil_to_linear(&path),
+        }
+        .map_err(|source| Error::RegionError {
+            path: path.clone(),
+            source: RegionProcessingError::IOError(source),
+        })?;
+        converted_chunks.fetch_add(chunks, Ordering::Relaxed);
+        Ok::<_, Error>(())
+    })?;
+
+    Ok(ConversionReport {
+        converted_regions,
+        converted_chunks: converted_chunks.into_inner(),
+    })
+}
+
+/// The region files [`convert`] would convert into the format, without converting them.
+pub fn files_to_convert(world_folder: &Path, format: RegionFormat) -> Result<Vec<PathBuf>, Error> {
+    if !world_folder.try_exists().is_ok_and(|b| b) {
+        return Err(Error::WorldFolderNotFound);
+    }
+
+    let (from, kinds): (_, &[&str]) = match format {
+        RegionFormat::Anvil => (RegionFormat::Linear, &crate::REGION_KINDS),
+        RegionFormat::Linear => (RegionFormat::Anvil, &["region"]),
+    };
+    region_files(world_folder, from, kinds)
+}
+
+/// The region files of the format in the given kinds of folders of every dimension.
+fn region_files(
+    world_folder: &Path,
+    format: RegionFormat,
+    kinds: &[&str],
+) -> Result<Vec<PathBuf>, Error> {
+    let layout = WorldLayout {
+        detect_bukkit: true,
+        ..Default::default()
+    };
+    let mut files = vec![];
+    for (_, folder) in region_folders(world_folder, &layout)? {
+        for kind in kinds {
+            let folder = folder.with_file_name(kind);
+            if !folder.try_exists().is_ok_and(|b| b) {
+                continue;
+            }
+            for entry in fs::read_dir(&folder)? {
+                let path = entry?.path();
+                let is_region = path
+                    .extension()
+                    .is_some_and(|ext| ext == format.extension())
+                    && path
+                        .file_name()
+                        .is_some_and(|name| name.to_string_lossy().starts_with("r."));
+                if is_region {
+                    files.push(path);
+                }
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Writes the chunks of the Anvil file into a Linear file next to it and removes the Anvil file. Returns the amount of
+/// chunks.
+fn anvil_to_linear(path: &Path) -> io::Result<u64> {
+    let mut chunks = (0..32 * 32).map(|_| None).collect::<Vec<_>>();
+    region::for_each_raw_chunk(path, |chunk| {
+        let index = chunk.x + chunk.z * 32;
+        let timestamp = chunk.location.timestamp;
+        let compression = ChunkCompression::from_id(chunk.id);
+        let data = analysis::chunk_data(path, chunk)?;
+        let data = compression::decompress(compression, &data, &HashMap::new())?
+            .ok_or_else(|| invalid(&format!("unsupported compression {compression}")))?;
+        chunks[index] = Some(LinearChunk { timestamp, data });
+        Ok::<_, io::Error>(())
+    })?;
+
+    write(
+        &path.with_extension(RegionFormat::Linear.extension()),
+        path,
+        &chunks,
+    )?;
+    let (region_x, region_z) = region_coordinates(path);
+    for (index, chunk) in chunks.iter().enumerate() {
+        // chunks stored in separate files are part of the Linear file now
+        if chunk.is_some() {
+            let _ = fs::remove_file(path.with_file_name(format!(
+                "c.{}.{}.mcc",
+                region_x * 32 + (index % 32) as i32,
+                region_z * 32 + (index / 32) as i32
+            )));
+        }
+    }
+    fs::remove_file(path)?;
+    Ok(chunks.iter().flatten().count() as u64)
+}
+
+/// Writes the chunks of the Linear file into an Anvil file next to it and removes the Linear file. Returns the amount
+/// of chunks.
+fn linear_to_anvil(path: &Path) -> io::Result<u64> {
+    let chunks = read(path)?;
+    let (region_x, region_z) = region_coordinates(path);
+    let mut anvil_chunks = Vec::with_capacity(chunks.len());
+    for (index, chunk) in chunks.iter().enumerate() {
+        let Some(chunk) = chunk else {
+            anvil_chunks.push(None);
+            continue;
+        };
+        let compressed =
+            compression::compress(ChunkCompression::Zlib, &chunk.data, &HashMap::new())?
+                .unwrap_or_default();
+        let id = ChunkCompression::Zlib.id();
+        // like Minecraft, chunks that don't fit into 255 sectors go into a separate file
+        if 5 + compressed.len() as u64 > u8::MAX as u64 * region::SECTOR_SIZE {
+            fs::write(
+                path.with_file_name(format!(
+                    "c.{}.{}.mcc",
+                    region_x * 32 + (index % 32) as i32,
+                    region_z * 32 + (index / 32) as i32
+                )),
+                compressed,
+            )?;
+            anvil_chunks.push(Some(StoredChunk {
+                timestamp: chunk.timestamp,
+                id: id | EXTERNAL_FLAG,
+                data: vec![],
+            }));
+        } else {
+            anvil_chunks.push(Some(StoredChunk {
+                timestamp: chunk.timestamp,
+                id,
+                data: compressed,
+            }));
+        }
+    }
+
+    region::write_region(
+        &path.with_extension(RegionFormat::Anvil.extension()),
+        path,
+        &anvil_chunks,
+    )?;
+    fs::remove_file(path)?;
+    Ok(chunks.iter().flatten().count() as u64)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
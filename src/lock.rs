@@ -0,0 +1,119 @@
+//! Locking a world against concurrent executions, e.g. two overlapping cron jobs.
+//!
+//! The lock lives in the [`STATE_FOLDER`] inside the world, which also holds other files lessanvil keeps between the
+//! steps of a run (e.g. progress snapshots, see [`crate::snapshot`]). The folder is removed again once it's empty.
+
+use crate::Error;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// The folder inside the world holding lessanvil's own files.
+pub const STATE_FOLDER: &str = ".lessanvil";
+
+/// The name of the lock file inside the [`STATE_FOLDER`].
+const LOCK_FILE: &str = "lock";
+
+/// A lock on a world, held while lessanvil modifies it and released when dropped.
+///
+/// [`crate::execute`] takes the lock itself unless [`crate::Config::dry_run`] is set.
+#[derive(Debug)]
+pub struct WorldLock {
+    path: PathBuf,
+}
+
+impl WorldLock {
+    /// Locks the world. Fails with [`Error::WorldLocked`] if another process holds the lock. Locks of processes that
+    /// no longer exist (e.g. killed runs) are taken over where this can be determined (Linux), otherwise they have to be
+    /// removed with [`unlock`]. Since process ids are reused, e.g. every container starts with 1, the lock records when
+    /// its process was started as well.
+    pub fn acquire(world_folder: &Path) -> Result<Self, Error> {
+        let folder = world_folder.join(STATE_FOLDER);
+        fs::create_dir_all(&folder)?;
+        let path = folder.join(LOCK_FILE);
+        // a stale or just released lock is created again
+        for _ in 0..2 {
+            match File::options().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    let pid = std::process::id();
+                    match process_start(pid) {
+                        Some(start) => write!(file, "{pid} {start}")?,
+                        None => write!(file, "{pid}")?,
+                    }
+                    return Ok(Self { path });
+                }
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    let content = match fs::read_to_string(&path) {
+                        Ok(content) => content,
+                        Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                        Err(err) => return Err(err.into()),
+                    };
+                    // locks written by older versions only contain the id
+                    let mut parts = content.split_whitespace();
+                    // `None` if the lock is still being written
+                    let pid = parts.next().and_then(|pid| pid.parse().ok());
+                    let start = parts.next();
+                    if pid.and_then(|pid| process_exists(pid, start)) != Some(false) {
+                        return Err(Error::WorldLocked { path, pid });
+                    }
+                    fs::remove_file(&path)?;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Err(Error::WorldLocked { path, pid: None })
+    }
+}
+
+impl Drop for WorldLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+        if let Some(folder) = self.path.parent() {
+            // only succeeds if nothing else is left in the folder
+            let _ = fs::remove_dir(folder);
+        }
+    }
+}
+
+/// Removes the lock of the world, e.g. after a run was killed on a platform where stale locks can't be detected.
+/// Returns whether the world was locked.
+///
+/// Only do this if no other lessanvil process is working on the world.
+pub fn unlock(world_folder: &Path) -> Result<bool, Error> {
+    let folder = world_folder.join(STATE_FOLDER);
+    match fs::remove_file(folder.join(LOCK_FILE)) {
+        Ok(()) => {
+            let _ = fs::remove_dir(folder);
+            Ok(true)
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Whether a process with the id is running, if this can be determined. If the start of the process is known (see
+/// [`process_start`]), a process that reused the id doesn't count.
+fn process_exists(pid: u32, start: Option<&str>) -> Option<bool> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let current = process_start(pid);
+    Some(match start {
+        Some(start) => current.as_deref() == Some(start),
+        None => current.is_some(),
+    })
+}
+
+/// When the process with the id was started (Linux only): the boot id of the system and the time since the boot in
+/// clock ticks, e.g. `3f5c1c1e-8b9e-4b0e-9c1a-5a3e2b1d0c4f/123456`. `None` if the process doesn't exist.
+fn process_start(pid: u32) -> Option<String> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let stat = fs::read_to_string(Path::new("/proc").join(pid.to_string()).join("stat")).ok()?;
+    // the name of the process is in parentheses and may contain spaces, the start time is the 22nd field
+    let (_, fields) = stat.rsplit_once(')')?;
+    let start = fields.split_whitespace().nth(19)?;
+    let boot_id = fs::read_to_string("/proc/sys/kernel/random/boot_id").unwrap_or_default();
+    Some(format!("{}/{start}", boot_id.trim()))
+}
@@ -0,0 +1,73 @@
+//! Limiting the chunk data held in memory by all workers together, see [`crate::Config::max_memory`].
+
+use std::sync::{Condvar, Mutex};
+
+/// Tracks the bytes reserved by the workers and makes them wait while the budget is used up.
+#[derive(Default)]
+pub(crate) struct MemoryBudget {
+    /// The maximum amount of bytes, unlimited if `None`.
+    max: Option<u64>,
+    used: Mutex<u64>,
+    released: Condvar,
+}
+
+impl MemoryBudget {
+    pub(crate) fn new(max: Option<u64>) -> Self {
+        Self {
+            max,
+            ..Default::default()
+        }
+    }
+
+    /// Reserves the bytes, waiting until enough of the budget is free. A reservation larger than the whole budget
+    /// only waits until nothing else is reserved, so it can't block forever.
+    pub(crate) fn reserve(&self, bytes: u64) -> Reservation<'_> {
+        let Some(max) = self.max else {
+            return Reservation {
+                budget: self,
+                bytes: 0,
+            };
+        };
+        let mut used = self.used.lock().unwrap();
+        while *used > 0 && used.saturating_add(bytes) > max {
+            used = self.released.wait(used).unwrap();
+        }
+        *used += bytes;
+        Reservation {
+            budget: self,
+            bytes,
+        }
+    }
+
+    fn release(&self, bytes: u64) {
+        if bytes == 0 {
+            return;
+        }
+        *self.used.lock().unwrap() -= bytes;
+        self.released.notify_all();
+    }
+}
+
+/// Bytes reserved from a [`MemoryBudget`], released when dropped.
+pub(crate) struct Reservation<'a> {
+    budget: &'a MemoryBudget,
+    bytes: u64,
+}
+
+impl Reservation<'_> {
+    /// Grows the reservation to the given size if it's smaller, e.g. once the actual size of data that was estimated
+    /// beforehand is known. Doesn't wait, as the memory is already in use at that point.
+    pub(crate) fn grow_to(&mut self, bytes: u64) {
+        if self.budget.max.is_none() || bytes <= self.bytes {
+            return;
+        }
+        *self.budget.used.lock().unwrap() += bytes - self.bytes;
+        self.bytes = bytes;
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        self.budget.release(self.bytes);
+    }
+}
@@ -0,0 +1,115 @@
+//! Deserializing NBT from untrusted files.
+
+use serde::de::{DeserializeOwned, Error as _};
+
+/// The deepest nesting of compounds and lists the game itself accepts.
+const MAX_DEPTH: usize = 512;
+
+/// Deserializes NBT like [`fastnbt::from_bytes`], but rejects data nested deeper than the game allows and negative
+/// lengths up front instead of overflowing the stack or allocating for them while deserializing.
+pub(crate) fn from_bytes<T: DeserializeOwned>(data: &[u8]) -> Result<T, fastnbt::error::Error> {
+    match scan(data, MAX_DEPTH) {
+        Err(Stop::TooDeep) => Err(fastnbt::error::Error::custom("NBT nested too deeply")),
+        Err(Stop::NegativeLength) => Err(fastnbt::error::Error::custom("negative NBT length")),
+        // anything else wrong with the data is reported by fastnbt
+        Ok(()) | Err(Stop::Invalid) => fastnbt::from_bytes(data),
+    }
+}
+
+/// Why [`scan`] stopped early.
+enum Stop {
+    TooDeep,
+    NegativeLength,
+    /// Truncated data or an unknown tag id.
+    Invalid,
+}
+
+fn take<'a>(data: &mut &'a [u8], n: usize) -> Result<&'a [u8], Stop> {
+    if data.len() < n {
+        return Err(Stop::Invalid);
+    }
+    let (head, tail) = data.split_at(n);
+    *data = tail;
+    Ok(head)
+}
+
+fn take_u8(data: &mut &[u8]) -> Result<u8, Stop> {
+    take(data, 1).map(|bytes| bytes[0])
+}
+
+/// Reads the length prefix of an array or list.
+fn take_len(data: &mut &[u8]) -> Result<usize, Stop> {
+    let bytes = take(data, 4)?;
+    usize::try_from(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .map_err(|_| Stop::NegativeLength)
+}
+
+fn skip_string(data: &mut &[u8]) -> Result<(), Stop> {
+    let bytes = take(data, 2)?;
+    take(data, u16::from_be_bytes([bytes[0], bytes[1]]) as usize).map(|_| ())
+}
+
+/// Skips the payload of a tag that can't contain other tags.
+fn skip_payload(data: &mut &[u8], id: u8) -> Result<(), Stop> {
+    let size = match id {
+        1 => 1,
+        2 => 2,
+        3 | 5 => 4,
+        4 | 6 => 8,
+        7 => take_len(data)?,
+        8 => return skip_string(data),
+        11 => take_len(data)?.saturating_mul(4),
+        12 => take_len(data)?.saturating_mul(8),
+        _ => return Err(Stop::Invalid),
+    };
+    take(data, size).map(|_| ())
+}
+
+/// Walks the tags of the NBT without recursion, checking that compounds and lists are nested at most `max` deep and
+/// that no length is negative.
+fn scan(mut data: &[u8], max: usize) -> Result<(), Stop> {
+    // `None` for a compound, otherwise the element id of a list and the amount of elements left
+    let mut stack: Vec<Option<(u8, usize)>> = vec![];
+    let mut next = take_u8(&mut data)?;
+    skip_string(&mut data)?;
+    loop {
+        match next {
+            10 => stack.push(None),
+            9 => {
+                let id = take_u8(&mut data)?;
+                let len = take_len(&mut data)?;
+                // lists of end tags are empty, whatever their length claims
+                stack.push(Some((id, if id == 0 { 0 } else { len })));
+            }
+            id => skip_payload(&mut data, id)?,
+        }
+        if stack.len() > max {
+            return Err(Stop::TooDeep);
+        }
+
+        // find the next tag, leaving the compounds and lists that are done
+        loop {
+            match stack.last_mut() {
+                None => return Ok(()),
+                Some(None) => {
+                    let id = take_u8(&mut data)?;
+                    if id == 0 {
+                        stack.pop();
+                        continue;
+                    }
+                    skip_string(&mut data)?;
+                    next = id;
+                    break;
+                }
+                Some(Some((_, 0))) => {
+                    stack.pop();
+                }
+                Some(Some((id, remaining))) => {
+                    *remaining -= 1;
+                    next = *id;
+                    break;
+                }
+            }
+        }
+    }
+}
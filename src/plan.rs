@@ -0,0 +1,347 @@
+//! Deletion plans: the exact chunks to delete, determined once (e.g. against a read-only backup of the world) and
+//! applied to the world later via [`Config::planned_chunks`].
+
+use crate::cleanup::read_nbt;
+use crate::{
+    config_region_folders, region, ChunkPos, Config, Dimension, Error, InhabitedTime, NetherRoof,
+};
+use fastnbt::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// The version of the plans written by [`write_plan`].
+pub const PLAN_VERSION: u32 = 2;
+
+/// The first line of plans of version 1, which only list the chunks.
+const PLAN_V1_HEADER: &str = "# lessanvil plan v1";
+
+/// The chunks to delete together with what they were determined by.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Plan {
+    /// The version of the format, see [`PLAN_VERSION`].
+    pub version: u32,
+    /// The settings the chunks were selected with. `None` for plans of version 1.
+    pub filter: Option<PlanFilter>,
+    /// The world the plan was made for. `None` for plans of version 1.
+    pub world: Option<WorldFingerprint>,
+    /// The chunks to delete.
+    pub chunks: Vec<PlannedChunk>,
+}
+
+/// The settings a [`Plan`] was made with, for reviewing it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanFilter {
+    /// See [`Config::max_inhabited_time`].
+    pub max_inhabited_time: InhabitedTime,
+    /// [`Config::not_touched_since`] in seconds since the unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_touched_since: Option<u64>,
+    /// See [`Config::dimensions`].
+    pub dimensions: Vec<Dimension>,
+    /// The amount of [`Config::protected_areas`].
+    pub protected_areas: usize,
+    /// See [`Config::player_blocks`].
+    pub player_blocks: Vec<String>,
+    /// Whether chunks with blocks above the nether roof were protected, see [`NetherRoof::Protect`].
+    pub protect_nether_roof: bool,
+}
+
+impl PlanFilter {
+    /// The filter of the config.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_inhabited_time: config.max_inhabited_time,
+            not_touched_since: config.not_touched_since.map(|time| {
+                time.duration_since(UNIX_EPOCH)
+                    .map_or(0, |duration| duration.as_secs())
+            }),
+            dimensions: config.dimensions.clone(),
+            protected_areas: config.protected_areas.len(),
+            player_blocks: config.player_blocks.clone(),
+            protect_nether_roof: config.nether_roof == Some(NetherRoof::Protect),
+        }
+    }
+}
+
+/// Identifies a world, based on its `level.dat`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorldFingerprint {
+    /// The name of the world.
+    pub level_name: Option<String>,
+    /// The seed of the world.
+    pub seed: Option<i64>,
+}
+
+impl WorldFingerprint {
+    /// Reads the fingerprint from the `level.dat` of the world. Missing values are `None`.
+    pub fn read(world_folder: &Path) -> Result<Self, Error> {
+        let path = world_folder.join("level.dat");
+        if !path.try_exists().is_ok_and(|b| b) {
+            return Ok(Self::default());
+        }
+        let Value::Compound(root) = read_nbt(&path)? else {
+            return Ok(Self::default());
+        };
+        let Some(Value::Compound(data)) = root.get("Data") else {
+            return Ok(Self::default());
+        };
+        // 1.16 moved the seed into the world generation settings
+        let seed = match data.get("WorldGenSettings") {
+            Some(Value::Compound(settings)) => settings.get("seed"),
+            _ => data.get("RandomSeed"),
+        };
+        Ok(Self {
+            level_name: data
+                .get("LevelName")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            seed: seed.and_then(Value::as_i64),
+        })
+    }
+
+    /// Whether both fingerprints may belong to the same world. Worlds are only told apart by their seed, as copies of
+    /// a world may be renamed.
+    pub fn matches(&self, other: &WorldFingerprint) -> bool {
+        match (self.seed, other.seed) {
+            (Some(a), Some(b)) => a == b,
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for WorldFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.level_name, self.seed) {
+            (Some(name), Some(seed)) => write!(f, "`{name}` with seed {seed}"),
+            (Some(name), None) => write!(f, "`{name}`"),
+            (None, Some(seed)) => write!(f, "seed {seed}"),
+            (None, None) => f.write_str("unknown"),
+        }
+    }
+}
+
+/// A chunk to delete.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedChunk {
+    /// The position of the chunk.
+    #[serde(flatten)]
+    pub position: ChunkPos,
+    /// When the chunk was last saved when the plan was made, in seconds since the unix epoch. See [`check_plan`].
+    pub timestamp: Option<u32>,
+}
+
+impl Plan {
+    /// Makes a plan to delete the chunks of the world of the config, recording the world and the last time each chunk
+    /// was saved.
+    pub fn new(config: &Config, chunks: impl IntoIterator<Item = ChunkPos>) -> Result<Self, Error> {
+        let mut chunks = chunks.into_iter().collect::<Vec<_>>();
+        chunks.sort();
+        let timestamps = timestamps(config, &chunks)?;
+        Ok(Self {
+            version: PLAN_VERSION,
+            filter: Some(PlanFilter::from_config(config)),
+            world: Some(WorldFingerprint::read(&config.world_folder)?),
+            chunks: chunks
+                .into_iter()
+                .map(|position| PlannedChunk {
+                    timestamp: timestamps.get(&position).copied(),
+                    position,
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Writes a plan as JSON.
+pub fn write_plan(plan: &Plan, writer: impl Write) -> io::Result<()> {
+    serde_json::to_writer_pretty(writer, plan)?;
+    Ok(())
+}
+
+/// Reads a plan written by [`write_plan`]. Plans of version 1 are supported as well.
+pub fn read_plan(path: &Path) -> Result<Plan, Error> {
+    let content = fs::read_to_string(path)?;
+    let invalid = |line: usize, message: &str| Error::InvalidPlan {
+        path: path.to_path_buf(),
+        line,
+        message: message.to_string(),
+    };
+
+    if content.lines().next() == Some(PLAN_V1_HEADER) {
+        return read_plan_v1(&content, invalid);
+    }
+    let plan: Plan =
+        serde_json::from_str(&content).map_err(|err| invalid(err.line(), &err.to_string()))?;
+    if plan.version > PLAN_VERSION {
+        return Err(invalid(
+            1,
+            "plan was written by a newer version of lessanvil",
+        ));
+    }
+    Ok(plan)
+}
+
+fn read_plan_v1(content: &str, invalid: impl Fn(usize, &str) -> Error) -> Result<Plan, Error> {
+    let mut chunks = vec![];
+    for (i, line) in content.lines().enumerate().skip(1) {
+        let [dimension, x, z] = line.split(' ').collect::<Vec<_>>()[..] else {
+            return Err(invalid(i + 1, "expected `<dimension> <x> <z>`"));
+        };
+        chunks.push(PlannedChunk {
+            position: ChunkPos {
+                dimension: dimension
+                    .parse()
+                    .map_err(|_| invalid(i + 1, "unknown dimension"))?,
+                x: x.parse()
+                    .map_err(|_| invalid(i + 1, "invalid x coordinate"))?,
+                z: z.parse()
+                    .map_err(|_| invalid(i + 1, "invalid z coordinate"))?,
+            },
+            timestamp: None,
+        });
+    }
+    Ok(Plan {
+        version: 1,
+        filter: None,
+        world: None,
+        chunks,
+    })
+}
+
+/// The result of [`check_plan`].
+#[derive(Clone, Debug, Default)]
+pub struct PlanCheck {
+    /// The chunks that are unchanged since the plan was made and can be deleted. Pass them to
+    /// [`Config::planned_chunks`].
+    pub unchanged: HashSet<ChunkPos>,
+    /// The chunks that were saved again or removed since the plan was made.
+    pub changed: Vec<ChunkPos>,
+}
+
+/// Checks the plan against the current state of the world of the config: fails with [`Error::PlanWorldMismatch`] if
+/// the plan was made for another world, and sorts out the chunks that were saved since the plan was made, e.g. because
+/// players visited them in the meantime. Chunks without a timestamp are considered unchanged.
+pub fn check_plan(config: &Config, plan: &Plan) -> Result<PlanCheck, Error> {
+    if let Some(expected) = &plan.world {
+        let actual = WorldFingerprint::read(&config.world_folder)?;
+        if !expected.matches(&actual) {
+            return Err(Error::PlanWorldMismatch {
+                expected: expected.clone(),
+                actual,
+            });
+        }
+    }
+
+    let positions = plan
+        .chunks
+        .iter()
+        .map(|chunk| chunk.position.clone())
+        .collect::<Vec<_>>();
+    let timestamps = timestamps(config, &positions)?;
+    let mut check = PlanCheck::default();
+    for chunk in &plan.chunks {
+        match chunk.timestamp {
+            Some(expected) if timestamps.get(&chunk.position) != Some(&expected) => {
+                check.changed.push(chunk.position.clone())
+            }
+            _ => {
+                check.unchanged.insert(chunk.position.clone());
+            }
+        }
+    }
+    Ok(check)
+}
+
+/// How the chunks of a plan differ from the ones of an earlier plan, see [`compare_plans`].
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanComparison {
+    /// The chunks that newly qualify for deletion, e.g. because of a higher threshold or because they were generated
+    /// since the earlier plan was made. Sorted.
+    pub added: Vec<ChunkPos>,
+    /// The chunks that no longer qualify for deletion, e.g. because players spent time in them or because of a lower
+    /// threshold. Sorted.
+    pub removed: Vec<ChunkPos>,
+    /// The amount of chunks in both plans.
+    pub unchanged: usize,
+}
+
+/// Compares the chunks of a plan with the ones of an earlier plan of the same world.
+pub fn compare_plans(earlier: &Plan, plan: &Plan) -> PlanComparison {
+    let before = earlier
+        .chunks
+        .iter()
+        .map(|chunk| &chunk.position)
+        .collect::<HashSet<_>>();
+    let after = plan
+        .chunks
+        .iter()
+        .map(|chunk| &chunk.position)
+        .collect::<HashSet<_>>();
+    let mut added = after
+        .difference(&before)
+        .map(|&position| position.clone())
+        .collect::<Vec<_>>();
+    let mut removed = before
+        .difference(&after)
+        .map(|&position| position.clone())
+        .collect::<Vec<_>>();
+    added.sort();
+    removed.sort();
+    PlanComparison {
+        added,
+        removed,
+        unchanged: after.intersection(&before).count(),
+    }
+}
+
+/// The timestamps of the chunks that exist in the region folders of the config, read from the region headers.
+fn timestamps(config: &Config, chunks: &[ChunkPos]) -> Result<HashMap<ChunkPos, u32>, Error> {
+    let mut folders = BTreeMap::<&Dimension, Vec<PathBuf>>::new();
+    let region_folders = config_region_folders(config, &mut vec![])?;
+    for (dimension, folder) in &region_folders {
+        folders.entry(dimension).or_default().push(folder.clone());
+    }
+
+    let mut regions = BTreeMap::<(&Dimension, i32, i32), Vec<&ChunkPos>>::new();
+    for chunk in chunks {
+        regions
+            .entry((&chunk.dimension, chunk.x >> 5, chunk.z >> 5))
+            .or_default()
+            .push(chunk);
+    }
+
+    let mut timestamps = HashMap::new();
+    for ((dimension, x, z), chunks) in regions {
+        let name = format!("r.{x}.{z}.mca");
+        let Some(path) = folders
+            .get(dimension)
+            .into_iter()
+            .flatten()
+            .map(|folder| folder.join(&name))
+            .find(|path| path.try_exists().is_ok_and(|b| b))
+        else {
+            continue;
+        };
+        let header = region::Header::read(File::open(path)?)?;
+        for chunk in chunks {
+            let location = header.location(
+                chunk.x.rem_euclid(32) as usize,
+                chunk.z.rem_euclid(32) as usize,
+            );
+            if location.is_present() {
+                timestamps.insert(chunk.clone(), location.timestamp);
+            }
+        }
+    }
+    Ok(timestamps)
+}
@@ -0,0 +1,186 @@
+//! Pruning the data of players.
+
+use crate::cleanup::{read_nbt, write_nbt};
+use crate::Error;
+use fastnbt::Value;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What [`remove_orphaned_files`] removed.
+#[derive(Default, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct OrphanReport {
+    /// The amount of `advancements/<uuid>.json` files removed.
+    pub removed_advancements: u64,
+    /// The amount of `stats/<uuid>.json` files removed.
+    pub removed_stats: u64,
+}
+
+/// Removes the advancements and statistics of players that have no `playerdata/<uuid>.dat` file (anymore), e.g.
+/// because their player data was deleted.
+pub fn remove_orphaned_files(world_folder: &Path) -> Result<OrphanReport, Error> {
+    remove_orphaned_files_except(world_folder, &HashSet::new())
+}
+
+/// Like [`remove_orphaned_files`], but keeps the files of the players with the given UUIDs, e.g. the whitelisted
+/// players and operators of the server.
+pub fn remove_orphaned_files_except(
+    world_folder: &Path,
+    kept: &HashSet<String>,
+) -> Result<OrphanReport, Error> {
+    orphaned_files(world_folder, kept, true)
+}
+
+/// Counts the files [`remove_orphaned_files_except`] would remove, without removing them.
+pub fn find_orphaned_files_except(
+    world_folder: &Path,
+    kept: &HashSet<String>,
+) -> Result<OrphanReport, Error> {
+    orphaned_files(world_folder, kept, false)
+}
+
+fn orphaned_files(
+    world_folder: &Path,
+    kept: &HashSet<String>,
+    remove: bool,
+) -> Result<OrphanReport, Error> {
+    if !world_folder.try_exists().is_ok_and(|b| b) {
+        return Err(Error::WorldFolderNotFound);
+    }
+
+    // without any player data every file would look orphaned
+    let player_data = world_folder.join("playerdata");
+    if !player_data.try_exists().is_ok_and(|b| b) {
+        return Ok(OrphanReport::default());
+    }
+    let mut players = uuids(&player_data, "dat")?;
+    players.extend(kept.iter().map(OsString::from));
+    Ok(OrphanReport {
+        removed_advancements: remove_orphans(&world_folder.join("advancements"), &players, remove)?,
+        removed_stats: remove_orphans(&world_folder.join("stats"), &players, remove)?,
+    })
+}
+
+/// What [`remove_scores`] removed.
+#[derive(Default, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ScoreboardReport {
+    /// The amount of scores removed.
+    pub removed_scores: u64,
+    /// The amount of players removed from teams.
+    pub removed_team_members: u64,
+    /// The copy of the scoreboard before it was changed, `None` if nothing was removed.
+    pub backup: Option<PathBuf>,
+}
+
+/// Removes the scores and team memberships of the players (by name, as the scoreboard stores them) from
+/// `data/scoreboard.dat`, e.g. of players whose player data was deleted. Objectives and teams are kept, even if they end
+/// up empty. The original file is copied to `data/scoreboard.dat.bak` before it's changed.
+pub fn remove_scores(
+    world_folder: &Path,
+    players: &HashSet<String>,
+) -> Result<ScoreboardReport, Error> {
+    scores(world_folder, players, true)
+}
+
+/// Counts the scores and team memberships [`remove_scores`] would remove, without changing the scoreboard.
+pub fn find_scores(
+    world_folder: &Path,
+    players: &HashSet<String>,
+) -> Result<ScoreboardReport, Error> {
+    scores(world_folder, players, false)
+}
+
+fn scores(
+    world_folder: &Path,
+    players: &HashSet<String>,
+    remove: bool,
+) -> Result<ScoreboardReport, Error> {
+    if !world_folder.try_exists().is_ok_and(|b| b) {
+        return Err(Error::WorldFolderNotFound);
+    }
+    let path = world_folder.join("data").join("scoreboard.dat");
+    if players.is_empty() || !path.try_exists().is_ok_and(|b| b) {
+        return Ok(ScoreboardReport::default());
+    }
+
+    let mut nbt = read_nbt(&path)?;
+    let Value::Compound(root) = &mut nbt else {
+        return Ok(ScoreboardReport::default());
+    };
+    let Some(Value::Compound(data)) = root.get_mut("data") else {
+        return Ok(ScoreboardReport::default());
+    };
+
+    let mut report = ScoreboardReport::default();
+    if let Some(Value::List(scores)) = data.get_mut("PlayerScores") {
+        let before = scores.len();
+        scores.retain(|score| match score {
+            Value::Compound(score) => !score
+                .get("Name")
+                .and_then(Value::as_str)
+                .is_some_and(|name| players.contains(name)),
+            _ => true,
+        });
+        report.removed_scores = (before - scores.len()) as u64;
+    }
+    if let Some(Value::List(teams)) = data.get_mut("Teams") {
+        for team in teams {
+            let Value::Compound(team) = team else {
+                continue;
+            };
+            let Some(Value::List(members)) = team.get_mut("Players") else {
+                continue;
+            };
+            let before = members.len();
+            members.retain(|member| !member.as_str().is_some_and(|name| players.contains(name)));
+            report.removed_team_members += (before - members.len()) as u64;
+        }
+    }
+
+    if remove && (report.removed_scores > 0 || report.removed_team_members > 0) {
+        let backup = path.with_extension("dat.bak");
+        fs::copy(&path, &backup)?;
+        write_nbt(&path, &nbt)?;
+        report.backup = Some(backup);
+    }
+    Ok(report)
+}
+
+/// The file stems of all files with the extension in the folder.
+fn uuids(folder: &Path, extension: &str) -> Result<HashSet<OsString>, Error> {
+    let mut uuids = HashSet::new();
+    if !folder.try_exists().is_ok_and(|b| b) {
+        return Ok(uuids);
+    }
+    for entry in folder.read_dir()? {
+        let path = entry?.path();
+        if let (Some(stem), true) = (
+            path.file_stem(),
+            path.extension().is_some_and(|ext| ext == extension),
+        ) {
+            uuids.insert(stem.to_owned());
+        }
+    }
+    Ok(uuids)
+}
+
+/// Removes the files of the players missing from `players`, or only counts them unless `remove` is set.
+fn remove_orphans(folder: &Path, players: &HashSet<OsString>, remove: bool) -> Result<u64, Error> {
+    let mut removed = 0;
+    for uuid in uuids(folder, "json")? {
+        if !players.contains(&uuid) {
+            if remove {
+                let mut name = uuid;
+                name.push(".json");
+                fs::remove_file(folder.join(name))?;
+            }
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
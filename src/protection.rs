@@ -0,0 +1,557 @@
+//! Areas of a world in which chunks will never be deleted, regardless of their InhabitedTime.
+
+use crate::{Dimension, Error};
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+/// A block position in a dimension.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Position {
+    /// The dimension the position is in.
+    pub dimension: Dimension,
+    /// The x block coordinate.
+    pub x: i32,
+    /// The z block coordinate.
+    pub z: i32,
+}
+
+impl Position {
+    /// The coordinates of the chunk containing this position.
+    pub fn chunk(&self) -> (i32, i32) {
+        (self.x >> 4, self.z >> 4)
+    }
+}
+
+/// An area in which no chunk will be deleted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProtectedArea {
+    /// All chunks within `radius` chunks (in both directions) of the chunk containing `position`.
+    Around {
+        /// The center of the area.
+        position: Position,
+        /// The radius in chunks.
+        radius: u32,
+    },
+    /// All chunks overlapping the rectangle between the two block positions (inclusive).
+    Rectangle {
+        /// The dimension the rectangle is in.
+        dimension: Dimension,
+        /// One corner of the rectangle as `(x, z)` block coordinates.
+        from: (i32, i32),
+        /// The opposite corner of the rectangle as `(x, z)` block coordinates.
+        to: (i32, i32),
+    },
+}
+
+impl ProtectedArea {
+    /// Whether the chunk at the given absolute chunk coordinates is inside the area.
+    pub fn contains(&self, dimension: &Dimension, chunk_x: i32, chunk_z: i32) -> bool {
+        match self {
+            ProtectedArea::Around { position, radius } => {
+                let (x, z) = position.chunk();
+                position.dimension == *dimension
+                    && x.abs_diff(chunk_x) <= *radius
+                    && z.abs_diff(chunk_z) <= *radius
+            }
+            ProtectedArea::Rectangle {
+                dimension: area_dimension,
+                from,
+                to,
+            } => {
+                let (min_x, max_x) = (from.0.min(to.0) >> 4, from.0.max(to.0) >> 4);
+                let (min_z, max_z) = (from.1.min(to.1) >> 4, from.1.max(to.1) >> 4);
+                area_dimension == dimension
+                    && (min_x..=max_x).contains(&chunk_x)
+                    && (min_z..=max_z).contains(&chunk_z)
+            }
+        }
+    }
+
+    /// The dimension the area is in.
+    pub fn dimension(&self) -> &Dimension {
+        match self {
+            ProtectedArea::Around { position, .. } => &position.dimension,
+            ProtectedArea::Rectangle { dimension, .. } => dimension,
+        }
+    }
+
+    /// The smallest rectangle of chunks containing the area, as the `(x, z)` chunk coordinates of its north-west and
+    /// south-east corner (inclusive).
+    pub fn chunk_bounds(&self) -> ((i32, i32), (i32, i32)) {
+        match self {
+            ProtectedArea::Around { position, radius } => {
+                let (x, z) = position.chunk();
+                (
+                    (
+                        x.saturating_sub_unsigned(*radius),
+                        z.saturating_sub_unsigned(*radius),
+                    ),
+                    (
+                        x.saturating_add_unsigned(*radius),
+                        z.saturating_add_unsigned(*radius),
+                    ),
+                )
+            }
+            ProtectedArea::Rectangle { from, to, .. } => (
+                (from.0.min(to.0) >> 4, from.1.min(to.1) >> 4),
+                (from.0.max(to.0) >> 4, from.1.max(to.1) >> 4),
+            ),
+        }
+    }
+
+    /// Whether any chunk of the region at the given region coordinates is inside the area.
+    pub(crate) fn intersects_region(
+        &self,
+        dimension: &Dimension,
+        region_x: i32,
+        region_z: i32,
+    ) -> bool {
+        match self {
+            ProtectedArea::Around { position, radius } => {
+                let (x, z) = position.chunk();
+                let radius = *radius as i64;
+                let (min_x, min_z) = (region_x as i64 * 32, region_z as i64 * 32);
+                position.dimension == *dimension
+                    && (x as i64 + radius) >= min_x
+                    && (x as i64 - radius) < min_x + 32
+                    && (z as i64 + radius) >= min_z
+                    && (z as i64 - radius) < min_z + 32
+            }
+            ProtectedArea::Rectangle {
+                dimension: area_dimension,
+                from,
+                to,
+            } => {
+                let (min_x, max_x) = (from.0.min(to.0) >> 9, from.0.max(to.0) >> 9);
+                let (min_z, max_z) = (from.1.min(to.1) >> 9, from.1.max(to.1) >> 9);
+                area_dimension == dimension
+                    && (min_x..=max_x).contains(&region_x)
+                    && (min_z..=max_z).contains(&region_z)
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PlayerData {
+    #[serde(rename = "Pos")]
+    pos: Vec<f64>,
+    #[serde(rename = "Dimension")]
+    dimension: fastnbt::Value,
+}
+
+/// Reads the last known position of every player from the `playerdata` folder of the world.
+pub fn player_positions(world_folder: &Path) -> Result<Vec<Position>, Error> {
+    read_player_positions(world_folder, |_| true)
+}
+
+/// Reads the last known position of the players with the given UUIDs (e.g. the operators of the server) from the
+/// `playerdata` folder of the world. Players without player data are skipped.
+pub fn positions_of_players(
+    world_folder: &Path,
+    uuids: &HashSet<String>,
+) -> Result<Vec<Position>, Error> {
+    read_player_positions(world_folder, |uuid| uuids.contains(uuid))
+}
+
+fn read_player_positions(
+    world_folder: &Path,
+    include: impl Fn(&str) -> bool,
+) -> Result<Vec<Position>, Error> {
+    let folder = world_folder.join("playerdata");
+    if !folder.try_exists().is_ok_and(|b| b) {
+        return Ok(vec![]);
+    }
+
+    let mut positions = vec![];
+    for entry in folder.read_dir()? {
+        let path = entry?.path();
+        if !path.extension().is_some_and(|ext| ext == "dat")
+            || !path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .is_some_and(&include)
+        {
+            continue;
+        }
+
+        let mut data = vec![];
+        GzDecoder::new(fs::File::open(&path)?).read_to_end(&mut data)?;
+        let player: PlayerData =
+            crate::nbt::from_bytes(&data).map_err(|source| Error::InvalidPlayerData {
+                path: path.clone(),
+                source,
+            })?;
+
+        let dimension = match player.dimension {
+            fastnbt::Value::String(id) => id.parse().ok(),
+            fastnbt::Value::Int(id) => Dimension::from_legacy_id(id),
+            _ => None,
+        };
+        if let (Some(dimension), [x, _, z]) = (dimension, player.pos.as_slice()) {
+            positions.push(Position {
+                dimension,
+                x: x.floor() as i32,
+                z: z.floor() as i32,
+            });
+        }
+    }
+    Ok(positions)
+}
+
+/// Reads positions from a CSV file with one `dimension,x,z` entry (block coordinates) per line.
+///
+/// Empty lines and lines starting with `#` are ignored.
+pub fn positions_from_csv(path: &Path) -> Result<Vec<Position>, Error> {
+    let content = fs::read_to_string(path)?;
+    let mut positions = vec![];
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |message: &str| Error::InvalidProtectionFile {
+            path: path.to_path_buf(),
+            line: i + 1,
+            message: message.to_string(),
+        };
+
+        let [dimension, x, z] = line.split(',').map(str::trim).collect::<Vec<_>>()[..] else {
+            return Err(invalid("expected `dimension,x,z`"));
+        };
+        positions.push(Position {
+            dimension: dimension
+                .parse()
+                .map_err(|_| invalid("unknown dimension"))?,
+            x: x.parse::<f64>()
+                .map_err(|_| invalid("invalid x coordinate"))?
+                .floor() as i32,
+            z: z.parse::<f64>()
+                .map_err(|_| invalid("invalid z coordinate"))?
+                .floor() as i32,
+        });
+    }
+    Ok(positions)
+}
+
+/// Reads protected areas exported from a mapping tool or drawn by hand. Files ending in `.geojson` or `.json` are
+/// read as GeoJSON (see [`areas_from_geojson`]), all others as CSV (see [`areas_from_csv`]).
+pub fn areas_from_file(path: &Path) -> Result<Vec<ProtectedArea>, Error> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        #[cfg(feature = "geojson")]
+        Some("geojson" | "json") => areas_from_geojson(path),
+        #[cfg(not(feature = "geojson"))]
+        Some("geojson" | "json") => Err(Error::InvalidProtectionFile {
+            path: path.to_path_buf(),
+            line: 1,
+            message: "lessanvil was built without the `geojson` feature".to_string(),
+        }),
+        _ => areas_from_csv(path),
+    }
+}
+
+/// Reads rectangles from a CSV file with one `x1,z1,x2,z2` entry (the block coordinates of two opposite corners) per
+/// line. An entry may start with the dimension (`dimension,x1,z1,x2,z2`), it's in the overworld otherwise.
+///
+/// Empty lines and lines starting with `#` are ignored.
+pub fn areas_from_csv(path: &Path) -> Result<Vec<ProtectedArea>, Error> {
+    let content = fs::read_to_string(path)?;
+    let mut areas = vec![];
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |message: &str| Error::InvalidProtectionFile {
+            path: path.to_path_buf(),
+            line: i + 1,
+            message: message.to_string(),
+        };
+
+        let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+        let (dimension, corners) = match fields[..] {
+            [x1, z1, x2, z2] => (Dimension::Overworld, [x1, z1, x2, z2]),
+            [dimension, x1, z1, x2, z2] => (
+                dimension
+                    .parse()
+                    .map_err(|_| invalid("unknown dimension"))?,
+                [x1, z1, x2, z2],
+            ),
+            _ => return Err(invalid("expected `x1,z1,x2,z2` or `dimension,x1,z1,x2,z2`")),
+        };
+        let mut coordinates = [0; 4];
+        for (coordinate, value) in coordinates.iter_mut().zip(corners) {
+            *coordinate = value
+                .parse::<f64>()
+                .map_err(|_| invalid(&format!("invalid coordinate `{value}`")))?
+                .floor() as i32;
+        }
+        let [x1, z1, x2, z2] = coordinates;
+        areas.push(ProtectedArea::Rectangle {
+            dimension,
+            from: (x1, z1),
+            to: (x2, z2),
+        });
+    }
+    Ok(areas)
+}
+
+/// Reads the polygons of a GeoJSON file: a `FeatureCollection`, a single `Feature` or a bare geometry, of the types
+/// `Polygon` and `MultiPolygon`. The coordinates are block coordinates as `[x, z]`, like the game shows them. A
+/// feature's dimension is taken from its `dimension` property and defaults to the overworld.
+///
+/// Every chunk a polygon touches is protected, including the chunks on the edges of its holes. Requires the `geojson`
+/// feature.
+#[cfg(feature = "geojson")]
+pub fn areas_from_geojson(path: &Path) -> Result<Vec<ProtectedArea>, Error> {
+    let content = fs::read_to_string(path)?;
+    let invalid = |line: usize, message: String| Error::InvalidProtectionFile {
+        path: path.to_path_buf(),
+        line,
+        message,
+    };
+    let root: serde_json::Value =
+        serde_json::from_str(&content).map_err(|err| invalid(err.line(), err.to_string()))?;
+    let kind = root
+        .get("type")
+        .and_then(|kind| kind.as_str())
+        .unwrap_or_default()
+        .to_string();
+    let features = match kind.as_str() {
+        "FeatureCollection" => root
+            .get("features")
+            .and_then(|features| features.as_array())
+            .cloned()
+            .ok_or_else(|| invalid(1, "a FeatureCollection needs `features`".to_string()))?,
+        "Feature" => vec![root],
+        _ => vec![serde_json::json!({ "type": "Feature", "geometry": root })],
+    };
+
+    let mut areas = vec![];
+    for (i, feature) in features.iter().enumerate() {
+        let invalid = |message: &str| invalid(1, format!("feature {}: {message}", i + 1));
+        let dimension = match feature
+            .get("properties")
+            .and_then(|properties| properties.get("dimension"))
+        {
+            Some(serde_json::Value::String(id)) => {
+                id.parse().map_err(|_| invalid("unknown dimension"))?
+            }
+            Some(serde_json::Value::Number(id)) => id
+                .as_i64()
+                .and_then(|id| Dimension::from_legacy_id(id as i32))
+                .ok_or_else(|| invalid("unknown dimension"))?,
+            _ => Dimension::Overworld,
+        };
+        let geometry = feature
+            .get("geometry")
+            .ok_or_else(|| invalid("missing `geometry`"))?;
+        let coordinates = geometry.get("coordinates").cloned().unwrap_or_default();
+        let polygons: Vec<Vec<Vec<[f64; 2]>>> =
+            match geometry.get("type").and_then(|kind| kind.as_str()) {
+                Some("Polygon") => vec![parse_coordinates(coordinates)
+                    .ok_or_else(|| invalid("invalid polygon coordinates"))?],
+                Some("MultiPolygon") => parse_coordinates(coordinates)
+                    .ok_or_else(|| invalid("invalid polygon coordinates"))?,
+                Some(kind) => {
+                    return Err(invalid(&format!(
+                        "unsupported geometry `{kind}`, only polygons can be protected"
+                    )))
+                }
+                None => return Err(invalid("missing geometry `type`")),
+            };
+        for rings in polygons {
+            areas.extend(polygon_areas(&dimension, &rings));
+        }
+    }
+    Ok(areas)
+}
+
+/// Parses GeoJSON coordinates, ignoring the altitude of positions.
+#[cfg(feature = "geojson")]
+fn parse_coordinates<T: serde::de::DeserializeOwned>(coordinates: serde_json::Value) -> Option<T> {
+    fn strip_altitude(value: serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::Array(values) if values.iter().all(|value| value.is_number()) => {
+                serde_json::Value::Array(values.into_iter().take(2).collect())
+            }
+            serde_json::Value::Array(values) => {
+                serde_json::Value::Array(values.into_iter().map(strip_altitude).collect())
+            }
+            value => value,
+        }
+    }
+    serde_json::from_value(strip_altitude(coordinates)).ok()
+}
+
+/// The chunks touched by the polygon (its outer ring followed by its holes, in block coordinates) as rectangles, one
+/// per run of chunks in a row.
+///
+/// A chunk touches the polygon if one of the edges passes through it or if its center row is inside of the polygon,
+/// which covers the chunks inside of it without testing every chunk of its bounding box.
+#[cfg(feature = "geojson")]
+fn polygon_areas(dimension: &Dimension, rings: &[Vec<[f64; 2]>]) -> Vec<ProtectedArea> {
+    let edges = rings
+        .iter()
+        .flat_map(|ring| {
+            ring.iter()
+                .zip(ring.iter().cycle().skip(1))
+                .map(|(&a, &b)| (a, b))
+        })
+        .collect::<Vec<_>>();
+    let Some(min_z) = edges.iter().map(|(a, _)| a[1]).reduce(f64::min) else {
+        return vec![];
+    };
+    let max_z = edges.iter().map(|(a, _)| a[1]).fold(min_z, f64::max);
+    let chunk = |block: f64| (block.floor() as i32) >> 4;
+
+    let mut areas = vec![];
+    for chunk_z in chunk(min_z)..=chunk(max_z) {
+        let (top, bottom) = (chunk_z as f64 * 16.0, chunk_z as f64 * 16.0 + 16.0);
+        // the columns of chunks touched in this row, as inclusive ranges
+        let mut columns = vec![];
+        for &([x1, z1], [x2, z2]) in &edges {
+            if z1.max(z2) < top || z1.min(z2) >= bottom {
+                continue;
+            }
+            let (from, to) = if z1 == z2 {
+                (x1, x2)
+            } else {
+                let at =
+                    |z: f64| x1 + (z.clamp(z1.min(z2), z1.max(z2)) - z1) * (x2 - x1) / (z2 - z1);
+                (at(top), at(bottom))
+            };
+            columns.push((chunk(from.min(to)), chunk(from.max(to))));
+        }
+        let center = top + 8.0;
+        let mut crossings = edges
+            .iter()
+            .filter(|([_, z1], [_, z2])| (*z1 <= center) != (*z2 <= center))
+            .map(|([x1, z1], [x2, z2])| x1 + (center - z1) * (x2 - x1) / (z2 - z1))
+            .collect::<Vec<_>>();
+        crossings.sort_by(f64::total_cmp);
+        for pair in crossings.chunks_exact(2) {
+            columns.push((chunk(pair[0]), chunk(pair[1])));
+        }
+
+        columns.sort();
+        let mut runs: Vec<(i32, i32)> = vec![];
+        for (from, to) in columns {
+            match runs.last_mut() {
+                Some(run) if from <= run.1.saturating_add(1) => run.1 = run.1.max(to),
+                _ => runs.push((from, to)),
+            }
+        }
+        areas.extend(runs.into_iter().map(|(from, to)| ProtectedArea::Rectangle {
+            dimension: dimension.clone(),
+            from: (from << 4, chunk_z << 4),
+            to: ((to << 4) + 15, (chunk_z << 4) + 15),
+        }));
+    }
+    areas
+}
+
+/// Reads waypoint positions from a minimap mod's waypoint file.
+///
+/// Supported are Xaero's Minimap `.txt` files (the dimension is taken from a `dim%<id>` parent
+/// folder and defaults to the overworld) and JourneyMap `.json` waypoint files containing either
+/// a single waypoint or a list of them. The latter require the `journeymap` feature.
+pub fn waypoints(path: &Path) -> Result<Vec<Position>, Error> {
+    let content = fs::read_to_string(path)?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+        #[cfg(feature = "journeymap")]
+        Some("json") => journeymap_waypoints(path, &content),
+        #[cfg(not(feature = "journeymap"))]
+        Some("json") => Err(Error::InvalidProtectionFile {
+            path: path.to_path_buf(),
+            line: 1,
+            message: "lessanvil was built without the `journeymap` feature".to_string(),
+        }),
+        _ => Ok(xaero_waypoints(path, &content)),
+    }
+}
+
+fn xaero_waypoints(path: &Path, content: &str) -> Vec<Position> {
+    let dimension = path
+        .ancestors()
+        .filter_map(|folder| folder.file_name()?.to_str()?.strip_prefix("dim%"))
+        .find_map(|id| Dimension::from_legacy_id(id.parse().ok()?))
+        .unwrap_or(Dimension::Overworld);
+
+    // waypoint:name:initials:x:y:z:color:disabled:type:set:...
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields = line
+                .strip_prefix("waypoint:")?
+                .split(':')
+                .collect::<Vec<_>>();
+            Some(Position {
+                dimension: dimension.clone(),
+                x: fields.get(2)?.parse().ok()?,
+                z: fields.get(4)?.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(feature = "journeymap")]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JourneyMapFile {
+    Single(JourneyMapWaypoint),
+    List(Vec<JourneyMapWaypoint>),
+}
+
+#[cfg(feature = "journeymap")]
+#[derive(Deserialize)]
+struct JourneyMapWaypoint {
+    x: f64,
+    z: f64,
+    #[serde(default)]
+    dimensions: Vec<serde_json::Value>,
+}
+
+#[cfg(feature = "journeymap")]
+fn journeymap_waypoints(path: &Path, content: &str) -> Result<Vec<Position>, Error> {
+    let waypoints = match serde_json::from_str(content) {
+        Ok(JourneyMapFile::Single(waypoint)) => vec![waypoint],
+        Ok(JourneyMapFile::List(waypoints)) => waypoints,
+        Err(err) => {
+            return Err(Error::InvalidProtectionFile {
+                path: path.to_path_buf(),
+                line: err.line(),
+                message: err.to_string(),
+            })
+        }
+    };
+
+    let mut positions = vec![];
+    for waypoint in waypoints {
+        let mut dimensions = waypoint
+            .dimensions
+            .iter()
+            .filter_map(|dimension| match dimension {
+                serde_json::Value::String(id) => id.parse().ok(),
+                serde_json::Value::Number(id) => Dimension::from_legacy_id(id.as_i64()? as i32),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if dimensions.is_empty() {
+            dimensions.push(Dimension::Overworld);
+        }
+
+        positions.extend(dimensions.into_iter().map(|dimension| Position {
+            dimension,
+            x: waypoint.x.floor() as i32,
+            z: waypoint.z.floor() as i32,
+        }));
+    }
+    Ok(positions)
+}
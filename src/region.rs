@@ -0,0 +1,547 @@
+//! Low-level access to the layout of region files, for what fastanvil doesn't expose.
+
+use crate::{compression, ChunkCompression, CustomCompression};
+use fastanvil::{CompressionScheme, Region};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// The size of a sector in bytes. Chunks are stored in whole sectors.
+pub(crate) const SECTOR_SIZE: u64 = 4096;
+
+/// The size of the header (chunk locations followed by timestamps) in bytes.
+pub(crate) const HEADER_SIZE: usize = 2 * SECTOR_SIZE as usize;
+
+/// Where a chunk is stored in a region file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Location {
+    /// The offset of the first sector of the chunk. 0 if the chunk doesn't exist.
+    pub(crate) offset: u32,
+    /// The amount of sectors the chunk occupies.
+    pub(crate) sectors: u8,
+    /// The time the chunk was last saved, in seconds since the unix epoch.
+    pub(crate) timestamp: u32,
+}
+
+impl Location {
+    /// Whether the region contains the chunk.
+    pub(crate) fn is_present(&self) -> bool {
+        self.offset != 0 && self.sectors != 0
+    }
+}
+
+/// The header of a region file.
+pub(crate) struct Header {
+    locations: Vec<Location>,
+}
+
+impl Header {
+    /// Reads the header from the beginning of a region file. Missing bytes of a truncated header are treated as 0.
+    pub(crate) fn read(reader: impl Read) -> io::Result<Self> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE);
+        reader.take(HEADER_SIZE as u64).read_to_end(&mut bytes)?;
+        bytes.resize(HEADER_SIZE, 0);
+        Ok(Self::parse(&bytes))
+    }
+
+    fn parse(bytes: &[u8]) -> Self {
+        let (locations, timestamps) = bytes[..HEADER_SIZE].split_at(HEADER_SIZE / 2);
+        let locations = locations
+            .chunks_exact(4)
+            .zip(timestamps.chunks_exact(4))
+            .map(|(location, timestamp)| Location {
+                offset: u32::from_be_bytes([0, location[0], location[1], location[2]]),
+                sectors: location[3],
+                timestamp: u32::from_be_bytes([
+                    timestamp[0],
+                    timestamp[1],
+                    timestamp[2],
+                    timestamp[3],
+                ]),
+            })
+            .collect();
+        Self { locations }
+    }
+
+    fn write(&self, bytes: &mut [u8]) {
+        let (locations, timestamps) = bytes[..HEADER_SIZE].split_at_mut(HEADER_SIZE / 2);
+        for ((location, timestamp), entry) in locations
+            .chunks_exact_mut(4)
+            .zip(timestamps.chunks_exact_mut(4))
+            .zip(&self.locations)
+        {
+            location[..3].copy_from_slice(&entry.offset.to_be_bytes()[1..]);
+            location[3] = entry.sectors;
+            timestamp.copy_from_slice(&entry.timestamp.to_be_bytes());
+        }
+    }
+
+    /// The location of the chunk at the given chunk coordinates relative to the region.
+    pub(crate) fn location(&self, x: usize, z: usize) -> Location {
+        self.locations[(x % 32) + (z % 32) * 32]
+    }
+
+    /// The amount of sectors occupied by chunks.
+    pub(crate) fn used_sectors(&self) -> u64 {
+        self.locations
+            .iter()
+            .filter(|location| location.is_present())
+            .map(|location| location.sectors as u64)
+            .sum()
+    }
+}
+
+/// The space removing the chunks from the region file and compacting it would free: the sectors of the chunks plus
+/// the sectors that are already unused. Reads only the header.
+pub(crate) fn reclaimable_space(path: &Path, chunks: &[(usize, usize)]) -> io::Result<u64> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let header = Header::read(&mut file)?;
+    let unused = size.saturating_sub(2 * SECTOR_SIZE + header.used_sectors() * SECTOR_SIZE);
+    let removed = chunks
+        .iter()
+        .map(|&(x, z)| header.location(x, z))
+        .filter(Location::is_present)
+        .map(|location| location.sectors as u64 * SECTOR_SIZE)
+        .sum::<u64>();
+    Ok(unused + removed)
+}
+
+/// The space in the region file that no chunk occupies ("dead space"), e.g. left behind by chunks that were deleted or
+/// grew and moved elsewhere. Rewriting the file with [`RegionWriter::compact`] releases it. Reads only the header.
+pub(crate) fn dead_space(path: &Path) -> io::Result<u64> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let header = Header::read(&mut file)?;
+    let first = HEADER_SIZE as u64 / SECTOR_SIZE;
+    let sectors = (size + SECTOR_SIZE - 1) / SECTOR_SIZE;
+    let mut used = vec![false; sectors.saturating_sub(first) as usize];
+    for location in header.locations.iter().filter(|l| l.is_present()) {
+        let start = location.offset as u64;
+        for sector in start.max(first)..(start + location.sectors as u64).min(sectors) {
+            used[(sector - first) as usize] = true;
+        }
+    }
+    Ok((first..sectors)
+        .filter(|&sector| !used[(sector - first) as usize])
+        // the last sector may be cut short
+        .map(|sector| SECTOR_SIZE.min(size - sector * SECTOR_SIZE))
+        .sum())
+}
+
+/// The flag set on the compression id of chunks that are too large for the region file and stored in a separate
+/// `c.<x>.<z>.mcc` file instead.
+pub(crate) const EXTERNAL_FLAG: u8 = 0x80;
+
+/// Reads the compression id and the compressed data of a chunk as stored in the region file. The data is empty for
+/// chunks stored in a separate file, see [`EXTERNAL_FLAG`].
+pub(crate) fn read_raw_chunk(
+    mut reader: impl Read + Seek,
+    location: &Location,
+) -> io::Result<(u8, Vec<u8>)> {
+    reader.seek(SeekFrom::Start(location.offset as u64 * SECTOR_SIZE))?;
+    let mut prefix = [0; 5];
+    reader.read_exact(&mut prefix)?;
+    let length = u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as u64;
+    if length > location.sectors as u64 * SECTOR_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "chunk length exceeds its sectors",
+        ));
+    }
+    let mut data = Vec::with_capacity(length.saturating_sub(1) as usize);
+    reader
+        .take(length.saturating_sub(1))
+        .read_to_end(&mut data)?;
+    Ok((prefix[4], data))
+}
+
+/// A chunk as stored in a region file, see [`for_each_raw_chunk`].
+pub(crate) struct RawChunk {
+    /// The x-coordinate relative to the region.
+    pub(crate) x: usize,
+    /// The z-coordinate relative to the region.
+    pub(crate) z: usize,
+    pub(crate) location: Location,
+    /// The compression id, see [`EXTERNAL_FLAG`].
+    pub(crate) id: u8,
+    /// The compressed data, empty for chunks stored in a separate file.
+    pub(crate) data: Vec<u8>,
+}
+
+/// Reads every chunk of the region file as stored (see [`read_raw_chunk`]), in the order of the header. With the
+/// `uring` feature the chunks are read through io_uring on Linux, falling back to regular reads where it isn't
+/// available.
+pub(crate) fn for_each_raw_chunk<E: From<io::Error>>(
+    path: &Path,
+    mut f: impl FnMut(RawChunk) -> Result<(), E>,
+) -> Result<(), E> {
+    #[cfg(all(feature = "uring", target_os = "linux"))]
+    if let Some(result) = crate::uring::for_each_raw_chunk(path, &mut f) {
+        return result;
+    }
+
+    let mut file = BufReader::new(File::open(path)?);
+    let header = Header::read(&mut file)?;
+    for z in 0..32 {
+        for x in 0..32 {
+            let location = header.location(x, z);
+            if !location.is_present() {
+                continue;
+            }
+            let (id, data) = read_raw_chunk(&mut file, &location)?;
+            f(RawChunk {
+                x,
+                z,
+                location,
+                id,
+                data,
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads and decompresses a chunk, together with its compression. Returns `None` if the chunk doesn't exist, is stored
+/// in a separate file or uses a compression lessanvil can't decompress.
+pub(crate) fn read_chunk(
+    reader: impl Read + Seek,
+    header: &Header,
+    x: usize,
+    z: usize,
+    custom: &HashMap<u8, CustomCompression>,
+) -> io::Result<Option<(ChunkCompression, Vec<u8>)>> {
+    let location = header.location(x, z);
+    if !location.is_present() {
+        return Ok(None);
+    }
+    let (id, data) = read_raw_chunk(reader, &location)?;
+    if id & EXTERNAL_FLAG != 0 {
+        return Ok(None);
+    }
+    let compression = ChunkCompression::from_id(id);
+    Ok(compression::decompress(compression, &data, custom)?.map(|data| (compression, data)))
+}
+
+/// Compresses and writes a chunk. fastanvil only knows the compressions up to [`ChunkCompression::Uncompressed`], so
+/// the id of other compressions is patched afterwards through `file`, a second handle of the region file.
+/// Compressions lessanvil can't compress fall back to [`ChunkCompression::Zlib`].
+pub(crate) fn write_chunk<S: Read + Write + Seek>(
+    region: &mut Region<S>,
+    mut file: impl Read + Write + Seek,
+    x: usize,
+    z: usize,
+    compression: ChunkCompression,
+    data: &[u8],
+    custom: &HashMap<u8, CustomCompression>,
+) -> fastanvil::Result<()> {
+    let (compression, compressed) = match compression::compress(compression, data, custom)? {
+        Some(compressed) => (compression, compressed),
+        None => (
+            ChunkCompression::Zlib,
+            compression::compress(ChunkCompression::Zlib, data, custom)?.unwrap_or_default(),
+        ),
+    };
+    let scheme = match compression {
+        ChunkCompression::Gzip => CompressionScheme::Gzip,
+        ChunkCompression::Zlib => CompressionScheme::Zlib,
+        _ => CompressionScheme::Uncompressed,
+    };
+    let patch = !matches!(
+        compression,
+        ChunkCompression::Gzip | ChunkCompression::Zlib | ChunkCompression::Uncompressed
+    );
+    region.write_compressed_chunk(x, z, scheme, &compressed)?;
+    if patch {
+        file.rewind()?;
+        let location = Header::read(&mut file)?.location(x, z);
+        file.seek(SeekFrom::Start(location.offset as u64 * SECTOR_SIZE + 4))?;
+        file.write_all(&[compression.id()])?;
+    }
+    Ok(())
+}
+
+/// A chunk to store with [`write_region`].
+#[derive(Clone, Debug)]
+pub(crate) struct StoredChunk {
+    /// The time the chunk was last saved, in seconds since the unix epoch.
+    pub(crate) timestamp: u32,
+    /// The compression id, see [`read_raw_chunk`].
+    pub(crate) id: u8,
+    /// The compressed data.
+    pub(crate) data: Vec<u8>,
+}
+
+/// Writes a new region file with the given chunks, indexed like [`Header::location`]. Chunks too large for a region
+/// file have to be stored in a separate file by the caller, with [`EXTERNAL_FLAG`] and without data. The file is
+/// replaced once it was written completely and gets the permissions and owner of `original`, see
+/// [`create_replacement`].
+pub(crate) fn write_region(
+    path: &Path,
+    original: &Path,
+    chunks: &[Option<StoredChunk>],
+) -> io::Result<()> {
+    let mut header = Header {
+        locations: vec![Location::default(); 32 * 32],
+    };
+    let mut body = vec![];
+    let mut next_sector = (HEADER_SIZE as u64) / SECTOR_SIZE;
+    for (index, chunk) in chunks.iter().enumerate().take(32 * 32) {
+        let Some(StoredChunk {
+            timestamp,
+            id,
+            data,
+        }) = chunk
+        else {
+            continue;
+        };
+        let length = u32::try_from(data.len() + 1).map_err(|_| invalid("chunk too large"))?;
+        let sectors = (4 + length as u64 + SECTOR_SIZE - 1) / SECTOR_SIZE;
+        header.locations[index] = Location {
+            offset: next_sector as u32,
+            sectors: u8::try_from(sectors).map_err(|_| invalid("chunk too large"))?,
+            timestamp: *timestamp,
+        };
+        body.extend_from_slice(&length.to_be_bytes());
+        body.push(*id);
+        body.extend_from_slice(data);
+        body.resize(
+            (sectors + next_sector) as usize * SECTOR_SIZE as usize - HEADER_SIZE,
+            0,
+        );
+        next_sector += sectors;
+    }
+
+    let mut bytes = vec![0; HEADER_SIZE];
+    header.write(&mut bytes);
+    bytes.extend_from_slice(&body);
+    replace_file(path, original, &bytes)
+}
+
+/// Replaces the file at `path` with the bytes, written to a new file (see [`create_replacement`]) which is synced and
+/// then renamed over it, so that a crash leaves either the old or the new file behind.
+pub(crate) fn replace_file(path: &Path, original: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".lessanvil-tmp");
+    let tmp = PathBuf::from(tmp);
+    let result = create_replacement(&tmp, original).and_then(|mut file| {
+        file.write_all(bytes)?;
+        file.sync_all()
+    });
+    if let Err(err) = result.and_then(|_| fs::rename(&tmp, path)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Creates the file at `path` that will be renamed over `original`, with the permissions and, where allowed, the owner
+/// of `original`. A new file is owned by whoever runs lessanvil, which could leave e.g. a server running as another
+/// user unable to write the region afterwards.
+pub(crate) fn create_replacement(path: &Path, original: &Path) -> io::Result<File> {
+    let file = File::create(path)?;
+    let metadata = fs::metadata(original)?;
+    file.set_permissions(metadata.permissions())?;
+    #[cfg(unix)]
+    {
+        use std::ffi::c_int;
+        use std::os::unix::fs::MetadataExt;
+        use std::os::unix::io::AsRawFd;
+
+        extern "C" {
+            fn fchown(fd: c_int, owner: u32, group: u32) -> c_int;
+        }
+        // only root may give files away, everyone else keeps owning the files they write
+        // SAFETY: the descriptor belongs to the open file
+        let _ = unsafe { fchown(file.as_raw_fd(), metadata.uid(), metadata.gid()) };
+    }
+    Ok(file)
+}
+
+/// The size of the buffers used when copying chunks, which bounds the memory used by [`RegionWriter`].
+const COPY_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Rewrites a region file in a single pass, with chunks removed or replaced. Kept chunks (including their compression)
+/// are copied as is, so that the chunks are stored right after each other afterwards, releasing the sectors left
+/// behind by deleted or moved chunks.
+///
+/// Unlike updating the file in place chunk by chunk and compacting it afterwards, the header is written once and every
+/// chunk only once: the chunks are streamed into a new file next to the old one, which is renamed over it by
+/// [`RegionWriter::finish`]. Replaced chunks are written as they arrive and kept ones are copied through a small
+/// buffer, so the memory used doesn't depend on the size of the region. A writer dropped before finishing leaves the
+/// file untouched.
+pub(crate) struct RegionWriter {
+    path: PathBuf,
+    tmp: PathBuf,
+    size: u64,
+    old_header: Header,
+    header: Header,
+    /// Whether the chunk at the index was removed or replaced.
+    changed: Vec<bool>,
+    /// The new file, created once the first chunk is replaced.
+    writer: Option<BufWriter<File>>,
+    next_sector: u64,
+}
+
+impl RegionWriter {
+    pub(crate) fn open(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let size = file.metadata()?.len();
+        let old_header = Header::read(&mut file)?;
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".lessanvil-tmp");
+        Ok(Self {
+            path: path.to_path_buf(),
+            tmp: PathBuf::from(tmp),
+            size,
+            header: Header {
+                locations: old_header.locations.clone(),
+            },
+            old_header,
+            changed: vec![false; 32 * 32],
+            writer: None,
+            next_sector: (HEADER_SIZE as u64) / SECTOR_SIZE,
+        })
+    }
+
+    /// Removes the chunk at the given chunk coordinates relative to the region.
+    pub(crate) fn remove(&mut self, x: usize, z: usize) {
+        let index = (x % 32) + (z % 32) * 32;
+        if self.old_header.locations[index].is_present() {
+            self.changed[index] = true;
+            self.header.locations[index] = Location::default();
+        }
+    }
+
+    /// Compresses the data and writes it in place of the chunk. Compressions lessanvil can't compress fall back to
+    /// [`ChunkCompression::Zlib`].
+    pub(crate) fn replace(
+        &mut self,
+        x: usize,
+        z: usize,
+        compression: ChunkCompression,
+        data: &[u8],
+        custom: &HashMap<u8, CustomCompression>,
+    ) -> io::Result<()> {
+        let (compression, compressed) = match compression::compress(compression, data, custom)? {
+            Some(compressed) => (compression, compressed),
+            None => (
+                ChunkCompression::Zlib,
+                compression::compress(ChunkCompression::Zlib, data, custom)?.unwrap_or_default(),
+            ),
+        };
+        let length = u32::try_from(compressed.len() + 1).map_err(|_| invalid("chunk too large"))?;
+        let index = (x % 32) + (z % 32) * 32;
+        self.changed[index] = true;
+        let writer = self.writer()?;
+        writer.write_all(&length.to_be_bytes())?;
+        writer.write_all(&[compression.id()])?;
+        writer.write_all(&compressed)?;
+        self.place(index, 4 + length as u64)
+    }
+
+    /// Whether a chunk was removed or replaced.
+    pub(crate) fn has_changes(&self) -> bool {
+        self.changed.contains(&true)
+    }
+
+    /// Writes the kept chunks and the header and replaces the old file, unless nothing changed.
+    pub(crate) fn finish(self) -> io::Result<()> {
+        if !self.has_changes() {
+            return Ok(());
+        }
+        self.compact()
+    }
+
+    /// Writes the kept chunks and the header and replaces the old file even if nothing changed, which releases the
+    /// [`dead_space`] of the file.
+    pub(crate) fn compact(mut self) -> io::Result<()> {
+        let result = self.write_rest().and_then(|_| {
+            let writer = self.writer.take().expect("created by write_rest");
+            writer
+                .into_inner()
+                .map_err(|err| err.into_error())?
+                .sync_all()
+        });
+        // files have to be closed before they can be renamed or removed on Windows
+        self.writer = None;
+        if let Err(err) = result.and_then(|_| fs::rename(&self.tmp, &self.path)) {
+            let _ = fs::remove_file(&self.tmp);
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Copies the kept chunks in the order they are stored in and writes the header.
+    fn write_rest(&mut self) -> io::Result<()> {
+        let mut kept = (0..32 * 32)
+            .filter(|&index| !self.changed[index] && self.old_header.locations[index].is_present())
+            .collect::<Vec<_>>();
+        kept.sort_by_key(|&index| self.old_header.locations[index].offset);
+
+        self.writer()?;
+        let mut reader = BufReader::with_capacity(COPY_BUFFER_SIZE, File::open(&self.path)?);
+        for index in kept {
+            let start = self.old_header.locations[index].offset as u64 * SECTOR_SIZE;
+            let mut length = [0; 4];
+            reader.seek(SeekFrom::Start(start))?;
+            reader
+                .read_exact(&mut length)
+                .map_err(|_| invalid("chunk located outside of the region file"))?;
+            let length = 4 + u32::from_be_bytes(length) as u64;
+            if start + length > self.size {
+                return Err(invalid("chunk exceeds the region file"));
+            }
+            reader.seek(SeekFrom::Start(start))?;
+            let writer = self.writer.as_mut().expect("created above");
+            io::copy(&mut (&mut reader).take(length), writer)?;
+            self.place(index, length)?;
+        }
+
+        let mut bytes = vec![0; HEADER_SIZE];
+        self.header.write(&mut bytes);
+        let writer = self.writer()?;
+        writer.rewind()?;
+        writer.write_all(&bytes)
+    }
+
+    /// The new file, created with a placeholder for the header.
+    fn writer(&mut self) -> io::Result<&mut BufWriter<File>> {
+        if self.writer.is_none() {
+            let mut writer = BufWriter::with_capacity(
+                COPY_BUFFER_SIZE,
+                create_replacement(&self.tmp, &self.path)?,
+            );
+            writer.write_all(&[0; HEADER_SIZE])?;
+            self.writer = Some(writer);
+        }
+        Ok(self.writer.as_mut().expect("created above"))
+    }
+
+    /// Pads the chunk of the given length just written to whole sectors and records its location.
+    fn place(&mut self, index: usize, length: u64) -> io::Result<()> {
+        let sectors = (length + SECTOR_SIZE - 1) / SECTOR_SIZE;
+        let location = &mut self.header.locations[index];
+        location.offset = self.next_sector as u32;
+        location.sectors = u8::try_from(sectors).map_err(|_| invalid("chunk too large"))?;
+        let padding = sectors * SECTOR_SIZE - length;
+        let writer = self.writer.as_mut().expect("written before");
+        io::copy(&mut io::repeat(0).take(padding), writer)?;
+        self.next_sector += sectors;
+        Ok(())
+    }
+}
+
+impl Drop for RegionWriter {
+    fn drop(&mut self) {
+        // not finished, the old file stays as it is
+        if self.writer.take().is_some() {
+            let _ = fs::remove_file(&self.tmp);
+        }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
@@ -0,0 +1,305 @@
+//! Repairing region files whose header is corrupted, e.g. after a crash or a failed copy.
+//!
+//! The game and lessanvil trust the header of a region file to find its chunks. Entries pointing into the header,
+//! past the end of the file or at the sectors of another chunk make a region fail to load as a whole, while most of
+//! its chunks are usually still intact. [`repair_world`] checks every entry against the data it points to and writes
+//! a new region file with the chunks that can still be read.
+
+use crate::region::{self, Header, Location, StoredChunk, EXTERNAL_FLAG, HEADER_SIZE, SECTOR_SIZE};
+use crate::{compression, ChunkCompression, Config, Dimension, Error};
+use fastnbt::Value;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How [`repair_world`] treats the regions it finds problems in.
+#[derive(Clone, Debug, Default)]
+pub struct RepairOptions {
+    /// Only report the problems, without changing any file.
+    pub dry_run: bool,
+    /// A folder to copy the region files into before rewriting them, laid out like the world (see
+    /// [`Config::backup_folder`]).
+    pub backup_folder: Option<PathBuf>,
+}
+
+/// What is wrong with an entry of a region header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub enum EntryProblem {
+    /// The entry points into the header itself.
+    InsideHeader,
+    /// The chunk starts or ends past the end of the file.
+    PastEnd,
+    /// The entry claims no sectors, or the chunk's data is empty.
+    ZeroLength,
+    /// The entry claims fewer sectors than the chunk's data occupies.
+    TooFewSectors,
+    /// The sectors of the entry overlap the ones of another chunk, given relative to the region. The chunk is kept
+    /// unless its data belongs to another chunk or was already kept for an earlier entry.
+    Overlapping { x: usize, z: usize },
+    /// The chunk's data can't be decompressed or isn't valid NBT.
+    Unreadable,
+}
+
+/// A problem found in an entry of a region header.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct EntryRepair {
+    /// The x-coordinate of the chunk relative to the region.
+    pub x: usize,
+    /// The z-coordinate of the chunk relative to the region.
+    pub z: usize,
+    pub problem: EntryProblem,
+    /// Whether the chunk could be read anyway and is kept, otherwise it's removed from the region.
+    pub recovered: bool,
+}
+
+/// The problems found in a region file.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct RegionRepair {
+    pub dimension: Dimension,
+    pub path: PathBuf,
+    /// The problems, in the order of the header.
+    pub entries: Vec<EntryRepair>,
+    /// The amount of chunks in the region after the repair.
+    pub kept_chunks: u64,
+    /// Whether the region file was rewritten, `false` for a dry run.
+    pub repaired: bool,
+}
+
+/// What [`repair_world`] found.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct RepairReport {
+    /// The amount of region files checked, including the ones of entities and POIs.
+    pub checked_regions: u64,
+    /// The region files with problems.
+    pub regions: Vec<RegionRepair>,
+}
+
+/// Checks the header of every region file of the world (chunks, entities and POIs) and rewrites the ones with
+/// problems, keeping the chunks that can still be read. Chunks using a compression lessanvil can't decompress and
+/// chunks stored in separate files are kept as they are.
+pub fn repair_world(world_folder: &Path, options: &RepairOptions) -> Result<RepairReport, Error> {
+    if !world_folder.try_exists().is_ok_and(|b| b) {
+        return Err(Error::WorldFolderNotFound);
+    }
+    let _lock = if options.dry_run {
+        None
+    } else {
+        Some(crate::lock::WorldLock::acquire(world_folder)?)
+    };
+
+    let files = crate::collect_all_region_files(world_folder)?;
+
+    // only used to back up the regions
+    let config = Config {
+        world_folder: world_folder.to_path_buf(),
+        backup_folder: options.backup_folder.clone(),
+        ..Default::default()
+    };
+    let mut report = RepairReport::default();
+    for (dimension, path) in files {
+        report.checked_regions += 1;
+        let unreadable = |source| Error::UnreadableFile {
+            path: path.clone(),
+            source,
+        };
+        let bytes = fs::read(&path).map_err(unreadable)?;
+        let (entries, chunks) = check_region(&bytes, crate::region_coordinates(&path));
+        if entries.is_empty() {
+            continue;
+        }
+        let repaired = !options.dry_run;
+        if repaired {
+            crate::back_up(&path, &config)?;
+            region::write_region(&path, &path, &chunks)?;
+        }
+        report.regions.push(RegionRepair {
+            dimension,
+            path,
+            entries,
+            kept_chunks: chunks.iter().flatten().count() as u64,
+            repaired,
+        });
+    }
+    Ok(report)
+}
+
+/// A header entry pointing to data that can be read.
+struct Candidate {
+    index: usize,
+    location: Location,
+    /// The sectors the data actually occupies.
+    sectors: u64,
+    id: u8,
+    data: Vec<u8>,
+    /// Whether the data belongs to another chunk, if it's known.
+    misplaced: Option<bool>,
+}
+
+/// Checks every entry of the region file's header, returning the problems and the chunks to keep, indexed like
+/// [`Header::location`].
+fn check_region(
+    bytes: &[u8],
+    (region_x, region_z): (i32, i32),
+) -> (Vec<EntryRepair>, Vec<Option<StoredChunk>>) {
+    let header = Header::read(bytes).expect("reading from a slice");
+    let size = bytes.len() as u64;
+    let mut problems = HashMap::new();
+    let mut candidates = vec![];
+    for index in 0..32 * 32 {
+        let (x, z) = (index % 32, index / 32);
+        let location = header.location(x, z);
+        if location.offset == 0 && location.sectors == 0 {
+            continue;
+        }
+        let start = location.offset as u64 * SECTOR_SIZE;
+        let problem = if start < HEADER_SIZE as u64 {
+            problems.insert(index, (EntryProblem::InsideHeader, false));
+            continue;
+        } else if start + 5 > size {
+            problems.insert(index, (EntryProblem::PastEnd, false));
+            continue;
+        } else if location.sectors == 0 {
+            Some(EntryProblem::ZeroLength)
+        } else {
+            None
+        };
+
+        let prefix = &bytes[start as usize..];
+        let length = u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as u64;
+        let sectors = (4 + length + SECTOR_SIZE - 1) / SECTOR_SIZE;
+        if length == 0 {
+            problems.insert(index, (EntryProblem::ZeroLength, false));
+            continue;
+        } else if start + 4 + length > size || sectors > u8::MAX as u64 {
+            problems.insert(index, (EntryProblem::PastEnd, false));
+            continue;
+        }
+        let problem =
+            problem.or((sectors > location.sectors as u64).then_some(EntryProblem::TooFewSectors));
+
+        let id = prefix[4];
+        let data = prefix[5..4 + length as usize].to_vec();
+        let misplaced = if id & EXTERNAL_FLAG != 0 {
+            None
+        } else {
+            let compression = ChunkCompression::from_id(id);
+            match compression::decompress(compression, &data, &HashMap::new()) {
+                Ok(Some(chunk)) => match crate::nbt::from_bytes::<Value>(&chunk) {
+                    Ok(chunk) => position(&chunk).map(|position| {
+                        position != (region_x * 32 + x as i32, region_z * 32 + z as i32)
+                    }),
+                    Err(_) => {
+                        problems.insert(index, (EntryProblem::Unreadable, false));
+                        continue;
+                    }
+                },
+                // can't be checked
+                Ok(None) => None,
+                Err(_) => {
+                    problems.insert(index, (EntryProblem::Unreadable, false));
+                    continue;
+                }
+            }
+        };
+        if let Some(problem) = problem {
+            problems.insert(index, (problem, true));
+        }
+        candidates.push(Candidate {
+            index,
+            location,
+            sectors,
+            id,
+            data,
+            misplaced,
+        });
+    }
+
+    // the game would overwrite one of the chunks sharing sectors with the other when saving, the new file stores them
+    // separately
+    let sectors = |candidate: &Candidate| {
+        let start = candidate.location.offset as u64;
+        start..start + candidate.sectors.max(candidate.location.sectors as u64)
+    };
+    let mut chunks = vec![None; 32 * 32];
+    let mut kept_offsets = HashSet::new();
+    for candidate in &candidates {
+        let range = sectors(candidate);
+        let other = candidates.iter().find(|other| {
+            let other_range = sectors(other);
+            other.index != candidate.index
+                && other_range.start < range.end
+                && range.start < other_range.end
+        });
+        let keep = match other {
+            Some(other) => {
+                let duplicate = kept_offsets.contains(&candidate.location.offset);
+                let recovered = candidate.misplaced != Some(true) && !duplicate;
+                problems.insert(
+                    candidate.index,
+                    (
+                        EntryProblem::Overlapping {
+                            x: other.index % 32,
+                            z: other.index / 32,
+                        },
+                        recovered,
+                    ),
+                );
+                recovered
+            }
+            None => true,
+        };
+        if keep {
+            kept_offsets.insert(candidate.location.offset);
+            chunks[candidate.index] = Some(StoredChunk {
+                timestamp: candidate.location.timestamp,
+                id: candidate.id,
+                data: candidate.data.clone(),
+            });
+        }
+    }
+
+    let mut entries = problems
+        .into_iter()
+        .map(|(index, (problem, recovered))| EntryRepair {
+            x: index % 32,
+            z: index / 32,
+            problem,
+            recovered,
+        })
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|entry| (entry.z, entry.x));
+    (entries, chunks)
+}
+
+/// The chunk coordinates stored in a chunk (`xPos`/`zPos`) or an entity chunk (`Position`). POI chunks don't store
+/// them.
+fn position(chunk: &Value) -> Option<(i32, i32)> {
+    let Value::Compound(root) = chunk else {
+        return None;
+    };
+    if let Some(Value::IntArray(position)) = root.get("Position") {
+        return match position[..] {
+            [x, z] => Some((x, z)),
+            _ => None,
+        };
+    }
+    // chunks before 1.18 keep their data inside of a `Level` compound
+    let compound = match root.get("Level") {
+        Some(Value::Compound(level)) => level,
+        _ => root,
+    };
+    match (compound.get("xPos"), compound.get("zPos")) {
+        (Some(Value::Int(x)), Some(Value::Int(z))) => Some((*x, *z)),
+        _ => None,
+    }
+}
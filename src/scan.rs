@@ -0,0 +1,172 @@
+//! Enumerating the chunks of a world without judging or modifying them, e.g. to build other tools on lessanvil's
+//! reading of region files.
+//!
+//! A [`WorldScanner`] reads one region at a time and yields its chunks before reading the next one, so scanning a large
+//! world doesn't hold more than a few regions in memory. Regions can be read in parallel, see [`ScanBackend`].
+
+use crate::chunk::ChunkMeta;
+use crate::{
+    analysis, collect_region_files, compression, region, region_coordinates, ChunkCompression,
+    Dimension, Error, RegionProcessingError,
+};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use rayon::ThreadPoolBuilder;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::{thread, vec};
+
+/// How a [`WorldScanner`] reads the regions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ScanBackend {
+    /// One region after another on the thread iterating, sorted by dimension and region coordinates.
+    #[default]
+    Sequential,
+    /// Several regions at once on a separate thread pool. `threads` works like [`crate::Config::thread_count`], 0
+    /// uses one thread per CPU. The chunks of a region are yielded together, but the regions in the order they were
+    /// read in.
+    Parallel { threads: usize },
+}
+
+/// A chunk found by a [`WorldScanner`].
+#[derive(Clone, Debug)]
+pub struct ScannedChunk {
+    pub dimension: Dimension,
+    /// The region file containing the chunk.
+    pub region: PathBuf,
+    /// The absolute x-coordinate of the chunk.
+    pub x: i32,
+    /// The absolute z-coordinate of the chunk.
+    pub z: i32,
+    /// The size of the sectors the chunk occupies in the region file in bytes.
+    pub size: u64,
+    /// The time the chunk was last saved at according to the region header, in seconds since the Unix epoch.
+    pub timestamp: u32,
+    pub meta: ChunkMeta,
+}
+
+enum Source {
+    Sequential(vec::IntoIter<(Dimension, PathBuf)>),
+    Parallel(mpsc::Receiver<Vec<Result<ScannedChunk, Error>>>),
+}
+
+/// An iterator over the chunks of all dimensions of a world.
+///
+/// Chunks using a compression lessanvil can't decompress are skipped. A region that can't be read yields an
+/// [`Error::RegionError`] after the chunks read before the failure, a chunk that can't be read, decompressed or parsed
+/// yields one in its place; the scan continues with the next chunk either way.
+pub struct WorldScanner {
+    source: Source,
+    regions: usize,
+    chunks: vec::IntoIter<Result<ScannedChunk, Error>>,
+}
+
+impl WorldScanner {
+    /// Finds the region files of the world. No region is read before the first chunk is requested.
+    pub fn new(world_folder: &Path, backend: ScanBackend) -> Result<Self, Error> {
+        if !world_folder.try_exists().is_ok_and(|b| b) {
+            return Err(Error::WorldFolderNotFound);
+        }
+        let mut files = collect_region_files(world_folder)?;
+        files.sort_by_cached_key(|(dimension, path)| (dimension.clone(), region_coordinates(path)));
+        let regions = files.len();
+
+        let source = match backend {
+            ScanBackend::Sequential => Source::Sequential(files.into_iter()),
+            ScanBackend::Parallel { threads } => {
+                let pool = ThreadPoolBuilder::new().num_threads(threads).build()?;
+                // bounded, so that the regions aren't read faster than they are consumed
+                let (tx, rx) = mpsc::sync_channel(pool.current_num_threads());
+                thread::spawn(move || {
+                    pool.install(|| {
+                        // stops once the scanner is dropped
+                        let _ = files
+                            .into_par_iter()
+                            .try_for_each_with(tx, |tx, (dimension, path)| {
+                                tx.send(scan_region(dimension, path))
+                            });
+                    })
+                });
+                Source::Parallel(rx)
+            }
+        };
+        Ok(WorldScanner {
+            source,
+            regions,
+            chunks: vec![].into_iter(),
+        })
+    }
+
+    /// The amount of region files the scan covers.
+    pub fn regions(&self) -> usize {
+        self.regions
+    }
+}
+
+impl Iterator for WorldScanner {
+    type Item = Result<ScannedChunk, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(chunk) = self.chunks.next() {
+                return Some(chunk);
+            }
+            let chunks = match &mut self.source {
+                Source::Sequential(files) => {
+                    let (dimension, path) = files.next()?;
+                    scan_region(dimension, path)
+                }
+                Source::Parallel(rx) => rx.recv().ok()?,
+            };
+            self.chunks = chunks.into_iter();
+        }
+    }
+}
+
+/// Reads the metadata of every chunk of the region file, in the order of the header.
+fn scan_region(dimension: Dimension, path: PathBuf) -> Vec<Result<ScannedChunk, Error>> {
+    let region_error = |source: RegionProcessingError| Error::RegionError {
+        path: path.clone(),
+        source,
+    };
+    let (region_x, region_z) = region_coordinates(&path);
+    let mut chunks = vec![];
+    let result = region::for_each_raw_chunk(&path, |chunk| {
+        let (x, z) = (
+            region_x * 32 + chunk.x as i32,
+            region_z * 32 + chunk.z as i32,
+        );
+        let size = chunk.location.sectors as u64 * region::SECTOR_SIZE;
+        let timestamp = chunk.location.timestamp;
+        let compression = ChunkCompression::from_id(chunk.id);
+        let data = analysis::chunk_data(&path, chunk)
+            .and_then(|data| compression::decompress(compression, &data, &HashMap::new()));
+        let data = match data {
+            Ok(Some(data)) => data,
+            Ok(None) => return Ok::<_, RegionProcessingError>(()),
+            // a corrupt chunk or a missing `.mcc` file doesn't keep the other chunks of the region from being read
+            Err(err) => {
+                chunks.push(Err(region_error(err.into())));
+                return Ok(());
+            }
+        };
+        chunks.push(
+            ChunkMeta::from_bytes(&data)
+                .map(|meta| ScannedChunk {
+                    dimension: dimension.clone(),
+                    region: path.clone(),
+                    x,
+                    z,
+                    size,
+                    timestamp,
+                    meta,
+                })
+                .map_err(|err| region_error(err.into())),
+        );
+        Ok(())
+    });
+    if let Err(err) = result {
+        chunks.push(Err(region_error(err)));
+    }
+    chunks
+}
@@ -0,0 +1,162 @@
+//! Editing the vertical sections of a chunk.
+
+use fastnbt::Value;
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+/// Block ids that count as empty space.
+const AIR: [&str; 3] = ["minecraft:air", "minecraft:cave_air", "minecraft:void_air"];
+
+/// The compound holding the chunk data, which is nested in `Level` before 1.18.
+fn level(chunk: &mut Value) -> Option<&mut HashMap<String, Value>> {
+    let Value::Compound(root) = chunk else {
+        return None;
+    };
+    if !matches!(root.get("Level"), Some(Value::Compound(_))) {
+        return Some(root);
+    }
+    match root.get_mut("Level") {
+        Some(Value::Compound(level)) => Some(level),
+        _ => None,
+    }
+}
+
+fn list<'a>(compound: &'a mut HashMap<String, Value>, keys: &[&str]) -> Option<&'a mut Vec<Value>> {
+    let key = keys.iter().find(|key| compound.contains_key(**key))?;
+    match compound.get_mut(*key) {
+        Some(Value::List(list)) => Some(list),
+        _ => None,
+    }
+}
+
+fn get_i64(value: &Value, key: &str) -> Option<i64> {
+    match value {
+        Value::Compound(compound) => compound.get(key)?.as_i64(),
+        _ => None,
+    }
+}
+
+/// Whether the block Y coordinate lies within the range. Corrupted chunks may contain coordinates far outside of `i32`.
+fn contains(range: &RangeInclusive<i32>, y: i64) -> bool {
+    i32::try_from(y).is_ok_and(|y| range.contains(&y))
+}
+
+/// The block Y coordinates of the bottom and top of the section at the given section Y coordinate.
+fn section_bounds(section_y: i64) -> (i64, i64) {
+    let bottom = section_y.saturating_mul(16);
+    (bottom, bottom.saturating_add(15))
+}
+
+/// Whether the section at the given section Y coordinate lies entirely within the block Y range.
+fn section_within(section_y: i64, range: &RangeInclusive<i32>) -> bool {
+    let (bottom, top) = section_bounds(section_y);
+    contains(range, bottom) && contains(range, top)
+}
+
+/// The block ids in the palette of a section (1.18+ `block_states.palette` or legacy `Palette`).
+fn palette(section: &Value) -> impl Iterator<Item = &str> {
+    let Value::Compound(section) = section else {
+        return None.into_iter().flatten();
+    };
+    let palette = match section.get("block_states") {
+        Some(Value::Compound(states)) => states.get("palette"),
+        _ => section.get("Palette"),
+    };
+    let Some(Value::List(palette)) = palette else {
+        return None.into_iter().flatten();
+    };
+    Some(palette.iter().filter_map(|state| match state {
+        Value::Compound(state) => state.get("Name")?.as_str(),
+        _ => None,
+    }))
+    .into_iter()
+    .flatten()
+}
+
+/// Whether any section of the (uncompressed) chunk overlapping the block Y range contains something other than air.
+pub(crate) fn has_blocks(
+    chunk: &[u8],
+    range: &RangeInclusive<i32>,
+) -> Result<bool, fastnbt::error::Error> {
+    let mut chunk: Value = crate::nbt::from_bytes(chunk)?;
+    let Some(sections) = level(&mut chunk).and_then(|level| list(level, &["sections", "Sections"]))
+    else {
+        return Ok(false);
+    };
+    Ok(sections.iter().any(|section| {
+        get_i64(section, "Y").is_some_and(|y| {
+            let (bottom, top) = section_bounds(y);
+            contains(range, top) || contains(range, bottom)
+        }) && palette(section).any(|block| !AIR.contains(&block))
+    }))
+}
+
+/// Replaces all sections of the (uncompressed) chunk that lie entirely within the block Y range with air, together
+/// with the block entities and scheduled ticks inside of them. Biomes are kept and the light of the chunk is
+/// recalculated by the game on the next load.
+///
+/// Returns `None` if none of these sections contained any blocks.
+pub(crate) fn clear_sections(
+    chunk: &[u8],
+    range: &RangeInclusive<i32>,
+) -> Result<Option<Vec<u8>>, fastnbt::error::Error> {
+    let mut chunk: Value = crate::nbt::from_bytes(chunk)?;
+    let Some(level) = level(&mut chunk) else {
+        return Ok(None);
+    };
+
+    let mut changed = false;
+    if let Some(sections) = list(level, &["sections", "Sections"]) {
+        for section in sections.iter_mut() {
+            if !get_i64(section, "Y").is_some_and(|y| section_within(y, range))
+                || palette(section).all(|block| AIR.contains(&block))
+            {
+                continue;
+            }
+            let Value::Compound(section) = section else {
+                continue;
+            };
+            changed = true;
+            section.remove("BlockLight");
+            section.remove("SkyLight");
+            if section.contains_key("block_states") {
+                let air = HashMap::from([(
+                    "Name".to_string(),
+                    Value::String("minecraft:air".to_string()),
+                )]);
+                section.insert(
+                    "block_states".to_string(),
+                    Value::Compound(HashMap::from([(
+                        "palette".to_string(),
+                        Value::List(vec![Value::Compound(air)]),
+                    )])),
+                );
+            } else {
+                // legacy sections without block data are empty
+                section.remove("Palette");
+                section.remove("BlockStates");
+            }
+        }
+    }
+    if !changed {
+        return Ok(None);
+    }
+
+    for key in [
+        "block_entities",
+        "TileEntities",
+        "block_ticks",
+        "fluid_ticks",
+        "TileTicks",
+        "LiquidTicks",
+    ] {
+        if let Some(entries) = list(level, &[key]) {
+            entries.retain(|entry| {
+                !get_i64(entry, "y").is_some_and(|y| section_within(y.div_euclid(16), range))
+            });
+        }
+    }
+    level.insert("isLightOn".to_string(), Value::Byte(0));
+
+    fastnbt::to_bytes(&chunk).map(Some)
+}
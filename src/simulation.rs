@@ -0,0 +1,125 @@
+//! Injecting I/O failures and cancellations into the processing of a world, so tests can simulate a full disk,
+//! missing permissions or an interrupted run deterministically. See [`Config::fault_injector`]. Only available with
+//! the `simulation` feature.
+//!
+//! Regions are still read from and written to disk, the faults are injected right before lessanvil touches a file.
+//! With [`Config::thread_count`] set to 1 and [`Config::ordered_updates`] set, the [`ProcessingUpdate`]s (apart from
+//! [`ProcessingUpdate::Progress`], which depends on timing) are the same on every run.
+//!
+//! ```no_run
+//! use lessanvil::simulation::{FaultInjector, IoOperation};
+//! use lessanvil::Config;
+//! use std::io;
+//!
+//! let config = Config {
+//!     world_folder: "world".into(),
+//!     thread_count: 1,
+//!     ordered_updates: true,
+//!     dry_run: false,
+//!     fault_injector: Some(
+//!         FaultInjector::default()
+//!             .fail(IoOperation::Compact, "r.0.0.mca", || {
+//!                 io::Error::new(io::ErrorKind::Other, "No space left on device")
+//!             })
+//!             .cancel(IoOperation::Open, "r.1.0.mca"),
+//!     ),
+//!     ..Default::default()
+//! };
+//! ```
+//!
+//! [`Config::fault_injector`]: crate::Config::fault_injector
+//! [`Config::thread_count`]: crate::Config::thread_count
+//! [`Config::ordered_updates`]: crate::Config::ordered_updates
+//! [`ProcessingUpdate`]: crate::ProcessingUpdate
+//! [`ProcessingUpdate::Progress`]: crate::ProcessingUpdate::Progress
+
+use crate::CancelHandle;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+/// The operations on region files faults can be injected into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IoOperation {
+    /// Opening a region file, or the entity or POI file belonging to it, for processing.
+    Open,
+    /// Removing a chunk from a region file.
+    RemoveChunk,
+    /// Writing a modified or recompressed chunk back into a region file.
+    WriteChunk,
+    /// Rewriting a region file after chunks were removed or replaced, which also compacts it.
+    Compact,
+}
+
+/// Creates the error of a failing operation, see [`FaultInjector::fail`].
+pub type ErrorFn = Arc<dyn Fn() -> io::Error + Send + Sync>;
+
+#[derive(Clone)]
+enum Action {
+    Fail(ErrorFn),
+    Cancel,
+}
+
+#[derive(Clone)]
+struct Fault {
+    operation: IoOperation,
+    file_name: String,
+    action: Action,
+}
+
+/// The faults to inject, see the [module documentation](self).
+#[derive(Clone, Default)]
+pub struct FaultInjector {
+    faults: Vec<Fault>,
+    cancel_handle: CancelHandle,
+}
+
+impl FaultInjector {
+    /// Makes the operation on every file with the name (e.g. `r.0.0.mca`, which also matches the entity and POI files
+    /// of the region) fail with the error. The region fails with
+    /// [`RegionProcessingError::IOError`](crate::RegionProcessingError::IOError).
+    pub fn fail(
+        mut self,
+        operation: IoOperation,
+        file_name: &str,
+        error: impl Fn() -> io::Error + Send + Sync + 'static,
+    ) -> Self {
+        self.faults.push(Fault {
+            operation,
+            file_name: file_name.to_string(),
+            action: Action::Fail(Arc::new(error)),
+        });
+        self
+    }
+
+    /// Cancels the execution when the operation is performed on a file with the name. Like
+    /// [`Execution::cancel`](crate::Execution::cancel), the regions already being processed are finished.
+    pub fn cancel(mut self, operation: IoOperation, file_name: &str) -> Self {
+        self.faults.push(Fault {
+            operation,
+            file_name: file_name.to_string(),
+            action: Action::Cancel,
+        });
+        self
+    }
+
+    /// The handle cancelling the execution the injector is used in.
+    pub(crate) fn cancel_handle(&self) -> CancelHandle {
+        self.cancel_handle.clone()
+    }
+
+    /// Applies the faults registered for the operation on the file.
+    pub(crate) fn inject(&self, operation: IoOperation, path: &Path) -> io::Result<()> {
+        let file_name = path.file_name().and_then(|name| name.to_str());
+        for fault in &self.faults {
+            if fault.operation != operation || file_name != Some(fault.file_name.as_str()) {
+                continue;
+            }
+            match &fault.action {
+                Action::Fail(error) => return Err(error()),
+                Action::Cancel => self.cancel_handle.cancel(),
+            }
+        }
+        Ok(())
+    }
+}
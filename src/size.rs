@@ -0,0 +1,75 @@
+//! Measuring the disk space taken up by files, for the freed space in the [`crate::Report`].
+//!
+//! On Windows, NTFS may store region files sparse or compressed, so that they take up less space than their length.
+//! Their allocated size is measured there, matching the size Explorer shows on disk. Elsewhere the length is used.
+
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::Path;
+
+/// How the size of a file is measured on a platform.
+pub(crate) trait SizeBackend: Sync {
+    /// The disk space taken up by the file in bytes.
+    fn size(&self, path: &Path, metadata: &Metadata) -> io::Result<u64>;
+}
+
+/// The length of the file.
+#[cfg_attr(windows, allow(dead_code))]
+struct Logical;
+
+impl SizeBackend for Logical {
+    fn size(&self, _: &Path, metadata: &Metadata) -> io::Result<u64> {
+        Ok(metadata.len())
+    }
+}
+
+/// The space allocated for the file, which is less than its length for sparse and compressed files.
+#[cfg(windows)]
+struct Allocated;
+
+#[cfg(windows)]
+impl SizeBackend for Allocated {
+    fn size(&self, path: &Path, _: &Metadata) -> io::Result<u64> {
+        use std::os::windows::ffi::OsStrExt;
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn GetCompressedFileSizeW(file_name: *const u16, file_size_high: *mut u32) -> u32;
+        }
+        const INVALID_FILE_SIZE: u32 = u32::MAX;
+
+        let name = path
+            .as_os_str()
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect::<Vec<_>>();
+        let mut high = 0;
+        // SAFETY: the name is null-terminated and both pointers are valid for the call
+        let low = unsafe { GetCompressedFileSizeW(name.as_ptr(), &mut high) };
+        if low == INVALID_FILE_SIZE {
+            // also a valid low part, in which case no error is set
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(0) {
+                return Err(err);
+            }
+        }
+        Ok((high as u64) << 32 | low as u64)
+    }
+}
+
+/// The backend of the current platform.
+pub(crate) fn backend() -> &'static dyn SizeBackend {
+    #[cfg(windows)]
+    {
+        &Allocated
+    }
+    #[cfg(not(windows))]
+    {
+        &Logical
+    }
+}
+
+/// The disk space taken up by the file in bytes, see [`backend`].
+pub(crate) fn file_size(path: &Path) -> io::Result<u64> {
+    backend().size(path, &fs::metadata(path)?)
+}
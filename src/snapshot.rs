@@ -0,0 +1,224 @@
+//! Progress snapshots: a small state file written periodically during an execution (see
+//! [`Config::progress_snapshot`]), so that a run that died can be diagnosed and resumed.
+//!
+//! The file is plain text, one `<key> <value>` per line, and is removed once the execution finished. It is kept if the
+//! execution was cancelled or the process died.
+
+use crate::{Config, Error, Progress, RegionProcessingError, RunId};
+use std::collections::{BTreeSet, HashSet, VecDeque};
+use std::error::Error as _;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Where the CLI writes the snapshot, relative to the world folder: inside the [`crate::lock::STATE_FOLDER`].
+pub const DEFAULT_PATH: &str = ".lessanvil/progress.txt";
+
+/// The first line of every snapshot.
+const HEADER: &str = "# lessanvil progress snapshot v1";
+
+/// The amount of errors kept in [`Snapshot::recent_errors`].
+const RECENT_ERRORS: usize = 10;
+
+/// The state of an execution at the time the snapshot was written.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The id of the execution. `None` for snapshots without one.
+    pub run_id: Option<RunId>,
+    /// The time since the processing started.
+    pub elapsed: Duration,
+    /// The amount of regions processed.
+    pub processed_regions: u64,
+    /// The total amount of regions to process.
+    pub total_regions: u64,
+    /// The amount of chunks processed.
+    pub processed_chunks: u64,
+    /// The amount of chunks deleted.
+    pub deleted_chunks: u64,
+    /// The region files being processed.
+    pub current_regions: Vec<PathBuf>,
+    /// The region files that weren't processed yet, in the order they would have been processed.
+    pub remaining_regions: Vec<PathBuf>,
+    /// The last errors of regions that failed to process, oldest first.
+    pub recent_errors: Vec<String>,
+}
+
+impl Snapshot {
+    /// Reads the snapshot from a file written during an execution.
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        let content = fs::read_to_string(path)?;
+        let invalid = |line: usize, message: &str| Error::InvalidSnapshot {
+            path: path.to_path_buf(),
+            line,
+            message: message.to_string(),
+        };
+
+        if content.lines().next() != Some(HEADER) {
+            return Err(invalid(1, "not a lessanvil progress snapshot"));
+        }
+        let mut snapshot = Snapshot::default();
+        for (i, line) in content.lines().enumerate().skip(1) {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let number = || {
+                value
+                    .parse::<u64>()
+                    .map_err(|_| invalid(i + 1, "expected a number"))
+            };
+            match key {
+                "run_id" => {
+                    snapshot.run_id = Some(
+                        value
+                            .parse()
+                            .map_err(|_| invalid(i + 1, "invalid run id"))?,
+                    )
+                }
+                "elapsed_ms" => snapshot.elapsed = Duration::from_millis(number()?),
+                "processed_regions" => snapshot.processed_regions = number()?,
+                "total_regions" => snapshot.total_regions = number()?,
+                "processed_chunks" => snapshot.processed_chunks = number()?,
+                "deleted_chunks" => snapshot.deleted_chunks = number()?,
+                "current" => snapshot.current_regions.push(PathBuf::from(value)),
+                "remaining" => snapshot.remaining_regions.push(PathBuf::from(value)),
+                "error" => snapshot.recent_errors.push(value.to_string()),
+                // written by a newer version
+                _ => {}
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// The regions an interrupted execution didn't finish, to resume it with [`crate::execute_subset`]. The regions
+    /// that were being processed are included, processing a region again is harmless.
+    pub fn unfinished_regions(&self) -> Vec<PathBuf> {
+        self.current_regions
+            .iter()
+            .chain(&self.remaining_regions)
+            .cloned()
+            .collect()
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = format!("{HEADER}\n");
+        if let Some(run_id) = self.run_id {
+            let _ = writeln!(text, "run_id {run_id}");
+        }
+        let _ = writeln!(text, "elapsed_ms {}", self.elapsed.as_millis());
+        let _ = writeln!(text, "processed_regions {}", self.processed_regions);
+        let _ = writeln!(text, "total_regions {}", self.total_regions);
+        let _ = writeln!(text, "processed_chunks {}", self.processed_chunks);
+        let _ = writeln!(text, "deleted_chunks {}", self.deleted_chunks);
+        for region in &self.current_regions {
+            let _ = writeln!(text, "current {}", region.display());
+        }
+        for region in &self.remaining_regions {
+            let _ = writeln!(text, "remaining {}", region.display());
+        }
+        for error in &self.recent_errors {
+            let _ = writeln!(text, "error {}", error.replace('\n', " "));
+        }
+        text
+    }
+}
+
+/// Tracks the regions of an execution and writes its snapshots. Does nothing without
+/// [`Config::progress_snapshot`].
+pub(crate) struct Recorder {
+    path: Option<PathBuf>,
+    run_id: RunId,
+    /// All regions of the execution, in the order they are processed.
+    regions: Vec<PathBuf>,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    started: HashSet<PathBuf>,
+    current: BTreeSet<PathBuf>,
+    errors: VecDeque<String>,
+}
+
+impl Recorder {
+    pub(crate) fn new(config: &Config, run_id: RunId, regions: Vec<PathBuf>) -> Self {
+        Self {
+            path: config.progress_snapshot.clone(),
+            run_id,
+            regions,
+            state: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn start(&self, region: &Path) {
+        if self.path.is_none() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.started.insert(region.to_path_buf());
+        state.current.insert(region.to_path_buf());
+    }
+
+    pub(crate) fn finish(&self, region: &Path, error: Option<&RegionProcessingError>) {
+        if self.path.is_none() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.current.remove(region);
+        if let Some(error) = error {
+            let mut message = format!("{}: {error}", region.display());
+            if let Some(source) = error.source() {
+                let _ = write!(message, ": {source}");
+            }
+            if state.errors.len() == RECENT_ERRORS {
+                state.errors.pop_front();
+            }
+            state.errors.push_back(message);
+        }
+    }
+
+    /// Writes the snapshot, replacing the previous one. Failing to write it doesn't affect the execution.
+    pub(crate) fn write(&self, progress: &Progress) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let snapshot = {
+            let state = self.state.lock().unwrap();
+            Snapshot {
+                run_id: Some(self.run_id),
+                elapsed: progress.elapsed,
+                processed_regions: progress.processed_regions,
+                total_regions: progress.total_regions,
+                processed_chunks: progress.processed_chunks,
+                deleted_chunks: progress.deleted_chunks,
+                current_regions: state.current.iter().cloned().collect(),
+                remaining_regions: self
+                    .regions
+                    .iter()
+                    .filter(|region| !state.started.contains(*region))
+                    .cloned()
+                    .collect(),
+                recent_errors: state.errors.iter().cloned().collect(),
+            }
+        };
+
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".lessanvil-tmp");
+        let tmp = PathBuf::from(tmp);
+        if let Some(folder) = path.parent() {
+            let _ = fs::create_dir_all(folder);
+        }
+        if fs::write(&tmp, snapshot.to_text())
+            .and_then(|_| fs::rename(&tmp, path))
+            .is_err()
+        {
+            let _ = fs::remove_file(&tmp);
+        }
+    }
+
+    /// Removes the snapshot after the execution finished.
+    pub(crate) fn remove(&self) {
+        if let Some(path) = &self.path {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
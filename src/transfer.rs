@@ -0,0 +1,308 @@
+//! Copying chunks from one world into another.
+
+use crate::chunk::ChunkMeta;
+use crate::protection::{self, ProtectedArea};
+use crate::{
+    collect_region_files, region, region_coordinates, ChunkPos, Dimension, Error, InhabitedTime,
+    RegionProcessingError, REGION_KINDS,
+};
+use fastanvil::Region;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Selects the chunks to copy.
+pub enum Selection {
+    /// Chunks with more InhabitedTime than the given value, i.e. the chunks a prune with the same value would keep.
+    InhabitedTimeAbove(InhabitedTime),
+    /// Chunks inside any of the areas.
+    Areas(Vec<ProtectedArea>),
+    /// An explicit list of chunks.
+    Chunks(HashSet<ChunkPos>),
+}
+
+impl Selection {
+    fn matches(
+        &self,
+        dimension: &Dimension,
+        x: i32,
+        z: i32,
+        chunk: &[u8],
+    ) -> Result<bool, fastnbt::error::Error> {
+        Ok(match self {
+            Selection::InhabitedTimeAbove(min) => {
+                ChunkMeta::from_bytes(chunk)?.inhabited_time > *min
+            }
+            Selection::Areas(areas) => areas.iter().any(|area| area.contains(dimension, x, z)),
+            Selection::Chunks(chunks) => chunks.contains(&ChunkPos {
+                dimension: dimension.clone(),
+                x,
+                z,
+            }),
+        })
+    }
+}
+
+/// A Report that will be handed out after chunks were copied.
+pub struct TransferReport {
+    /// The total amount of region(-file-)s read from the source world.
+    pub total_regions: u64,
+    /// The total amount of chunks copied.
+    pub copied_chunks: u64,
+}
+
+/// Reads a list of chunks from a CSV file with one `dimension,x,z` entry (chunk coordinates) per line.
+///
+/// Empty lines and lines starting with `#` are ignored.
+pub fn chunks_from_csv(path: &Path) -> Result<HashSet<ChunkPos>, Error> {
+    Ok(protection::positions_from_csv(path)?
+        .into_iter()
+        .map(|position| ChunkPos {
+            dimension: position.dimension,
+            x: position.x,
+            z: position.z,
+        })
+        .collect())
+}
+
+/// Copies all chunks matching the selection from the `source` world into the `destination` world,
+/// overwriting chunks that already exist there. The matching entity and POI data is copied as well.
+pub fn merge(
+    source: &Path,
+    destination: &Path,
+    selection: &Selection,
+) -> Result<TransferReport, Error> {
+    if !source.try_exists().is_ok_and(|b| b) || !destination.try_exists().is_ok_and(|b| b) {
+        return Err(Error::WorldFolderNotFound);
+    }
+
+    let files = collect_region_files(source)?;
+    let total_regions = files.len() as u64;
+    let copied_chunks = AtomicU64::new(0);
+
+    files.into_par_iter().try_for_each(|(dimension, path)| {
+        let copied = copy_region(source, destination, &dimension, &path, selection)
+            .map_err(|source| Error::RegionError { path, source })?;
+        copied_chunks.fetch_add(copied, Ordering::Relaxed);
+        Ok::<_, Error>(())
+    })?;
+
+    Ok(TransferReport {
+        total_regions,
+        copied_chunks: copied_chunks.into_inner(),
+    })
+}
+
+/// Counts the chunks [`merge`] would copy from the `source` world, without writing anything.
+pub fn matching_chunks(source: &Path, selection: &Selection) -> Result<TransferReport, Error> {
+    if !source.try_exists().is_ok_and(|b| b) {
+        return Err(Error::WorldFolderNotFound);
+    }
+
+    let files = collect_region_files(source)?;
+    let total_regions = files.len() as u64;
+    let copied_chunks = files
+        .into_par_iter()
+        .map(|(dimension, path)| {
+            select_chunks(&dimension, &path, selection)
+                .map(|selected| selected.len() as u64)
+                .map_err(|source| Error::RegionError { path, source })
+        })
+        .sum::<Result<u64, Error>>()?;
+
+    Ok(TransferReport {
+        total_regions,
+        copied_chunks,
+    })
+}
+
+fn copy_region(
+    source: &Path,
+    destination: &Path,
+    dimension: &Dimension,
+    region_file_path: &Path,
+    selection: &Selection,
+) -> Result<u64, RegionProcessingError> {
+    let selected = select_chunks(dimension, region_file_path, selection)?;
+    if selected.is_empty() {
+        return Ok(0);
+    }
+
+    let file_name = region_file_path.file_name().unwrap_or_default();
+    for kind in REGION_KINDS {
+        let source_path = source.join(dimension.folder()).join(kind).join(file_name);
+        let destination_path = destination
+            .join(dimension.folder())
+            .join(kind)
+            .join(file_name);
+
+        let mut source_region = if source_path.try_exists().is_ok_and(|b| b) {
+            let mut file = File::open(&source_path)?;
+            let header = region::Header::read(&mut file)?;
+            Some((file, header))
+        } else {
+            None
+        };
+        if source_region.is_none() && !destination_path.try_exists().is_ok_and(|b| b) {
+            continue;
+        }
+
+        let mut destination_region = open_or_create_region(&destination_path)?;
+        let mut destination_file = File::options()
+            .read(true)
+            .write(true)
+            .open(&destination_path)?;
+        for &(chunk_x, chunk_z) in &selected {
+            let data = match &mut source_region {
+                Some((file, header)) => {
+                    region::read_chunk(file, header, chunk_x, chunk_z, &HashMap::new())?
+                }
+                None => None,
+            };
+            match data {
+                Some((compression, data)) => region::write_chunk(
+                    &mut destination_region,
+                    &mut destination_file,
+                    chunk_x,
+                    chunk_z,
+                    compression,
+                    &data,
+                    &HashMap::new(),
+                )?,
+                // the destination must not keep e.g. entities of the chunk it replaced
+                None => destination_region.remove_chunk(chunk_x, chunk_z)?,
+            }
+        }
+
+        let mut destination_file = destination_region.into_inner()?;
+        let len = destination_file.stream_position()?;
+        destination_file.set_len(len)?;
+    }
+
+    Ok(selected.len() as u64)
+}
+
+/// The chunks of the region file matching the selection, with coordinates relative to the region.
+fn select_chunks(
+    dimension: &Dimension,
+    region_file_path: &Path,
+    selection: &Selection,
+) -> Result<Vec<(usize, usize)>, RegionProcessingError> {
+    let (x, z) = region_coordinates(region_file_path);
+    let mut file = File::open(region_file_path)?;
+    let header = region::Header::read(&mut file)?;
+    let mut selected = vec![];
+    for chunk_x in 0..32 {
+        for chunk_z in 0..32 {
+            let Ok(Some((_, chunk))) =
+                region::read_chunk(&mut file, &header, chunk_x, chunk_z, &HashMap::new())
+            else {
+                continue;
+            };
+            let (absolute_x, absolute_z) = (x * 32 + chunk_x as i32, z * 32 + chunk_z as i32);
+            if selection.matches(dimension, absolute_x, absolute_z, &chunk)? {
+                selected.push((chunk_x, chunk_z));
+            }
+        }
+    }
+    Ok(selected)
+}
+
+/// Creates a new world at `destination` containing only the chunks of `source` matching the selection.
+///
+/// The `level.dat` and `datapacks` of the source world are copied as well, with the level name adjusted to the name
+/// of the destination folder. The destination must not exist yet.
+pub fn extract(
+    source: &Path,
+    destination: &Path,
+    selection: &Selection,
+) -> Result<TransferReport, Error> {
+    if !source.try_exists().is_ok_and(|b| b) {
+        return Err(Error::WorldFolderNotFound);
+    }
+    if destination.try_exists().is_ok_and(|b| b) {
+        return Err(Error::DestinationExists(destination.to_path_buf()));
+    }
+
+    fs::create_dir_all(destination)?;
+    let level_dat = source.join("level.dat");
+    if level_dat.try_exists().is_ok_and(|b| b) {
+        let level_name = destination
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        copy_level_dat(&level_dat, &destination.join("level.dat"), &level_name)?;
+    }
+    let datapacks = source.join("datapacks");
+    if datapacks.try_exists().is_ok_and(|b| b) {
+        copy_dir(&datapacks, &destination.join("datapacks"))?;
+    }
+
+    merge(source, destination, selection)
+}
+
+fn copy_level_dat(source: &Path, destination: &Path, level_name: &str) -> Result<(), Error> {
+    let mut data = vec![];
+    GzDecoder::new(File::open(source)?).read_to_end(&mut data)?;
+    let mut level: fastnbt::Value =
+        crate::nbt::from_bytes(&data).map_err(|source| Error::InvalidLevelDat { source })?;
+
+    if let fastnbt::Value::Compound(root) = &mut level {
+        if let Some(fastnbt::Value::Compound(data)) = root.get_mut("Data") {
+            data.insert(
+                "LevelName".to_string(),
+                fastnbt::Value::String(level_name.to_string()),
+            );
+        }
+    }
+
+    let data = fastnbt::to_bytes(&level).map_err(|source| Error::InvalidLevelDat { source })?;
+    let mut encoder = GzEncoder::new(File::create(destination)?, Compression::default());
+    encoder.write_all(&data)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+fn copy_dir(source: &Path, destination: &Path) -> io::Result<()> {
+    fs::create_dir_all(destination)?;
+    for entry in source.read_dir()? {
+        let entry = entry?;
+        let target = destination.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Opens the region file for reading and writing, creating an empty region if it doesn't exist yet.
+pub(crate) fn open_or_create_region(path: &Path) -> Result<Region<File>, RegionProcessingError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+
+    // fastanvil places the first chunk on top of the header when loading a region without any chunks
+    let mut locations = [0; 4096];
+    let is_empty = file.read_exact(&mut locations).is_err() || locations.iter().all(|b| *b == 0);
+    file.rewind()?;
+
+    Ok(if is_empty {
+        Region::new(file)?
+    } else {
+        Region::from_stream(file)?
+    })
+}
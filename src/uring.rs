@@ -0,0 +1,154 @@
+//! Reading region files through io_uring on Linux (`uring` feature), see
+//! [`for_each_raw_chunk`](crate::region::for_each_raw_chunk).
+//!
+//! The chunks of a region are read in batches of reads submitted at once instead of one blocking read per chunk. Fast
+//! drives (NVMe in particular) need many reads in flight to reach their throughput, which makes scanning worlds with
+//! many regions, e.g. for the [analysis](crate::analysis), noticeably faster.
+
+use crate::region::{Header, Location, RawChunk, HEADER_SIZE, SECTOR_SIZE};
+use io_uring::{opcode, types, IoUring};
+use std::cell::RefCell;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+/// The amount of reads submitted at once.
+const QUEUE_DEPTH: u32 = 64;
+
+thread_local! {
+    /// `None` if io_uring isn't available (old kernels, containers blocking it) or failed before.
+    static RING: RefCell<Option<IoUring>> = RefCell::new(IoUring::new(QUEUE_DEPTH).ok());
+}
+
+/// Like [`crate::region::for_each_raw_chunk`]. Returns `None` if io_uring isn't available.
+pub(crate) fn for_each_raw_chunk<E: From<io::Error>>(
+    path: &Path,
+    f: &mut impl FnMut(RawChunk) -> Result<(), E>,
+) -> Option<Result<(), E>> {
+    RING.with(|ring| {
+        let mut ring = ring.borrow_mut();
+        ring.as_ref()?;
+        Some(read_region(&mut ring, path, f))
+    })
+}
+
+fn read_region<E: From<io::Error>>(
+    ring: &mut Option<IoUring>,
+    path: &Path,
+    f: &mut impl FnMut(RawChunk) -> Result<(), E>,
+) -> Result<(), E> {
+    let file = File::open(path)?;
+    let header = read_batch(ring, &file, vec![(0, vec![0; HEADER_SIZE])])?
+        .pop()
+        .unwrap_or_default();
+    let header = Header::read(header.as_slice())?;
+
+    let chunks = (0..32 * 32)
+        .map(|index| (index % 32, index / 32))
+        .map(|(x, z)| (x, z, header.location(x, z)))
+        .filter(|(_, _, location)| location.is_present())
+        .collect::<Vec<_>>();
+    for batch in chunks.chunks(QUEUE_DEPTH as usize) {
+        let reads = batch
+            .iter()
+            .map(|(_, _, location)| {
+                let size = location.sectors as u64 * SECTOR_SIZE;
+                (location.offset as u64 * SECTOR_SIZE, vec![0; size as usize])
+            })
+            .collect();
+        let buffers = read_batch(ring, &file, reads)?;
+        for (&(x, z, location), buffer) in batch.iter().zip(buffers) {
+            let (id, data) = split_raw_chunk(buffer, &location)?;
+            f(RawChunk {
+                x,
+                z,
+                location,
+                id,
+                data,
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads the buffers from the file at their offsets, at most [`QUEUE_DEPTH`] at once. Buffers are shortened where the
+/// file ends early.
+fn read_batch(
+    ring: &mut Option<IoUring>,
+    file: &File,
+    mut reads: Vec<(u64, Vec<u8>)>,
+) -> io::Result<Vec<Vec<u8>>> {
+    let Some(uring) = ring.as_mut() else {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "io_uring failed before",
+        ));
+    };
+    let fd = types::Fd(file.as_raw_fd());
+    let mut lengths = vec![None; reads.len()];
+    let result = (|| {
+        for (i, (offset, buffer)) in reads.iter_mut().enumerate() {
+            let entry = opcode::Read::new(fd, buffer.as_mut_ptr(), buffer.len() as u32)
+                .offset(*offset)
+                .build()
+                .user_data(i as u64);
+            // SAFETY: the buffers aren't touched until the kernel is done with them, see below
+            unsafe { uring.submission().push(&entry) }
+                .map_err(|_| io::Error::new(io::ErrorKind::Other, "io_uring queue is full"))?;
+        }
+        let mut completed = 0;
+        while completed < reads.len() {
+            match uring.submit_and_wait(reads.len() - completed) {
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+            for entry in uring.completion() {
+                if let Some(length) = lengths.get_mut(entry.user_data() as usize) {
+                    *length = Some(entry.result());
+                    completed += 1;
+                }
+            }
+        }
+        Ok(())
+    })();
+    if let Err(err) = result {
+        // reads may still be queued or in flight, so the buffers must stay valid and the ring can't be used again
+        std::mem::forget(reads);
+        *ring = None;
+        return Err(err);
+    }
+
+    reads
+        .into_iter()
+        .zip(lengths)
+        .map(|((_, mut buffer), length)| match length {
+            Some(length) if length >= 0 => {
+                buffer.truncate(length as usize);
+                Ok(buffer)
+            }
+            Some(errno) => Err(io::Error::from_raw_os_error(-errno)),
+            None => unreachable!("all reads completed"),
+        })
+        .collect()
+}
+
+/// Splits a chunk read as whole sectors into its compression id and compressed data, like
+/// [`crate::region::read_raw_chunk`].
+fn split_raw_chunk(mut buffer: Vec<u8>, location: &Location) -> io::Result<(u8, Vec<u8>)> {
+    if buffer.len() < 5 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    let length = u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as u64;
+    if length > location.sectors as u64 * SECTOR_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "chunk length exceeds its sectors",
+        ));
+    }
+    let id = buffer[4];
+    buffer.truncate((4 + length as usize).clamp(5, buffer.len()));
+    buffer.drain(..5);
+    Ok((id, buffer))
+}
@@ -0,0 +1,109 @@
+//! Writing a region on a dedicated thread while its chunks are still being judged, see [`WriterThread`].
+
+use crate::region::RegionWriter;
+use crate::{ChunkCompression, CustomCompression};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::mpsc::{self, SyncSender};
+use std::thread::{self, JoinHandle};
+
+/// The amount of chunks queued for the writer thread, bounding the memory held by chunks waiting to be written.
+const QUEUE_SIZE: usize = 4;
+
+enum Message {
+    Remove {
+        x: usize,
+        z: usize,
+    },
+    Replace {
+        x: usize,
+        z: usize,
+        compression: ChunkCompression,
+        data: Vec<u8>,
+    },
+    Finish,
+}
+
+/// A [`RegionWriter`] on its own thread, so that compressing and writing the modified chunks of a region overlaps with
+/// reading and judging the next ones. The region file is only replaced by [`WriterThread::finish`], dropping the
+/// writer (e.g. because processing the region failed) leaves it untouched.
+pub(crate) struct WriterThread {
+    tx: Option<SyncSender<Message>>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl WriterThread {
+    pub(crate) fn spawn(path: &Path, custom: &HashMap<u8, CustomCompression>) -> io::Result<Self> {
+        let mut writer = RegionWriter::open(path)?;
+        let custom = custom.clone();
+        let (tx, rx) = mpsc::sync_channel(QUEUE_SIZE);
+        let thread = thread::spawn(move || {
+            let mut result = Ok(());
+            for message in rx {
+                // after a failure the remaining chunks are only received, the error is returned by `finish`
+                match message {
+                    Message::Remove { x, z } => writer.remove(x, z),
+                    Message::Replace {
+                        x,
+                        z,
+                        compression,
+                        data,
+                    } if result.is_ok() => {
+                        result = writer.replace(x, z, compression, &data, &custom);
+                    }
+                    Message::Replace { .. } => {}
+                    Message::Finish => return result.and_then(|_| writer.finish()),
+                }
+            }
+            Err(io::ErrorKind::Interrupted.into())
+        });
+        Ok(Self {
+            tx: Some(tx),
+            thread: Some(thread),
+        })
+    }
+
+    /// Removes the chunk at the given chunk coordinates relative to the region.
+    pub(crate) fn remove(&self, x: usize, z: usize) {
+        self.send(Message::Remove { x, z });
+    }
+
+    /// Writes the data in place of the chunk, see [`RegionWriter::replace`].
+    pub(crate) fn replace(&self, x: usize, z: usize, compression: ChunkCompression, data: Vec<u8>) {
+        self.send(Message::Replace {
+            x,
+            z,
+            compression,
+            data,
+        });
+    }
+
+    /// Waits for the chunks to be written and replaces the region file, see [`RegionWriter::finish`].
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        self.send(Message::Finish);
+        drop(self.tx.take());
+        self.thread
+            .take()
+            .expect("only taken here")
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+
+    fn send(&self, message: Message) {
+        // only fails if the thread panicked, which `finish` passes on
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(message);
+        }
+    }
+}
+
+impl Drop for WriterThread {
+    fn drop(&mut self) {
+        // without `Finish` the writer gives up once the channel is closed
+        drop(self.tx.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
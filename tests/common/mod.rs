@@ -0,0 +1,534 @@
+//! Synthetic worlds for the integration tests: region files with known chunks, a way to read them back and golden
+//! files to compare the results against.
+
+#![allow(dead_code)]
+
+use fastnbt::Value;
+use flate2::read::{GzDecoder, GzEncoder, ZlibDecoder, ZlibEncoder};
+use lessanvil::chunk::ChunkMeta;
+use lessanvil::{Config, Report};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const SECTOR_SIZE: usize = 4096;
+
+/// The DataVersion of 1.20.1, saved with the flat layout.
+pub const MODERN_DATA_VERSION: i32 = 3465;
+/// The DataVersion of 1.16.5, saved with the `Level` compound.
+pub const LEGACY_DATA_VERSION: i32 = 2586;
+
+/// The compression of a [`TestChunk`], with the ids used in region files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Gzip = 1,
+    Zlib = 2,
+    Uncompressed = 3,
+}
+
+/// A chunk to write into a region file.
+#[derive(Clone, Debug)]
+pub struct TestChunk {
+    /// The x coordinate relative to the region.
+    pub x: usize,
+    /// The z coordinate relative to the region.
+    pub z: usize,
+    /// In game ticks.
+    pub inhabited_time: i64,
+    pub data_version: i32,
+    pub compression: Compression,
+    /// Block ids placed in the lowest section.
+    pub blocks: Vec<String>,
+    /// The amount of chests in the chunk's block entities.
+    pub block_entities: usize,
+    /// Write garbage instead of the compressed chunk.
+    pub corrupt: bool,
+    /// Compress garbage instead of the chunk's NBT.
+    pub invalid_nbt: bool,
+}
+
+impl TestChunk {
+    /// A zlib compressed 1.20.1 chunk of stone.
+    pub fn new(x: usize, z: usize, inhabited_time: i64) -> Self {
+        TestChunk {
+            x,
+            z,
+            inhabited_time,
+            data_version: MODERN_DATA_VERSION,
+            compression: Compression::Zlib,
+            blocks: vec!["minecraft:stone".to_string()],
+            block_entities: 0,
+            corrupt: false,
+            invalid_nbt: false,
+        }
+    }
+
+    pub fn legacy(mut self) -> Self {
+        self.data_version = LEGACY_DATA_VERSION;
+        self
+    }
+
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn block(mut self, id: &str) -> Self {
+        self.blocks.push(id.to_string());
+        self
+    }
+
+    pub fn block_entities(mut self, count: usize) -> Self {
+        self.block_entities = count;
+        self
+    }
+
+    pub fn corrupt(mut self) -> Self {
+        self.corrupt = true;
+        self
+    }
+
+    pub fn invalid_nbt(mut self) -> Self {
+        self.invalid_nbt = true;
+        self
+    }
+
+    /// The uncompressed NBT of the chunk at the given absolute chunk coordinates.
+    pub fn nbt(&self, x_pos: i32, z_pos: i32) -> Vec<u8> {
+        let palette = Value::List(
+            std::iter::once("minecraft:air")
+                .chain(self.blocks.iter().map(String::as_str))
+                .map(|id| compound([("Name", Value::String(id.to_string()))]))
+                .collect(),
+        );
+        let fields = [
+            ("xPos", Value::Int(x_pos)),
+            ("zPos", Value::Int(z_pos)),
+            ("InhabitedTime", Value::Long(self.inhabited_time)),
+            ("LastUpdate", Value::Long(self.inhabited_time + 1)),
+        ];
+        // only written when there are any, to keep the other chunks as they were
+        let block_entities = (self.block_entities > 0).then(|| {
+            Value::List(
+                (0..self.block_entities)
+                    .map(|i| {
+                        compound([
+                            ("id", Value::String("minecraft:chest".to_string())),
+                            ("x", Value::Int(x_pos * 16 + (i % 16) as i32)),
+                            ("y", Value::Int((i / 256) as i32)),
+                            ("z", Value::Int(z_pos * 16 + (i / 16 % 16) as i32)),
+                        ])
+                    })
+                    .collect(),
+            )
+        });
+        let root = if self.data_version >= lessanvil::chunk::FLAT_LAYOUT_DATA_VERSION {
+            let section = compound([
+                ("Y", Value::Byte(0)),
+                ("block_states", compound([("palette", palette)])),
+            ]);
+            compound(
+                fields
+                    .into_iter()
+                    .chain([
+                        ("DataVersion", Value::Int(self.data_version)),
+                        ("Status", Value::String("minecraft:full".to_string())),
+                        ("sections", Value::List(vec![section])),
+                    ])
+                    .chain(block_entities.map(|list| ("block_entities", list))),
+            )
+        } else {
+            let section = compound([("Y", Value::Byte(0)), ("Palette", palette)]);
+            let level = compound(
+                fields
+                    .into_iter()
+                    .chain([
+                        ("Status", Value::String("full".to_string())),
+                        ("Sections", Value::List(vec![section])),
+                    ])
+                    .chain(block_entities.map(|list| ("TileEntities", list))),
+            );
+            compound([
+                ("DataVersion", Value::Int(self.data_version)),
+                ("Level", level),
+            ])
+        };
+        fastnbt::to_bytes(&root).unwrap()
+    }
+
+    fn encode(&self, x_pos: i32, z_pos: i32) -> Vec<u8> {
+        if self.corrupt {
+            return b"not a compressed chunk".to_vec();
+        }
+        let nbt = if self.invalid_nbt {
+            b"not a chunk".to_vec()
+        } else {
+            self.nbt(x_pos, z_pos)
+        };
+        let mut encoded = vec![];
+        match self.compression {
+            Compression::Gzip => {
+                GzEncoder::new(&nbt[..], flate2::Compression::fast())
+                    .read_to_end(&mut encoded)
+                    .unwrap();
+            }
+            Compression::Zlib => {
+                ZlibEncoder::new(&nbt[..], flate2::Compression::fast())
+                    .read_to_end(&mut encoded)
+                    .unwrap();
+            }
+            Compression::Uncompressed => encoded = nbt,
+        }
+        encoded
+    }
+}
+
+fn compound<'a>(fields: impl IntoIterator<Item = (&'a str, Value)>) -> Value {
+    Value::Compound(
+        fields
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect::<HashMap<_, _>>(),
+    )
+}
+
+/// A world in a temporary folder that is removed when it's dropped.
+pub struct TestWorld {
+    path: PathBuf,
+}
+
+impl TestWorld {
+    /// An empty world with a `level.dat`.
+    pub fn new(name: &str) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "lessanvil-test-{}-{name}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(path.join("region")).unwrap();
+
+        let level = compound([(
+            "Data",
+            compound([
+                ("LevelName", Value::String(name.to_string())),
+                ("DataVersion", Value::Int(MODERN_DATA_VERSION)),
+            ]),
+        )]);
+        let mut encoder = flate2::write::GzEncoder::new(
+            fs::File::create(path.join("level.dat")).unwrap(),
+            flate2::Compression::default(),
+        );
+        encoder
+            .write_all(&fastnbt::to_bytes(&level).unwrap())
+            .unwrap();
+        encoder.finish().unwrap();
+
+        TestWorld { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// A config modifying this world with two threads.
+    pub fn config(&self) -> Config {
+        Config {
+            world_folder: self.path.clone(),
+            thread_count: 2,
+            dry_run: false,
+            ..Default::default()
+        }
+    }
+
+    /// Writes the region file `r.<x>.<z>.mca` into the folder (relative to the world, e.g. `DIM-1/region`).
+    pub fn write_region(&self, folder: &str, x: i32, z: i32, chunks: &[TestChunk]) -> PathBuf {
+        let chunks = chunks
+            .iter()
+            .map(|chunk| {
+                let data = chunk.encode(x * 32 + chunk.x as i32, z * 32 + chunk.z as i32);
+                (
+                    chunk.x,
+                    chunk.z,
+                    chunk.compression,
+                    data,
+                    chunk.inhabited_time,
+                )
+            })
+            .collect::<Vec<_>>();
+        self.write_raw_region(folder, x, z, &chunks)
+    }
+
+    /// Writes the entity data of the region `r.<x>.<z>.mca` into the folder (relative to the world, e.g. `entities`):
+    /// the ids of the entities of each chunk, given with its coordinates relative to the region.
+    pub fn write_entities(
+        &self,
+        folder: &str,
+        x: i32,
+        z: i32,
+        chunks: &[(usize, usize, &[&str])],
+    ) -> PathBuf {
+        let chunks = chunks
+            .iter()
+            .map(|&(chunk_x, chunk_z, ids)| {
+                let root = compound([
+                    ("DataVersion", Value::Int(MODERN_DATA_VERSION)),
+                    (
+                        "Position",
+                        Value::IntArray(fastnbt::IntArray::new(vec![
+                            x * 32 + chunk_x as i32,
+                            z * 32 + chunk_z as i32,
+                        ])),
+                    ),
+                    (
+                        "Entities",
+                        Value::List(
+                            ids.iter()
+                                .map(|id| compound([("id", Value::String(id.to_string()))]))
+                                .collect(),
+                        ),
+                    ),
+                ]);
+                let nbt = fastnbt::to_bytes(&root).unwrap();
+                (chunk_x, chunk_z, Compression::Uncompressed, nbt, 0)
+            })
+            .collect::<Vec<_>>();
+        self.write_raw_region(folder, x, z, &chunks)
+    }
+
+    /// Writes already encoded chunks, the last field is their InhabitedTime for the timestamp.
+    fn write_raw_region(
+        &self,
+        folder: &str,
+        x: i32,
+        z: i32,
+        chunks: &[(usize, usize, Compression, Vec<u8>, i64)],
+    ) -> PathBuf {
+        let folder = self.path.join(folder);
+        fs::create_dir_all(&folder).unwrap();
+        let path = folder.join(format!("r.{x}.{z}.mca"));
+
+        let mut file = vec![0; 2 * SECTOR_SIZE];
+        for (chunk_x, chunk_z, compression, data, inhabited_time) in chunks {
+            let index = (chunk_z * 32 + chunk_x) * 4;
+            let offset = file.len() / SECTOR_SIZE;
+            file.extend_from_slice(&(data.len() as u32 + 1).to_be_bytes());
+            file.push(*compression as u8);
+            file.extend_from_slice(data);
+            file.resize(
+                (file.len() + SECTOR_SIZE - 1) / SECTOR_SIZE * SECTOR_SIZE,
+                0,
+            );
+            let sectors = file.len() / SECTOR_SIZE - offset;
+
+            file[index..index + 3].copy_from_slice(&(offset as u32).to_be_bytes()[1..]);
+            file[index + 3] = sectors as u8;
+            let timestamp = 1_700_000_000 + *inhabited_time as u32;
+            file[SECTOR_SIZE + index..SECTOR_SIZE + index + 4]
+                .copy_from_slice(&timestamp.to_be_bytes());
+        }
+        fs::write(&path, file).unwrap();
+        path
+    }
+
+    /// The region files of the world, relative to it and sorted.
+    pub fn region_files(&self) -> Vec<PathBuf> {
+        let mut files = vec![];
+        let mut folders = vec![self.path.clone()];
+        while let Some(folder) = folders.pop() {
+            for entry in fs::read_dir(folder).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    folders.push(path);
+                } else if path.extension().is_some_and(|ext| ext == "mca") {
+                    files.push(path.strip_prefix(&self.path).unwrap().to_path_buf());
+                }
+            }
+        }
+        files.sort();
+        files
+    }
+
+    /// Reads the chunks of a region file back, by their coordinates relative to the region.
+    pub fn read_region(&self, file: &Path) -> BTreeMap<(usize, usize), StoredChunk> {
+        let data = fs::read(self.path.join(file)).unwrap();
+        let mut chunks = BTreeMap::new();
+        if data.len() < 2 * SECTOR_SIZE {
+            return chunks;
+        }
+        for index in 0..1024 {
+            let location = &data[index * 4..index * 4 + 4];
+            let offset = u32::from_be_bytes([0, location[0], location[1], location[2]]) as usize;
+            if offset == 0 {
+                continue;
+            }
+            let start = offset * SECTOR_SIZE;
+            let length = u32::from_be_bytes(data[start..start + 4].try_into().unwrap()) as usize;
+            let compression = data[start + 4];
+            let payload = &data[start + 5..start + 4 + length];
+            let mut nbt = vec![];
+            let decoded = match compression {
+                1 => GzDecoder::new(payload).read_to_end(&mut nbt).is_ok(),
+                2 => ZlibDecoder::new(payload).read_to_end(&mut nbt).is_ok(),
+                3 => {
+                    nbt.extend_from_slice(payload);
+                    true
+                }
+                _ => false,
+            };
+            chunks.insert(
+                (index % 32, index / 32),
+                StoredChunk {
+                    compression,
+                    meta: decoded.then(|| ChunkMeta::from_bytes(&nbt).ok()).flatten(),
+                    nbt: decoded.then_some(nbt),
+                },
+            );
+        }
+        chunks
+    }
+
+    /// A description of every chunk of the world, for golden files.
+    pub fn describe(&self) -> String {
+        let mut description = String::new();
+        for file in self.region_files() {
+            writeln!(
+                description,
+                "{}",
+                file.display().to_string().replace('\\', "/")
+            )
+            .unwrap();
+            for ((x, z), chunk) in self.read_region(&file) {
+                write!(
+                    description,
+                    "  {x:>2},{z:>2} compression={}",
+                    chunk.compression
+                )
+                .unwrap();
+                match chunk.meta {
+                    Some(meta) => writeln!(
+                        description,
+                        " version={} inhabited={}",
+                        meta.data_version.unwrap_or_default(),
+                        meta.inhabited_time.ticks()
+                    ),
+                    None => writeln!(description, " unreadable"),
+                }
+                .unwrap();
+            }
+        }
+        description
+    }
+}
+
+impl Drop for TestWorld {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// A chunk read back by [`TestWorld::read_region`].
+#[derive(Clone, Debug)]
+pub struct StoredChunk {
+    /// The compression id.
+    pub compression: u8,
+    /// The uncompressed NBT, `None` if it couldn't be decompressed.
+    pub nbt: Option<Vec<u8>>,
+    /// `None` if the chunk couldn't be decompressed or parsed.
+    pub meta: Option<ChunkMeta>,
+}
+
+/// Runs the config to the end.
+pub fn run(config: Config) -> Report {
+    match lessanvil::execute(config).unwrap().join() {
+        Ok(report) => report,
+        Err(report) => panic!("the execution was cancelled: {:?}", report.warnings),
+    }
+}
+
+/// A description of the report without the parts that vary between runs, for golden files.
+pub fn describe_report(world: &TestWorld, report: &Report) -> String {
+    let relative = |path: &Path| {
+        path.strip_prefix(world.path())
+            .unwrap_or(path)
+            .display()
+            .to_string()
+            .replace('\\', "/")
+    };
+    let mut description = String::new();
+    writeln!(description, "regions: {}", report.total_regions).unwrap();
+    writeln!(description, "chunks: {}", report.total_chunks).unwrap();
+    writeln!(description, "deleted: {}", report.total_deleted_chunks).unwrap();
+    writeln!(description, "reset: {}", report.total_reset_chunks).unwrap();
+    writeln!(description, "trimmed: {}", report.total_trimmed_chunks).unwrap();
+    writeln!(
+        description,
+        "recompressed: {}",
+        report.total_recompressed_chunks
+    )
+    .unwrap();
+    writeln!(description, "freed: {}", report.total_freed_space).unwrap();
+    for region in &report.failed_regions {
+        writeln!(description, "failed: {}", relative(region)).unwrap();
+    }
+    for file in &report.skipped_files {
+        writeln!(description, "skipped: {}", relative(&file.path)).unwrap();
+    }
+    for warning in &report.warnings {
+        writeln!(description, "warning: {warning}").unwrap();
+    }
+    description
+}
+
+/// Compares the text with `tests/golden/<name>.txt`. Set `LESSANVIL_UPDATE_GOLDEN=1` to write the file instead.
+pub fn assert_golden(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.txt"));
+    if std::env::var_os("LESSANVIL_UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "{} is missing, run the tests with LESSANVIL_UPDATE_GOLDEN=1 to create it",
+            path.display()
+        )
+    });
+    assert_eq!(
+        expected.replace("\r\n", "\n"),
+        actual,
+        "differs from {}, run the tests with LESSANVIL_UPDATE_GOLDEN=1 to update it",
+        path.display()
+    );
+}
+
+/// A small deterministic random number generator (xorshift64*) for generating worlds from a seed.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// A number in `0..bound`.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+
+    pub fn chance(&mut self, percent: u64) -> bool {
+        self.below(100) < percent
+    }
+}
@@ -0,0 +1,40 @@
+//! Runs the fuzzing entry points on the seed corpus of broken region files and chunks, none of which may panic.
+
+#![cfg(feature = "fuzzing")]
+
+use lessanvil::fuzzing;
+use std::fs;
+use std::path::Path;
+
+fn corpus(target: &str) -> Vec<Vec<u8>> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fuzz/corpus")
+        .join(target);
+    let mut files = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    files.sort();
+    assert!(!files.is_empty());
+    files
+        .into_iter()
+        .map(|file| fs::read(file).unwrap())
+        .collect()
+}
+
+#[test]
+fn region_corpus() {
+    for data in corpus("region") {
+        fuzzing::region(&data);
+    }
+}
+
+#[test]
+fn chunk_corpus() {
+    for data in corpus("chunk") {
+        fuzzing::chunk(&data);
+        for compression_id in 0..=4 {
+            fuzzing::compressed_chunk(compression_id, &data);
+        }
+    }
+}
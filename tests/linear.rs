@@ -0,0 +1,56 @@
+//! Converting worlds between Anvil and the Linear region format.
+
+#![cfg(feature = "linear")]
+
+mod common;
+
+use common::{Compression, TestChunk, TestWorld};
+use lessanvil::linear::{self, RegionFormat};
+
+#[test]
+fn round_trip() {
+    let world = TestWorld::new("linear");
+    world.write_region(
+        "region",
+        0,
+        0,
+        &[
+            TestChunk::new(0, 0, 0),
+            TestChunk::new(5, 3, 72_000).compression(Compression::Gzip),
+            TestChunk::new(31, 31, 100).legacy(),
+        ],
+    );
+    world.write_region(
+        "DIM-1/region",
+        -1,
+        2,
+        &[TestChunk::new(1, 31, 10).compression(Compression::Uncompressed)],
+    );
+    let read_chunks = |world: &TestWorld| {
+        world
+            .region_files()
+            .iter()
+            .map(|file| {
+                let chunks = world
+                    .read_region(file)
+                    .into_iter()
+                    .map(|(position, chunk)| (position, chunk.nbt.unwrap()))
+                    .collect::<Vec<_>>();
+                (file.clone(), chunks)
+            })
+            .collect::<Vec<_>>()
+    };
+    let before = read_chunks(&world);
+
+    let report = linear::convert(world.path(), RegionFormat::Linear).unwrap();
+    assert_eq!((report.converted_regions, report.converted_chunks), (2, 4));
+    assert!(world.region_files().is_empty());
+    assert!(world.path().join("region/r.0.0.linear").exists());
+    assert!(world.path().join("DIM-1/region/r.-1.2.linear").exists());
+
+    let report = linear::convert(world.path(), RegionFormat::Anvil).unwrap();
+    assert_eq!((report.converted_regions, report.converted_chunks), (2, 4));
+    assert!(!world.path().join("region/r.0.0.linear").exists());
+    // the chunks are the same, only compressed with zlib now
+    assert_eq!(read_chunks(&world), before);
+}
@@ -0,0 +1,941 @@
+//! End-to-end tests of the processing pipeline on synthetic worlds.
+
+mod common;
+
+use common::{assert_golden, describe_report, run, Compression, Rng, TestChunk, TestWorld};
+#[cfg(feature = "plan")]
+use lessanvil::cache;
+use lessanvil::lock::{self, WorldLock};
+use lessanvil::protection;
+use lessanvil::scan::{ScanBackend, WorldScanner};
+use lessanvil::{
+    ChunkCompression, ChunkCount, Config, Dimension, Error, HardlinkPolicy, InhabitedTime,
+    Operation, ProcessingUpdate, RunId,
+};
+use std::fs;
+
+/// Two regions in the overworld and one in the nether, mixing versions and compressions.
+fn mixed_world(name: &str) -> TestWorld {
+    let world = TestWorld::new(name);
+    world.write_region(
+        "region",
+        0,
+        0,
+        &[
+            TestChunk::new(0, 0, 0),
+            TestChunk::new(1, 0, 199),
+            TestChunk::new(2, 0, 200),
+            TestChunk::new(3, 0, 201),
+            TestChunk::new(4, 0, 72_000).compression(Compression::Gzip),
+            TestChunk::new(5, 0, 10).compression(Compression::Uncompressed),
+            TestChunk::new(0, 1, 50).legacy(),
+            TestChunk::new(1, 1, 5_000).legacy(),
+            TestChunk::new(31, 31, 100).block("minecraft:crafting_table"),
+        ],
+    );
+    world.write_region(
+        "region",
+        -1,
+        0,
+        &[TestChunk::new(31, 0, 0), TestChunk::new(30, 0, 0)],
+    );
+    world.write_region(
+        "DIM-1/region",
+        0,
+        -1,
+        &[
+            TestChunk::new(0, 31, 300).legacy(),
+            TestChunk::new(1, 31, 100).compression(Compression::Gzip),
+        ],
+    );
+    world
+}
+
+fn ten_seconds(world: &TestWorld) -> Config {
+    Config {
+        max_inhabited_time: InhabitedTime::from_secs(10),
+        ..world.config()
+    }
+}
+
+#[test]
+fn prune() {
+    let world = mixed_world("prune");
+    let report = run(ten_seconds(&world));
+    assert_golden(
+        "prune",
+        &format!("{}\n{}", describe_report(&world, &report), world.describe()),
+    );
+}
+
+#[test]
+fn blocking() {
+    let world = mixed_world("blocking");
+    let report = lessanvil::execute_blocking(ten_seconds(&world)).unwrap();
+    // the same result as following the execution
+    assert_golden(
+        "prune",
+        &format!("{}\n{}", describe_report(&world, &report), world.describe()),
+    );
+}
+
+#[test]
+fn count_chunks() {
+    let world = mixed_world("count-chunks");
+    let counts = lessanvil::count_chunks(&ten_seconds(&world)).unwrap();
+    assert_eq!(
+        counts.into_iter().collect::<Vec<_>>(),
+        [
+            (
+                Dimension::Overworld,
+                ChunkCount {
+                    regions: 2,
+                    chunks: 11
+                }
+            ),
+            (
+                Dimension::Nether,
+                ChunkCount {
+                    regions: 1,
+                    chunks: 2
+                }
+            ),
+        ]
+    );
+
+    let nether = lessanvil::count_chunks(&Config {
+        dimensions: vec![Dimension::Nether],
+        ..ten_seconds(&world)
+    })
+    .unwrap();
+    assert_eq!(nether.keys().collect::<Vec<_>>(), [&Dimension::Nether]);
+}
+
+#[test]
+fn dry_run_leaves_files_untouched() {
+    let world = mixed_world("dry-run");
+    let before = world
+        .region_files()
+        .into_iter()
+        .map(|file| fs::read(world.path().join(file)).unwrap())
+        .collect::<Vec<_>>();
+
+    let report = run(Config {
+        dry_run: true,
+        ..ten_seconds(&world)
+    });
+
+    let after = world
+        .region_files()
+        .into_iter()
+        .map(|file| fs::read(world.path().join(file)).unwrap())
+        .collect::<Vec<_>>();
+    assert!(before == after, "a dry run modified the world");
+    // a dry run reports the same as the real run
+    let real = run(ten_seconds(&world));
+    assert_eq!(report.total_chunks, real.total_chunks);
+    assert_eq!(report.total_deleted_chunks, real.total_deleted_chunks);
+}
+
+#[test]
+fn reset_inhabited_time() {
+    let world = mixed_world("reset");
+    let report = run(Config {
+        operation: Operation::ResetInhabitedTime,
+        ..ten_seconds(&world)
+    });
+    assert_golden(
+        "reset_inhabited_time",
+        &format!("{}\n{}", describe_report(&world, &report), world.describe()),
+    );
+}
+
+#[test]
+fn protected_blocks() {
+    let world = mixed_world("protected-blocks");
+    let report = run(Config {
+        player_blocks: vec!["minecraft:crafting_table".to_string()],
+        ..ten_seconds(&world)
+    });
+    assert_golden(
+        "protected_blocks",
+        &format!("{}\n{}", describe_report(&world, &report), world.describe()),
+    );
+}
+
+#[test]
+fn surface_player_blocks() {
+    let world = TestWorld::new("surface-player-blocks");
+    // the blocks of the test chunks are below the sea level
+    world.write_region(
+        "region",
+        0,
+        0,
+        &[TestChunk::new(0, 0, 0).block("minecraft:torch")],
+    );
+    world.write_region(
+        "DIM-1/region",
+        0,
+        0,
+        &[TestChunk::new(0, 0, 0).block("minecraft:wall_torch")],
+    );
+    let config = Config {
+        player_blocks: lessanvil::DEFAULT_PLAYER_BLOCKS
+            .iter()
+            .map(|id| id.to_string())
+            .collect(),
+        dry_run: true,
+        ..ten_seconds(&world)
+    };
+    // only the torch underground in the overworld doesn't count
+    assert_eq!(run(config.clone()).total_deleted_chunks, 1);
+    let report = run(Config {
+        dimensions: vec![Dimension::Nether],
+        ..config
+    });
+    assert_eq!(report.total_deleted_chunks, 0);
+}
+
+#[test]
+fn max_delete_ratio() {
+    let world = mixed_world("max-delete-ratio");
+    // 9 of the 13 chunks are below the threshold
+    let config = Config {
+        max_delete_ratio: Some(0.5),
+        target_free_space: Some(u64::MAX),
+        dry_run: false,
+        ..ten_seconds(&world)
+    };
+    let Err(report) = lessanvil::execute(config.clone()).unwrap().join() else {
+        panic!("the execution wasn't cancelled");
+    };
+    assert_eq!(report.total_deleted_chunks, 0);
+    assert!(matches!(
+        report.warnings[..],
+        [lessanvil::ReportWarning::DeleteRatioExceeded { max_ratio, .. }] if max_ratio == 0.5
+    ));
+    assert_eq!(
+        world
+            .read_region(&world.path().join("region/r.0.0.mca"))
+            .len(),
+        9
+    );
+
+    let report = run(Config {
+        max_delete_ratio: Some(0.7),
+        ..config
+    });
+    assert_eq!(report.total_deleted_chunks, 9);
+}
+
+#[test]
+fn recompress() {
+    let world = mixed_world("recompress");
+    let report = run(Config {
+        recompress: Some(ChunkCompression::Uncompressed),
+        ..ten_seconds(&world)
+    });
+    assert_golden(
+        "recompress",
+        &format!("{}\n{}", describe_report(&world, &report), world.describe()),
+    );
+}
+
+#[test]
+fn corrupted_chunk() {
+    let world = TestWorld::new("corrupted");
+    world.write_region(
+        "region",
+        0,
+        0,
+        &[
+            TestChunk::new(0, 0, 0),
+            TestChunk::new(1, 0, 0).corrupt(),
+            TestChunk::new(2, 0, 100_000),
+        ],
+    );
+    world.write_region("region", 1, 0, &[TestChunk::new(0, 0, 0)]);
+    let report = run(ten_seconds(&world));
+    assert_golden(
+        "corrupted_chunk",
+        &format!("{}\n{}", describe_report(&world, &report), world.describe()),
+    );
+}
+
+#[test]
+fn invalid_chunk_nbt() {
+    let world = TestWorld::new("invalid-nbt");
+    let path = world.write_region(
+        "region",
+        0,
+        0,
+        &[
+            TestChunk::new(0, 0, 0),
+            TestChunk::new(1, 0, 0).invalid_nbt(),
+            TestChunk::new(2, 0, 100_000),
+        ],
+    );
+    let before = world.read_region(&path);
+    let report = run(ten_seconds(&world));
+
+    // the rest of the region is processed, the chunk is kept as it is
+    assert_eq!(report.total_deleted_chunks, 1);
+    assert!(report.warnings.iter().any(|warning| matches!(
+        warning,
+        lessanvil::ReportWarning::UnreadableChunks { chunks: 1 }
+    )));
+    let after = world.read_region(&path);
+    assert_eq!(after.keys().collect::<Vec<_>>(), [&(1, 0), &(2, 0)]);
+    assert_eq!(after[&(1, 0)].nbt, before[&(1, 0)].nbt);
+}
+
+/// Random worlds: every chunk at or below the threshold is deleted, every other chunk is kept byte for byte and the
+/// report adds up.
+#[test]
+fn random_worlds() {
+    for seed in 0..16 {
+        let mut rng = Rng::new(seed);
+        let world = TestWorld::new(&format!("random-{seed}"));
+        let max_inhabited_time = InhabitedTime::from_ticks(rng.below(400));
+
+        let mut written = vec![];
+        for (folder, x, z) in [("region", 0, 0), ("region", -1, 2), ("DIM1/region", 0, 0)] {
+            let mut chunks = vec![];
+            for index in 0..1024 {
+                if !rng.chance(30) {
+                    continue;
+                }
+                let mut chunk = TestChunk::new(index % 32, index / 32, rng.below(800) as i64)
+                    .compression(match rng.below(3) {
+                        0 => Compression::Gzip,
+                        1 => Compression::Zlib,
+                        _ => Compression::Uncompressed,
+                    });
+                if rng.chance(30) {
+                    chunk = chunk.legacy();
+                }
+                chunks.push(chunk);
+            }
+            let file = world.write_region(folder, x, z, &chunks);
+            let relative = file.strip_prefix(world.path()).unwrap().to_path_buf();
+            written.push((relative, world.read_region(&file), chunks));
+        }
+
+        let report = run(Config {
+            max_inhabited_time,
+            ..world.config()
+        });
+
+        let mut total_chunks = 0;
+        let mut deleted_chunks = 0;
+        for (file, before, chunks) in written {
+            let after = world.read_region(&file);
+            total_chunks += chunks.len() as u64;
+            for chunk in chunks {
+                let position = (chunk.x, chunk.z);
+                if InhabitedTime::from_ticks(chunk.inhabited_time as u64) <= max_inhabited_time {
+                    deleted_chunks += 1;
+                    assert!(
+                        !after.contains_key(&position),
+                        "seed {seed}: {position:?} of {} wasn't deleted",
+                        file.display()
+                    );
+                } else {
+                    assert_eq!(
+                        before[&position].nbt,
+                        after.get(&position).and_then(|chunk| chunk.nbt.clone()),
+                        "seed {seed}: {position:?} of {} changed",
+                        file.display()
+                    );
+                }
+            }
+        }
+        assert_eq!(report.total_chunks, total_chunks, "seed {seed}");
+        assert_eq!(report.total_deleted_chunks, deleted_chunks, "seed {seed}");
+        assert!(report.failed_regions.is_empty(), "seed {seed}");
+    }
+}
+
+#[test]
+fn locked_world() {
+    let world = mixed_world("locked");
+    let lock = WorldLock::acquire(world.path()).unwrap();
+    assert!(matches!(
+        lessanvil::execute(ten_seconds(&world)),
+        Err(Error::WorldLocked { pid: Some(pid), .. }) if pid == std::process::id()
+    ));
+    // a dry run doesn't need the lock
+    run(Config {
+        dry_run: true,
+        ..ten_seconds(&world)
+    });
+
+    drop(lock);
+    run(ten_seconds(&world));
+    assert!(!world.path().join(lock::STATE_FOLDER).exists());
+    assert!(!lock::unlock(world.path()).unwrap());
+
+    // the lock of an earlier process with the same id is stale
+    if cfg!(target_os = "linux") {
+        let folder = world.path().join(lock::STATE_FOLDER);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("lock"), format!("{} 0/0", std::process::id())).unwrap();
+        drop(WorldLock::acquire(world.path()).unwrap());
+    }
+}
+
+#[test]
+fn run_id() {
+    let world = mixed_world("run-id");
+    let id = "67e55044-10b1-426f-9247-bb680e5fe0c8"
+        .parse::<RunId>()
+        .unwrap();
+    assert_eq!(id.to_string(), "67e55044-10b1-426f-9247-bb680e5fe0c8");
+    let report = run(Config {
+        run_id: Some(id),
+        ..ten_seconds(&world)
+    });
+    assert_eq!(report.run_id, id);
+
+    // generated ids are random version 4 UUIDs
+    let (a, b) = (RunId::new().to_string(), RunId::new().to_string());
+    assert_ne!(a, b);
+    assert_eq!(&a[14..15], "4");
+    assert!("89ab".contains(&a[19..20]));
+}
+
+#[test]
+fn inhabited_times() {
+    let world = mixed_world("inhabited-times");
+    let execution = lessanvil::execute(Config {
+        dry_run: true,
+        ..ten_seconds(&world)
+    })
+    .unwrap();
+    let mut ticks = execution
+        .updates()
+        .iter()
+        .filter_map(|update| match update {
+            ProcessingUpdate::ProcessedRegion(Ok(region)) => Some(region.inhabited_times),
+            _ => None,
+        })
+        .flatten()
+        .map(InhabitedTime::ticks)
+        .collect::<Vec<_>>();
+    let _ = execution.join();
+    ticks.sort_unstable();
+    assert_eq!(
+        ticks,
+        [0, 0, 0, 10, 50, 100, 100, 199, 200, 201, 300, 5_000, 72_000]
+    );
+}
+
+#[test]
+fn backup_of_modified_regions() {
+    let world = mixed_world("backup");
+    let backup = world.path().with_extension("backup");
+    let original = fs::read(world.path().join("region/r.0.0.mca")).unwrap();
+    run(Config {
+        max_inhabited_time: InhabitedTime::from_ticks(50),
+        backup_folder: Some(backup.clone()),
+        ..world.config()
+    });
+
+    assert_eq!(fs::read(backup.join("region/r.0.0.mca")).unwrap(), original);
+    assert!(backup.join("region/r.-1.0.mca").exists());
+    // none of the nether's chunks were deleted
+    assert!(!backup.join("DIM-1").exists());
+    let _ = fs::remove_dir_all(backup);
+}
+
+#[test]
+fn duplicate_chunks() {
+    let world = mixed_world("duplicates");
+    let stats = lessanvil::analysis::duplicate_chunks(world.path()).unwrap();
+    assert_eq!(stats.total_chunks, 13);
+    // the modern and the legacy chunks of stone, wherever they are and however they're compressed
+    assert_eq!(stats.duplicate_chunks, 8 + 2);
+    let chunks = stats
+        .largest_groups
+        .iter()
+        .map(|group| (group.chunks, group.empty))
+        .collect::<Vec<_>>();
+    assert_eq!(chunks, [(9, false), (3, false)]);
+    assert!(stats.duplicate_bytes > 0);
+}
+
+#[test]
+fn outdated_chunks() {
+    let world = mixed_world("outdated");
+    let outdated = lessanvil::OutdatedChunks {
+        min_data_version: lessanvil::chunk::FLAT_LAYOUT_DATA_VERSION,
+        prune: false,
+    };
+    let report = run(Config {
+        outdated_chunks: Some(outdated),
+        dry_run: true,
+        ..ten_seconds(&world)
+    });
+    assert_eq!(report.total_outdated_chunks, 3);
+
+    // the legacy chunk with 5000 ticks is pruned as well, the one in the nether with 300 ticks too
+    let report = run(Config {
+        outdated_chunks: Some(lessanvil::OutdatedChunks {
+            prune: true,
+            ..outdated
+        }),
+        ..ten_seconds(&world)
+    });
+    assert_eq!(report.total_outdated_chunks, 3);
+    let legacy = world
+        .region_files()
+        .iter()
+        .flat_map(|file| world.read_region(file).into_values())
+        .filter(|chunk| {
+            chunk.meta.as_ref().unwrap().data_version
+                < Some(lessanvil::chunk::FLAT_LAYOUT_DATA_VERSION)
+        })
+        .count();
+    assert_eq!(legacy, 0);
+}
+
+#[test]
+fn oversized_chunks() {
+    let world = TestWorld::new("oversized");
+    let mut lag_machine = TestChunk::new(0, 0, 100_000).compression(Compression::Uncompressed);
+    for i in 0..300 {
+        lag_machine = lag_machine.block(&format!("minecraft:hopper_{i}"));
+    }
+    world.write_region(
+        "region",
+        0,
+        0,
+        &[lag_machine, TestChunk::new(1, 0, 100_000)],
+    );
+    let oversized = lessanvil::OversizedChunks {
+        max_size: 4096,
+        prune: false,
+    };
+    let report = run(Config {
+        oversized_chunks: Some(oversized),
+        ..ten_seconds(&world)
+    });
+    assert_eq!(report.total_oversized_chunks, 1);
+    assert_eq!(report.total_deleted_chunks, 0);
+
+    // pruned despite its InhabitedTime
+    let report = run(Config {
+        oversized_chunks: Some(lessanvil::OversizedChunks {
+            prune: true,
+            ..oversized
+        }),
+        ..ten_seconds(&world)
+    });
+    assert_eq!(report.total_deleted_chunks, 1);
+    let chunks = world.read_region(&world.path().join("region/r.0.0.mca"));
+    assert_eq!(chunks.keys().collect::<Vec<_>>(), [&(1, 0)]);
+}
+
+#[test]
+fn crowded_chunks() {
+    let world = TestWorld::new("crowded");
+    world.write_region(
+        "region",
+        0,
+        0,
+        &[
+            TestChunk::new(0, 0, 100_000),
+            TestChunk::new(1, 0, 100_000),
+            TestChunk::new(2, 0, 100_000),
+        ],
+    );
+    world.write_entities(
+        "entities",
+        0,
+        0,
+        &[
+            (0, 0, &["minecraft:item"; 5]),
+            (1, 0, &["minecraft:zombie", "minecraft:zombie"]),
+            (2, 0, &["minecraft:cow"]),
+        ],
+    );
+    let limits = lessanvil::EntityLimits {
+        max_entities: Some(2),
+        max_by_type: [("minecraft:zombie".to_string(), 1)].into(),
+        prune: false,
+    };
+    let report = run(Config {
+        entity_limits: Some(limits.clone()),
+        ..ten_seconds(&world)
+    });
+    assert_eq!(report.total_crowded_chunks, 2);
+    assert_eq!(report.total_deleted_chunks, 0);
+
+    let report = run(Config {
+        entity_limits: Some(lessanvil::EntityLimits {
+            prune: true,
+            ..limits
+        }),
+        ..ten_seconds(&world)
+    });
+    assert_eq!(report.total_deleted_chunks, 2);
+    for folder in ["region", "entities"] {
+        let chunks = world.read_region(&world.path().join(folder).join("r.0.0.mca"));
+        assert_eq!(chunks.keys().collect::<Vec<_>>(), [&(2, 0)], "{folder}");
+    }
+}
+
+#[test]
+fn cluttered_chunks() {
+    let world = TestWorld::new("cluttered");
+    world.write_region(
+        "region",
+        0,
+        0,
+        &[
+            TestChunk::new(0, 0, 100_000).block_entities(50),
+            TestChunk::new(1, 0, 100_000).block_entities(50).legacy(),
+            TestChunk::new(2, 0, 100_000).block_entities(10),
+        ],
+    );
+    let limit = lessanvil::BlockEntityLimit {
+        max_block_entities: 10,
+        prune: false,
+    };
+    let report = run(Config {
+        block_entity_limit: Some(limit),
+        ..ten_seconds(&world)
+    });
+    assert_eq!(report.total_cluttered_chunks, 2);
+    assert_eq!(report.total_deleted_chunks, 0);
+
+    let report = run(Config {
+        block_entity_limit: Some(lessanvil::BlockEntityLimit {
+            prune: true,
+            ..limit
+        }),
+        ..ten_seconds(&world)
+    });
+    assert_eq!(report.total_deleted_chunks, 2);
+    let chunks = world.read_region(&world.path().join("region/r.0.0.mca"));
+    assert_eq!(chunks.keys().collect::<Vec<_>>(), [&(2, 0)]);
+}
+
+#[test]
+fn repair_headers() {
+    use lessanvil::repair::{self, EntryProblem, RepairOptions};
+
+    let world = TestWorld::new("repair");
+    let path = world.write_region(
+        "region",
+        0,
+        0,
+        &[
+            TestChunk::new(0, 0, 0),
+            TestChunk::new(1, 0, 0),
+            TestChunk::new(2, 0, 0),
+            TestChunk::new(3, 0, 0).corrupt(),
+        ],
+    );
+    let mut data = fs::read(&path).unwrap();
+    // (1, 0) points at the data of (0, 0), (2, 0) claims no sectors, (4, 0) points into the header and (5, 0) past
+    // the end of the file
+    let (first, second) = data.split_at_mut(4);
+    second[..3].copy_from_slice(&first[..3]);
+    data[11] = 0;
+    data[16..20].copy_from_slice(&[0, 0, 1, 1]);
+    data[20..24].copy_from_slice(&[0, 1, 0, 1]);
+    fs::write(&path, &data).unwrap();
+
+    let options = RepairOptions {
+        dry_run: true,
+        ..Default::default()
+    };
+    let report = repair::repair_world(world.path(), &options).unwrap();
+    assert_eq!(report.checked_regions, 1);
+    let problems = report.regions[0]
+        .entries
+        .iter()
+        .map(|entry| (entry.x, entry.problem.clone(), entry.recovered))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        problems,
+        [
+            (0, EntryProblem::Overlapping { x: 1, z: 0 }, true),
+            (1, EntryProblem::Overlapping { x: 0, z: 0 }, false),
+            (2, EntryProblem::ZeroLength, true),
+            (3, EntryProblem::Unreadable, false),
+            (4, EntryProblem::InsideHeader, false),
+            (5, EntryProblem::PastEnd, false),
+        ]
+    );
+    assert_eq!(fs::read(&path).unwrap(), data);
+
+    let report = repair::repair_world(world.path(), &RepairOptions::default()).unwrap();
+    assert!(report.regions[0].repaired);
+    let chunks = world.read_region(&path);
+    assert_eq!(chunks.keys().collect::<Vec<_>>(), [&(0, 0), &(2, 0)]);
+    assert!(chunks.values().all(|chunk| chunk.meta.is_some()));
+    let report = repair::repair_world(world.path(), &RepairOptions::default()).unwrap();
+    assert!(report.regions.is_empty());
+}
+
+#[test]
+fn dead_space() {
+    let world = mixed_world("dead-space");
+    let path = world.path().join("region/r.0.0.mca");
+    let before = world.read_region(&path);
+    // forget (1, 0) and append a sector no entry refers to
+    let mut data = fs::read(&path).unwrap();
+    let dead_bytes = (data[7] as u64 + 1) * 4096;
+    data[4..8].fill(0);
+    data.extend_from_slice(&[1; 4096]);
+    fs::write(&path, &data).unwrap();
+
+    let dead_space = lessanvil::analysis::dead_space(world.path()).unwrap();
+    assert_eq!(dead_space.total_regions, 3);
+    assert_eq!(dead_space.dead_bytes, dead_bytes);
+    assert_eq!(dead_space.regions.len(), 1);
+    assert_eq!(dead_space.regions[0].path, path);
+
+    let released = lessanvil::compact(world.path()).unwrap();
+    assert_eq!(released.dead_bytes, dead_bytes);
+    assert_eq!(
+        fs::metadata(&path).unwrap().len(),
+        data.len() as u64 - dead_bytes
+    );
+    let mut after = world.read_region(&path);
+    assert!(after.remove(&(1, 0)).is_none());
+    for (position, chunk) in after {
+        assert_eq!(chunk.nbt, before[&position].nbt);
+    }
+    assert_eq!(
+        lessanvil::analysis::dead_space(world.path())
+            .unwrap()
+            .dead_bytes,
+        0
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn rewritten_regions_keep_their_mode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let world = mixed_world("keep-mode");
+    let path = world.path().join("region/r.0.0.mca");
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+    run(ten_seconds(&world));
+
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o640);
+}
+
+// the links can't be counted elsewhere
+#[cfg(unix)]
+#[test]
+fn hardlinked_regions() {
+    let world = mixed_world("hardlinked");
+    let path = world.path().join("region/r.0.0.mca");
+    let link = world.path().with_extension("link.mca");
+    let _ = fs::remove_file(&link);
+    fs::hard_link(&path, &link).unwrap();
+    let original = fs::read(&path).unwrap();
+
+    run(Config {
+        hardlink_policy: HardlinkPolicy::Skip,
+        ..ten_seconds(&world)
+    });
+    assert_eq!(fs::read(&path).unwrap(), original);
+
+    // the other regions were processed by the first run
+    let report = run(ten_seconds(&world));
+    assert_ne!(fs::read(&path).unwrap(), original);
+    // the region was replaced rather than modified in place, its old contents are still on disk
+    assert_eq!(fs::read(&link).unwrap(), original);
+    assert_eq!(report.total_freed_space, 0);
+    assert!(report.warnings.iter().any(|warning| matches!(
+        warning,
+        lessanvil::ReportWarning::HardlinkedFiles { files: 1, bytes }
+            if *bytes == original.len() as u64
+    )));
+    let _ = fs::remove_file(link);
+}
+
+#[test]
+fn not_touched_since() {
+    // the test chunks were saved at 1_700_000_000 plus their InhabitedTime
+    let since = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_150);
+    let world = mixed_world("not-touched-since");
+    // a chunk without a timestamp is kept, its age is unknown
+    let path = world.path().join("region/r.-1.0.mca");
+    let mut data = fs::read(&path).unwrap();
+    data[4096 + 31 * 4..4096 + 32 * 4].fill(0);
+    fs::write(&path, data).unwrap();
+
+    let report = run(Config {
+        not_touched_since: Some(since),
+        dry_run: true,
+        ..world.config()
+    });
+    assert_eq!(report.total_deleted_chunks, 6);
+
+    // judged by reading the chunks, which protects the one with a crafting table
+    let report = run(Config {
+        not_touched_since: Some(since),
+        player_blocks: vec!["minecraft:crafting_table".to_string()],
+        ..world.config()
+    });
+    assert_eq!(report.total_deleted_chunks, 5);
+    let chunks = world.read_region(&world.path().join("region/r.0.0.mca"));
+    assert_eq!(
+        chunks.keys().collect::<Vec<_>>(),
+        [&(1, 0), &(1, 1), &(2, 0), &(3, 0), &(4, 0), &(31, 31)]
+    );
+}
+
+#[test]
+fn world_scanner() {
+    let world = mixed_world("world-scanner");
+    let scanner = WorldScanner::new(world.path(), ScanBackend::Sequential).unwrap();
+    assert_eq!(scanner.regions(), 3);
+    let chunks = scanner.map(Result::unwrap).collect::<Vec<_>>();
+    assert_eq!(chunks.len(), 13);
+    // sorted by dimension and region
+    assert_eq!((chunks[0].x, chunks[0].z), (-2, 0));
+    assert_eq!(chunks[12].dimension, Dimension::Nether);
+
+    let legacy = chunks
+        .iter()
+        .find(|chunk| (chunk.x, chunk.z) == (1, 1))
+        .unwrap();
+    assert_eq!(legacy.dimension, Dimension::Overworld);
+    assert_eq!(legacy.region, world.path().join("region/r.0.0.mca"));
+    assert_eq!(legacy.meta.inhabited_time.ticks(), 5_000);
+    assert_eq!(legacy.timestamp, 1_700_000_000 + 5_000);
+    assert_eq!(legacy.size % 4096, 0);
+
+    let positions = |chunks: Vec<lessanvil::scan::ScannedChunk>| {
+        let mut positions = chunks
+            .into_iter()
+            .map(|chunk| (chunk.dimension, chunk.x, chunk.z, chunk.meta.inhabited_time))
+            .collect::<Vec<_>>();
+        positions.sort();
+        positions
+    };
+    let parallel = WorldScanner::new(world.path(), ScanBackend::Parallel { threads: 2 })
+        .unwrap()
+        .map(Result::unwrap)
+        .collect::<Vec<_>>();
+    assert_eq!(positions(parallel), positions(chunks));
+
+    // a corrupt chunk doesn't end the scan of its region
+    let world = TestWorld::new("world-scanner-corrupted");
+    world.write_region(
+        "region",
+        0,
+        0,
+        &[
+            TestChunk::new(0, 0, 0),
+            TestChunk::new(1, 0, 0).corrupt(),
+            TestChunk::new(2, 0, 0),
+        ],
+    );
+    let results = WorldScanner::new(world.path(), ScanBackend::Sequential)
+        .unwrap()
+        .map(|chunk| chunk.map(|chunk| chunk.x))
+        .collect::<Vec<_>>();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().ok(), Some(&0));
+    assert!(results[1].is_err());
+    assert_eq!(results[2].as_ref().ok(), Some(&2));
+}
+
+#[cfg(feature = "plan")]
+#[test]
+fn chunk_cache() {
+    let world = mixed_world("chunk-cache");
+    let path = world.path().join("chunks.cache");
+    let mut file = fs::File::create(&path).unwrap();
+    let written = cache::write_cache(world.path(), ScanBackend::Sequential, &mut file).unwrap();
+    assert_eq!(written, 13);
+    assert!(cache::is_cache(&path));
+    assert!(!cache::is_cache(&world.path().join("level.dat")));
+
+    let chunk_cache = cache::read_cache(&path).unwrap();
+    assert_eq!(chunk_cache.chunks.len(), 13);
+    let chunk = &chunk_cache.chunks[0];
+    assert_eq!(
+        (
+            &chunk.position.dimension,
+            chunk.position.x,
+            chunk.position.z
+        ),
+        (&Dimension::Overworld, -2, 0)
+    );
+    assert_eq!(chunk.timestamp, 1_700_000_000);
+
+    // applied like a plan, without reading the chunks to select them
+    let config = ten_seconds(&world);
+    let plan = chunk_cache.plan(&config);
+    assert_eq!(plan.chunks.len(), 9);
+    let check = lessanvil::plan::check_plan(&config, &plan).unwrap();
+    assert!(check.changed.is_empty());
+    let report = run(Config {
+        planned_chunks: Some(check.unchanged),
+        ..config
+    });
+    assert_eq!(report.total_deleted_chunks, 9);
+
+    fs::write(&path, &fs::read(&path).unwrap()[..40]).unwrap();
+    assert!(matches!(
+        cache::read_cache(&path),
+        Err(Error::InvalidCache { .. })
+    ));
+}
+
+#[test]
+fn protected_areas_from_csv() {
+    let world = TestWorld::new("protected-areas-from-csv");
+    let csv = world.path().join("areas.csv");
+    fs::write(&csv, "# spawn\n0,0,31,15\nnether, -16, -16, -1, -1\n").unwrap();
+    let areas = protection::areas_from_file(&csv).unwrap();
+    assert_eq!(areas.len(), 2);
+    assert!(areas[0].contains(&Dimension::Overworld, 1, 0));
+    assert!(!areas[0].contains(&Dimension::Overworld, 2, 0));
+    assert!(areas[1].contains(&Dimension::Nether, -1, -1));
+}
+
+#[cfg(feature = "geojson")]
+#[test]
+fn protected_areas_from_geojson() {
+    let world = TestWorld::new("protected-areas-from-geojson");
+    let geojson = world.path().join("areas.geojson");
+    fs::write(
+        &geojson,
+        r#"{"type": "FeatureCollection", "features": [{
+            "type": "Feature",
+            "properties": {"name": "town"},
+            "geometry": {"type": "Polygon", "coordinates": [[[0, 0], [160, 0], [0, 160], [0, 0]]]}
+        }]}"#,
+    )
+    .unwrap();
+    let areas = protection::areas_from_file(&geojson).unwrap();
+    let protected = |x, z| {
+        areas
+            .iter()
+            .any(|area| area.contains(&Dimension::Overworld, x, z))
+    };
+    // inside, on the diagonal edge and outside of the triangle
+    assert!(protected(0, 0) && protected(8, 1) && protected(9, 0));
+    assert!(!protected(9, 9) && !protected(-1, 0) && !protected(1, 10));
+    assert!(areas
+        .iter()
+        .all(|area| !area.contains(&Dimension::Nether, 0, 0)));
+
+    fs::write(&geojson, r#"{"type": "Point", "coordinates": [0, 0]}"#).unwrap();
+    assert!(matches!(
+        protection::areas_from_file(&geojson),
+        Err(Error::InvalidProtectionFile { .. })
+    ));
+}
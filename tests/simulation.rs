@@ -0,0 +1,170 @@
+//! Injected I/O failures and cancellations, asserting on the exact sequence of updates.
+
+#![cfg(feature = "simulation")]
+
+mod common;
+
+use common::{TestChunk, TestWorld};
+use lessanvil::simulation::{FaultInjector, IoOperation};
+use lessanvil::snapshot::{self, Snapshot};
+use lessanvil::{Config, InhabitedTime, ProcessingUpdate};
+use std::io;
+
+/// Three regions, each with a chunk to delete and one to keep.
+fn world(name: &str) -> TestWorld {
+    let world = TestWorld::new(name);
+    for x in 0..3 {
+        world.write_region(
+            "region",
+            x,
+            0,
+            &[TestChunk::new(0, 0, 0), TestChunk::new(1, 0, 100_000)],
+        );
+    }
+    world
+}
+
+fn config(world: &TestWorld, fault_injector: FaultInjector) -> Config {
+    Config {
+        max_inhabited_time: InhabitedTime::from_secs(10),
+        thread_count: 1,
+        ordered_updates: true,
+        fault_injector: Some(fault_injector),
+        ..world.config()
+    }
+}
+
+/// The updates of the execution without the timing dependent progress updates, one line each.
+fn updates(config: Config) -> Vec<String> {
+    let execution = lessanvil::execute(config).unwrap();
+    let updates = execution
+        .updates()
+        .iter()
+        .filter_map(|update| match update {
+            ProcessingUpdate::Starting { total_files, .. } => {
+                Some(format!("starting {total_files}"))
+            }
+            ProcessingUpdate::ProcessedRegion(Ok(region)) => Some(format!(
+                "r.{}.{} deleted {}",
+                region.x, region.y, region.deleted_chunks
+            )),
+            ProcessingUpdate::ProcessedRegion(Err(err)) => {
+                Some(format!("failed: {}", io_error(&err)))
+            }
+            ProcessingUpdate::Progress(_) => None,
+            ProcessingUpdate::Finished(report) => Some(format!(
+                "finished {} regions, {} failed",
+                report.total_regions,
+                report.failed_regions.len()
+            )),
+            ProcessingUpdate::Cancelled(report) => Some(format!(
+                "cancelled after {} of {} regions",
+                report.processed_regions, report.total_regions
+            )),
+        })
+        .collect();
+    let _ = execution.join();
+    updates
+}
+
+fn io_error(err: &lessanvil::RegionProcessingError) -> String {
+    match err {
+        lessanvil::RegionProcessingError::IOError(err) => err.to_string(),
+        err => panic!("expected an I/O error, got {err:?}"),
+    }
+}
+
+#[test]
+fn disk_full_while_compacting() {
+    let world = world("disk-full");
+    let faults = FaultInjector::default().fail(IoOperation::Compact, "r.1.0.mca", || {
+        io::Error::new(io::ErrorKind::Other, "No space left on device")
+    });
+    assert_eq!(
+        updates(config(&world, faults)),
+        [
+            "starting 3",
+            "r.0.0 deleted 1",
+            "failed: No space left on device",
+            "r.2.0 deleted 1",
+            "finished 3 regions, 1 failed",
+        ]
+    );
+    // the region is only replaced once it was written completely
+    let region = world.region_files()[1].clone();
+    assert_eq!(world.read_region(&region).len(), 2);
+}
+
+#[test]
+fn permission_denied() {
+    let world = world("permission-denied");
+    let faults = FaultInjector::default().fail(IoOperation::Open, "r.0.0.mca", || {
+        io::Error::new(io::ErrorKind::PermissionDenied, "Permission denied")
+    });
+    assert_eq!(
+        updates(config(&world, faults)),
+        [
+            "starting 3",
+            "failed: Permission denied",
+            "r.1.0 deleted 1",
+            "r.2.0 deleted 1",
+            "finished 3 regions, 1 failed",
+        ]
+    );
+    // the failed region is left untouched
+    let region = world.region_files()[0].clone();
+    assert_eq!(world.read_region(&region).len(), 2);
+}
+
+#[test]
+fn cancelled_mid_run() {
+    let world = world("cancelled");
+    let faults = FaultInjector::default().cancel(IoOperation::Open, "r.1.0.mca");
+    assert_eq!(
+        updates(config(&world, faults)),
+        [
+            "starting 3",
+            "r.0.0 deleted 1",
+            "r.1.0 deleted 1",
+            "cancelled after 2 of 3 regions",
+        ]
+    );
+}
+
+#[test]
+fn snapshot_of_cancelled_run() {
+    let world = world("snapshot");
+    let path = world.path().join(snapshot::DEFAULT_PATH);
+    let faults = FaultInjector::default()
+        .fail(IoOperation::Open, "r.0.0.mca", || {
+            io::Error::new(io::ErrorKind::PermissionDenied, "Permission denied")
+        })
+        .cancel(IoOperation::Open, "r.1.0.mca");
+    let config = Config {
+        progress_snapshot: Some(path.clone()),
+        ..config(&world, faults)
+    };
+    assert!(lessanvil::execute(config.clone()).unwrap().join().is_err());
+
+    let snapshot = Snapshot::read(&path).unwrap();
+    assert_eq!(snapshot.processed_regions, 2);
+    assert_eq!(snapshot.total_regions, 3);
+    assert_eq!(
+        snapshot.unfinished_regions(),
+        [world.path().join("region/r.2.0.mca")]
+    );
+    assert_eq!(snapshot.recent_errors.len(), 1);
+    assert!(snapshot.recent_errors[0].ends_with("Permission denied"));
+
+    // resuming processes the rest and removes the snapshot
+    let config = Config {
+        fault_injector: None,
+        ..config
+    };
+    let report = lessanvil::execute_subset(config, snapshot.unfinished_regions())
+        .unwrap()
+        .join()
+        .unwrap_or_else(|_| panic!("the resumed execution was cancelled"));
+    assert_eq!(report.total_regions, 1);
+    assert!(!path.exists());
+}